@@ -0,0 +1,392 @@
+//! Allocators of the deep copies.
+
+use std::{
+    alloc::{self, Layout},
+    cell::RefCell,
+    ffi::{c_char, c_void},
+    mem, ptr,
+};
+
+use crate::{
+    internal,
+    wide::{self, CWideChar},
+    CDeepCopy, Error,
+};
+
+/// Allocator of the memory for the deep copies.
+/// # Safety
+/// [`CDumpAllocator::allocate`] must return memory valid for reads and writes of the `layout`, and
+/// [`CDumpAllocator::deallocate`] must accept every pointer returned by [`CDumpAllocator::allocate`] of the same
+/// allocator.
+pub unsafe trait CDumpAllocator {
+    /// Allocate memory for the `layout`, which size is never zero.
+    fn allocate(&self, layout: Layout) -> Result<*mut u8, Error>;
+
+    /// Deallocate memory returned by [`CDumpAllocator::allocate`] with the same `layout`.
+    /// # Safety
+    /// `ptr` must be returned by [`CDumpAllocator::allocate`] of this allocator with the same `layout`.
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+
+    /// Called by [`DestinationPolicy::overwrite`] before `size` bytes at `slot` of the destination are overwritten, e.g.
+    /// to restore them when deserialization fails.
+    /// # Safety
+    /// `slot` must be valid for reads of `size` bytes.
+    #[inline]
+    unsafe fn before_overwrite(&self, slot: *const u8, size: usize) {
+        let _ = (slot, size);
+    }
+}
+
+unsafe impl<A: CDumpAllocator> CDumpAllocator for &A {
+    fn allocate(&self, layout: Layout) -> Result<*mut u8, Error> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+
+    unsafe fn before_overwrite(&self, slot: *const u8, size: usize) {
+        (**self).before_overwrite(slot, size)
+    }
+}
+
+/// Allocator which uses Rust's global allocator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalAllocator;
+
+unsafe impl CDumpAllocator for GlobalAllocator {
+    fn allocate(&self, layout: Layout) -> Result<*mut u8, Error> {
+        // Safety: size of the layout is not zero.
+        let ptr = unsafe { alloc::alloc(layout) };
+        match ptr.is_null() {
+            true => Err(allocation_failed(layout)),
+            false => Ok(ptr),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        alloc::dealloc(ptr, layout)
+    }
+}
+
+/// Allocator which uses libc's `malloc`, so every allocation of the deep copy can be released by C's `free`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LibcAllocator;
+
+unsafe impl CDumpAllocator for LibcAllocator {
+    fn allocate(&self, layout: Layout) -> Result<*mut u8, Error> {
+        let ptr = match layout.align() <= mem::size_of::<usize>() {
+            // Safety: size of the layout is not zero.
+            true => unsafe { libc::malloc(layout.size()) },
+            false => allocate_aligned(layout),
+        };
+
+        match ptr.is_null() {
+            true => Err(allocation_failed(layout)),
+            false => Ok(ptr as *mut u8),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, _layout: Layout) {
+        libc::free(ptr as *mut libc::c_void)
+    }
+}
+
+#[cfg(unix)]
+fn allocate_aligned(layout: Layout) -> *mut libc::c_void {
+    let mut ptr = ptr::null_mut();
+    // Safety: alignment is a power of two, and a multiple of the pointer size.
+    match unsafe { libc::posix_memalign(&mut ptr, layout.align(), layout.size()) } {
+        0 => ptr,
+        _ => ptr::null_mut(),
+    }
+}
+
+#[cfg(not(unix))]
+fn allocate_aligned(_layout: Layout) -> *mut libc::c_void {
+    ptr::null_mut()
+}
+
+/// Alignment of dynamic objects allocated by [`DestinationPolicy`], which types are unknown.
+pub const DYNAMIC_ALIGN: usize = 16;
+
+/// Policy of [`crate::CDeserialize::deserialize_to`] for destination pointers which are null, or point to storage
+/// which is too small for the deserialized data.
+/// # Remarks
+/// Memory allocated by the policy is owned by the caller, and is not freed by cdump.
+#[derive(Debug, Clone, Copy)]
+pub enum DestinationPolicy<A: CDumpAllocator = GlobalAllocator> {
+    /// Return [`Error::DestinationTooSmall`].
+    Reject,
+    /// Allocate new storage with the allocator. Arrays and objects are zeroed before they are written.
+    Allocate(A),
+}
+
+impl DestinationPolicy<GlobalAllocator> {
+    /// Create policy which returns an error for missing destinations.
+    pub const fn reject() -> Self {
+        Self::Reject
+    }
+
+    /// Create policy which allocates missing destinations with the global allocator.
+    pub const fn allocate() -> Self {
+        Self::Allocate(GlobalAllocator)
+    }
+}
+
+impl<A: CDumpAllocator> DestinationPolicy<A> {
+    /// Get the destination for `len` elements of `T`, which is `dst` when it is not null and its `capacity` is
+    /// sufficient. New storage has room for at least `capacity` elements, so the declared capacity stays valid.
+    pub fn try_destination<T>(
+        &self,
+        dst: *mut T,
+        len: usize,
+        capacity: usize,
+    ) -> Result<*mut T, Error> {
+        if capacity >= len && !dst.is_null() {
+            return Ok(dst);
+        }
+
+        match self {
+            DestinationPolicy::Reject => Err(Error::DestinationTooSmall {
+                len,
+                capacity: match dst.is_null() {
+                    true => 0,
+                    false => capacity,
+                },
+            }),
+            DestinationPolicy::Allocate(alloc) => try_alloc_zeroed(alloc, len.max(capacity)),
+        }
+    }
+
+    /// Get the destination for dynamic object with `size` bytes, which is `dst` when it is not null and its
+    /// `capacity` is sufficient. New storage is aligned to [`DYNAMIC_ALIGN`].
+    pub fn try_dynamic_destination(
+        &self,
+        dst: *mut c_void,
+        size: usize,
+        capacity: usize,
+    ) -> Result<*mut c_void, Error> {
+        let capacity = match dst.is_null() {
+            true => 0,
+            false => capacity,
+        };
+
+        if capacity >= size && !dst.is_null() {
+            return Ok(dst);
+        }
+
+        match self {
+            DestinationPolicy::Reject => Err(Error::DestinationTooSmall {
+                len: size,
+                capacity,
+            }),
+            DestinationPolicy::Allocate(alloc) => {
+                let layout = Layout::from_size_align(size.max(1), DYNAMIC_ALIGN)
+                    .map_err(|_| Error::LengthOverflow)?;
+                let ptr = alloc.allocate(layout)?;
+                // Safety: allocated memory is valid for the layout.
+                unsafe { ptr::write_bytes(ptr, 0, layout.size()) };
+                Ok(ptr as *mut c_void)
+            }
+        }
+    }
+
+    /// Write the `value` to the `slot` of the destination, which is restored when
+    /// [`crate::CDeserialize::deserialize_to`] fails after it.
+    /// # Safety
+    /// `slot` must be valid for reads and writes of `T`.
+    #[inline]
+    pub unsafe fn overwrite<T>(&self, slot: *mut T, value: T) {
+        if let DestinationPolicy::Allocate(alloc) = self {
+            alloc.before_overwrite(slot as *const u8, mem::size_of::<T>());
+        }
+        ptr::write(slot, value);
+    }
+}
+
+/// Allocator used by [`crate::CDeserialize::deserialize_to`], which records allocations and overwritten storage of the
+/// destination, so they can be rolled back when deserialization fails.
+pub(crate) struct DestinationTransaction<'a, A: CDumpAllocator> {
+    alloc: &'a A,
+    allocations: RefCell<Vec<(*mut u8, Layout)>>,
+    /// Overwritten slots with offsets of their previous bytes in `saved`.
+    overwritten: RefCell<Vec<(*mut u8, usize, usize)>>,
+    saved: RefCell<Vec<u8>>,
+}
+
+impl<'a, A: CDumpAllocator> DestinationTransaction<'a, A> {
+    pub(crate) fn new(alloc: &'a A) -> Self {
+        Self {
+            alloc,
+            allocations: RefCell::new(Vec::new()),
+            overwritten: RefCell::new(Vec::new()),
+            saved: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Restore overwritten slots in reverse order, and free every allocation.
+    /// # Safety
+    /// Overwritten slots must be still valid for writes.
+    pub(crate) unsafe fn roll_back(self) {
+        let saved = self.saved.into_inner();
+        for (slot, offset, size) in self.overwritten.into_inner().into_iter().rev() {
+            ptr::copy_nonoverlapping(saved.as_ptr().add(offset), slot, size);
+        }
+
+        for (ptr, layout) in self.allocations.into_inner() {
+            self.alloc.deallocate(ptr, layout);
+        }
+    }
+}
+
+unsafe impl<A: CDumpAllocator> CDumpAllocator for DestinationTransaction<'_, A> {
+    fn allocate(&self, layout: Layout) -> Result<*mut u8, Error> {
+        let ptr = self.alloc.allocate(layout)?;
+        self.allocations.borrow_mut().push((ptr, layout));
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        self.allocations
+            .borrow_mut()
+            .retain(|&(allocation, _)| allocation != ptr);
+        self.alloc.deallocate(ptr, layout)
+    }
+
+    unsafe fn before_overwrite(&self, slot: *const u8, size: usize) {
+        let mut saved = self.saved.borrow_mut();
+        self.overwritten
+            .borrow_mut()
+            .push((slot as *mut u8, saved.len(), size));
+        saved.extend_from_slice(std::slice::from_raw_parts(slot, size));
+    }
+}
+
+fn allocation_failed(layout: Layout) -> Error {
+    Error::AllocationFailed {
+        size: layout.size(),
+        align: layout.align(),
+    }
+}
+
+/// Get the layout of the array, which is at least one byte long, so even empty arrays are allocated, and can be freed
+/// by C code.
+fn array_layout<T>(len: usize) -> Result<Layout, Error> {
+    let layout = Layout::array::<T>(len).map_err(|_| Error::LengthOverflow)?;
+    Layout::from_size_align(layout.size().max(1), layout.align()).map_err(|_| Error::LengthOverflow)
+}
+
+/// Allocate the array of `len` elements of `T`, with zeroed memory. Zero sized array is allocated with one byte.
+pub fn try_alloc_zeroed<T, A: CDumpAllocator>(alloc: &A, len: usize) -> Result<*mut T, Error> {
+    let layout = array_layout::<T>(len)?;
+    let ptr = alloc.allocate(layout)?;
+    // Safety: allocated memory is valid for the layout.
+    unsafe { ptr::write_bytes(ptr, 0, layout.size()) };
+    Ok(ptr as *mut T)
+}
+
+/// Deallocate the array returned by [`try_alloc_zeroed`].
+/// # Safety
+/// `ptr` must be returned by [`try_alloc_zeroed`] of the same allocator with the same `len`.
+pub unsafe fn free<T, A: CDumpAllocator>(alloc: &A, ptr: *mut T, len: usize) {
+    let layout = array_layout::<T>(len).expect("layout of allocated array should be valid");
+    alloc.deallocate(ptr as *mut u8, layout);
+}
+
+/// Copy the array of `len` elements of `T`, without deep data of elements.
+/// # Safety
+/// `src` must be valid for reads of `len` elements.
+pub unsafe fn try_copy_array<T, A: CDumpAllocator>(
+    src: *const T,
+    alloc: &A,
+    len: usize,
+) -> Result<*mut T, Error> {
+    let ptr = try_alloc_zeroed::<T, A>(alloc, len)?;
+    ptr::copy_nonoverlapping(src, ptr, len);
+    Ok(ptr)
+}
+
+/// Create deep copy of the `src` in the memory of the allocator.
+/// # Remarks
+/// When an error is returned, every allocation of the copy is freed.
+/// # Safety
+/// The caller must ensure that every pointer in the object tree is valid or null.
+pub unsafe fn try_deep_copy<T: CDeepCopy, A: CDumpAllocator>(
+    src: *const T,
+    alloc: &A,
+) -> Result<*mut T, Error> {
+    let ptr = try_copy_array(src, alloc, 1)?;
+    if let Err(err) = T::try_deep_copy_without_shallow_copy(src, ptr, alloc) {
+        deep_free(ptr, alloc);
+        return Err(err);
+    }
+    Ok(ptr)
+}
+
+/// Free the deep copy returned by [`try_deep_copy`].
+/// # Safety
+/// `ptr` must be returned by [`try_deep_copy`] of the same allocator.
+pub unsafe fn deep_free<T: CDeepCopy, A: CDumpAllocator>(ptr: *mut T, alloc: &A) {
+    T::deep_free_without_shallow_copy(ptr, alloc);
+    free(alloc, ptr, 1);
+}
+
+/// Copy the C string to the memory of the allocator.
+/// # Safety
+/// `src` must be a valid C string.
+pub unsafe fn try_copy_cstring<A: CDumpAllocator>(
+    src: *const c_char,
+    alloc: &A,
+) -> Result<*mut c_char, Error> {
+    try_copy_array(src, alloc, internal::libc_strlen(src) + 1)
+}
+
+/// Free the C string returned by [`try_copy_cstring`].
+/// # Safety
+/// `ptr` must be returned by [`try_copy_cstring`] of the same allocator.
+pub unsafe fn free_cstring<A: CDumpAllocator>(ptr: *mut c_char, alloc: &A) {
+    free(alloc, ptr, internal::libc_strlen(ptr) + 1)
+}
+
+/// Copy the wide string to the memory of the allocator.
+/// # Safety
+/// `src` must be a valid wide string.
+pub unsafe fn try_copy_wstring<T: CWideChar, A: CDumpAllocator>(
+    src: *const T,
+    alloc: &A,
+) -> Result<*mut T, Error> {
+    try_copy_array(src, alloc, wide::wcslen(src) + 1)
+}
+
+/// Free the wide string returned by [`try_copy_wstring`].
+/// # Safety
+/// `ptr` must be returned by [`try_copy_wstring`] of the same allocator.
+pub unsafe fn free_wstring<T: CWideChar, A: CDumpAllocator>(ptr: *mut T, alloc: &A) {
+    free(alloc, ptr, wide::wcslen(ptr) + 1)
+}
+
+/// Copy `len` bytes aligned to `align` to the memory of the allocator.
+/// # Safety
+/// `src` must be valid for reads of `len` bytes.
+pub unsafe fn try_copy_bytes<A: CDumpAllocator>(
+    src: *const u8,
+    alloc: &A,
+    len: usize,
+    align: usize,
+) -> Result<*mut u8, Error> {
+    let layout = Layout::from_size_align(len.max(1), align).map_err(|_| Error::LengthOverflow)?;
+    let ptr = alloc.allocate(layout)?;
+    ptr::copy_nonoverlapping(src, ptr, len);
+    Ok(ptr)
+}
+
+/// Free the bytes returned by [`try_copy_bytes`].
+/// # Safety
+/// `ptr` must be returned by [`try_copy_bytes`] of the same allocator with the same `len` and `align`.
+pub unsafe fn free_bytes<A: CDumpAllocator>(ptr: *mut u8, alloc: &A, len: usize, align: usize) {
+    let layout = Layout::from_size_align(len.max(1), align)
+        .expect("layout of allocated bytes should be valid");
+    alloc.deallocate(ptr, layout);
+}
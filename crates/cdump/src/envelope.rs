@@ -0,0 +1,266 @@
+//! Self-describing message envelope.
+//!
+//! Envelope is a header written before the serialized object, which records format version, platform of the writer,
+//! root type, payload length and required alignment of the buffer. Header is always little endian, so it can be
+//! inspected on any platform.
+
+use std::{cmp, mem};
+
+use crate::{shared, CDeserialize, CDumpReader, CDumpWriter, CSerialize, Error};
+
+/// Magic number at the start of every envelope.
+pub const MAGIC: [u8; 4] = *b"CDMP";
+/// Version of the format written by this crate.
+pub const FORMAT_VERSION: u16 = 1;
+/// Length of the header in bytes.
+pub const HEADER_LEN: usize = 32;
+
+/// Header of the envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Version of the format.
+    pub version: u16,
+    /// Byte order of the payload.
+    pub big_endian: bool,
+    /// Size of pointers in the payload, in bytes.
+    pub pointer_width: u8,
+    /// Alignment required from the buffer of the payload.
+    pub align: u32,
+    /// Layout hash of the root type.
+    pub type_id: u64,
+    /// Length of the payload after the header, in bytes.
+    pub payload_len: u64,
+}
+
+impl Header {
+    /// Create the header of the payload written on this platform.
+    pub fn native(type_id: u64, align: u32, payload_len: u64) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            big_endian: cfg!(target_endian = "big"),
+            pointer_width: mem::size_of::<usize>() as u8,
+            align,
+            type_id,
+            payload_len,
+        }
+    }
+
+    /// Get the bytes of the header.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&self.version.to_le_bytes());
+        bytes[6] = self.big_endian as u8;
+        bytes[7] = self.pointer_width;
+        bytes[8..12].copy_from_slice(&self.align.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.type_id.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes
+    }
+
+    /// Parse the header from the bytes, e.g. to get length of the message in socket framing.
+    /// # Remarks
+    /// Only the magic number is checked, use [`Header::validate`] before reading the payload.
+    pub fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Result<Self, Error> {
+        if bytes[0..4] != MAGIC {
+            return Err(Error::InvalidMagic([
+                bytes[0], bytes[1], bytes[2], bytes[3],
+            ]));
+        }
+
+        Ok(Self {
+            version: u16::from_le_bytes([bytes[4], bytes[5]]),
+            big_endian: bytes[6] != 0,
+            pointer_width: bytes[7],
+            align: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            type_id: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            payload_len: u64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+        })
+    }
+
+    /// Check if the payload can be read on this platform as `type_id`.
+    pub fn validate(&self, type_id: u64) -> Result<(), Error> {
+        if self.version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(self.version));
+        }
+
+        let native = Self::native(type_id, self.align, self.payload_len);
+        if self.big_endian != native.big_endian
+            || self.pointer_width != native.pointer_width
+            || !self.align.is_power_of_two()
+        {
+            return Err(Error::IncompatiblePlatform {
+                big_endian: self.big_endian,
+                pointer_width: self.pointer_width,
+                align: self.align,
+            });
+        }
+
+        if self.type_id != type_id {
+            return Err(Error::LayoutMismatch {
+                expected: type_id,
+                found: self.type_id,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Writer which tracks the biggest alignment of the data written to the inner writer.
+pub struct EnvelopeWriter<'a, W> {
+    inner: &'a mut W,
+    align: usize,
+}
+
+unsafe impl<W: CDumpWriter> CDumpWriter for EnvelopeWriter<'_, W> {
+    fn align<T>(&mut self) {
+        self.align = cmp::max(self.align, mem::align_of::<T>());
+        self.inner.align::<T>()
+    }
+
+    fn push_slice(&mut self, slice: &[u8]) {
+        self.inner.push_slice(slice)
+    }
+
+    fn try_align<T>(&mut self) -> Result<(), Error> {
+        self.align = cmp::max(self.align, mem::align_of::<T>());
+        self.inner.try_align::<T>()
+    }
+
+    fn try_push_slice(&mut self, slice: &[u8]) -> Result<(), Error> {
+        self.inner.try_push_slice(slice)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    unsafe fn as_mut_ptr_at(&mut self, index: usize) -> *mut u8 {
+        self.inner.as_mut_ptr_at(index)
+    }
+}
+
+/// Serialize the header and the object to the buffer.
+/// # Safety
+/// The caller must ensure that every pointer in the object tree is valid or null.
+pub unsafe fn try_serialize<T, W>(obj: &T, buf: &mut W) -> Result<(), Error>
+where
+    T: for<'a> CSerialize<EnvelopeWriter<'a, W>>,
+    W: CDumpWriter,
+{
+    buf.try_align::<u64>()?;
+    let header_index = buf.len();
+    buf.try_push_slice(&[0; HEADER_LEN])?;
+
+    let mut writer = EnvelopeWriter {
+        inner: buf,
+        align: mem::align_of::<u64>(),
+    };
+    obj.try_serialize(&mut writer)?;
+    let align = u32::try_from(writer.align).map_err(|_| Error::LengthOverflow)?;
+
+    let payload_len = (buf.len() - header_index - HEADER_LEN) as u64;
+    let header = Header::native(
+        <T as CSerialize<EnvelopeWriter<W>>>::LAYOUT_HASH,
+        align,
+        payload_len,
+    );
+    std::ptr::copy_nonoverlapping(
+        header.to_bytes().as_ptr(),
+        buf.as_mut_ptr_at(header_index),
+        HEADER_LEN,
+    );
+    Ok(())
+}
+
+/// Read the header from the buffer without validation.
+pub fn try_read_header<R: CDumpReader>(buf: &mut R) -> Result<Header, Error> {
+    buf.try_align::<u64>()?;
+    let bytes = buf.try_read_raw_slice(HEADER_LEN)?;
+    // Safety: slice is in bounds of the buffer.
+    Header::from_bytes(unsafe { &*(bytes as *const [u8; HEADER_LEN]) })
+}
+
+/// Reader which ends at the end of the payload, so the deserialization never reads past it.
+pub struct EnvelopeReader<'a, R> {
+    inner: &'a mut R,
+    end: usize,
+}
+
+unsafe impl<R: CDumpReader> CDumpReader for EnvelopeReader<'_, R> {
+    fn align<T>(&mut self) {
+        self.inner.align::<T>()
+    }
+
+    fn add_read(&mut self, len: usize) {
+        self.inner.add_read(len)
+    }
+
+    unsafe fn read_raw_slice(&mut self, len: usize) -> *const u8 {
+        self.inner.read_raw_slice(len)
+    }
+
+    unsafe fn as_mut_ptr_at<T>(&self, index: usize) -> *mut T {
+        self.inner.as_mut_ptr_at(index)
+    }
+
+    fn get_read(&self) -> usize {
+        self.inner.get_read()
+    }
+
+    fn len(&self) -> usize {
+        self.end
+    }
+
+    fn patched_pointer(&mut self, slot: *const u8) {
+        self.inner.patched_pointer(slot)
+    }
+
+    fn shared_pointer(
+        &self,
+        value: usize,
+        key: shared::SharedKey,
+    ) -> Result<Option<*mut u8>, Error> {
+        self.inner.shared_pointer(value, key)
+    }
+
+    fn record_shared(&mut self, offset: usize, key: shared::SharedKey, ptr: *mut u8) {
+        self.inner.record_shared(offset, key, ptr)
+    }
+}
+
+/// Validate the header, and deserialize the object from the payload with checks.
+/// # Remarks
+/// After success the buffer is read to the end of the payload, so the next envelope can be read.
+/// # Safety
+/// Same as [`CDeserialize::try_deserialize_ref_mut`].
+pub unsafe fn try_deserialize_ref<T, R>(buf: &mut R) -> Result<&T, Error>
+where
+    T: for<'a> CDeserialize<EnvelopeReader<'a, R>>,
+    R: CDumpReader,
+{
+    let header = try_read_header(buf)?;
+    header.validate(<T as CDeserialize<EnvelopeReader<R>>>::LAYOUT_HASH)?;
+
+    let payload_start = buf.get_read();
+    let payload_len = usize::try_from(header.payload_len).map_err(|_| Error::LengthOverflow)?;
+    buf.check_bounds(payload_start, payload_len)?;
+
+    let align = header.align as usize;
+    let address = buf.as_mut_ptr_at::<u8>(payload_start) as usize;
+    if !address.is_multiple_of(align) {
+        return Err(Error::Misaligned {
+            offset: payload_start,
+            align,
+        });
+    }
+
+    let mut payload = EnvelopeReader {
+        inner: buf,
+        end: payload_start + payload_len,
+    };
+    let obj = T::try_deserialize_ref_mut(&mut payload)? as *const T;
+    let read = buf.get_read() - payload_start;
+    buf.add_read(payload_len - read);
+    Ok(&*obj)
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+/// Error returned by the checked operations of cdump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Data at `offset` with `len` bytes exceeds the buffer, which has `buffer_len` bytes.
+    OutOfBounds {
+        offset: usize,
+        len: usize,
+        buffer_len: usize,
+    },
+    /// Data at `offset` is not aligned in memory to `align` bytes.
+    Misaligned { offset: usize, align: usize },
+    /// Size of the data does not fit in `usize`.
+    LengthOverflow,
+    /// C string at `offset` is not terminated by the null character.
+    MissingNulTerminator { offset: usize },
+    /// Dynamic field does not provide a function required by the operation, e.g. checked deserializer or deep copy.
+    UnsupportedDynamic,
+    /// Buffer cannot fit `len` more bytes, because only `available` bytes are left.
+    BufferFull { len: usize, available: usize },
+    /// Dynamic object has an unknown discriminant, e.g. `sType` of Vulkan structure.
+    UnknownDiscriminant(i64),
+    /// Layout hash of the data does not match the layout hash of the deserialized type.
+    LayoutMismatch { expected: u64, found: u64 },
+    /// Envelope does not start with [`crate::envelope::MAGIC`].
+    InvalidMagic([u8; 4]),
+    /// Envelope was written in unsupported version of the format.
+    UnsupportedVersion(u16),
+    /// Envelope was written on a platform with different byte order or pointer width, or requires invalid alignment.
+    IncompatiblePlatform {
+        big_endian: bool,
+        pointer_width: u8,
+        align: u32,
+    },
+    /// Allocator cannot allocate `size` bytes aligned to `align`.
+    AllocationFailed { size: usize, align: usize },
+    /// Destination of `deserialize_to` can hold `capacity` elements, or bytes for dynamic objects, but `len` are
+    /// required.
+    DestinationTooSmall { len: usize, capacity: usize },
+    /// Back-reference to the data at `offset` does not point to the data of the same type which was already read.
+    InvalidBackReference { offset: usize },
+    /// Tagged object has a `tag` which is not registered in its tag type.
+    UnregisteredTag { tag: String },
+    /// Array at `offset` does not end with its terminator.
+    MissingTerminator { offset: usize },
+    /// Pointer at `offset` of the relocatable image does not point into the image.
+    InvalidRelocation { offset: usize },
+    /// Object of the type named `ty` is reached again through its own pointers, which the operation cannot follow.
+    Cycle { ty: &'static str },
+    /// Error occurred in the `field` of the type named `ty`.
+    Field {
+        ty: &'static str,
+        field: &'static str,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Wrap the error with the field in which it occurred.
+    pub fn in_field(self, ty: &'static str, field: &'static str) -> Self {
+        Error::Field {
+            ty,
+            field,
+            source: Box::new(self),
+        }
+    }
+
+    /// Get the error without fields in which it occurred.
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Error::Field { source, .. } => source.root_cause(),
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::OutOfBounds {
+                offset,
+                len,
+                buffer_len,
+            } => write!(
+                f,
+                "{len} bytes at offset {offset} exceed the buffer of {buffer_len} bytes"
+            ),
+            Error::Misaligned { offset, align } => {
+                write!(f, "data at offset {offset} is not aligned to {align} bytes")
+            }
+            Error::LengthOverflow => write!(f, "size of the data overflows `usize`"),
+            Error::MissingNulTerminator { offset } => {
+                write!(f, "C string at offset {offset} is not null terminated")
+            }
+            Error::UnsupportedDynamic => write!(
+                f,
+                "dynamic field does not provide a function required by the operation"
+            ),
+            Error::BufferFull { len, available } => write!(
+                f,
+                "buffer cannot fit {len} bytes, only {available} bytes are available"
+            ),
+            Error::UnknownDiscriminant(discriminant) => {
+                write!(f, "unknown discriminant {discriminant}")
+            }
+            Error::LayoutMismatch { expected, found } => write!(
+                f,
+                "layout hash {found:#018x} does not match expected {expected:#018x}"
+            ),
+            Error::InvalidMagic(magic) => write!(f, "invalid magic number {magic:02x?}"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            Error::IncompatiblePlatform {
+                big_endian,
+                pointer_width,
+                align,
+            } => write!(
+                f,
+                "incompatible platform with {} endian, {pointer_width} bytes pointers, and {align} bytes alignment",
+                match big_endian {
+                    true => "big",
+                    false => "little",
+                }
+            ),
+            Error::AllocationFailed { size, align } => {
+                write!(f, "cannot allocate {size} bytes aligned to {align} bytes")
+            }
+            Error::DestinationTooSmall { len, capacity } => write!(
+                f,
+                "destination has capacity of {capacity}, but {len} is required"
+            ),
+            Error::InvalidBackReference { offset } => {
+                write!(f, "invalid back-reference to offset {offset}")
+            }
+            Error::UnregisteredTag { tag } => write!(f, "tag {tag} is not registered"),
+            Error::MissingTerminator { offset } => {
+                write!(f, "array at offset {offset} does not end with its terminator")
+            }
+            Error::InvalidRelocation { offset } => {
+                write!(f, "pointer at offset {offset} does not point into the image")
+            }
+            Error::Cycle { ty } => write!(f, "object of type `{ty}` is reached again by its pointers"),
+            Error::Field { ty, field, source } => write!(f, "`{ty}::{field}`: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,6 +1,64 @@
-use std::{ffi::c_char, mem, ptr};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    ffi::{c_char, c_void},
+    fmt,
+    marker::PhantomData,
+    mem, ptr,
+};
 
-use crate::{CDeserialize, CDumpReader, CDumpWriter};
+use crate::{
+    alloc::{CDumpAllocator, DestinationPolicy, DestinationTransaction},
+    shared::{SharedKey, BACK_REFERENCE},
+    wide::CWideChar,
+    CDeserialize, CDumpReader, CDumpWriter, Error,
+};
+
+#[cfg(feature = "portable")]
+pub use crate::portable::{
+    read_native, read_slot, transcode_shallow, transcode_slot, transcode_slots, write_slot,
+};
+
+thread_local! {
+    /// Objects visited by recursive traversals of the current thread, with names of their types.
+    static VISITING: RefCell<HashSet<(usize, &'static str)>> = RefCell::new(HashSet::new());
+}
+
+/// Guard of the object visited by recursive traversal, like `CDebug`, serialized size or deep copy, which follows
+/// pointers without [`crate::CDumpSharedWriter`]. The object is visited until the guard is dropped, so pointers which
+/// lead back to it are detected as cycles, while data shared by several pointers is visited every time.
+pub struct Visit {
+    key: (usize, &'static str),
+}
+
+impl Visit {
+    /// Start to visit the object of type `T` at `obj`, or return `None` when it is already visited.
+    pub fn enter<T>(obj: *const T) -> Option<Self> {
+        let key = (obj as usize, std::any::type_name::<T>());
+        // Guard is created only for the new object, because dropping it ends the visit.
+        VISITING
+            .with(|visiting| visiting.borrow_mut().insert(key))
+            .then(|| Self { key })
+    }
+}
+
+impl Drop for Visit {
+    fn drop(&mut self) {
+        VISITING.with(|visiting| visiting.borrow_mut().remove(&self.key));
+    }
+}
+
+/// Check that the pointer with `value` in the buffer is not a back-reference, which is returned as
+/// [`Error::InvalidBackReference`], because `deserialize_to` does not record the data it reads.
+#[inline]
+pub fn reject_back_reference<T: CDumpReader>(buf: &T, value: usize) -> Result<(), Error> {
+    match buf.shared_pointer(value, SharedKey::new(0, 0)) {
+        Ok(None) => Ok(()),
+        _ => Err(Error::InvalidBackReference {
+            offset: value & !BACK_REFERENCE,
+        }),
+    }
+}
 
 /// Get the length of the C string.
 /// # Safety
@@ -40,20 +98,89 @@ where
     reference
 }
 
+/// Deserialize the data in the buffer to the destination, like [`CDeserialize::deserialize_to`].
+/// # Remarks
+/// When an error is returned, storage allocated by the policy is freed, and storage of the destination which was
+/// overwritten with pointers to it is restored.
+/// # Safety
+/// Same as [`CDeserialize::deserialize_to`].
+pub unsafe fn deserialize_root_to<T1, T2, A>(
+    buf: &mut T1,
+    dst: *mut T2,
+    policy: &DestinationPolicy<A>,
+) -> Result<(), Error>
+where
+    T1: crate::CDumpReader,
+    T2: crate::CDeserialize<T1>,
+    A: CDumpAllocator,
+{
+    let DestinationPolicy::Allocate(alloc) = policy else {
+        return deserialize_shallow_copied_to(buf, dst, policy);
+    };
+
+    let policy = DestinationPolicy::Allocate(DestinationTransaction::new(alloc));
+    let result = deserialize_shallow_copied_to(buf, dst, &policy);
+    if let (Err(_), DestinationPolicy::Allocate(transaction)) = (&result, policy) {
+        transaction.roll_back();
+    }
+    result
+}
+
 /// Deserialize the shallow copied data in the buffer and returns the reference to it.
 /// # Safety
 /// Caller must ensure that the next data in the buffer is a valid representation of `T2`.
 #[inline]
-pub unsafe fn deserialize_shallow_copied_to<T1, T2>(buf: &mut T1, dst: *mut T2)
+pub unsafe fn deserialize_shallow_copied_to<T1, T2, A>(
+    buf: &mut T1,
+    dst: *mut T2,
+    policy: &DestinationPolicy<A>,
+) -> Result<(), Error>
 where
     T1: crate::CDumpReader,
     T2: crate::CDeserialize<T1>,
+    A: CDumpAllocator,
 {
     align_reader::<T1, T2>(buf);
     let temp = buf.as_mut_ptr_at::<T2>(buf.get_read());
     buf.add_read(::std::mem::size_of::<T2>());
-    CDeserialize::deserialize_to_without_shallow_copy(buf, temp, dst);
-    ptr::copy_nonoverlapping(temp, dst, 1);
+    deserialize_shallow_copied_at_to(buf, temp, dst, policy)
+}
+
+/// Deserialize the shallow copied data at `temp` in the buffer to the destination.
+/// # Safety
+/// Caller must ensure that `temp` points to shallow copy of `T2` in the buffer, followed by its deep part.
+#[inline]
+pub unsafe fn deserialize_shallow_copied_at_to<T1, T2, A>(
+    buf: &mut T1,
+    temp: *mut T2,
+    dst: *mut T2,
+    policy: &DestinationPolicy<A>,
+) -> Result<(), Error>
+where
+    T1: crate::CDumpReader,
+    T2: crate::CDeserialize<T1>,
+    A: CDumpAllocator,
+{
+    CDeserialize::deserialize_to_without_shallow_copy(buf, temp, dst, policy)?;
+    policy.overwrite(dst, ptr::read(temp));
+    Ok(())
+}
+
+/// Deserialize the shallow copied data in the buffer with checks, and returns the reference to it.
+/// # Safety
+/// Same as [`CDeserialize::try_deserialize_ref_mut`].
+#[inline]
+pub unsafe fn try_deserialize_shallow_copied<T1, T2>(buf: &mut T1) -> Result<*mut T2, Error>
+where
+    T1: crate::CDumpReader,
+    T2: crate::CDeserialize<T1>,
+{
+    buf.try_align::<T2>()?;
+    let index = buf.get_read();
+    buf.try_read_raw_slice(::std::mem::size_of::<T2>())?;
+    let reference = buf.as_mut_ptr_at::<T2>(index);
+    CDeserialize::try_deserialize_ref_mut_without_shallow_copy(buf, reference)?;
+    Ok(reference)
 }
 
 // Deserialize the shallow copied data in the buffer and returns the reference to it.
@@ -70,6 +197,304 @@ where
     reference
 }
 
+/// Deserialize the shallow copied data in the buffer with checks, and returns the reference to it.
+/// # Safety
+/// Same as [`CDeserialize::try_deserialize_ref_mut`], and caller must ensure that shallow copy of `T2` at the `index`
+/// is in bounds of the buffer, and is properly aligned.
+#[inline]
+pub unsafe fn try_deserialize_shallow_copied_at<T1, T2>(
+    buf: &mut T1,
+    index: usize,
+) -> Result<*mut T2, Error>
+where
+    T1: crate::CDumpReader,
+    T2: crate::CDeserialize<T1>,
+{
+    let reference = buf.as_mut_ptr_at(index);
+    CDeserialize::try_deserialize_ref_mut_without_shallow_copy(buf, reference)?;
+    Ok(reference)
+}
+
+/// Read the C string with `len` bytes, including the null terminator, and returns the pointer to it.
+/// # Remarks
+/// Returns an error when the string exceeds the buffer, or when its last byte is not the null terminator.
+#[inline]
+pub fn try_read_cstring<T>(buf: &mut T, len: usize) -> Result<*mut c_char, Error>
+where
+    T: CDumpReader,
+{
+    let offset = buf.get_read();
+    if len == 0 {
+        return Err(Error::MissingNulTerminator { offset });
+    }
+
+    let ptr = buf.try_read_raw_slice(len)?;
+    // Safety: the slice with `len` bytes is in bounds of the buffer.
+    match unsafe { *ptr.add(len - 1) } {
+        0 => Ok(ptr as *mut c_char),
+        _ => Err(Error::MissingNulTerminator { offset }),
+    }
+}
+
+/// Read the wide string with `len` characters, including the null terminator, and returns the pointer to it.
+/// # Remarks
+/// Buffer must be aligned to the character before. Returns an error when the string exceeds the buffer, or when its
+/// last character is not the null terminator.
+#[inline]
+pub fn try_read_wstring<T1, T2>(buf: &mut T1, len: usize) -> Result<*mut T2, Error>
+where
+    T1: CDumpReader,
+    T2: CWideChar,
+{
+    let offset = buf.get_read();
+    if len == 0 {
+        return Err(Error::MissingNulTerminator { offset });
+    }
+
+    let ptr = buf.try_read_raw_slice(try_array_size(mem::size_of::<T2>(), len)?)? as *mut T2;
+    // Safety: the slice with `len` characters is in bounds of the buffer, and aligned to the character.
+    match unsafe { *ptr.add(len - 1) } == T2::NUL {
+        true => Ok(ptr),
+        false => Err(Error::MissingNulTerminator { offset }),
+    }
+}
+
+/// Convert length of the array to `usize`.
+#[inline]
+pub fn try_array_len<T>(len: T) -> Result<usize, Error>
+where
+    T: TryInto<usize>,
+{
+    len.try_into().map_err(|_| Error::LengthOverflow)
+}
+
+/// Call the function, and wrap the returned error with the field of type.
+#[inline]
+pub fn in_field<T, F>(ty: &'static str, field: &'static str, f: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    f().map_err(|err| err.in_field(ty, field))
+}
+
+/// Get size of the array with `len` elements of `size` bytes.
+#[inline]
+pub fn try_array_size(size: usize, len: usize) -> Result<usize, Error> {
+    size.checked_mul(len).ok_or(Error::LengthOverflow)
+}
+
+/// Get the length of the array at `ptr`, including the first element for which `is_terminator` returns true, or 0 for
+/// null pointer.
+/// # Safety
+/// Caller must ensure that `ptr` is null or points to the array which contains the terminator.
+#[inline]
+pub unsafe fn terminated_len<T>(ptr: *const T, is_terminator: impl Fn(&T) -> bool) -> usize {
+    if ptr.is_null() {
+        return 0;
+    }
+
+    let mut len = 0;
+    while !is_terminator(&*ptr.add(len)) {
+        len += 1;
+    }
+    len + 1
+}
+
+/// Check whether all bytes of the `value` are zero, e.g. of the null pointer.
+/// # Safety
+/// Caller must ensure that bytes of the `value`, including padding, are initialized.
+#[inline]
+pub unsafe fn is_zeroed<T>(value: &T) -> bool {
+    as_bytes(value).iter().all(|&byte| byte == 0)
+}
+
+/// Check whether the last element of the array at `ptr` with `len` elements, which starts at `offset` of the buffer,
+/// is its terminator.
+/// # Safety
+/// Caller must ensure that `ptr` points to `len` elements.
+#[inline]
+pub unsafe fn try_check_terminator<T>(
+    ptr: *const T,
+    len: usize,
+    offset: usize,
+    is_terminator: impl Fn(&T) -> bool,
+) -> Result<(), Error> {
+    match len {
+        0 => Err(Error::MissingTerminator { offset }),
+        len if is_terminator(&*ptr.add(len - 1)) => Ok(()),
+        _ => Err(Error::MissingTerminator { offset }),
+    }
+}
+
+/// Get size of the array with `len` dynamic objects stored one after another at `ptr`, which sizes are returned by
+/// `size_of`.
+/// # Safety
+/// Caller must ensure that `ptr` points to `len` valid dynamic objects.
+#[inline]
+pub unsafe fn dynamic_array_size(
+    ptr: *const c_void,
+    len: usize,
+    size_of: unsafe fn(*const c_void) -> usize,
+) -> usize {
+    let mut size: usize = 0;
+    for _ in 0..len {
+        size = size.saturating_add(size_of(ptr.byte_add(size)));
+    }
+    size
+}
+
+/// Copy the dynamic object with `size` bytes at `src` to the `offset` of the array with `array_size` bytes, and
+/// returns the offset of the next object.
+/// # Safety
+/// Caller must ensure that `src` points to `size` readable bytes, and `array` to `array_size` writable bytes.
+#[inline]
+pub unsafe fn try_copy_to_dynamic_array(
+    array: *mut u8,
+    array_size: usize,
+    offset: usize,
+    src: *const c_void,
+    size: usize,
+) -> Result<usize, Error> {
+    match offset.checked_add(size) {
+        Some(end) if end <= array_size => {
+            ptr::copy_nonoverlapping(src as *const u8, array.add(offset), size);
+            Ok(end)
+        }
+        _ => Err(Error::OutOfBounds {
+            offset,
+            len: size,
+            buffer_len: array_size,
+        }),
+    }
+}
+
+/// Capacity of the destination's array, and capacities of its inner arrays, which are captured before any field of
+/// the destination is written, because lengths of inner arrays can be stored in data which is overwritten.
+#[derive(Default)]
+pub struct ArrayCapacity {
+    pub len: usize,
+    pub inner: Vec<ArrayCapacity>,
+}
+
+static EMPTY_CAPACITY: ArrayCapacity = ArrayCapacity {
+    len: 0,
+    inner: Vec::new(),
+};
+
+impl ArrayCapacity {
+    /// Get the capacity of the inner array at `index`, which is empty when the destination does not have it.
+    #[inline]
+    pub fn inner(&self, index: usize) -> &Self {
+        self.inner.get(index).unwrap_or(&EMPTY_CAPACITY)
+    }
+}
+
+/// Validator of the shallow field of type `T`, which validates it by [`CDeserialize::try_validate_shallow`] when `T`
+/// implements `CDeserialize`, and otherwise by [`NoShallowValidation`], which accepts any value.
+pub struct ShallowValidator<T, B>(PhantomData<(T, B)>);
+
+impl<T, B> ShallowValidator<T, B> {
+    #[inline]
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T, B> Default for ShallowValidator<T, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: CDeserialize<B>, B: CDumpReader> ShallowValidator<T, B> {
+    /// Validate the shallow field at `dst`.
+    /// # Safety
+    /// Same as [`CDeserialize::try_validate_shallow`].
+    #[inline]
+    pub unsafe fn try_validate(&self, dst: *mut T) -> Result<(), Error> {
+        T::try_validate_shallow(dst)
+    }
+}
+
+/// Fallback of [`ShallowValidator`] for types which do not implement `CDeserialize`, which are plain data.
+pub trait NoShallowValidation<T> {
+    /// Accept any value of the shallow field.
+    /// # Safety
+    /// Always safe, it is unsafe only to match [`ShallowValidator::try_validate`].
+    #[inline]
+    unsafe fn try_validate(&self, _dst: *mut T) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<T, B> NoShallowValidation<T> for ShallowValidator<T, B> {}
+
+/// Const FNV-1a hasher used to compute the layout hash of the types.
+#[derive(Clone, Copy)]
+pub struct LayoutHasher(u64);
+
+impl LayoutHasher {
+    #[inline]
+    pub const fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    #[inline]
+    pub const fn write_bytes(mut self, bytes: &[u8]) -> Self {
+        let mut i = 0;
+        while i < bytes.len() {
+            self.0 ^= bytes[i] as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+            i += 1;
+        }
+        self
+    }
+
+    /// Write the string with separator, so that `"ab", "c"` and `"a", "bc"` have different hashes.
+    #[inline]
+    pub const fn write_str(self, value: &str) -> Self {
+        self.write_bytes(value.as_bytes()).write_bytes(&[0xff])
+    }
+
+    #[inline]
+    pub const fn write_usize(self, value: usize) -> Self {
+        self.write_u64(value as u64)
+    }
+
+    #[inline]
+    pub const fn write_u64(self, value: u64) -> Self {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    #[inline]
+    pub const fn finish(self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for LayoutHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the bytes of the `value`.
+/// # Safety
+/// Caller must ensure that bytes of the `value` are initialized.
+#[inline]
+pub unsafe fn as_bytes<T>(value: &T) -> &[u8] {
+    std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>())
+}
+
+/// Get the `index` aligned to the `T`.
+#[inline]
+pub const fn align_index<T>(index: usize) -> usize {
+    match index % mem::align_of::<T>() {
+        0 => index,
+        m => index.saturating_add(mem::align_of::<T>() - m),
+    }
+}
+
 /// Align the buffer to the size of `T2`.
 #[inline]
 pub fn align_writer<T1, T2>(buf: &mut T1)
@@ -99,3 +524,40 @@ where
         (unsafe { buf.as_mut_ptr_at::<u8>(buf.get_read()) } as usize) % mem::align_of::<T2>()
     );
 }
+
+/// Zero-sized types with alignment of the `bytes` attribute, which the buffer is aligned to.
+#[repr(align(1))]
+pub struct Align1;
+#[repr(align(2))]
+pub struct Align2;
+#[repr(align(4))]
+pub struct Align4;
+#[repr(align(8))]
+pub struct Align8;
+#[repr(align(16))]
+pub struct Align16;
+
+/// Debug representation of the bytes, which shows the length and hex of the first bytes.
+pub struct HexPreview<'a>(pub &'a [u8]);
+
+impl HexPreview<'_> {
+    /// Number of the bytes written as hex.
+    pub const LEN: usize = 16;
+}
+
+impl fmt::Debug for HexPreview<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} bytes", self.0.len())?;
+        for (i, byte) in self.0.iter().take(Self::LEN).enumerate() {
+            let separator = match i {
+                0 => ": ",
+                _ => " ",
+            };
+            write!(f, "{separator}{byte:02x}")?;
+        }
+        if self.0.len() > Self::LEN {
+            write!(f, " ...")?;
+        }
+        write!(f, ">")
+    }
+}
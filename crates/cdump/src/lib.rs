@@ -7,13 +7,39 @@ use aligned_vec::AVec;
 #[cfg(feature = "builtin-buffer")]
 use std::cell::UnsafeCell;
 
-pub use cdump_macro::{CDeserialize, CSerialize};
+pub use cdump_macro::{CDeserialize, CSerialize, CTag, CView};
+pub use error::Error;
 pub use memoffset::offset_of;
+pub use owned::COwned;
+pub mod alloc;
+pub mod envelope;
+mod error;
 pub mod internal;
+mod owned;
+pub mod pnext;
+#[cfg(feature = "builtin-buffer")]
+pub mod relocatable;
+#[cfg(feature = "builtin-buffer")]
+pub use relocatable::{CDumpRelocatableBuffer, CDumpRelocatingReader};
+pub mod shared;
+pub use shared::{CDumpSharedReader, CDumpSharedWriter};
+pub mod tagged;
+pub use tagged::CTag;
+pub mod union;
+pub mod view;
+pub use view::CView;
+pub mod wide;
 
 #[cfg(feature = "cdebug")]
 pub use cdump_macro::CDebug;
 
+#[cfg(feature = "portable")]
+pub use cdump_macro::CPortable;
+#[cfg(feature = "portable")]
+pub use portable::CPortable;
+#[cfg(feature = "portable")]
+mod portable;
+
 /// Trait for buffer suitable for CSerialization.
 /// # Safety
 /// The implementor must ensure that the buffer is prepared for the serialization next objects.
@@ -26,6 +52,18 @@ pub unsafe trait CDumpWriter {
     /// Push the slice to the buffer.
     fn push_slice(&mut self, slice: &[u8]);
 
+    /// Align the buffer to the `T`, or returns an error when the buffer cannot grow.
+    fn try_align<T>(&mut self) -> Result<(), Error> {
+        self.align::<T>();
+        Ok(())
+    }
+
+    /// Push the slice to the buffer, or returns an error when the buffer cannot fit it.
+    fn try_push_slice(&mut self, slice: &[u8]) -> Result<(), Error> {
+        self.push_slice(slice);
+        Ok(())
+    }
+
     /// Get current length of the buffer.
     fn len(&self) -> usize;
 
@@ -38,6 +76,26 @@ pub unsafe trait CDumpWriter {
     /// # Safety
     /// Caller must ensure that the `index` is a valid index in the buffer.
     unsafe fn as_mut_ptr_at(&mut self, index: usize) -> *mut u8;
+
+    /// Called by serialization before the data with `key` under non-null pointer `address` is written, where `slot` is
+    /// the index of the pointer in the buffer. Returns `true` when the data was already written and the slot now
+    /// contains back-reference to it, so the data must be skipped.
+    /// # Remarks
+    /// Default implementation never shares the data, see [`CDumpSharedWriter`].
+    /// # Safety
+    /// Caller must ensure that the `slot` is a valid index of the pointer in the buffer.
+    #[inline]
+    unsafe fn share(&mut self, slot: usize, address: *const u8, key: shared::SharedKey) -> bool {
+        let _ = (slot, address, key);
+        false
+    }
+
+    /// Called by serialization of the struct at `address` before it is written at the current length of the buffer, so
+    /// pointers to it written later, e.g. back-edges of cycles to the root object, can be shared.
+    #[inline]
+    fn record_shared(&mut self, address: *const u8, key: shared::SharedKey) {
+        let _ = (address, key);
+    }
 }
 
 /// Trait for buffer suitable for CDeserialization.
@@ -64,40 +122,232 @@ pub unsafe trait CDumpReader {
     unsafe fn as_mut_ptr_at<T>(&self, index: usize) -> *mut T;
 
     fn get_read(&self) -> usize;
+
+    /// Get length of the whole buffer.
+    fn len(&self) -> usize;
+
+    /// Check if the buffer is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Called by deserialization after the pointer at `slot`, which is located in the buffer, was rewritten to point to
+    /// the data in the buffer, e.g. to record the fixup table of `CDumpRelocatableBuffer`.
+    #[inline]
+    fn patched_pointer(&mut self, slot: *const u8) {
+        let _ = slot;
+    }
+
+    /// Get the data with `key` already read from the buffer, when the pointer with `value` is a back-reference to it.
+    /// Returns `None` when the data under pointer follows in the buffer.
+    /// # Remarks
+    /// Default implementation never resolves back-references, see [`CDumpSharedReader`].
+    #[inline]
+    fn shared_pointer(
+        &self,
+        value: usize,
+        key: shared::SharedKey,
+    ) -> Result<Option<*mut u8>, Error> {
+        let _ = (value, key);
+        Ok(None)
+    }
+
+    /// Called by deserialization before the data with `key` at the `offset` of the buffer is read, or after it for
+    /// dynamic objects, where `ptr` is the deserialized data, so it can be back-referenced later.
+    #[inline]
+    fn record_shared(&mut self, offset: usize, key: shared::SharedKey, ptr: *mut u8) {
+        let _ = (offset, key, ptr);
+    }
+
+    /// Align the buffer to the `T`, and check if the aligned position is in bounds and properly aligned in memory.
+    fn try_align<T>(&mut self) -> Result<(), Error> {
+        self.align::<T>();
+
+        let read = self.get_read();
+        self.check_bounds(read, 0)?;
+
+        // Safety: index is in bounds of the buffer, or one byte past the end of it.
+        let address = unsafe { self.as_mut_ptr_at::<u8>(read) } as usize;
+        match address % mem::align_of::<T>() {
+            0 => Ok(()),
+            _ => Err(Error::Misaligned {
+                offset: read,
+                align: mem::align_of::<T>(),
+            }),
+        }
+    }
+
+    /// Check if `len` bytes starting at the `index` are in bounds of the buffer.
+    fn check_bounds(&self, index: usize, len: usize) -> Result<(), Error> {
+        match index.checked_add(len) {
+            Some(end) if end <= self.len() => Ok(()),
+            _ => Err(Error::OutOfBounds {
+                offset: index,
+                len,
+                buffer_len: self.len(),
+            }),
+        }
+    }
+
+    /// Read raw slice from the buffer, and returns the pointer to start of it. Returns an error instead of reading past
+    /// the end of the buffer.
+    fn try_read_raw_slice(&mut self, len: usize) -> Result<*const u8, Error> {
+        self.check_bounds(self.get_read(), len)?;
+        // Safety: slice is in bounds of the buffer.
+        Ok(unsafe { self.read_raw_slice(len) })
+    }
 }
 
 /// Trait for serializing the raw data to the buffer.
 pub trait CSerialize<T: CDumpWriter> {
+    /// Hash of the layout of `Self`, which covers names, offsets, sizes and alignment of fields, and cdump
+    /// annotations.
+    const LAYOUT_HASH: u64;
+
+    /// Hash of the layout of `Self` without layouts of types under pointers, which is a part of
+    /// [`CSerialize::LAYOUT_HASH`] of types pointing to `Self`, so types can point to each other.
+    const SHALLOW_LAYOUT_HASH: u64 = Self::LAYOUT_HASH;
+
     /// Serialize the data to the buffer.
+    /// # Panics
+    /// Panics when [`CSerialize::try_serialize`] returns an error.
     /// # Safety
-    /// The caller must ensure that the
-    unsafe fn serialize(&self, buf: &mut T);
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn serialize(&self, buf: &mut T) {
+        if let Err(err) = self.try_serialize(buf) {
+            panic!("serialization failed: {}", err);
+        }
+    }
 
     /// Serializes the data to the buffer, ommiting the shallow copy.'
     /// # Params
     /// * `buf` - The buffer to write to.
     /// * `start_index` - The index in the buffer where shallow copied data of the object is located.
+    /// # Panics
+    /// Panics when [`CSerialize::try_serialize_without_shallow_copy`] returns an error.
     /// # Safety
     /// Caller must ensure that the `start_index` is valid.
-    unsafe fn serialize_without_shallow_copy(&self, buf: &mut T, start_index: usize);
+    unsafe fn serialize_without_shallow_copy(&self, buf: &mut T, start_index: usize) {
+        if let Err(err) = self.try_serialize_without_shallow_copy(buf, start_index) {
+            panic!("serialization failed: {}", err);
+        }
+    }
+
+    /// Serialize the data to the buffer, or returns an error when the buffer is full, length of an array overflows
+    /// `usize`, or serializer of dynamic field fails.
+    /// # Remarks
+    /// When an error is returned, the buffer can contain part of the object.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn try_serialize(&self, buf: &mut T) -> Result<(), Error>;
+
+    /// Serialize the [`CSerialize::LAYOUT_HASH`] and the data to the buffer, which can be read by
+    /// [`CDeserialize::try_deserialize_ref_with_layout`].
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn try_serialize_with_layout(&self, buf: &mut T) -> Result<(), Error> {
+        Self::LAYOUT_HASH.try_serialize(buf)?;
+        self.try_serialize(buf)
+    }
+
+    /// Serializes the data to the buffer, ommiting the shallow copy, or returns an error like
+    /// [`CSerialize::try_serialize`].
+    /// # Safety
+    /// Caller must ensure that the `start_index` is valid.
+    unsafe fn try_serialize_without_shallow_copy(
+        &self,
+        buf: &mut T,
+        start_index: usize,
+    ) -> Result<(), Error>;
+}
+
+/// Trait for computing size of the serialized data, implemented by the `CSerialize` derive.
+pub trait CSerializedSize {
+    /// Get number of bytes, including alignment padding, which serialization of the object writes to the empty buffer.
+    /// # Remarks
+    /// Returns [`usize::MAX`] when the size does not fit in `usize`, e.g. length of an array is negative.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn serialized_size(&self) -> usize {
+        self.serialized_end(0)
+    }
+
+    /// Get length of the buffer after serialization of the object to the buffer with `start` bytes.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn serialized_end(&self, start: usize) -> usize;
+
+    /// Get length of the buffer after serialization of the object without the shallow copy, where `end` is the
+    /// length of the buffer after shallow copy.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn serialized_end_without_shallow_copy(&self, end: usize) -> usize;
+}
+
+/// Trait for creating deep copies of the object tree, implemented by the `CDeserialize` derive.
+/// # Safety
+/// [`CDeepCopy::deep_free_without_shallow_copy`] must free every allocation made by
+/// [`CDeepCopy::try_deep_copy_without_shallow_copy`].
+pub unsafe trait CDeepCopy: Sized {
+    /// Set pointers of the shallow copy to null, so it can be freed by [`CDeepCopy::deep_free_without_shallow_copy`]
+    /// before its data is copied.
+    /// # Safety
+    /// `obj` must be valid for writes.
+    unsafe fn null_without_shallow_copy(obj: *mut Self);
+
+    /// Copy the data under pointers of `src` to the memory of the allocator, and write pointers to it to `dst`.
+    /// # Remarks
+    /// When an error is returned, pointers of `dst` which are not copied yet are null, so `dst` can be freed by
+    /// [`CDeepCopy::deep_free_without_shallow_copy`].
+    /// # Safety
+    /// `dst` must contain the shallow copy of `src`, and every pointer in the object tree of `src` must be valid or
+    /// null.
+    unsafe fn try_deep_copy_without_shallow_copy<A: alloc::CDumpAllocator>(
+        src: *const Self,
+        dst: *mut Self,
+        alloc: &A,
+    ) -> Result<(), Error>;
+
+    /// Free the data under pointers of `obj`, without freeing `obj` itself.
+    /// # Safety
+    /// Data under pointers of `obj` must be created by [`CDeepCopy::try_deep_copy_without_shallow_copy`] with the
+    /// same allocator.
+    unsafe fn deep_free_without_shallow_copy<A: alloc::CDumpAllocator>(obj: *mut Self, alloc: &A);
 }
 
 /// Trait for deserializing the raw data from the buffer.
 pub trait CDeserialize<T: CDumpReader>: Sized {
+    /// Hash of the layout of `Self`, equal to [`CSerialize::LAYOUT_HASH`] of the same definition.
+    const LAYOUT_HASH: u64;
+
+    /// Hash of the layout of `Self`, equal to [`CSerialize::SHALLOW_LAYOUT_HASH`] of the same definition.
+    const SHALLOW_LAYOUT_HASH: u64 = Self::LAYOUT_HASH;
+
     /// Deserialize the data from the buffer to the initialized memory.
     /// # Remarks
-    /// Copy the whole object tree to the destination memory with reuse of destination's pointers.
-    /// When debug assertions are enabled, the function will try to check if the destination memory have sufficient
-    /// size.
+    /// Copy the whole object tree to the destination memory with reuse of destination's pointers. Pointers which are
+    /// null, or which storage is too small for the data, are handled by the `policy`.
+    /// Capacity of arrays is given by their length field in `dst`, and capacity of dynamic objects by `size_of`.
+    /// When an error is returned, memory allocated by the policy is freed, and pointers of `dst` are restored, but the
+    /// data under them is unspecified.
     /// # Safety
-    /// The caller must ensure that the next data in the buffer is a valid representation of `Self`.
-    /// Field `dst` can be uninitialized, then reading from it is undefined behavior.
-    unsafe fn deserialize_to(buf: &mut T, dst: *mut Self);
+    /// The caller must ensure that the next data in the buffer is a valid representation of `Self`, and that every
+    /// pointer in the object tree of `dst` is null or valid for writes of its capacity.
+    unsafe fn deserialize_to<A: alloc::CDumpAllocator>(
+        buf: &mut T,
+        dst: *mut Self,
+        policy: &alloc::DestinationPolicy<A>,
+    ) -> Result<(), Error>;
 
     /// Deserializes the data from the buffer to the destination, ommiting the shallow copy.
     /// # Safety
     /// The caller must ensure that the next data in the buffer is a valid representation of deep part of `Self`.
-    unsafe fn deserialize_to_without_shallow_copy(buf: &mut T, temp: *mut Self, dst: *mut Self);
+    unsafe fn deserialize_to_without_shallow_copy<A: alloc::CDumpAllocator>(
+        buf: &mut T,
+        temp: *mut Self,
+        dst: *mut Self,
+        policy: &alloc::DestinationPolicy<A>,
+    ) -> Result<(), Error>;
 
     /// Deserialize the data from the buffer, and returns the reference to object which memory is located in the buffer.
     /// # Safety
@@ -115,34 +365,140 @@ pub trait CDeserialize<T: CDumpReader>: Sized {
     unsafe fn deserialize_ref(buf: &mut T) -> &Self {
         Self::deserialize_ref_mut(buf)
     }
+
+    /// Deserialize the data from the buffer, and returns the reference to object which memory is located in the buffer.
+    /// # Remarks
+    /// Every length, offset, alignment and null terminator is checked against bounds of the buffer before a pointer is
+    /// rewritten, which makes this function suitable for buffers received from an untrusted source. When an error is
+    /// returned, the content of the buffer is unspecified.
+    /// # Safety
+    /// Every bit pattern must be a valid representation of shallow fields of `Self`, e.g. `Self` must not contain
+    /// `bool` or Rust's enums, other than enums deriving `CDeserialize`, which are validated. Deserializers of dynamic
+    /// fields must be sound for any input.
+    unsafe fn try_deserialize_ref_mut(buf: &mut T) -> Result<&mut Self, Error>;
+
+    /// Deserializes the data from the buffer to the destination with checks, ommiting the shallow copy.
+    /// # Safety
+    /// Same as [`CDeserialize::try_deserialize_ref_mut`], and the `dst` must point to the shallow copy of `Self`
+    /// located in the buffer.
+    unsafe fn try_deserialize_ref_mut_without_shallow_copy(
+        buf: &mut T,
+        dst: *mut Self,
+    ) -> Result<(), Error>;
+
+    /// Validate values embedded by value in the shallow copy, like discriminants of enums, which are undefined
+    /// behavior when invalid. Unknown discriminants are replaced by the fallback of the enum, if it has one.
+    /// # Safety
+    /// The `dst` must point to the shallow copy of `Self`, which is not used as `Self` until it is validated.
+    unsafe fn try_validate_shallow(_dst: *mut Self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Deserialize the data from the buffer with checks, and returns the reference to object which memory is located in
+    /// the buffer.
+    /// # Safety
+    /// Same as [`CDeserialize::try_deserialize_ref_mut`].
+    unsafe fn try_deserialize_ref(buf: &mut T) -> Result<&Self, Error> {
+        Self::try_deserialize_ref_mut(buf).map(|reference| &*reference)
+    }
+
+    /// Verify the layout hash written by [`CSerialize::try_serialize_with_layout`], and deserialize the data like
+    /// [`CDeserialize::try_deserialize_ref`].
+    /// # Remarks
+    /// Returns [`Error::LayoutMismatch`] when the data was serialized from different definition of `Self`.
+    /// # Safety
+    /// Same as [`CDeserialize::try_deserialize_ref_mut`].
+    unsafe fn try_deserialize_ref_with_layout(buf: &mut T) -> Result<&Self, Error> {
+        let found = *u64::try_deserialize_ref(buf)?;
+        if found != Self::LAYOUT_HASH {
+            return Err(Error::LayoutMismatch {
+                expected: Self::LAYOUT_HASH,
+                found,
+            });
+        }
+        Self::try_deserialize_ref(buf)
+    }
 }
 
 macro_rules! impl_cserialize_cdeserialize {
     ($t:ident) => {
         impl<T: CDumpWriter> CSerialize<T> for $t {
-            unsafe fn serialize(&self, buf: &mut T) {
-                internal::align_writer::<T, Self>(buf);
-                buf.push_slice(&self.to_ne_bytes());
+            const LAYOUT_HASH: u64 = internal::LayoutHasher::new()
+                .write_str(stringify!($t))
+                .write_usize(mem::size_of::<$t>())
+                .write_usize(mem::align_of::<$t>())
+                .finish();
+
+            unsafe fn try_serialize(&self, buf: &mut T) -> Result<(), Error> {
+                buf.try_align::<Self>()?;
+                buf.try_push_slice(&self.to_ne_bytes())
+            }
+
+            unsafe fn try_serialize_without_shallow_copy(
+                &self,
+                _buf: &mut T,
+                _start_index: usize,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        impl CSerializedSize for $t {
+            unsafe fn serialized_end(&self, start: usize) -> usize {
+                internal::align_index::<Self>(start).saturating_add(mem::size_of::<Self>())
+            }
+
+            unsafe fn serialized_end_without_shallow_copy(&self, end: usize) -> usize {
+                end
+            }
+        }
+
+        unsafe impl CDeepCopy for $t {
+            unsafe fn null_without_shallow_copy(_obj: *mut Self) {}
+
+            unsafe fn try_deep_copy_without_shallow_copy<A: alloc::CDumpAllocator>(
+                _src: *const Self,
+                _dst: *mut Self,
+                _alloc: &A,
+            ) -> Result<(), Error> {
+                Ok(())
             }
 
-            unsafe fn serialize_without_shallow_copy(&self, _buf: &mut T, _start_index: usize) {}
+            unsafe fn deep_free_without_shallow_copy<A: alloc::CDumpAllocator>(
+                _obj: *mut Self,
+                _alloc: &A,
+            ) {
+            }
         }
 
         impl<T: CDumpReader> CDeserialize<T> for $t {
-            unsafe fn deserialize_to(buf: &mut T, dst: *mut Self) {
+            const LAYOUT_HASH: u64 = internal::LayoutHasher::new()
+                .write_str(stringify!($t))
+                .write_usize(mem::size_of::<$t>())
+                .write_usize(mem::align_of::<$t>())
+                .finish();
+
+            unsafe fn deserialize_to<A: alloc::CDumpAllocator>(
+                buf: &mut T,
+                dst: *mut Self,
+                _policy: &alloc::DestinationPolicy<A>,
+            ) -> Result<(), Error> {
                 let size = mem::size_of::<Self>();
                 ptr::copy_nonoverlapping(
                     Self::deserialize_ref_mut(buf) as *mut _ as *mut u8,
                     dst as *mut u8,
                     size,
-                )
+                );
+                Ok(())
             }
 
-            unsafe fn deserialize_to_without_shallow_copy(
+            unsafe fn deserialize_to_without_shallow_copy<A: alloc::CDumpAllocator>(
                 _buf: &mut T,
                 _temp: *mut Self,
                 _dst: *mut Self,
-            ) {
+                _policy: &alloc::DestinationPolicy<A>,
+            ) -> Result<(), Error> {
+                Ok(())
             }
 
             unsafe fn deserialize_ref_mut(buf: &mut T) -> &mut Self {
@@ -152,6 +508,19 @@ macro_rules! impl_cserialize_cdeserialize {
             }
 
             unsafe fn deserialize_ref_mut_without_shallow_copy(_buf: &mut T, _dst: *mut Self) {}
+
+            unsafe fn try_deserialize_ref_mut(buf: &mut T) -> Result<&mut Self, Error> {
+                buf.try_align::<Self>()?;
+                let reference = buf.try_read_raw_slice(mem::size_of::<Self>())?;
+                Ok(&mut *(reference as *mut Self))
+            }
+
+            unsafe fn try_deserialize_ref_mut_without_shallow_copy(
+                _buf: &mut T,
+                _dst: *mut Self,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
         }
     };
 }
@@ -185,6 +554,19 @@ impl CDumpBufferWriter {
         }
     }
 
+    /// Create writer with reserved memory for `capacity` bytes, e.g. computed by
+    /// [`CSerializedSize::serialized_size`].
+    pub fn with_capacity(align: usize, capacity: usize) -> Self {
+        Self {
+            data: AVec::with_capacity(align, capacity),
+        }
+    }
+
+    /// Get number of bytes which can be written without reallocation.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
     pub fn into_reader(self) -> CDumpBufferReader {
         CDumpBufferReader::new(self.data)
     }
@@ -224,6 +606,92 @@ unsafe impl CDumpWriter for CDumpBufferWriter {
     }
 }
 
+/// Buffer writer for CSerialization to the fixed-size memory, e.g. preallocated slot of shared memory.
+/// # Remarks
+/// Infallible methods of [`CDumpWriter`] panic when the slice is full, use `try_` methods of traits to get an error
+/// instead.
+#[cfg(feature = "builtin-buffer")]
+pub struct CDumpSliceWriter<'a> {
+    data: &'a mut [u8],
+    len: usize,
+}
+
+#[cfg(feature = "builtin-buffer")]
+impl<'a> CDumpSliceWriter<'a> {
+    /// Create writer to the `data`.
+    /// # Remarks
+    /// First byte of the `data` must be aligned to the greatest alignment of serialized objects, otherwise `try_align`
+    /// returns an error.
+    pub fn new(data: &'a mut [u8]) -> Self {
+        Self { data, len: 0 }
+    }
+
+    /// Get written part of the slice.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    fn check_capacity(&self, len: usize) -> Result<(), Error> {
+        match self.data.len() - self.len >= len {
+            true => Ok(()),
+            false => Err(Error::BufferFull {
+                len,
+                available: self.data.len() - self.len,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "builtin-buffer")]
+unsafe impl CDumpWriter for CDumpSliceWriter<'_> {
+    fn align<T>(&mut self) {
+        if let Err(err) = self.try_align::<T>() {
+            panic!("unable to align the slice: {}", err);
+        }
+    }
+
+    fn push_slice(&mut self, slice: &[u8]) {
+        if let Err(err) = self.try_push_slice(slice) {
+            panic!("unable to push to the slice: {}", err);
+        }
+    }
+
+    fn try_align<T>(&mut self) -> Result<(), Error> {
+        let address = self.data.as_ptr() as usize + self.len;
+        let missing = match address % mem::align_of::<T>() {
+            0 => 0,
+            m => mem::align_of::<T>() - m,
+        };
+
+        self.check_capacity(missing)?;
+        self.data[self.len..self.len + missing].fill(0);
+        self.len += missing;
+
+        match self.len % mem::align_of::<T>() {
+            0 => Ok(()),
+            _ => Err(Error::Misaligned {
+                offset: self.len,
+                align: mem::align_of::<T>(),
+            }),
+        }
+    }
+
+    fn try_push_slice(&mut self, slice: &[u8]) -> Result<(), Error> {
+        self.check_capacity(slice.len())?;
+        self.data[self.len..self.len + slice.len()].copy_from_slice(slice);
+        self.len += slice.len();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    unsafe fn as_mut_ptr_at(&mut self, index: usize) -> *mut u8 {
+        self.data.as_mut_ptr().add(index)
+    }
+}
+
 /// Simple buffer reader for CDeserialization.
 #[cfg(feature = "builtin-buffer")]
 pub struct CDumpBufferReader {
@@ -270,4 +738,9 @@ unsafe impl CDumpReader for CDumpBufferReader {
     fn get_read(&self) -> usize {
         self.read
     }
+
+    fn len(&self) -> usize {
+        // Safety: the buffer is not mutably borrowed outside of the methods of the reader.
+        unsafe { (*self.data.get()).len() }
+    }
 }
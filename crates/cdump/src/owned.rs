@@ -0,0 +1,108 @@
+use std::{fmt, mem, ops::Deref, ptr::NonNull};
+
+use crate::{
+    alloc::{self, CDumpAllocator, GlobalAllocator},
+    CDeepCopy, CDeserialize, CDumpReader, Error,
+};
+
+/// Owner of the deep copy of `T`, in which every object, array and string under pointers is allocated separately.
+/// The whole tree is freed on drop.
+pub struct COwned<T: CDeepCopy, A: CDumpAllocator = GlobalAllocator> {
+    ptr: NonNull<T>,
+    alloc: A,
+}
+
+impl<T: CDeepCopy> COwned<T> {
+    /// Create deep copy of `src` with the global allocator.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    pub unsafe fn try_new(src: &T) -> Result<Self, Error> {
+        Self::try_new_in(src, GlobalAllocator)
+    }
+
+    /// Deserialize `T` from the buffer with checks, and create deep copy of it with the global allocator.
+    /// # Safety
+    /// Same as [`CDeserialize::try_deserialize_ref_mut`].
+    pub unsafe fn try_deserialize<R>(buf: &mut R) -> Result<Self, Error>
+    where
+        T: CDeserialize<R>,
+        R: CDumpReader,
+    {
+        Self::try_deserialize_in(buf, GlobalAllocator)
+    }
+}
+
+impl<T: CDeepCopy, A: CDumpAllocator> COwned<T, A> {
+    /// Create deep copy of `src` with the allocator.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    pub unsafe fn try_new_in(src: &T, alloc: A) -> Result<Self, Error> {
+        let ptr = alloc::try_deep_copy(src, &alloc)?;
+        Ok(Self {
+            ptr: NonNull::new_unchecked(ptr),
+            alloc,
+        })
+    }
+
+    /// Deserialize `T` from the buffer with checks, and create deep copy of it with the allocator.
+    /// # Safety
+    /// Same as [`CDeserialize::try_deserialize_ref_mut`].
+    pub unsafe fn try_deserialize_in<R>(buf: &mut R, alloc: A) -> Result<Self, Error>
+    where
+        T: CDeserialize<R>,
+        R: CDumpReader,
+    {
+        let src = T::try_deserialize_ref(buf)?;
+        Self::try_new_in(src, alloc)
+    }
+
+    /// Take ownership of the deep copy created by [`COwned::into_raw`].
+    /// # Safety
+    /// `ptr` must be returned by [`COwned::into_raw`] with the same allocator.
+    pub unsafe fn from_raw_in(ptr: *mut T, alloc: A) -> Self {
+        Self {
+            ptr: NonNull::new_unchecked(ptr),
+            alloc,
+        }
+    }
+
+    /// Release the deep copy without freeing it. With [`alloc::LibcAllocator`] every allocation of the tree can be
+    /// freed by C code which calls `free`.
+    pub fn into_raw(self) -> *mut T {
+        let ptr = self.ptr.as_ptr();
+        mem::forget(self);
+        ptr
+    }
+
+    /// Get the pointer to the root of the deep copy.
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+
+    /// Get the allocator of the deep copy.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+}
+
+impl<T: CDeepCopy, A: CDumpAllocator> Deref for COwned<T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the pointer is valid until drop.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: CDeepCopy + fmt::Debug, A: CDumpAllocator> fmt::Debug for COwned<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: CDeepCopy, A: CDumpAllocator> Drop for COwned<T, A> {
+    fn drop(&mut self) {
+        // Safety: the tree is created by `try_deep_copy` with the same allocator.
+        unsafe { alloc::deep_free(self.ptr.as_ptr(), &self.alloc) }
+    }
+}
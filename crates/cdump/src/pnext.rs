@@ -0,0 +1,349 @@
+//! Chains of structures linked by `pNext`, like extension chains of Vulkan.
+//!
+//! Every structure in the chain starts with `sType` and `pNext` fields, and is registered by the `stype` attribute of
+//! `CSerialize` derive. Structures which can occur in the chain are listed by [`crate::pnext_registry`], and the
+//! field with `#[cdump(pnext(registry = ...))]` dispatches every node of the chain to the registered implementation.
+//!
+//! Every serialized node is preceded by its header with presence flag and `sType`. Unregistered nodes are skipped or
+//! rejected on serialization according to [`UnknownStructure`] of the registry, so the reader never has to skip data of
+//! unknown size.
+
+use std::{collections::HashSet, ffi::c_void, fmt, ptr};
+
+use crate::{alloc::CDumpAllocator, CDeserialize, CDumpReader, CDumpWriter, CSerialize, Error};
+
+/// Structure which can be a node of the `pNext` chain, implemented by `#[cdump(stype = ...)]` attribute.
+pub trait CStructureType {
+    /// Value of the leading `sType` field, which identifies the structure in the chain.
+    const STRUCTURE_TYPE: i32;
+}
+
+/// Common header of every structure in the chain, like `VkBaseInStructure`.
+#[derive(Debug)]
+#[repr(C)]
+pub struct BaseStructure {
+    pub s_type: i32,
+    pub p_next: *const BaseStructure,
+}
+
+/// Policy for nodes of the chain which `sType` is not registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownStructure {
+    /// Return [`Error::UnknownDiscriminant`] with the `sType`.
+    Reject,
+    /// Remove the node from the chain, and link its predecessor with the next registered node.
+    Skip,
+}
+
+/// Set of structures which can occur in the chain, implemented by [`crate::pnext_registry`].
+/// # Safety
+/// Methods must treat the object as the structure registered with the given `sType`, and return `None` only for
+/// unregistered `sType`.
+pub unsafe trait CPNextRegistry {
+    /// Policy for unregistered nodes, used by serialization and deep copy.
+    const UNKNOWN: UnknownStructure;
+
+    /// Get size of the structure registered with `s_type`.
+    fn size_of(s_type: i32) -> Option<usize>;
+
+    /// Serialize the structure registered with `s_type`.
+    /// # Safety
+    /// `obj` must point to the valid structure.
+    unsafe fn try_serialize<T: CDumpWriter>(
+        s_type: i32,
+        obj: *const c_void,
+        buf: &mut T,
+    ) -> Option<Result<(), Error>>;
+
+    /// Get end of the serialized structure registered with `s_type`, like [`crate::CSerializedSize::serialized_end`].
+    /// # Safety
+    /// `obj` must point to the valid structure.
+    unsafe fn serialized_end(s_type: i32, obj: *const c_void, start: usize) -> Option<usize>;
+
+    /// Deserialize the structure registered with `s_type` like [`CDeserialize::deserialize_ref_mut`].
+    /// # Safety
+    /// The buffer must contain the structure.
+    unsafe fn deserialize<T: CDumpReader>(s_type: i32, buf: &mut T) -> Option<*mut c_void>;
+
+    /// Deserialize the structure registered with `s_type` like [`CDeserialize::try_deserialize_ref_mut`].
+    /// # Safety
+    /// Like [`CDeserialize::try_deserialize_ref_mut`].
+    unsafe fn try_deserialize<T: CDumpReader>(
+        s_type: i32,
+        buf: &mut T,
+    ) -> Option<Result<*mut c_void, Error>>;
+
+    /// Create deep copy of the structure registered with `s_type`, like [`crate::alloc::try_deep_copy`].
+    /// # Safety
+    /// `obj` must point to the valid structure.
+    unsafe fn try_deep_copy<A: CDumpAllocator>(
+        s_type: i32,
+        obj: *const c_void,
+        alloc: &A,
+    ) -> Option<Result<*mut c_void, Error>>;
+
+    /// Free the deep copy of the structure registered with `s_type`, like [`crate::alloc::deep_free`].
+    /// # Safety
+    /// `obj` must be returned by [`CPNextRegistry::try_deep_copy`] with the same allocator.
+    unsafe fn deep_free<A: CDumpAllocator>(s_type: i32, obj: *mut c_void, alloc: &A) -> Option<()>;
+}
+
+/// Declare the registry of structures which can occur in the `pNext` chain.
+/// # Usage
+/// ```ignore
+/// cdump::pnext_registry! {
+///     pub struct Chain {
+///         DeviceFeatures,
+///         DeviceProperties,
+///     }
+///     unknown = Skip;
+/// }
+/// ```
+/// Every structure must derive `CSerialize` with `stype` attribute, and `CDeserialize`. The optional `unknown` is a
+/// variant of [`UnknownStructure`], which is `Reject` by default.
+#[macro_export]
+macro_rules! pnext_registry {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident { $($ty:ty),* $(,)? }) => {
+        $crate::pnext_registry! {
+            $(#[$meta])*
+            $vis struct $name { $($ty),* }
+            unknown = Reject;
+        }
+    };
+    (
+        $(#[$meta:meta])* $vis:vis struct $name:ident { $($ty:ty),* $(,)? }
+        unknown = $unknown:ident;
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        unsafe impl $crate::pnext::CPNextRegistry for $name {
+            const UNKNOWN: $crate::pnext::UnknownStructure = $crate::pnext::UnknownStructure::$unknown;
+
+            fn size_of(s_type: i32) -> Option<usize> {
+                $crate::pnext_registry!(@dispatch s_type, S, [$($ty),*], ::std::mem::size_of::<S>())
+            }
+
+            unsafe fn try_serialize<T: $crate::CDumpWriter>(
+                s_type: i32,
+                obj: *const ::std::ffi::c_void,
+                buf: &mut T,
+            ) -> Option<Result<(), $crate::Error>> {
+                $crate::pnext_registry!(@dispatch s_type, S, [$($ty),*],
+                    <S as $crate::CSerialize<T>>::try_serialize(&*(obj as *const S), buf))
+            }
+
+            unsafe fn serialized_end(
+                s_type: i32,
+                obj: *const ::std::ffi::c_void,
+                start: usize,
+            ) -> Option<usize> {
+                $crate::pnext_registry!(@dispatch s_type, S, [$($ty),*],
+                    $crate::CSerializedSize::serialized_end(&*(obj as *const S), start))
+            }
+
+            unsafe fn deserialize<T: $crate::CDumpReader>(
+                s_type: i32,
+                buf: &mut T,
+            ) -> Option<*mut ::std::ffi::c_void> {
+                $crate::pnext_registry!(@dispatch s_type, S, [$($ty),*],
+                    <S as $crate::CDeserialize<T>>::deserialize_ref_mut(buf) as *mut S as *mut ::std::ffi::c_void)
+            }
+
+            unsafe fn try_deserialize<T: $crate::CDumpReader>(
+                s_type: i32,
+                buf: &mut T,
+            ) -> Option<Result<*mut ::std::ffi::c_void, $crate::Error>> {
+                $crate::pnext_registry!(@dispatch s_type, S, [$($ty),*],
+                    <S as $crate::CDeserialize<T>>::try_deserialize_ref_mut(buf)
+                        .map(|obj| obj as *mut S as *mut ::std::ffi::c_void))
+            }
+
+            unsafe fn try_deep_copy<A: $crate::alloc::CDumpAllocator>(
+                s_type: i32,
+                obj: *const ::std::ffi::c_void,
+                alloc: &A,
+            ) -> Option<Result<*mut ::std::ffi::c_void, $crate::Error>> {
+                $crate::pnext_registry!(@dispatch s_type, S, [$($ty),*],
+                    $crate::alloc::try_deep_copy(obj as *const S, alloc).map(|obj| obj as *mut ::std::ffi::c_void))
+            }
+
+            unsafe fn deep_free<A: $crate::alloc::CDumpAllocator>(
+                s_type: i32,
+                obj: *mut ::std::ffi::c_void,
+                alloc: &A,
+            ) -> Option<()> {
+                $crate::pnext_registry!(@dispatch s_type, S, [$($ty),*],
+                    $crate::alloc::deep_free(obj as *mut S, alloc))
+            }
+        }
+    };
+    (@dispatch $s_type:ident, $S:ident, [$($ty:ty),*], $body:expr) => {{
+        $(
+            if $s_type == <$ty as $crate::pnext::CStructureType>::STRUCTURE_TYPE {
+                type $S = $ty;
+                return Some($body);
+            }
+        )*
+        None
+    }};
+}
+
+/// Find the first node of the chain which is registered in `R`, skipping or rejecting unregistered nodes. Returns
+/// [`Error::Cycle`] when the skipped nodes lead back to themselves.
+unsafe fn first_registered<R: CPNextRegistry>(
+    obj: *const c_void,
+) -> Result<*const BaseStructure, Error> {
+    let mut node = obj as *const BaseStructure;
+    let mut skipped = HashSet::new();
+    while !node.is_null() && R::size_of((*node).s_type).is_none() {
+        if R::UNKNOWN == UnknownStructure::Reject {
+            return Err(Error::UnknownDiscriminant((*node).s_type as i64));
+        }
+        if !skipped.insert(node) {
+            return Err(Error::Cycle {
+                ty: std::any::type_name::<BaseStructure>(),
+            });
+        }
+        node = (*node).p_next;
+    }
+    Ok(node)
+}
+
+/// Serializer of the `pnext` field.
+/// # Safety
+/// `obj` must point to the valid chain.
+pub unsafe fn try_serialize<R: CPNextRegistry, T: CDumpWriter>(
+    buf: &mut T,
+    obj: *const c_void,
+) -> Result<(), Error> {
+    let node = first_registered::<R>(obj)?;
+    if node.is_null() {
+        return 0i32.try_serialize(buf);
+    }
+
+    let s_type = (*node).s_type;
+    1i32.try_serialize(buf)?;
+    s_type.try_serialize(buf)?;
+    R::try_serialize(s_type, node as *const c_void, buf).expect("sType should be registered")
+}
+
+/// Serialized size function of the `pnext` field.
+/// # Safety
+/// `obj` must point to the valid chain.
+pub unsafe fn serialized_end<R: CPNextRegistry>(obj: *const c_void, end: usize) -> usize {
+    let node = match first_registered::<R>(obj) {
+        Ok(node) => node,
+        Err(_) => return usize::MAX,
+    };
+
+    let end = crate::internal::align_index::<i32>(end);
+    match node.is_null() {
+        true => end.saturating_add(std::mem::size_of::<i32>()),
+        false => R::serialized_end(
+            (*node).s_type,
+            node as *const c_void,
+            end.saturating_add(2 * std::mem::size_of::<i32>()),
+        )
+        .expect("sType should be registered"),
+    }
+}
+
+/// Deserializer of the `pnext` field, which returns null pointer when every node of the chain was skipped.
+/// # Panics
+/// Panics when `sType` is not registered in `R`.
+/// # Safety
+/// The buffer must contain the chain.
+pub unsafe fn deserialize<R: CPNextRegistry, T: CDumpReader>(buf: &mut T) -> (*mut c_void, usize) {
+    if *i32::deserialize_ref(buf) == 0 {
+        return (ptr::null_mut(), 0);
+    }
+
+    let s_type = *i32::deserialize_ref(buf);
+    match R::deserialize(s_type, buf) {
+        Some(obj) => (obj, R::size_of(s_type).unwrap_or_default()),
+        None => panic!("unknown sType {s_type}"),
+    }
+}
+
+/// Checked deserializer of the `pnext` field, which returns [`Error::UnknownDiscriminant`] when `sType` is not
+/// registered in `R`.
+/// # Safety
+/// Like [`CDeserialize::try_deserialize_ref_mut`].
+pub unsafe fn try_deserialize<R: CPNextRegistry, T: CDumpReader>(
+    buf: &mut T,
+) -> Result<(*mut c_void, usize), Error> {
+    if *i32::try_deserialize_ref(buf)? == 0 {
+        return Ok((ptr::null_mut(), 0));
+    }
+
+    let s_type = *i32::try_deserialize_ref(buf)?;
+    match R::try_deserialize(s_type, buf) {
+        Some(obj) => Ok((obj?, R::size_of(s_type).unwrap_or_default())),
+        None => Err(Error::UnknownDiscriminant(s_type as i64)),
+    }
+}
+
+/// Size function of the `pnext` field, which returns zero for unregistered `sType` of the destination.
+/// # Safety
+/// `obj` must point to the valid structure.
+pub unsafe fn size_of<R: CPNextRegistry>(obj: *const c_void) -> usize {
+    R::size_of((*(obj as *const BaseStructure)).s_type).unwrap_or_default()
+}
+
+/// Deep copy function of the `pnext` field.
+/// # Safety
+/// `obj` must point to the valid chain.
+pub unsafe fn try_deep_copy<R: CPNextRegistry, A: CDumpAllocator>(
+    obj: *const c_void,
+    alloc: &A,
+) -> Result<*mut c_void, Error> {
+    let node = first_registered::<R>(obj)?;
+    match node.is_null() {
+        true => Ok(ptr::null_mut()),
+        false => R::try_deep_copy((*node).s_type, node as *const c_void, alloc)
+            .expect("sType should be registered"),
+    }
+}
+
+/// Free function of the `pnext` field.
+/// # Safety
+/// `obj` must be returned by [`try_deep_copy`] with the same allocator.
+pub unsafe fn deep_free<R: CPNextRegistry, A: CDumpAllocator>(obj: *mut c_void, alloc: &A) {
+    if !obj.is_null() {
+        R::deep_free((*(obj as *const BaseStructure)).s_type, obj, alloc)
+            .expect("sType should be registered");
+    }
+}
+
+/// Debug representation of the chain, which lists `sType` of its nodes, and ends with `<cycle>` when the chain leads
+/// back to its node.
+#[repr(transparent)]
+pub struct Chain(BaseStructure);
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        let mut node = &self.0 as *const BaseStructure;
+        let mut visited = HashSet::new();
+        while !node.is_null() {
+            if !visited.insert(node) {
+                list.entry(&format_args!("<cycle>"));
+                break;
+            }
+            // Safety: nodes of the chain are valid, which is required by `cdebugger`.
+            unsafe {
+                list.entry(&(*node).s_type);
+                node = (*node).p_next;
+            }
+        }
+        list.finish()
+    }
+}
+
+/// Debugger of the `pnext` field.
+/// # Safety
+/// `obj` must point to the valid chain, which lives as long as the returned reference is used.
+pub unsafe fn cdebugger(obj: *const c_void) -> &'static dyn fmt::Debug {
+    &*(obj as *const Chain)
+}
@@ -0,0 +1,303 @@
+use std::{
+    ffi::c_void,
+    mem::{self, MaybeUninit},
+    ptr,
+};
+
+use crate::{internal, CDumpReader, CDumpWriter, Error};
+
+/// Trait for the portable encoding, independent of endianness and pointer width.
+///
+/// Integers are written in little endian, and pointers are written as `u64` slots field by field, without any
+/// padding. The portable data is transcoded to the native cdump buffer by the receiver.
+pub trait CPortable: Sized {
+    /// Write the fields of the object, pointers are written as `u64` slots.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object is valid or null.
+    unsafe fn try_write_portable_shallow<W: CDumpWriter>(&self, buf: &mut W) -> Result<(), Error>;
+
+    /// Write the data under the pointers of the object.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn try_write_portable_deep<W: CDumpWriter>(&self, buf: &mut W) -> Result<(), Error>;
+
+    /// Read the fields of the object, pointers contain values of `u64` slots instead of addresses.
+    /// # Safety
+    /// Returned object must not be dereferenced.
+    unsafe fn try_read_portable_shallow<R: CDumpReader>(buf: &mut R) -> Result<Self, Error>;
+
+    /// Transcode the fields of the object from portable `src` to native `dst` buffer at `index`, which is zeroed and
+    /// reserved for the object, so its padding stays zeroed. Types with padding must write each field separately.
+    /// # Safety
+    /// `dst` must contain `size_of::<Self>()` bytes at `index`.
+    unsafe fn try_transcode_portable_shallow<R: CDumpReader, W: CDumpWriter>(
+        src: &mut R,
+        dst: &mut W,
+        index: usize,
+    ) -> Result<(), Error> {
+        let value = Self::try_read_portable_shallow(src)?;
+        ptr::write_unaligned(dst.as_mut_ptr_at(index) as *mut Self, value);
+        Ok(())
+    }
+
+    /// Transcode the data under the pointers of the object from portable `src` to native `dst` buffer.
+    /// # Safety
+    /// `self` must be returned by [`CPortable::try_read_portable_shallow`] from `src`.
+    unsafe fn try_transcode_portable_deep<R: CDumpReader, W: CDumpWriter>(
+        &self,
+        src: &mut R,
+        dst: &mut W,
+    ) -> Result<(), Error>;
+
+    /// Serialize the object to the portable encoding.
+    /// # Safety
+    /// The caller must ensure that every pointer in the object tree is valid or null.
+    unsafe fn try_serialize_portable<W: CDumpWriter>(&self, buf: &mut W) -> Result<(), Error> {
+        self.try_write_portable_shallow(buf)?;
+        self.try_write_portable_deep(buf)
+    }
+
+    /// Transcode the object from portable `src` to native `dst` buffer, which can be deserialized by
+    /// [`crate::CDeserialize`].
+    /// # Safety
+    /// `dst` must be properly aligned for `Self`.
+    unsafe fn try_transcode_portable<R: CDumpReader, W: CDumpWriter>(
+        src: &mut R,
+        dst: &mut W,
+    ) -> Result<(), Error> {
+        dst.try_align::<Self>()?;
+        let index = transcode_shallow::<Self, R, W>(src, dst)?;
+        read_native::<W, Self>(dst, index).try_transcode_portable_deep(src, dst)
+    }
+}
+
+macro_rules! impl_cportable {
+    ($t:ident) => {
+        impl_cportable!($t, $t);
+    };
+    ($t:ident, $wire:ident) => {
+        impl CPortable for $t {
+            unsafe fn try_write_portable_shallow<W: CDumpWriter>(
+                &self,
+                buf: &mut W,
+            ) -> Result<(), Error> {
+                buf.try_push_slice(&(*self as $wire).to_le_bytes())
+            }
+
+            unsafe fn try_write_portable_deep<W: CDumpWriter>(
+                &self,
+                _buf: &mut W,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+
+            unsafe fn try_read_portable_shallow<R: CDumpReader>(
+                buf: &mut R,
+            ) -> Result<Self, Error> {
+                let value = $wire::from_le_bytes(read_bytes(buf)?);
+                value.try_into().map_err(|_| Error::LengthOverflow)
+            }
+
+            unsafe fn try_transcode_portable_deep<R: CDumpReader, W: CDumpWriter>(
+                &self,
+                _src: &mut R,
+                _dst: &mut W,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_cportable!(u8);
+impl_cportable!(u16);
+impl_cportable!(u32);
+impl_cportable!(u64);
+impl_cportable!(u128);
+impl_cportable!(usize, u64);
+impl_cportable!(i8);
+impl_cportable!(i16);
+impl_cportable!(i32);
+impl_cportable!(i64);
+impl_cportable!(i128);
+impl_cportable!(isize, i64);
+
+macro_rules! impl_cportable_float {
+    ($t:ident) => {
+        impl CPortable for $t {
+            unsafe fn try_write_portable_shallow<W: CDumpWriter>(
+                &self,
+                buf: &mut W,
+            ) -> Result<(), Error> {
+                self.to_bits().try_write_portable_shallow(buf)
+            }
+
+            unsafe fn try_write_portable_deep<W: CDumpWriter>(
+                &self,
+                _buf: &mut W,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+
+            unsafe fn try_read_portable_shallow<R: CDumpReader>(
+                buf: &mut R,
+            ) -> Result<Self, Error> {
+                CPortable::try_read_portable_shallow(buf).map($t::from_bits)
+            }
+
+            unsafe fn try_transcode_portable_deep<R: CDumpReader, W: CDumpWriter>(
+                &self,
+                _src: &mut R,
+                _dst: &mut W,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_cportable_float!(f32);
+impl_cportable_float!(f64);
+
+impl CPortable for bool {
+    unsafe fn try_write_portable_shallow<W: CDumpWriter>(&self, buf: &mut W) -> Result<(), Error> {
+        (*self as u8).try_write_portable_shallow(buf)
+    }
+
+    unsafe fn try_write_portable_deep<W: CDumpWriter>(&self, _buf: &mut W) -> Result<(), Error> {
+        Ok(())
+    }
+
+    unsafe fn try_read_portable_shallow<R: CDumpReader>(buf: &mut R) -> Result<Self, Error> {
+        match u8::try_read_portable_shallow(buf)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(Error::UnknownDiscriminant(value as i64)),
+        }
+    }
+
+    unsafe fn try_transcode_portable_deep<R: CDumpReader, W: CDumpWriter>(
+        &self,
+        _src: &mut R,
+        _dst: &mut W,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<T: CPortable, const N: usize> CPortable for [T; N] {
+    unsafe fn try_write_portable_shallow<W: CDumpWriter>(&self, buf: &mut W) -> Result<(), Error> {
+        for element in self {
+            element.try_write_portable_shallow(buf)?;
+        }
+        Ok(())
+    }
+
+    unsafe fn try_write_portable_deep<W: CDumpWriter>(&self, _buf: &mut W) -> Result<(), Error> {
+        Ok(())
+    }
+
+    unsafe fn try_transcode_portable_shallow<R: CDumpReader, W: CDumpWriter>(
+        src: &mut R,
+        dst: &mut W,
+        index: usize,
+    ) -> Result<(), Error> {
+        for i in 0..N {
+            T::try_transcode_portable_shallow(src, dst, index + mem::size_of::<T>() * i)?;
+        }
+        Ok(())
+    }
+
+    unsafe fn try_read_portable_shallow<R: CDumpReader>(buf: &mut R) -> Result<Self, Error> {
+        let mut array = MaybeUninit::<[T; N]>::uninit();
+        let ptr = array.as_mut_ptr() as *mut T;
+        for i in 0..N {
+            ptr.add(i).write(T::try_read_portable_shallow(buf)?);
+        }
+        Ok(array.assume_init())
+    }
+
+    unsafe fn try_transcode_portable_deep<R: CDumpReader, W: CDumpWriter>(
+        &self,
+        _src: &mut R,
+        _dst: &mut W,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Write the pointer slot of the portable encoding.
+#[inline]
+pub fn write_slot<W: CDumpWriter>(buf: &mut W, slot: usize) -> Result<(), Error> {
+    buf.try_push_slice(&(slot as u64).to_le_bytes())
+}
+
+/// Read the pointer slot of the portable encoding.
+#[inline]
+pub fn read_slot<R: CDumpReader>(buf: &mut R) -> Result<usize, Error> {
+    internal::try_array_len(u64::from_le_bytes(read_bytes(buf)?))
+}
+
+/// Transcode the array of pointer slots from portable `src` to native `dst` buffer, and returns index of the array
+/// in `dst`.
+#[inline]
+pub fn transcode_slots<R: CDumpReader, W: CDumpWriter>(
+    src: &mut R,
+    dst: &mut W,
+    len: usize,
+) -> Result<usize, Error> {
+    dst.try_align::<*const c_void>()?;
+    let array_start_index = dst.len();
+    for _ in 0..len {
+        dst.try_push_slice(&read_slot(src)?.to_ne_bytes())?;
+    }
+    Ok(array_start_index)
+}
+
+/// Reserve zeroed storage for `T` at the end of native `dst` buffer, and transcode its fields from portable `src` to
+/// it. Returns index of the object in `dst`.
+/// # Safety
+/// `dst` must be properly aligned for `T`.
+pub unsafe fn transcode_shallow<T: CPortable, R: CDumpReader, W: CDumpWriter>(
+    src: &mut R,
+    dst: &mut W,
+) -> Result<usize, Error> {
+    const ZEROS: [u8; 64] = [0; 64];
+
+    let index = dst.len();
+    let mut remaining = mem::size_of::<T>();
+    while remaining > 0 {
+        let len = remaining.min(ZEROS.len());
+        dst.try_push_slice(&ZEROS[..len])?;
+        remaining -= len;
+    }
+    T::try_transcode_portable_shallow(src, dst, index)?;
+    Ok(index)
+}
+
+/// Write the pointer slot of the portable encoding to the native `dst` buffer at `index`.
+/// # Safety
+/// `dst` must contain a pointer at `index`.
+#[inline]
+pub unsafe fn transcode_slot<R: CDumpReader, W: CDumpWriter>(
+    src: &mut R,
+    dst: &mut W,
+    index: usize,
+) -> Result<(), Error> {
+    let slot = read_slot(src)?;
+    ptr::write_unaligned(dst.as_mut_ptr_at(index) as *mut usize, slot);
+    Ok(())
+}
+
+/// Get the native value written to the `dst` buffer at `index`.
+/// # Safety
+/// Caller must ensure that `dst` contains valid `T` at `index`.
+#[inline]
+pub unsafe fn read_native<W: CDumpWriter, T>(dst: &mut W, index: usize) -> T {
+    ptr::read_unaligned(dst.as_mut_ptr_at(index) as *const T)
+}
+
+fn read_bytes<R: CDumpReader, const N: usize>(buf: &mut R) -> Result<[u8; N], Error> {
+    let ptr = buf.try_read_raw_slice(N)?;
+    Ok(unsafe { ptr::read_unaligned(ptr as *const [u8; N]) })
+}
@@ -0,0 +1,183 @@
+use std::mem;
+
+use aligned_vec::AVec;
+
+use crate::{CDeserialize, CDumpBufferReader, CDumpBufferWriter, CDumpReader, Error};
+
+/// Reader which records locations of pointers rewritten by deserialization.
+pub struct CDumpRelocatingReader {
+    reader: CDumpBufferReader,
+    fixups: Vec<usize>,
+}
+
+unsafe impl CDumpReader for CDumpRelocatingReader {
+    fn align<T>(&mut self) {
+        self.reader.align::<T>()
+    }
+
+    fn add_read(&mut self, len: usize) {
+        self.reader.add_read(len)
+    }
+
+    unsafe fn read_raw_slice(&mut self, len: usize) -> *const u8 {
+        self.reader.read_raw_slice(len)
+    }
+
+    unsafe fn as_mut_ptr_at<T>(&self, index: usize) -> *mut T {
+        self.reader.as_mut_ptr_at(index)
+    }
+
+    fn get_read(&self) -> usize {
+        self.reader.get_read()
+    }
+
+    fn len(&self) -> usize {
+        self.reader.len()
+    }
+
+    fn patched_pointer(&mut self, slot: *const u8) {
+        // Safety: index 0 is in bounds of the buffer, or one byte past the end of it.
+        let base = unsafe { self.reader.as_mut_ptr_at::<u8>(0) } as usize;
+        self.fixups.push(slot as usize - base);
+    }
+}
+
+/// Base of pointers in relocatable image. It is not 0, so the pointer to the start of the image is not null.
+pub const IMAGE_BASE: usize = 1;
+
+/// Buffer with deserialized object, which can be moved to any address.
+///
+/// Every pointer in the buffer is stored as `base + offset`, where `offset` is the index of the pointed data in the
+/// buffer. Locations of the pointers are stored in the fixup table, which allows to change the `base` in one pass.
+/// Buffer with `base` equal to [`IMAGE_BASE`] is relocatable image, which can be copied e.g. to shared memory or a
+/// file.
+pub struct CDumpRelocatableBuffer {
+    data: AVec<u8>,
+    fixups: Vec<usize>,
+    base: usize,
+}
+
+impl CDumpRelocatableBuffer {
+    /// Deserialize `T` located at the start of the `writer` with checks, and returns relocatable image of it.
+    /// # Safety
+    /// Same as [`CDeserialize::try_deserialize_ref_mut`].
+    pub unsafe fn try_new<T>(writer: CDumpBufferWriter) -> Result<Self, Error>
+    where
+        T: CDeserialize<CDumpRelocatingReader>,
+    {
+        let mut reader = CDumpRelocatingReader {
+            reader: writer.into_reader(),
+            fixups: Vec::new(),
+        };
+        T::try_deserialize_ref_mut(&mut reader)?;
+
+        let data = reader.reader.data.into_inner();
+        let mut buffer = Self {
+            base: data.as_ptr() as usize,
+            data,
+            fixups: reader.fixups,
+        };
+        buffer.rebase(IMAGE_BASE);
+        Ok(buffer)
+    }
+
+    /// Create the buffer from relocatable image and its fixup table, e.g. loaded from a file.
+    /// # Remarks
+    /// Returns an error when any fixup is out of bounds of the `image` or is not aligned to a pointer, or when the
+    /// pointer at it does not point into the `image`.
+    pub fn try_from_image(image: &[u8], fixups: Vec<usize>, align: usize) -> Result<Self, Error> {
+        for &fixup in &fixups {
+            match fixup.checked_add(mem::size_of::<usize>()) {
+                Some(end) if end <= image.len() => {}
+                _ => {
+                    return Err(Error::OutOfBounds {
+                        offset: fixup,
+                        len: mem::size_of::<usize>(),
+                        buffer_len: image.len(),
+                    })
+                }
+            }
+            if fixup % mem::align_of::<usize>() != 0 {
+                return Err(Error::Misaligned {
+                    offset: fixup,
+                    align: mem::align_of::<usize>(),
+                });
+            }
+
+            // Pointer to the end of the image is valid, e.g. for an empty array at the end.
+            let slot = &image[fixup..fixup + mem::size_of::<usize>()];
+            let ptr = usize::from_ne_bytes(slot.try_into().unwrap());
+            match ptr.checked_sub(IMAGE_BASE) {
+                Some(offset) if offset <= image.len() => {}
+                _ => return Err(Error::InvalidRelocation { offset: fixup }),
+            }
+        }
+
+        Ok(Self {
+            data: AVec::from_slice(align, image),
+            fixups,
+            base: IMAGE_BASE,
+        })
+    }
+
+    /// Get bytes of the buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get indices of pointers in the buffer.
+    pub fn fixups(&self) -> &[usize] {
+        &self.fixups
+    }
+
+    /// Get the base which is added to the offsets of pointers.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Change the base of every pointer in the buffer, e.g. to [`IMAGE_BASE`] before copying the image, or to the
+    /// address where the image is mapped in another process.
+    pub fn rebase(&mut self, new_base: usize) {
+        // Safety: fixups are validated to be in bounds of the buffer.
+        unsafe { rebase(&mut self.data, &self.fixups, self.base, new_base) };
+        self.base = new_base;
+    }
+
+    /// Get the address in this buffer of the pointer read from the buffer with the current base, without patching
+    /// the buffer.
+    pub fn resolve<T>(&self, ptr: *const T) -> *const T {
+        if ptr.is_null() {
+            return ptr;
+        }
+        (ptr as usize)
+            .wrapping_sub(self.base)
+            .wrapping_add(self.data.as_ptr() as usize) as *const T
+    }
+
+    /// Get the shallow copy of the root object, which pointers are not rebased. Use [`CDumpRelocatableBuffer::resolve`]
+    /// to read data under them.
+    /// # Safety
+    /// The buffer must contain `T` at the start.
+    pub unsafe fn root_unpatched<T>(&self) -> &T {
+        &*(self.data.as_ptr() as *const T)
+    }
+
+    /// Rebase the buffer to its own address, and returns the root object.
+    /// # Safety
+    /// The buffer must contain `T` at the start.
+    pub unsafe fn root<T>(&mut self) -> &T {
+        self.rebase(self.data.as_ptr() as usize);
+        self.root_unpatched()
+    }
+}
+
+/// Change the base of pointers located at `fixups` in the `data` from `old_base` to `new_base`.
+/// # Safety
+/// Every fixup must be an index of the pointer in the `data`.
+pub unsafe fn rebase(data: &mut [u8], fixups: &[usize], old_base: usize, new_base: usize) {
+    let delta = new_base.wrapping_sub(old_base);
+    for &fixup in fixups {
+        let slot = data.as_mut_ptr().add(fixup) as *mut usize;
+        slot.write_unaligned(slot.read_unaligned().wrapping_add(delta));
+    }
+}
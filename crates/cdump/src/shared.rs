@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use crate::{internal::LayoutHasher, CDumpReader, CDumpWriter, Error};
+
+/// Bit of the pointer slot which marks the back-reference, other bits contain the offset of the referenced data.
+pub const BACK_REFERENCE: usize = 1 << (usize::BITS - 1);
+
+/// Identity of the data under pointer, which is shared only with data of the same type and length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SharedKey {
+    pub type_hash: u64,
+    pub len: usize,
+}
+
+impl SharedKey {
+    #[inline]
+    pub const fn new(type_hash: u64, len: usize) -> Self {
+        Self { type_hash, len }
+    }
+
+    /// Key of the C string, which length is given by its address.
+    #[inline]
+    pub const fn cstring() -> Self {
+        Self::new(LayoutHasher::new().write_str("CString").finish(), 0)
+    }
+
+    /// Key of the wide string with characters of `char_size` bytes, which length is given by its address.
+    #[inline]
+    pub const fn wstring(char_size: usize) -> Self {
+        Self::new(
+            LayoutHasher::new()
+                .write_str("WString")
+                .write_usize(char_size)
+                .finish(),
+            0,
+        )
+    }
+
+    /// Key of `len` opaque bytes aligned to `align`.
+    #[inline]
+    pub const fn bytes(align: usize, len: usize) -> Self {
+        Self::new(
+            LayoutHasher::new()
+                .write_str("bytes")
+                .write_usize(align)
+                .finish(),
+            len,
+        )
+    }
+
+    /// Key of the dynamic object read by the deserializer with `name`.
+    #[inline]
+    pub const fn dynamic(name: &str) -> Self {
+        Self::new(
+            LayoutHasher::new()
+                .write_str("dynamic")
+                .write_str(name)
+                .finish(),
+            0,
+        )
+    }
+
+    /// Key of the array with `len` pointers to elements with `type_hash`.
+    #[inline]
+    pub const fn pointers(type_hash: u64, len: usize) -> Self {
+        Self::new(
+            LayoutHasher::new()
+                .write_str("*")
+                .write_u64(type_hash)
+                .finish(),
+            len,
+        )
+    }
+
+    /// Key of the array with `len` pointers to arrays of elements with `type_hash`.
+    #[inline]
+    pub const fn arrays(type_hash: u64, len: usize) -> Self {
+        Self::new(
+            LayoutHasher::new()
+                .write_str("[]")
+                .write_u64(type_hash)
+                .finish(),
+            len,
+        )
+    }
+}
+
+/// Writer which serializes data shared by multiple pointers only once, and writes back-references to it instead.
+/// # Remarks
+/// Data is identified by its address and [`SharedKey`], so a struct and its first field are not shared. Buffer must be
+/// read by [`CDumpSharedReader`].
+pub struct CDumpSharedWriter<W> {
+    writer: W,
+    offsets: HashMap<(usize, SharedKey), usize>,
+}
+
+impl<W: CDumpWriter> CDumpSharedWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offsets: HashMap::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+unsafe impl<W: CDumpWriter> CDumpWriter for CDumpSharedWriter<W> {
+    fn align<T>(&mut self) {
+        self.writer.align::<T>()
+    }
+
+    fn push_slice(&mut self, slice: &[u8]) {
+        self.writer.push_slice(slice)
+    }
+
+    fn try_align<T>(&mut self) -> Result<(), Error> {
+        self.writer.try_align::<T>()
+    }
+
+    fn try_push_slice(&mut self, slice: &[u8]) -> Result<(), Error> {
+        self.writer.try_push_slice(slice)
+    }
+
+    fn len(&self) -> usize {
+        self.writer.len()
+    }
+
+    unsafe fn as_mut_ptr_at(&mut self, index: usize) -> *mut u8 {
+        self.writer.as_mut_ptr_at(index)
+    }
+
+    unsafe fn share(&mut self, slot: usize, address: *const u8, key: SharedKey) -> bool {
+        let len = self.len();
+        let (value, shared) = match self.offsets.get(&(address as usize, key)) {
+            Some(&offset) => (BACK_REFERENCE | offset, true),
+            None => {
+                self.offsets.insert((address as usize, key), len);
+                // Value of the new pointer is only compared with null, but it must not look like a back-reference.
+                (1, false)
+            }
+        };
+
+        (self.as_mut_ptr_at(slot) as *mut usize).write_unaligned(value);
+        shared
+    }
+
+    fn record_shared(&mut self, address: *const u8, key: SharedKey) {
+        let len = self.len();
+        self.offsets.entry((address as usize, key)).or_insert(len);
+    }
+}
+
+/// Reader of the buffer written by [`CDumpSharedWriter`], which resolves back-references to the data already read.
+pub struct CDumpSharedReader<R> {
+    reader: R,
+    pointers: HashMap<(usize, SharedKey), usize>,
+}
+
+impl<R: CDumpReader> CDumpSharedReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pointers: HashMap::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+unsafe impl<R: CDumpReader> CDumpReader for CDumpSharedReader<R> {
+    fn align<T>(&mut self) {
+        self.reader.align::<T>()
+    }
+
+    fn add_read(&mut self, len: usize) {
+        self.reader.add_read(len)
+    }
+
+    unsafe fn read_raw_slice(&mut self, len: usize) -> *const u8 {
+        self.reader.read_raw_slice(len)
+    }
+
+    unsafe fn as_mut_ptr_at<T>(&self, index: usize) -> *mut T {
+        self.reader.as_mut_ptr_at(index)
+    }
+
+    fn get_read(&self) -> usize {
+        self.reader.get_read()
+    }
+
+    fn len(&self) -> usize {
+        self.reader.len()
+    }
+
+    fn patched_pointer(&mut self, slot: *const u8) {
+        self.reader.patched_pointer(slot)
+    }
+
+    fn try_align<T>(&mut self) -> Result<(), Error> {
+        self.reader.try_align::<T>()
+    }
+
+    fn shared_pointer(&self, value: usize, key: SharedKey) -> Result<Option<*mut u8>, Error> {
+        if value & BACK_REFERENCE == 0 {
+            return Ok(None);
+        }
+
+        let offset = value & !BACK_REFERENCE;
+        match self.pointers.get(&(offset, key)) {
+            Some(&ptr) => Ok(Some(ptr as *mut u8)),
+            None => Err(Error::InvalidBackReference { offset }),
+        }
+    }
+
+    fn record_shared(&mut self, offset: usize, key: SharedKey, ptr: *mut u8) {
+        self.pointers.insert((offset, key), ptr as usize);
+    }
+}
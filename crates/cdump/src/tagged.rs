@@ -0,0 +1,373 @@
+//! Dynamic objects identified by a leading tag, like `enum` discriminant at the start of the struct.
+//!
+//! Tag enum derives `CTag`, and every struct registers itself for a variant by `#[cdump(tag = ...)]` attribute of
+//! `CSerialize` derive. Field with `#[cdump(tagged = ...)]` dispatches the object by its tag to the registered
+//! implementation.
+//!
+//! Every serialized object is preceded by the index of the variant of its tag, so the reader never interprets tag
+//! values from the buffer before they are validated.
+
+use std::{ffi::c_void, fmt::Debug, marker::PhantomData};
+
+use crate::{
+    alloc::CDumpAllocator, CDeepCopy, CDeserialize, CDumpReader, CDumpWriter, CSerialize,
+    CSerializedSize, Error,
+};
+
+/// Struct registered in the tag type `T`, implemented by `#[cdump(tag = ...)]` attribute.
+pub trait CTagged<T> {
+    /// Value of the leading tag field, which identifies the struct.
+    const TAG: T;
+}
+
+/// Registration of the struct tagged by the variant with discriminant `TAG`, implemented for the tag enum by
+/// `#[cdump(tag = ...)]` attribute.
+/// # Safety
+/// `Struct` must start with the tag, which is the variant with discriminant `TAG`.
+pub unsafe trait Registered<const TAG: i64>: Sized {
+    /// Struct tagged by the variant.
+    type Struct: CTagged<Self> + 'static;
+}
+
+/// Tag type of dynamic objects, implemented by the `CTag` derive.
+/// # Safety
+/// Methods must treat the object as the struct registered at the given index, and `index_of` must return only indexes
+/// of registered structs.
+pub unsafe trait CTag: Copy + PartialEq + Debug {
+    /// Get index of the struct registered with `tag`.
+    fn index_of(tag: Self) -> Option<u32>;
+
+    /// Read discriminant of the tag at the start of `obj`, without interpreting it as `Self`.
+    /// # Safety
+    /// `obj` must point to the aligned memory of the tag.
+    unsafe fn discriminant(obj: *const c_void) -> i64;
+
+    /// Get size of the struct registered at `index`.
+    fn size_of(index: u32) -> Option<usize>;
+
+    /// Serialize the struct registered at `index`.
+    /// # Safety
+    /// `obj` must point to the valid struct.
+    unsafe fn try_serialize<T: CDumpWriter>(
+        index: u32,
+        obj: *const c_void,
+        buf: &mut T,
+    ) -> Result<(), Error>;
+
+    /// Get end of the serialized struct registered at `index`, like [`crate::CSerializedSize::serialized_end`].
+    /// # Safety
+    /// `obj` must point to the valid struct.
+    unsafe fn serialized_end(index: u32, obj: *const c_void, start: usize) -> usize;
+
+    /// Deserialize the struct registered at `index` like [`CDeserialize::deserialize_ref_mut`].
+    /// # Safety
+    /// The buffer must contain the struct.
+    unsafe fn deserialize<T: CDumpReader>(index: u32, buf: &mut T) -> Option<*mut c_void>;
+
+    /// Deserialize the struct registered at `index` like [`CDeserialize::try_deserialize_ref_mut`].
+    /// # Safety
+    /// Like [`CDeserialize::try_deserialize_ref_mut`].
+    unsafe fn try_deserialize<T: CDumpReader>(
+        index: u32,
+        buf: &mut T,
+    ) -> Option<Result<*mut c_void, Error>>;
+
+    /// Create deep copy of the struct registered at `index`, like [`crate::alloc::try_deep_copy`].
+    /// # Safety
+    /// `obj` must point to the valid struct.
+    unsafe fn try_deep_copy<A: CDumpAllocator>(
+        index: u32,
+        obj: *const c_void,
+        alloc: &A,
+    ) -> Result<*mut c_void, Error>;
+
+    /// Free the deep copy of the struct registered at `index`, like [`crate::alloc::deep_free`].
+    /// # Safety
+    /// `obj` must be returned by [`CTag::try_deep_copy`] with the same allocator.
+    unsafe fn deep_free<A: CDumpAllocator>(index: u32, obj: *mut c_void, alloc: &A);
+
+    /// Get debug representation of the struct registered at `index`.
+    /// # Safety
+    /// `obj` must point to the valid struct, which lives as long as the returned reference is used.
+    #[cfg(feature = "cdebug")]
+    unsafe fn debug(index: u32, obj: *const c_void) -> &'static dyn Debug;
+}
+
+/// Dispatcher of the variant with discriminant `TAG`, which methods are implemented for the registered struct, and
+/// return `None` by [`NotRegistered`] otherwise.
+pub struct Registry<Tag, const TAG: i64>(PhantomData<Tag>);
+
+impl<Tag, const TAG: i64> Registry<Tag, TAG> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<Tag, const TAG: i64> Default for Registry<Tag, TAG> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Tag: CTag + Registered<TAG>, const TAG: i64> Registry<Tag, TAG> {
+    pub fn size_of(&self) -> Option<usize> {
+        Some(std::mem::size_of::<Tag::Struct>())
+    }
+
+    /// # Safety
+    /// `obj` must point to the valid struct.
+    pub unsafe fn try_serialize<T: CDumpWriter>(
+        &self,
+        obj: *const c_void,
+        buf: &mut T,
+    ) -> Option<Result<(), Error>>
+    where
+        Tag::Struct: CSerialize<T>,
+    {
+        Some((*(obj as *const Tag::Struct)).try_serialize(buf))
+    }
+
+    /// # Safety
+    /// `obj` must point to the valid struct.
+    pub unsafe fn serialized_end(&self, obj: *const c_void, start: usize) -> Option<usize>
+    where
+        Tag::Struct: CSerializedSize,
+    {
+        Some((*(obj as *const Tag::Struct)).serialized_end(start))
+    }
+
+    /// # Safety
+    /// The buffer must contain the struct.
+    pub unsafe fn deserialize<T: CDumpReader>(&self, buf: &mut T) -> Option<*mut c_void>
+    where
+        Tag::Struct: CDeserialize<T>,
+    {
+        Some(Tag::Struct::deserialize_ref_mut(buf) as *mut Tag::Struct as *mut c_void)
+    }
+
+    /// Deserialize the struct and check that its tag is the registered variant, or return
+    /// [`Error::UnknownDiscriminant`] with the discriminant from the buffer.
+    /// # Safety
+    /// Like [`CDeserialize::try_deserialize_ref_mut`].
+    pub unsafe fn try_deserialize<T: CDumpReader>(
+        &self,
+        buf: &mut T,
+    ) -> Option<Result<*mut c_void, Error>>
+    where
+        Tag::Struct: CDeserialize<T>,
+    {
+        Some(Tag::Struct::try_deserialize_ref_mut(buf).and_then(|obj| {
+            let obj = obj as *mut Tag::Struct as *mut c_void;
+            let discriminant = Tag::discriminant(obj);
+            if discriminant == TAG {
+                Ok(obj)
+            } else {
+                Err(Error::UnknownDiscriminant(discriminant))
+            }
+        }))
+    }
+
+    /// # Safety
+    /// `obj` must point to the valid struct.
+    pub unsafe fn try_deep_copy<A: CDumpAllocator>(
+        &self,
+        obj: *const c_void,
+        alloc: &A,
+    ) -> Option<Result<*mut c_void, Error>>
+    where
+        Tag::Struct: CDeepCopy,
+    {
+        Some(
+            crate::alloc::try_deep_copy(obj as *const Tag::Struct, alloc)
+                .map(|obj| obj as *mut c_void),
+        )
+    }
+
+    /// # Safety
+    /// `obj` must be returned by [`Registry::try_deep_copy`] with the same allocator.
+    pub unsafe fn deep_free<A: CDumpAllocator>(&self, obj: *mut c_void, alloc: &A) -> Option<()>
+    where
+        Tag::Struct: CDeepCopy,
+    {
+        crate::alloc::deep_free(obj as *mut Tag::Struct, alloc);
+        Some(())
+    }
+
+    /// # Safety
+    /// `obj` must point to the valid struct, which lives as long as the returned reference is used.
+    #[cfg(feature = "cdebug")]
+    pub unsafe fn debug(&self, obj: *const c_void) -> Option<&'static dyn Debug>
+    where
+        Tag::Struct: Debug,
+    {
+        Some(&*(obj as *const Tag::Struct))
+    }
+}
+
+/// Methods of [`Registry`] for variants without registered struct.
+pub trait NotRegistered {
+    fn size_of(&self) -> Option<usize> {
+        None
+    }
+
+    /// # Safety
+    /// Does nothing.
+    unsafe fn try_serialize<T: CDumpWriter>(
+        &self,
+        _obj: *const c_void,
+        _buf: &mut T,
+    ) -> Option<Result<(), Error>> {
+        None
+    }
+
+    /// # Safety
+    /// Does nothing.
+    unsafe fn serialized_end(&self, _obj: *const c_void, _start: usize) -> Option<usize> {
+        None
+    }
+
+    /// # Safety
+    /// Does nothing.
+    unsafe fn deserialize<T: CDumpReader>(&self, _buf: &mut T) -> Option<*mut c_void> {
+        None
+    }
+
+    /// # Safety
+    /// Does nothing.
+    unsafe fn try_deserialize<T: CDumpReader>(
+        &self,
+        _buf: &mut T,
+    ) -> Option<Result<*mut c_void, Error>> {
+        None
+    }
+
+    /// # Safety
+    /// Does nothing.
+    unsafe fn try_deep_copy<A: CDumpAllocator>(
+        &self,
+        _obj: *const c_void,
+        _alloc: &A,
+    ) -> Option<Result<*mut c_void, Error>> {
+        None
+    }
+
+    /// # Safety
+    /// Does nothing.
+    unsafe fn deep_free<A: CDumpAllocator>(&self, _obj: *mut c_void, _alloc: &A) -> Option<()> {
+        None
+    }
+
+    /// # Safety
+    /// Does nothing.
+    #[cfg(feature = "cdebug")]
+    unsafe fn debug(&self, _obj: *const c_void) -> Option<&'static dyn Debug> {
+        None
+    }
+}
+
+impl<Tag, const TAG: i64> NotRegistered for Registry<Tag, TAG> {}
+
+/// Get index of the struct under `obj`, or [`Error::UnregisteredTag`].
+unsafe fn index_of<Tag: CTag>(obj: *const c_void) -> Result<u32, Error> {
+    let tag = *(obj as *const Tag);
+    Tag::index_of(tag).ok_or_else(|| Error::UnregisteredTag {
+        tag: format!("{tag:?}"),
+    })
+}
+
+/// Serializer of the `tagged` field.
+/// # Safety
+/// `obj` must point to the valid struct, which starts with the tag.
+pub unsafe fn try_serialize<Tag: CTag, T: CDumpWriter>(
+    buf: &mut T,
+    obj: *const c_void,
+) -> Result<(), Error> {
+    let index = index_of::<Tag>(obj)?;
+    index.try_serialize(buf)?;
+    Tag::try_serialize(index, obj, buf)
+}
+
+/// Serialized size function of the `tagged` field.
+/// # Safety
+/// `obj` must point to the valid struct, which starts with the tag.
+pub unsafe fn serialized_end<Tag: CTag>(obj: *const c_void, end: usize) -> usize {
+    match index_of::<Tag>(obj) {
+        Ok(index) => Tag::serialized_end(
+            index,
+            obj,
+            crate::internal::align_index::<u32>(end).saturating_add(std::mem::size_of::<u32>()),
+        ),
+        Err(_) => usize::MAX,
+    }
+}
+
+/// Deserializer of the `tagged` field.
+/// # Panics
+/// Panics when index of the struct is out of the registry.
+/// # Safety
+/// The buffer must contain the struct.
+pub unsafe fn deserialize<Tag: CTag, T: CDumpReader>(buf: &mut T) -> (*mut c_void, usize) {
+    let index = *u32::deserialize_ref(buf);
+    match Tag::deserialize(index, buf) {
+        Some(obj) => (obj, Tag::size_of(index).unwrap_or_default()),
+        None => panic!(
+            "unknown index {index} of struct tagged by {}",
+            std::any::type_name::<Tag>()
+        ),
+    }
+}
+
+/// Checked deserializer of the `tagged` field, which returns [`Error::UnknownDiscriminant`] with the index of the
+/// struct when it is out of the registry, or with the discriminant of the tag when it is not the registered variant.
+/// # Safety
+/// Like [`CDeserialize::try_deserialize_ref_mut`].
+pub unsafe fn try_deserialize<Tag: CTag, T: CDumpReader>(
+    buf: &mut T,
+) -> Result<(*mut c_void, usize), Error> {
+    let index = *u32::try_deserialize_ref(buf)?;
+    match Tag::try_deserialize(index, buf) {
+        Some(obj) => Ok((obj?, Tag::size_of(index).unwrap_or_default())),
+        None => Err(Error::UnknownDiscriminant(index as i64)),
+    }
+}
+
+/// Size function of the `tagged` field, which returns zero for unregistered tag of the destination.
+/// # Safety
+/// `obj` must point to the valid struct, which starts with the tag.
+pub unsafe fn size_of<Tag: CTag>(obj: *const c_void) -> usize {
+    index_of::<Tag>(obj)
+        .ok()
+        .and_then(Tag::size_of)
+        .unwrap_or_default()
+}
+
+/// Deep copy function of the `tagged` field.
+/// # Safety
+/// `obj` must point to the valid struct, which starts with the tag.
+pub unsafe fn try_deep_copy<Tag: CTag, A: CDumpAllocator>(
+    obj: *const c_void,
+    alloc: &A,
+) -> Result<*mut c_void, Error> {
+    Tag::try_deep_copy(index_of::<Tag>(obj)?, obj, alloc)
+}
+
+/// Free function of the `tagged` field.
+/// # Safety
+/// `obj` must be returned by [`try_deep_copy`] with the same allocator.
+pub unsafe fn deep_free<Tag: CTag, A: CDumpAllocator>(obj: *mut c_void, alloc: &A) {
+    if !obj.is_null() {
+        let index = index_of::<Tag>(obj).expect("tag of the copy should be registered");
+        Tag::deep_free(index, obj, alloc);
+    }
+}
+
+/// Debugger of the `tagged` field.
+/// # Safety
+/// `obj` must point to the valid struct, which lives as long as the returned reference is used.
+#[cfg(feature = "cdebug")]
+pub unsafe fn cdebugger<Tag: CTag>(obj: *const c_void) -> &'static dyn Debug {
+    match index_of::<Tag>(obj) {
+        Ok(index) => Tag::debug(index, obj),
+        Err(_) => &"<unregistered tag>",
+    }
+}
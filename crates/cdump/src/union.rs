@@ -0,0 +1,135 @@
+//! `#[repr(C)]` unions, which deep part depends on the active member.
+//!
+//! Derives of the union implement traits of this module, where members with pointers declare their discriminator
+//! value by `#[cdump(case = ...)]` attribute. Union is used by value in the parent struct, which field with
+//! `#[cdump(discriminator = ...)]` attribute gives the expression which selects the active member, e.g. a sibling
+//! field. Only pointers of the active member are followed, and other members are treated as shallow data.
+
+#[cfg(feature = "cdebug")]
+use std::fmt::{self, Debug, Formatter};
+
+use crate::{alloc, CDumpReader, CDumpWriter, Error};
+
+/// Serialization of the active member, implemented by the `CSerialize` derive of the union.
+pub trait CUnionSerialize<T: CDumpWriter> {
+    /// Serialize data under pointers of the member selected by `discriminator`, like
+    /// [`crate::CSerialize::try_serialize_without_shallow_copy`].
+    /// # Safety
+    /// The selected member must be active, and every pointer in its object tree must be valid or null.
+    unsafe fn try_serialize_member(
+        &self,
+        discriminator: i64,
+        buf: &mut T,
+        start_index: usize,
+    ) -> Result<(), Error>;
+}
+
+/// Serialized size of the active member, implemented by the `CSerialize` derive of the union.
+pub trait CUnionSerializedSize {
+    /// Get end of the serialized data under pointers of the member selected by `discriminator`, like
+    /// [`crate::CSerializedSize::serialized_end_without_shallow_copy`].
+    /// # Safety
+    /// The selected member must be active, and every pointer in its object tree must be valid or null.
+    unsafe fn serialized_end_member(&self, discriminator: i64, end: usize) -> usize;
+}
+
+/// Deserialization of the active member, implemented by the `CDeserialize` derive of the union.
+pub trait CUnionDeserialize<T: CDumpReader>: Sized {
+    /// Deserialize data under pointers of the member selected by `discriminator`, like
+    /// [`crate::CDeserialize::deserialize_ref_mut_without_shallow_copy`].
+    /// # Safety
+    /// The next data in the buffer must be a valid representation of deep part of the selected member.
+    unsafe fn deserialize_ref_mut_member(buf: &mut T, dst: *mut Self, discriminator: i64);
+
+    /// Deserialize data under pointers of the member selected by `discriminator` with checks, like
+    /// [`crate::CDeserialize::try_deserialize_ref_mut_without_shallow_copy`].
+    /// # Safety
+    /// Same as [`crate::CDeserialize::try_deserialize_ref_mut`], and the `dst` must point to the shallow copy of
+    /// `Self` located in the buffer.
+    unsafe fn try_deserialize_ref_mut_member(
+        buf: &mut T,
+        dst: *mut Self,
+        discriminator: i64,
+    ) -> Result<(), Error>;
+
+    /// Deserialize data under pointers of the member selected by `discriminator` to the destination, like
+    /// [`crate::CDeserialize::deserialize_to_without_shallow_copy`].
+    /// # Safety
+    /// Same as [`crate::CDeserialize::deserialize_to`], and the selected member of `dst` must be active.
+    unsafe fn deserialize_to_member<A: alloc::CDumpAllocator>(
+        buf: &mut T,
+        temp: *mut Self,
+        dst: *mut Self,
+        policy: &alloc::DestinationPolicy<A>,
+        discriminator: i64,
+    ) -> Result<(), Error>;
+}
+
+/// Deep copy of the active member, implemented by the `CDeserialize` derive of the union.
+/// # Safety
+/// [`CUnionDeepCopy::deep_free_member`] must free every allocation made by [`CUnionDeepCopy::try_deep_copy_member`]
+/// with the same discriminator.
+pub unsafe trait CUnionDeepCopy: Sized {
+    /// Set pointers of the member selected by `discriminator` to null, so the partial copy can be freed.
+    /// # Safety
+    /// `obj` must be valid for writes.
+    unsafe fn null_member(obj: *mut Self, discriminator: i64);
+
+    /// Copy the data under pointers of the member selected by `discriminator`, like
+    /// [`crate::CDeepCopy::try_deep_copy_without_shallow_copy`].
+    /// # Safety
+    /// Same as [`crate::CDeepCopy::try_deep_copy_without_shallow_copy`], and the selected member must be active.
+    unsafe fn try_deep_copy_member<A: alloc::CDumpAllocator>(
+        src: *const Self,
+        dst: *mut Self,
+        alloc: &A,
+        discriminator: i64,
+    ) -> Result<(), Error>;
+
+    /// Free the data under pointers of the member selected by `discriminator`, like
+    /// [`crate::CDeepCopy::deep_free_without_shallow_copy`].
+    /// # Safety
+    /// Data under pointers of the member must be created by [`CUnionDeepCopy::try_deep_copy_member`] with the same
+    /// allocator.
+    unsafe fn deep_free_member<A: alloc::CDumpAllocator>(
+        obj: *mut Self,
+        alloc: &A,
+        discriminator: i64,
+    );
+}
+
+/// Debug representation of the active member, implemented by the `CDebug` derive of the union.
+#[cfg(feature = "cdebug")]
+pub trait CUnionDebug {
+    /// Format the member selected by `discriminator`, or members without `case` when none is selected.
+    /// # Safety
+    /// The selected member must be active, and every pointer in its object tree must be valid or null.
+    unsafe fn fmt_member(&self, discriminator: i64, f: &mut Formatter<'_>) -> fmt::Result;
+}
+
+/// Union with its discriminator, which formats the active member.
+#[cfg(feature = "cdebug")]
+pub struct ActiveMember<'a, U> {
+    union: &'a U,
+    discriminator: i64,
+}
+
+#[cfg(feature = "cdebug")]
+impl<'a, U: CUnionDebug> ActiveMember<'a, U> {
+    /// Create the formatter of the member selected by `discriminator`.
+    /// # Safety
+    /// Same as [`CUnionDebug::fmt_member`], for every use of the returned value.
+    pub unsafe fn new(union: &'a U, discriminator: i64) -> Self {
+        Self {
+            union,
+            discriminator,
+        }
+    }
+}
+
+#[cfg(feature = "cdebug")]
+impl<U: CUnionDebug> Debug for ActiveMember<'_, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        unsafe { self.union.fmt_member(self.discriminator, f) }
+    }
+}
@@ -0,0 +1,383 @@
+//! Read-only views of the serialized data.
+
+use std::{ffi::CStr, fmt, marker::PhantomData, mem, ptr::NonNull, slice};
+
+use crate::{internal, Error};
+
+/// Trait for reading the serialized data through immutable slice, without rewriting pointers in the buffer.
+pub trait CView: Sized {
+    /// View of the object, which borrows the buffer.
+    type View<'buf>;
+
+    /// Get the view of `Self`, which shallow copy is located at `shallow` index, and deep data starts at `index`.
+    /// Returns the view and the index of the end of the deep data.
+    /// # Safety
+    /// Shallow copy must be in bounds of the `data`, and properly aligned. Every bit pattern must be a valid
+    /// representation of shallow fields of `Self`, like in [`crate::CDeserialize::try_deserialize_ref_mut`].
+    unsafe fn try_view_without_shallow_copy(
+        data: &[u8],
+        shallow: usize,
+        index: usize,
+    ) -> Result<(Self::View<'_>, usize), Error>;
+
+    /// Get the view of `Self` located at `index` of the `data`, and returns the index of the end of its data.
+    /// # Remarks
+    /// The whole object tree is checked against bounds of the buffer, like in checked deserialization.
+    /// # Safety
+    /// Every bit pattern must be a valid representation of shallow fields of `Self`, like in
+    /// [`crate::CDeserialize::try_deserialize_ref_mut`].
+    unsafe fn try_view_at(data: &[u8], index: usize) -> Result<(Self::View<'_>, usize), Error> {
+        let shallow = try_typed_index::<Self>(data, index, 1)?;
+        Self::try_view_without_shallow_copy(data, shallow, shallow + mem::size_of::<Self>())
+    }
+
+    /// Get the view of `Self` located at the start of the `data`.
+    /// # Safety
+    /// Same as [`CView::try_view_at`].
+    unsafe fn try_view(data: &[u8]) -> Result<Self::View<'_>, Error> {
+        Self::try_view_at(data, 0).map(|(view, _)| view)
+    }
+}
+
+macro_rules! impl_cview {
+    ($t:ident) => {
+        impl CView for $t {
+            type View<'buf> = &'buf $t;
+
+            unsafe fn try_view_without_shallow_copy(
+                data: &[u8],
+                shallow: usize,
+                index: usize,
+            ) -> Result<(Self::View<'_>, usize), Error> {
+                Ok((&*(data.as_ptr().add(shallow) as *const $t), index))
+            }
+        }
+    };
+}
+
+impl_cview!(u8);
+impl_cview!(u16);
+impl_cview!(u32);
+impl_cview!(u64);
+impl_cview!(u128);
+impl_cview!(usize);
+impl_cview!(i8);
+impl_cview!(i16);
+impl_cview!(i32);
+impl_cview!(i64);
+impl_cview!(i128);
+impl_cview!(isize);
+impl_cview!(f32);
+impl_cview!(f64);
+
+/// Kind of the element of [`CViewArray`].
+pub trait CViewElement {
+    /// View of the element, which borrows the buffer.
+    type View<'buf>;
+    /// Type of the element in the array.
+    type Shallow;
+
+    /// Get the view of the element located at `shallow` index, which deep data starts at `index`.
+    /// # Safety
+    /// Same as [`CView::try_view_without_shallow_copy`].
+    unsafe fn try_view_element(
+        data: &[u8],
+        shallow: usize,
+        index: usize,
+    ) -> Result<(Self::View<'_>, usize), Error>;
+}
+
+/// Element of the array which is stored by value.
+pub struct ByValue<T>(PhantomData<T>);
+
+impl<T: CView> CViewElement for ByValue<T> {
+    type View<'buf> = T::View<'buf>;
+    type Shallow = T;
+
+    unsafe fn try_view_element(
+        data: &[u8],
+        shallow: usize,
+        index: usize,
+    ) -> Result<(Self::View<'_>, usize), Error> {
+        T::try_view_without_shallow_copy(data, shallow, index)
+    }
+}
+
+/// Element of the array which is a pointer to `T`.
+pub struct ByPointer<T>(PhantomData<T>);
+
+impl<T: CView> CViewElement for ByPointer<T> {
+    type View<'buf> = Option<T::View<'buf>>;
+    type Shallow = usize;
+
+    unsafe fn try_view_element(
+        data: &[u8],
+        shallow: usize,
+        index: usize,
+    ) -> Result<(Self::View<'_>, usize), Error> {
+        match read_slot(data, shallow) {
+            0 => Ok((None, index)),
+            _ => T::try_view_at(data, index).map(|(view, end)| (Some(view), end)),
+        }
+    }
+}
+
+/// Element of the array which is a C string.
+pub struct CStringPointer;
+
+impl CViewElement for CStringPointer {
+    type View<'buf> = Option<&'buf CStr>;
+    type Shallow = usize;
+
+    unsafe fn try_view_element(
+        data: &[u8],
+        shallow: usize,
+        index: usize,
+    ) -> Result<(Self::View<'_>, usize), Error> {
+        match read_slot(data, shallow) {
+            0 => Ok((None, index)),
+            len => try_view_cstring(data, index, len).map(|(view, end)| (Some(view), end)),
+        }
+    }
+}
+
+/// View of the array, which elements are read lazily.
+pub struct CViewArray<'buf, E> {
+    data: &'buf [u8],
+    shallow: usize,
+    index: usize,
+    len: usize,
+    _marker: PhantomData<E>,
+}
+
+impl<E> Clone for CViewArray<'_, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for CViewArray<'_, E> {}
+
+impl<'buf, E: CViewElement> CViewArray<'buf, E> {
+    /// Check the array with `len` elements located at `index`, and returns its view and the index of the end of its
+    /// data.
+    /// # Safety
+    /// Same as [`CView::try_view_at`].
+    pub unsafe fn try_new(
+        data: &'buf [u8],
+        index: usize,
+        len: usize,
+    ) -> Result<(Self, usize), Error> {
+        let shallow = try_typed_index::<E::Shallow>(data, index, len)?;
+        let size = mem::size_of::<E::Shallow>();
+
+        let start = shallow + size * len;
+        let mut end = start;
+        for i in 0..len {
+            end = E::try_view_element(data, shallow + size * i, end)?.1;
+        }
+
+        Ok((
+            Self {
+                data,
+                shallow,
+                index: start,
+                len,
+                _marker: PhantomData,
+            },
+            end,
+        ))
+    }
+
+    /// Get number of the elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the array is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the view of the element at `index`.
+    /// # Remarks
+    /// Deep data of the previous elements is walked, use [`CViewArray::iter`] to read all elements.
+    pub fn get(&self, index: usize) -> Option<E::View<'buf>> {
+        self.iter().nth(index)
+    }
+
+    /// Iterate over views of the elements.
+    pub fn iter(&self) -> CViewArrayIter<'buf, E> {
+        CViewArrayIter {
+            array: *self,
+            next: 0,
+            index: self.index,
+        }
+    }
+}
+
+impl<'buf, E: CViewElement> IntoIterator for CViewArray<'buf, E> {
+    type Item = E::View<'buf>;
+    type IntoIter = CViewArrayIter<'buf, E>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'buf, E> fmt::Debug for CViewArray<'buf, E>
+where
+    E: CViewElement,
+    E::View<'buf>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over views of the elements of [`CViewArray`].
+pub struct CViewArrayIter<'buf, E> {
+    array: CViewArray<'buf, E>,
+    next: usize,
+    index: usize,
+}
+
+impl<'buf, E: CViewElement> Iterator for CViewArrayIter<'buf, E> {
+    type Item = E::View<'buf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.array.len {
+            return None;
+        }
+
+        let shallow = self.array.shallow + mem::size_of::<E::Shallow>() * self.next;
+        // Safety: the whole array is checked by `CViewArray::try_new`.
+        let (view, end) = unsafe { E::try_view_element(self.array.data, shallow, self.index) }
+            .expect("array should be checked when the view is created");
+        self.next += 1;
+        self.index = end;
+        Some(view)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.array.len - self.next;
+        (len, Some(len))
+    }
+}
+
+impl<E: CViewElement> ExactSizeIterator for CViewArrayIter<'_, E> {}
+
+/// Shallow copy of the viewed object in the buffer, which does not require `T: 'buf`, so views of generic types do not
+/// bound their parameters by the lifetime of the buffer.
+pub struct Shallow<'buf, T> {
+    ptr: NonNull<T>,
+    _marker: PhantomData<&'buf [u8]>,
+}
+
+impl<'buf, T> Shallow<'buf, T> {
+    /// Borrow the shallow copy located at `shallow` index of the `data`.
+    /// # Safety
+    /// Same as [`CView::try_view_without_shallow_copy`].
+    pub unsafe fn new(data: &'buf [u8], shallow: usize) -> Self {
+        Self {
+            ptr: NonNull::new_unchecked(data.as_ptr().add(shallow) as *mut T),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the shallow copy.
+    pub fn get(&self) -> &T {
+        // Safety: the shallow copy is valid as long as the buffer is borrowed.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+/// View of the dynamic field.
+#[derive(Debug, Clone, Copy)]
+pub struct CDynamicView<'buf> {
+    data: &'buf [u8],
+    index: usize,
+}
+
+impl<'buf> CDynamicView<'buf> {
+    /// Create the view of the dynamic object, which data starts at `index`, before alignment of the object.
+    pub fn new(data: &'buf [u8], index: usize) -> Self {
+        Self { data, index }
+    }
+
+    /// Get the buffer with the dynamic object.
+    pub fn data(&self) -> &'buf [u8] {
+        self.data
+    }
+
+    /// Get the index of the dynamic object in the buffer.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the view of the dynamic object as `T`.
+    /// # Safety
+    /// Same as [`CView::try_view_at`], and the object must be `T`.
+    pub unsafe fn view<T: CView>(&self) -> Result<T::View<'buf>, Error> {
+        T::try_view_at(self.data, self.index).map(|(view, _)| view)
+    }
+}
+
+/// Align the `index` to `T`, and check if `len` elements of `T` are in bounds of the buffer and properly aligned in
+/// memory. Returns the aligned index.
+pub fn try_typed_index<T>(data: &[u8], index: usize, len: usize) -> Result<usize, Error> {
+    let aligned = internal::align_index::<T>(index);
+    let size = internal::try_array_size(mem::size_of::<T>(), len)?;
+    match aligned.checked_add(size) {
+        Some(end) if end <= data.len() => {}
+        _ => {
+            return Err(Error::OutOfBounds {
+                offset: aligned,
+                len: size,
+                buffer_len: data.len(),
+            })
+        }
+    }
+
+    let address = data.as_ptr() as usize + aligned;
+    match address % mem::align_of::<T>() {
+        0 => Ok(aligned),
+        _ => Err(Error::Misaligned {
+            offset: aligned,
+            align: mem::align_of::<T>(),
+        }),
+    }
+}
+
+/// Get the view of C string with `len` bytes located at `index`, and returns the index of the end of it.
+pub fn try_view_cstring(data: &[u8], index: usize, len: usize) -> Result<(&CStr, usize), Error> {
+    let end = index.checked_add(len).ok_or(Error::LengthOverflow)?;
+    let bytes = data.get(index..end).ok_or(Error::OutOfBounds {
+        offset: index,
+        len,
+        buffer_len: data.len(),
+    })?;
+
+    match bytes.last() {
+        Some(0) => Ok((CStr::from_bytes_until_nul(bytes).unwrap(), end)),
+        _ => Err(Error::MissingNulTerminator { offset: index }),
+    }
+}
+
+/// Get the view of `len` elements of `T` located at `index`, and returns the index of the end of them.
+/// # Safety
+/// Every bit pattern must be a valid representation of `T`.
+pub unsafe fn try_view_slice<T>(
+    data: &[u8],
+    index: usize,
+    len: usize,
+) -> Result<(&[T], usize), Error> {
+    let start = try_typed_index::<T>(data, index, len)?;
+    let slice = slice::from_raw_parts(data.as_ptr().add(start) as *const T, len);
+    Ok((slice, start + mem::size_of::<T>() * len))
+}
+
+/// Read the pointer slot located at `index`, which is checked before.
+unsafe fn read_slot(data: &[u8], index: usize) -> usize {
+    *(data.as_ptr().add(index) as *const usize)
+}
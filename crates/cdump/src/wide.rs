@@ -0,0 +1,64 @@
+//! Wide strings, like `wchar_t` strings of C, which end with the zero character.
+//!
+//! Characters are UTF-16 code units for `u16`, e.g. `wchar_t` on Windows, or UTF-32 code points for `u32` and `i32`,
+//! e.g. `wchar_t` on Linux. Field with `#[cdump(wstring)]` is serialized with its terminator, and aligned to its
+//! character.
+
+/// Character of the wide string.
+pub trait CWideChar: Copy + PartialEq {
+    /// Character which terminates the string.
+    const NUL: Self;
+
+    /// Decode the characters to the string, replacing invalid ones with `U+FFFD`.
+    fn decode(chars: &[Self]) -> String;
+}
+
+impl CWideChar for u16 {
+    const NUL: Self = 0;
+
+    fn decode(chars: &[Self]) -> String {
+        String::from_utf16_lossy(chars)
+    }
+}
+
+impl CWideChar for u32 {
+    const NUL: Self = 0;
+
+    fn decode(chars: &[Self]) -> String {
+        chars
+            .iter()
+            .map(|&c| char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+}
+
+impl CWideChar for i32 {
+    const NUL: Self = 0;
+
+    fn decode(chars: &[Self]) -> String {
+        chars
+            .iter()
+            .map(|&c| char::from_u32(c as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+}
+
+/// Get the length of the wide string, without the terminator.
+/// # Safety
+/// Caller must ensure that `s` points to a valid wide string.
+#[inline]
+pub unsafe fn wcslen<T: CWideChar>(s: *const T) -> usize {
+    let mut len = 0;
+    while *s.add(len) != T::NUL {
+        len += 1;
+    }
+    len
+}
+
+/// Decode the wide string to the Rust string.
+/// # Safety
+/// Caller must ensure that `s` points to a valid wide string.
+#[inline]
+pub unsafe fn decode<T: CWideChar>(s: *const T) -> String {
+    T::decode(std::slice::from_raw_parts(s, wcslen(s)))
+}
@@ -1,11 +1,21 @@
 use quote::{quote, ToTokens};
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, DeriveInput, Ident, Member};
 
-use crate::field_analysis::{self, Field, FieldType};
+use crate::{
+    field_analysis::{self, DynamicField, Field, FieldType},
+    helpers::{split_generics, SplitGenerics},
+};
 
 pub fn c_debug_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let name = ast.ident.clone();
+    if field_analysis::is_union(&ast) {
+        return match field_analysis::get_fields(&ast, false) {
+            Ok(members) => crate::union::debug_impl(&ast, &members),
+            Err(err) => err.to_compile_error(),
+        }
+        .into();
+    }
 
     let write = match field_analysis::get_fields(&ast, false) {
         Ok(fields) => write_fmt(&fields),
@@ -13,10 +23,22 @@ pub fn c_debug_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     };
 
     let name_str = name.to_string();
+    let debug = match field_analysis::is_tuple_struct(&ast) {
+        true => quote! { debug_tuple },
+        false => quote! { debug_struct },
+    };
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(&ast.generics, None, Some(quote! { ::std::fmt::Debug }));
     proc_macro::TokenStream::from(quote! {
-        impl ::std::fmt::Debug for #name {
+        impl #impl_generics ::std::fmt::Debug for #name #ty_generics #where_clause {
             fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-                f.debug_struct(#name_str)
+                let Some(_visit) = ::cdump::internal::Visit::enter(self as *const Self) else {
+                    return f.write_str("<cycle>");
+                };
+                f.#debug(#name_str)
                     #write
                     .finish()
             }
@@ -24,12 +46,11 @@ pub fn c_debug_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     })
 }
 
-fn write_fmt(fields: &[Field]) -> proc_macro2::TokenStream {
+pub fn write_fmt(fields: &[Field]) -> proc_macro2::TokenStream {
     let mut quotes = Vec::new();
 
     for field in fields {
-        let ident = field.ident.as_ref();
-        let ident_str = ident.expect("expected field to have ident").to_string();
+        let ident = &field.ident;
 
         let value = match &field.ty {
             FieldType::Plain => quote! {
@@ -55,33 +76,67 @@ fn write_fmt(fields: &[Field]) -> proc_macro2::TokenStream {
                     false => Some(unsafe { ::std::ffi::CStr::from_ptr(self.#ident) }),
                 }
             },
-            FieldType::Array(len, ty) => {
-                let extension = match &ty.ty {
-                    FieldType::Reference => quote! {
-                        .iter().map(|&ptr| ptr.as_ref()).collect::<Vec<_>>()
-                    },
-                    FieldType::CString => quote! {
-                        .iter().map(|&ptr| match ptr.is_null() {
-                            true => None,
-                            false => Some(::std::ffi::CStr::from_ptr(ptr)),
-                        }).collect::<Vec<_>>()
-                    },
-                    FieldType::Dynamic(dynamic) => {
-                        let value = dynamic.call_cdebugger(quote! { ptr });
-                        quote! {
-                            .iter().map(|&ptr| (!ptr.is_null()).then(|| #value)).collect::<Vec<_>>()
-                        }
-                    }
-                    _ => quote! {},
-                };
-
+            FieldType::WString => quote! {
+                &match self.#ident.is_null() {
+                    true => None,
+                    false => Some(unsafe { ::cdump::wide::decode(self.#ident) }),
+                }
+            },
+            FieldType::Bytes(bytes) => {
+                let len = &bytes.len;
                 quote! {
                     &match self.#ident.is_null() {
                         true => None,
-                        false => Some(unsafe { ::std::slice::from_raw_parts(self.#ident, (#len) as usize)#extension }),
+                        false => Some(::cdump::internal::HexPreview(unsafe {
+                            ::std::slice::from_raw_parts(self.#ident as *const u8, (#len) as usize)
+                        })),
                     }
                 }
             }
+            FieldType::DeepInlineArray(_, element) => match element.ty {
+                FieldType::Nested => quote! { &self.#ident },
+                _ => {
+                    let extension = elements_extension(element, None);
+                    quote! {
+                        &unsafe { self.#ident #extension }
+                    }
+                }
+            },
+            FieldType::Array(len, ty) => match &ty.ty {
+                // Dynamic objects under one pointer are stored one after another.
+                FieldType::Dynamic(dynamic @ DynamicField { ptr_level: 1, .. }) => {
+                    let size_of = &dynamic.size_of;
+                    let value = dynamic.call_cdebugger(quote! { ptr });
+                    quote! {
+                        &match self.#ident.is_null() {
+                            true => None,
+                            false => Some(unsafe {
+                                let mut offset: usize = 0;
+                                (0..(#len) as usize).map(|_| {
+                                    let ptr = self.#ident.byte_add(offset) as *const ::std::ffi::c_void;
+                                    offset += #size_of(ptr);
+                                    #value
+                                }).collect::<Vec<_>>()
+                            }),
+                        }
+                    }
+                }
+                _ => {
+                    let extension = elements_extension(ty, len.index.as_ref());
+                    quote! {
+                        &match self.#ident.is_null() {
+                            true => None,
+                            false => Some(unsafe { ::std::slice::from_raw_parts(self.#ident, (#len) as usize)#extension }),
+                        }
+                    }
+                }
+            },
+            FieldType::Nested => quote! {
+                &self.#ident
+            },
+            FieldType::Union(discriminator) => quote! {
+                &unsafe { ::cdump::union::ActiveMember::new(&self.#ident, (#discriminator) as i64) }
+            },
             FieldType::Dynamic(dynamic) => {
                 let value = dynamic.call_cdebugger(quote! { self.#ident });
                 quote! {
@@ -90,10 +145,59 @@ fn write_fmt(fields: &[Field]) -> proc_macro2::TokenStream {
             }
         };
 
-        quotes.push(quote! {
-            .field(#ident_str, #value)
+        quotes.push(match ident {
+            Member::Named(ident) => {
+                let ident_str = ident.to_string();
+                quote! {
+                    .field(#ident_str, #value)
+                }
+            }
+            Member::Unnamed(_) => quote! {
+                .field(#value)
+            },
         });
     }
 
     quotes.into_iter().collect()
 }
+
+/// Get the code which maps the slice of elements to their debug representation, where pointers are followed. Lengths of
+/// inner arrays can use `index` of the current element.
+fn elements_extension(element: &Field, index: Option<&Ident>) -> proc_macro2::TokenStream {
+    match &element.ty {
+        FieldType::Reference => quote! {
+            .iter().map(|&ptr| ptr.as_ref()).collect::<Vec<_>>()
+        },
+        FieldType::CString => quote! {
+            .iter().map(|&ptr| match ptr.is_null() {
+                true => None,
+                false => Some(::std::ffi::CStr::from_ptr(ptr)),
+            }).collect::<Vec<_>>()
+        },
+        FieldType::WString => quote! {
+            .iter().map(|&ptr| match ptr.is_null() {
+                true => None,
+                false => Some(::cdump::wide::decode(ptr)),
+            }).collect::<Vec<_>>()
+        },
+        FieldType::Dynamic(dynamic) => {
+            let value = dynamic.call_cdebugger(quote! { ptr });
+            quote! {
+                .iter().map(|&ptr| (!ptr.is_null()).then(|| #value)).collect::<Vec<_>>()
+            }
+        }
+        FieldType::Array(len, inner) => {
+            let extension = elements_extension(inner, len.index.as_ref());
+            let index = match index {
+                Some(index) => quote! { #index },
+                None => quote! { _ },
+            };
+            quote! {
+                .iter().enumerate().map(|(#index, &ptr)| {
+                    (!ptr.is_null()).then(|| ::std::slice::from_raw_parts(ptr, (#len) as usize)#extension)
+                }).collect::<Vec<_>>()
+            }
+        }
+        _ => quote! {},
+    }
+}
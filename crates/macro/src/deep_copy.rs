@@ -0,0 +1,430 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::DeriveInput;
+
+use crate::{
+    array_len_call, bind_index, discriminator_function_ident, element_type,
+    field_analysis::{DynamicField, Field, FieldType},
+    helpers::{is_primitive_type, split_generics, SplitGenerics},
+    in_field, len_function_ident,
+};
+
+pub fn deep_copy_impl(ast: &DeriveInput, fields: &[Field]) -> TokenStream {
+    let name = &ast.ident;
+    let name_str = name.to_string();
+    let null_fields = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let field_ident = &field.ident;
+            match &field.ty {
+                // Shallow members of the union are kept.
+                FieldType::Union(_) => {
+                    let discriminator_function = discriminator_function_ident(index);
+                    quote! {
+                        ::cdump::union::CUnionDeepCopy::null_member(
+                            ::std::ptr::addr_of_mut!((*obj).#field_ident),
+                            (*obj).#discriminator_function(),
+                        );
+                    }
+                }
+                // Shallow fields of the nested struct are kept.
+                FieldType::Nested => quote! {
+                    ::cdump::CDeepCopy::null_without_shallow_copy(::std::ptr::addr_of_mut!((*obj).#field_ident));
+                },
+                FieldType::DeepInlineArray(array, element) if matches!(element.ty, FieldType::Nested) => {
+                    let len = &array.len;
+                    quote! {
+                        for i in 0..(#len) {
+                            ::cdump::CDeepCopy::null_without_shallow_copy(::std::ptr::addr_of_mut!((*obj).#field_ident[i]));
+                        }
+                    }
+                }
+                _ => quote! {
+                    (*obj).#field_ident = ::std::mem::zeroed();
+                },
+            }
+        })
+        .collect::<TokenStream>();
+    let copy_fields = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| in_field(name, field, copy_field(field, index)))
+        .collect::<TokenStream>();
+    // Fields are freed in reverse order, so lengths of inner arrays can use arrays of previous fields.
+    let free_fields = fields
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, field)| free_field(field, index))
+        .collect::<TokenStream>();
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(&ast.generics, None, Some(quote! { ::cdump::CDeepCopy }));
+
+    quote! {
+        unsafe impl #impl_generics ::cdump::CDeepCopy for #name #ty_generics #where_clause {
+            unsafe fn null_without_shallow_copy(obj: *mut Self) {
+                #null_fields
+            }
+
+            unsafe fn try_deep_copy_without_shallow_copy<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                src: *const Self,
+                dst: *mut Self,
+                alloc: &__CDumpAllocator,
+            ) -> Result<(), ::cdump::Error> {
+                // Pointers are set after their data is copied, so a partial copy can be freed.
+                Self::null_without_shallow_copy(dst);
+                let Some(_visit) = ::cdump::internal::Visit::enter(src) else {
+                    return Err(::cdump::Error::Cycle { ty: #name_str });
+                };
+                #copy_fields
+                Ok(())
+            }
+
+            unsafe fn deep_free_without_shallow_copy<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                obj: *mut Self,
+                alloc: &__CDumpAllocator,
+            ) {
+                #free_fields
+            }
+        }
+    }
+}
+
+pub fn copy_field(field: &Field, field_index: usize) -> TokenStream {
+    let field_ident = &field.ident;
+    match &field.ty {
+        FieldType::DeepInlineArray(array, element) => {
+            let len = &array.len;
+            let copy = copy_field_at(
+                element,
+                field_index,
+                quote! { (*src).#field_ident[i] },
+                quote! { (*dst).#field_ident[i] },
+            );
+            quote! {
+                for i in 0..(#len) {
+                    #copy
+                }
+            }
+        }
+        _ => copy_field_at(
+            field,
+            field_index,
+            quote! { (*src).#field_ident },
+            quote! { (*dst).#field_ident },
+        ),
+    }
+}
+
+/// Copy the data under pointers of the field, or of the element of the inline array, at `src` to `dst`.
+fn copy_field_at(
+    field: &Field,
+    field_index: usize,
+    src: TokenStream,
+    dst: TokenStream,
+) -> TokenStream {
+    let result = match &field.ty {
+        FieldType::Plain | FieldType::InlineArray(_) => {
+            unreachable!("shallow fields should not be under first level pointer")
+        }
+        FieldType::DeepInlineArray(..) => unreachable!("inline arrays are followed by elements"),
+        FieldType::Union(_) => {
+            let discriminator_function = discriminator_function_ident(field_index);
+            return quote! {
+                ::cdump::union::CUnionDeepCopy::try_deep_copy_member(
+                    ::std::ptr::addr_of!(#src),
+                    ::std::ptr::addr_of_mut!(#dst),
+                    alloc,
+                    (*src).#discriminator_function(),
+                )?;
+            };
+        }
+        FieldType::Nested => {
+            return quote! {
+                ::cdump::CDeepCopy::try_deep_copy_without_shallow_copy(
+                    ::std::ptr::addr_of!(#src),
+                    ::std::ptr::addr_of_mut!(#dst),
+                    alloc,
+                )?;
+            };
+        }
+        FieldType::Reference => quote! {
+            #dst = ::cdump::alloc::try_deep_copy(#src, alloc)? as _;
+        },
+        FieldType::CString => quote! {
+            #dst = ::cdump::alloc::try_copy_cstring(#src, alloc)? as _;
+        },
+        FieldType::WString => quote! {
+            #dst = ::cdump::alloc::try_copy_wstring(#src, alloc)? as _;
+        },
+        FieldType::Bytes(bytes) => {
+            let len_function = len_function_ident(field_index);
+            let align = bytes.align;
+            quote! {
+                let len = (*src).#len_function();
+                #dst = ::cdump::alloc::try_copy_bytes(#src as *const u8, alloc, len, #align)? as _;
+            }
+        }
+        FieldType::Dynamic(dynamic) => match &dynamic.deep_copy {
+            Some(deep_copy) => quote! {
+                #dst = #deep_copy(#src as *const ::std::ffi::c_void, alloc)? as _;
+            },
+            None => quote! {
+                return Err(::cdump::Error::UnsupportedDynamic);
+            },
+        },
+        FieldType::Array(len, inner) => {
+            let len_function = array_len_call(len, field_index);
+            let path = inner.path.to_token_stream();
+            let copy = match &inner.ty {
+                FieldType::Plain if is_primitive_type(&path) => quote! {
+                    #dst = ::cdump::alloc::try_copy_array(#src, alloc, len)? as _;
+                },
+                FieldType::Plain => quote! {
+                    let ptr = ::cdump::alloc::try_alloc_zeroed::<#path, __CDumpAllocator>(alloc, len)?;
+                    #dst = ptr as _;
+                    for i in 0..len {
+                        ::std::ptr::copy_nonoverlapping(#src.add(i), ptr.add(i), 1);
+                        ::cdump::CDeepCopy::try_deep_copy_without_shallow_copy(#src.add(i), ptr.add(i), alloc)?;
+                    }
+                },
+                FieldType::Reference => copy_array_of_pointers(
+                    &src,
+                    &dst,
+                    &path,
+                    quote! { ::cdump::alloc::try_deep_copy(element, alloc)? },
+                ),
+                FieldType::CString => copy_array_of_pointers(
+                    &src,
+                    &dst,
+                    &path,
+                    quote! { ::cdump::alloc::try_copy_cstring(element, alloc)? },
+                ),
+                FieldType::WString => copy_array_of_pointers(
+                    &src,
+                    &dst,
+                    &path,
+                    quote! { ::cdump::alloc::try_copy_wstring(element, alloc)? },
+                ),
+                FieldType::Dynamic(DynamicField { ptr_level: 1, .. }) => quote! {
+                    return Err(::cdump::Error::UnsupportedDynamic);
+                },
+                FieldType::Dynamic(DynamicField {
+                    deep_copy: Some(deep_copy),
+                    ..
+                }) => copy_array_of_pointers(
+                    &src,
+                    &dst,
+                    &path,
+                    quote! { #deep_copy(element as *const ::std::ffi::c_void, alloc)? },
+                ),
+                FieldType::Dynamic(_) => quote! {
+                    return Err(::cdump::Error::UnsupportedDynamic);
+                },
+                FieldType::Array(..) => {
+                    let bind_index = bind_index(len);
+                    let element_type = element_type(inner);
+                    let copy = copy_field_at(
+                        inner,
+                        field_index,
+                        quote! { (*src_element) },
+                        quote! { (*dst_element) },
+                    );
+                    quote! {
+                        let ptr = ::cdump::alloc::try_alloc_zeroed::<#element_type, __CDumpAllocator>(alloc, len)?;
+                        #dst = ptr as _;
+                        for i in 0..len {
+                            #bind_index
+                            let src_element = #src.add(i);
+                            let dst_element = ptr.add(i);
+                            #copy
+                        }
+                    }
+                }
+                _ => unimplemented!("2D arrays"),
+            };
+
+            quote! {
+                let len = (*src).#len_function;
+                #copy
+            }
+        }
+    };
+
+    quote! {
+        if !#src.is_null() {
+            #result
+        }
+    }
+}
+
+fn copy_array_of_pointers(
+    src: &TokenStream,
+    dst: &TokenStream,
+    path: &TokenStream,
+    copy: TokenStream,
+) -> TokenStream {
+    quote! {
+        let ptr = ::cdump::alloc::try_alloc_zeroed::<*mut #path, __CDumpAllocator>(alloc, len)?;
+        #dst = ptr as _;
+        for i in 0..len {
+            let element = *#src.add(i);
+            if !element.is_null() {
+                *ptr.add(i) = #copy as _;
+            }
+        }
+    }
+}
+
+pub fn free_field(field: &Field, field_index: usize) -> TokenStream {
+    let field_ident = &field.ident;
+    match &field.ty {
+        FieldType::DeepInlineArray(array, element) => {
+            let len = &array.len;
+            let free = free_field_at(element, field_index, quote! { (*obj).#field_ident[i] });
+            quote! {
+                for i in 0..(#len) {
+                    #free
+                }
+            }
+        }
+        _ => free_field_at(field, field_index, quote! { (*obj).#field_ident }),
+    }
+}
+
+/// Free the data under pointers of the field, or of the element of the inline array, at `ident`.
+fn free_field_at(field: &Field, field_index: usize, ident: TokenStream) -> TokenStream {
+    let result = match &field.ty {
+        FieldType::Plain | FieldType::InlineArray(_) => {
+            unreachable!("shallow fields should not be under first level pointer")
+        }
+        FieldType::DeepInlineArray(..) => unreachable!("inline arrays are followed by elements"),
+        FieldType::Union(_) => {
+            let discriminator_function = discriminator_function_ident(field_index);
+            return quote! {
+                ::cdump::union::CUnionDeepCopy::deep_free_member(
+                    ::std::ptr::addr_of_mut!(#ident),
+                    alloc,
+                    (*obj).#discriminator_function(),
+                );
+            };
+        }
+        FieldType::Nested => {
+            return quote! {
+                ::cdump::CDeepCopy::deep_free_without_shallow_copy(::std::ptr::addr_of_mut!(#ident), alloc);
+            };
+        }
+        FieldType::Reference => {
+            let path = field.path.to_token_stream();
+            quote! {
+                ::cdump::alloc::deep_free(#ident as *mut #path, alloc);
+            }
+        }
+        FieldType::CString => quote! {
+            ::cdump::alloc::free_cstring(#ident as *mut _, alloc);
+        },
+        FieldType::WString => {
+            let path = field.path.to_token_stream();
+            quote! {
+                ::cdump::alloc::free_wstring(#ident as *mut #path, alloc);
+            }
+        }
+        FieldType::Bytes(bytes) => {
+            let len_function = len_function_ident(field_index);
+            let align = bytes.align;
+            quote! {
+                let len = (*obj).#len_function();
+                ::cdump::alloc::free_bytes(#ident as *mut u8, alloc, len, #align);
+            }
+        }
+        FieldType::Dynamic(dynamic) => match &dynamic.free {
+            Some(free) => quote! {
+                #free(#ident as *mut ::std::ffi::c_void, alloc);
+            },
+            // Copy of the field is not supported, so it is always null.
+            None => quote! {},
+        },
+        FieldType::Array(len, inner) => {
+            let len_function = array_len_call(len, field_index);
+            let path = inner.path.to_token_stream();
+            let free_elements = match &inner.ty {
+                FieldType::Plain if is_primitive_type(&path) => quote! {},
+                FieldType::Plain => quote! {
+                    for i in 0..len {
+                        ::cdump::CDeepCopy::deep_free_without_shallow_copy((#ident as *mut #path).add(i), alloc);
+                    }
+                },
+                FieldType::Reference => free_array_of_pointers(
+                    &ident,
+                    &path,
+                    quote! { ::cdump::alloc::deep_free(element, alloc); },
+                ),
+                FieldType::CString => free_array_of_pointers(
+                    &ident,
+                    &path,
+                    quote! { ::cdump::alloc::free_cstring(element, alloc); },
+                ),
+                FieldType::WString => free_array_of_pointers(
+                    &ident,
+                    &path,
+                    quote! { ::cdump::alloc::free_wstring(element, alloc); },
+                ),
+                // Copy of the array of dynamic objects under one pointer is not supported, so it is always null.
+                FieldType::Dynamic(DynamicField { ptr_level: 1, .. }) => return quote! {},
+                FieldType::Dynamic(DynamicField {
+                    free: Some(free), ..
+                }) => free_array_of_pointers(
+                    &ident,
+                    &path,
+                    quote! { #free(element as *mut ::std::ffi::c_void, alloc); },
+                ),
+                FieldType::Dynamic(_) => quote! {},
+                FieldType::Array(..) => {
+                    let bind_index = bind_index(len);
+                    let element_type = element_type(inner);
+                    let free = free_field_at(inner, field_index, quote! { (*element) });
+                    quote! {
+                        for i in 0..len {
+                            #bind_index
+                            let element = (#ident as *mut #element_type).add(i);
+                            #free
+                        }
+                    }
+                }
+                _ => unimplemented!("2D arrays"),
+            };
+
+            let alignment_type = crate::get_alignment_type(inner);
+            quote! {
+                let len = (*obj).#len_function;
+                #free_elements
+                ::cdump::alloc::free::<#alignment_type, __CDumpAllocator>(alloc, #ident as *mut _, len);
+            }
+        }
+    };
+
+    quote! {
+        if !#ident.is_null() {
+            #result
+        }
+    }
+}
+
+fn free_array_of_pointers(
+    ident: &TokenStream,
+    path: &TokenStream,
+    free: TokenStream,
+) -> TokenStream {
+    quote! {
+        for i in 0..len {
+            let element = *(#ident as *mut *mut #path).add(i);
+            if !element.is_null() {
+                #free
+            }
+        }
+    }
+}
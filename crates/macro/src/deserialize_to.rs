@@ -0,0 +1,477 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{
+    array_len_call, bind_index, capacity_function_ident, discriminator_function_ident,
+    element_type,
+    field_analysis::{capacity_member, DynamicField, Field, FieldType},
+    get_alignment_type,
+    helpers::is_primitive_type,
+    in_field, len_function_ident,
+};
+
+pub fn read_deep_fields_to(fields: &[Field], name: &Ident) -> TokenStream {
+    let capacities = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| capture_capacity(field, index));
+    let reads = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| in_field(name, field, read_deep_field_to(field, index)));
+    let restores = fields.iter().filter_map(restore_capacity);
+    quote! {
+        #(#capacities)*
+        #(#reads)*
+        #(#restores)*
+    }
+}
+
+/// Keep the capacity field of the destination, which describes its storage, instead of the sender's value.
+fn restore_capacity(field: &Field) -> Option<TokenStream> {
+    let mut element = field;
+    while let FieldType::Array(_, inner) | FieldType::DeepInlineArray(_, inner) = &element.ty {
+        element = inner;
+    }
+
+    let member = capacity_member(element.capacity.as_ref()?)?;
+    Some(quote! {
+        (*temp).#member = (*dst).#member;
+    })
+}
+
+pub fn read_deep_field_to(field: &Field, field_index: usize) -> TokenStream {
+    let field_ident = &field.ident;
+    match &field.ty {
+        FieldType::DeepInlineArray(array, element) => {
+            let len = &array.len;
+            let read = read_deep_field_at_to(
+                element,
+                field_index,
+                quote! { (*temp).#field_ident[i] },
+                quote! { (*dst).#field_ident[i] },
+                quote! {},
+            );
+            quote! {
+                for i in 0..(#len) {
+                    #read
+                }
+            }
+        }
+        _ => {
+            let capacity = capacity_ident(field_index);
+            read_deep_field_at_to(
+                field,
+                field_index,
+                quote! { (*temp).#field_ident },
+                quote! { (*dst).#field_ident },
+                quote! { #capacity },
+            )
+        }
+    }
+}
+
+fn capacity_ident(field_index: usize) -> Ident {
+    Ident::new(
+        &format!("capacity_of_field_{}", field_index),
+        Span::call_site(),
+    )
+}
+
+/// Capture capacities of arrays and bytes of the destination, before any field of the destination is written. Lengths
+/// of the destination can be stored in its deep data, e.g. lengths of inner arrays in an array, which is overwritten
+/// by deserialization of earlier fields.
+pub fn capture_capacity(field: &Field, field_index: usize) -> TokenStream {
+    let field_ident = &field.ident;
+    let capacity = capacity_ident(field_index);
+    match &field.ty {
+        FieldType::Array(..) => {
+            let capture =
+                capture_array_capacity(field, field_index, quote! { (*dst).#field_ident });
+            quote! {
+                let #capacity = #capture;
+            }
+        }
+        FieldType::Bytes(_) => {
+            let len_function = len_function_ident(field_index);
+            quote! {
+                let #capacity = match (*dst).#field_ident.is_null() {
+                    true => 0,
+                    false => (*dst).#len_function(),
+                };
+            }
+        }
+        _ => quote! {},
+    }
+}
+
+/// Capture the capacity of the destination's `array`, and recursively of its inner arrays.
+fn capture_array_capacity(field: &Field, field_index: usize, array: TokenStream) -> TokenStream {
+    let FieldType::Array(len, inner) = &field.ty else {
+        unreachable!("capacity is captured only for arrays");
+    };
+
+    let len_function = array_len_call(len, field_index);
+    let inner_capacity = match &inner.ty {
+        FieldType::Array(..) => {
+            let bind_index = bind_index(len);
+            let capture = capture_array_capacity(inner, field_index, quote! { *array.add(i) });
+            quote! {
+                (0..len)
+                    .map(|i| {
+                        #bind_index
+                        #capture
+                    })
+                    .collect()
+            }
+        }
+        _ => quote! { ::std::vec::Vec::new() },
+    };
+
+    quote! {{
+        let array = #array;
+        match array.is_null() {
+            true => ::cdump::internal::ArrayCapacity::default(),
+            false => {
+                let len = (*dst).#len_function;
+                ::cdump::internal::ArrayCapacity {
+                    len,
+                    inner: #inner_capacity,
+                }
+            }
+        }
+    }}
+}
+
+/// Deserialize the data under pointers of the field, or of the element of the inline array, where `temp` is in the
+/// shallow copy in the buffer, `dst` in the destination, and `capacity` is captured by [`capture_capacity`].
+fn read_deep_field_at_to(
+    field: &Field,
+    field_index: usize,
+    temp: TokenStream,
+    dst: TokenStream,
+    capacity: TokenStream,
+) -> TokenStream {
+    let path = field.path.to_token_stream();
+
+    let result = match &field.ty {
+        FieldType::Plain | FieldType::InlineArray(_) => {
+            unreachable!("shallow fields should not be under first level pointer")
+        }
+        FieldType::DeepInlineArray(..) => unreachable!("inline arrays are followed by elements"),
+        FieldType::Union(_) => {
+            let discriminator_function = discriminator_function_ident(field_index);
+            // Pointers of the destination are reused only when the same member is active in it.
+            return quote! {
+                let discriminator = (*temp).#discriminator_function();
+                let mut empty = ::std::mem::zeroed::<#path>();
+                let member_dst = match (*dst).#discriminator_function() == discriminator {
+                    true => ::std::ptr::addr_of_mut!(#dst),
+                    false => &mut empty as *mut #path,
+                };
+                ::cdump::union::CUnionDeserialize::deserialize_to_member(
+                    buf,
+                    ::std::ptr::addr_of_mut!(#temp),
+                    member_dst,
+                    policy,
+                    discriminator,
+                )?;
+            };
+        }
+        FieldType::Nested => {
+            return quote! {
+                ::cdump::CDeserialize::deserialize_to_without_shallow_copy(
+                    buf,
+                    ::std::ptr::addr_of_mut!(#temp),
+                    ::std::ptr::addr_of_mut!(#dst),
+                    policy,
+                )?;
+            };
+        }
+        FieldType::Reference => match is_primitive_type(&path) {
+            true => quote! {
+                let ptr = policy.try_destination(#dst as *mut #path, 1, 1)?;
+                ::cdump::internal::align_reader::<__CDumpBuffer, #path>(buf);
+                let size = ::std::mem::size_of::<#path>();
+                ::std::ptr::copy_nonoverlapping(buf.read_raw_slice(size) as *const u8, ptr as *mut u8, size);
+                #temp = ptr as _;
+            },
+            false => quote! {
+                let ptr = policy.try_destination(#dst as *mut #path, 1, 1)?;
+                ::cdump::internal::deserialize_shallow_copied_to(buf, ptr, policy)?;
+                #temp = ptr as _;
+            },
+        },
+        FieldType::CString => {
+            let copy = copy_cstring(
+                quote! { #temp as usize },
+                quote! { #dst as *mut ::std::ffi::c_char },
+                field,
+                field_index,
+            );
+            quote! {
+                #copy
+                #temp = element as _;
+            }
+        }
+        FieldType::Bytes(bytes) => {
+            let len_function = len_function_ident(field_index);
+            let align_type = bytes.align_type();
+            // Destination is allocated with alignment of dynamic objects, which is not less than alignment of bytes.
+            quote! {
+                let len = (*temp).#len_function();
+                let capacity = #capacity;
+                ::cdump::internal::align_reader::<__CDumpBuffer, #align_type>(buf);
+                let ptr = policy.try_dynamic_destination(#dst as *mut ::std::ffi::c_void, len, capacity)? as *mut u8;
+                ::std::ptr::copy_nonoverlapping(buf.read_raw_slice(len), ptr, len);
+                #temp = ptr as _;
+            }
+        }
+        FieldType::WString => {
+            let copy = copy_wstring(field, quote! { #temp as usize }, quote! { #dst });
+            quote! {
+                #copy
+                #temp = element as _;
+            }
+        }
+        FieldType::Dynamic(dynamic) => {
+            let copy = copy_dynamic(dynamic, quote! { #dst as *mut ::std::ffi::c_void });
+            quote! {
+                #copy
+                #temp = element as _;
+            }
+        }
+        FieldType::Array(len, inner) => {
+            let len_function = array_len_call(len, field_index);
+            let alignment_type = get_alignment_type(inner);
+            let inner_path = inner.path.to_token_stream();
+
+            if let FieldType::Dynamic(dynamic @ DynamicField { ptr_level: 1, .. }) = &inner.ty {
+                let copy = copy_dynamic_array(dynamic, &temp, &dst, &capacity);
+                return quote! {
+                    if !#temp.is_null() {
+                        let len = (*temp).#len_function;
+                        #copy
+                        #temp = array as _;
+                    }
+                };
+            }
+
+            // Null elements, e.g. terminators of arrays of pointers, stay null in the destination.
+            let skip_null = quote! {
+                let value = *buf.as_mut_ptr_at::<usize>(array_start_index + size * i);
+                if value == 0 {
+                    policy.overwrite(ptr.add(i), ::std::ptr::null_mut());
+                    continue;
+                }
+                ::cdump::internal::reject_back_reference(buf, value)?;
+            };
+            let copy = match &inner.ty {
+                FieldType::Plain if is_primitive_type(&inner_path) => quote! {
+                    let ptr = policy.try_destination(#dst as *mut #inner_path, len, capacity)?;
+                    ::std::ptr::copy_nonoverlapping(
+                        buf.as_mut_ptr_at::<u8>(array_start_index),
+                        ptr as *mut u8,
+                        size * len,
+                    );
+                },
+                FieldType::Plain => quote! {
+                    let ptr = policy.try_destination(#dst as *mut #inner_path, len, capacity)?;
+                    for i in 0..len {
+                        let element = buf.as_mut_ptr_at::<#inner_path>(array_start_index + size * i);
+                        ::cdump::internal::deserialize_shallow_copied_at_to(buf, element, ptr.add(i), policy)?;
+                    }
+                },
+                FieldType::Reference => quote! {
+                    let ptr = policy.try_destination(#dst as *mut *mut #inner_path, len, capacity)?;
+                    for i in 0..len {
+                        #skip_null
+                        let element = policy.try_destination(*ptr.add(i), 1, 1)?;
+                        ::cdump::internal::deserialize_shallow_copied_to(buf, element, policy)?;
+                        policy.overwrite(ptr.add(i), element);
+                    }
+                },
+                FieldType::CString => {
+                    let copy = copy_cstring(
+                        quote! { *buf.as_mut_ptr_at::<usize>(array_start_index + size * i) },
+                        quote! { *ptr.add(i) },
+                        inner,
+                        field_index,
+                    );
+                    quote! {
+                        let ptr = policy.try_destination(#dst as *mut *mut ::std::ffi::c_char, len, capacity)?;
+                        for i in 0..len {
+                            #skip_null
+                            #copy
+                            policy.overwrite(ptr.add(i), element);
+                        }
+                    }
+                }
+                FieldType::WString => {
+                    let copy = copy_wstring(
+                        inner,
+                        quote! { *buf.as_mut_ptr_at::<usize>(array_start_index + size * i) },
+                        quote! { *ptr.add(i) },
+                    );
+                    quote! {
+                        let ptr = policy.try_destination(#dst as *mut *mut #inner_path, len, capacity)?;
+                        for i in 0..len {
+                            #skip_null
+                            #copy
+                            policy.overwrite(ptr.add(i), element);
+                        }
+                    }
+                }
+                FieldType::Dynamic(dynamic) => {
+                    let copy = copy_dynamic(dynamic, quote! { *ptr.add(i) });
+                    quote! {
+                        let ptr = policy.try_destination(#dst as *mut *mut ::std::ffi::c_void, len, capacity)?;
+                        for i in 0..len {
+                            #skip_null
+                            #copy
+                            policy.overwrite(ptr.add(i), element);
+                        }
+                    }
+                }
+                FieldType::Array(..) => {
+                    let bind_index = bind_index(len);
+                    let element_type = element_type(inner);
+                    let read = read_deep_field_at_to(
+                        inner,
+                        field_index,
+                        quote! { (*element) },
+                        quote! { (*dst_element) },
+                        quote! { array_capacity.inner(i) },
+                    );
+                    quote! {
+                        let ptr = policy.try_destination(#dst as *mut #element_type, len, capacity)?;
+                        for i in 0..len {
+                            #bind_index
+                            let element = buf.as_mut_ptr_at::<#element_type>(array_start_index + size * i);
+                            let dst_element = ptr.add(i);
+                            #read
+                            policy.overwrite(dst_element, *element);
+                        }
+                    }
+                }
+                _ => unimplemented!("2D arrays"),
+            };
+
+            let len = match len.terminator {
+                Some(_) => quote! { #temp as usize },
+                None => quote! { (*temp).#len_function },
+            };
+
+            quote! {
+                let len = #len;
+                let array_capacity: &::cdump::internal::ArrayCapacity = &#capacity;
+                let capacity = array_capacity.len;
+                let size = ::std::mem::size_of::<#alignment_type>();
+                ::cdump::internal::align_reader::<__CDumpBuffer, #alignment_type>(buf);
+                let array_start_index = buf.get_read();
+                buf.add_read(size * len);
+
+                #copy
+                #temp = ptr as _;
+            }
+        }
+    };
+
+    quote! {
+        if !#temp.is_null() {
+            ::cdump::internal::reject_back_reference(buf, #temp as usize)?;
+            #result
+        }
+    }
+}
+
+/// Copy the C string with `len` bytes from the buffer to `element`, which reuses `dst` when its declared capacity is
+/// sufficient.
+fn copy_cstring(
+    len: TokenStream,
+    dst: TokenStream,
+    field: &Field,
+    field_index: usize,
+) -> TokenStream {
+    let capacity = match field.capacity {
+        Some(_) => {
+            let capacity_function = capacity_function_ident(field_index);
+            quote! { (*dst).#capacity_function() }
+        }
+        None => quote! { 0 },
+    };
+
+    quote! {
+        let string_len = #len;
+        let element = policy.try_destination(#dst, string_len, #capacity)?;
+        ::std::ptr::copy_nonoverlapping(buf.read_raw_slice(string_len), element as *mut u8, string_len);
+    }
+}
+
+/// Copy the wide string with `len` characters from the buffer to `element`, which never reuses `dst`, because its
+/// capacity is unknown.
+fn copy_wstring(field: &Field, len: TokenStream, dst: TokenStream) -> TokenStream {
+    let path = &field.path;
+    quote! {
+        let string_len = #len;
+        ::cdump::internal::align_reader::<__CDumpBuffer, #path>(buf);
+        let element = policy.try_destination(#dst as *mut #path, string_len, 0)?;
+        ::std::ptr::copy_nonoverlapping(
+            buf.read_raw_slice(string_len * ::std::mem::size_of::<#path>()) as *const #path,
+            element,
+            string_len,
+        );
+    }
+}
+
+/// Copy the dynamic object from the buffer to `element`, which reuses `dst` when it is sufficient. Null object
+/// returned by the deserializer, e.g. `pNext` chain without registered nodes, is copied as null.
+fn copy_dynamic(dynamic: &DynamicField, dst: TokenStream) -> TokenStream {
+    let deserializer = &dynamic.deserializer;
+    let size_of = &dynamic.size_of;
+    quote! {
+        let element = #dst;
+        let (src, size) = #deserializer(buf);
+        let element = match src.is_null() {
+            true => ::std::ptr::null_mut(),
+            false => {
+                let capacity = match element.is_null() {
+                    true => 0,
+                    false => #size_of(element),
+                };
+                let element = policy.try_dynamic_destination(element, size, capacity)?;
+                ::std::ptr::copy_nonoverlapping(src as *const u8, element as *mut u8, size);
+                element
+            }
+        };
+    }
+}
+
+/// Copy the array of dynamic objects under one pointer from the buffer to `array`, which reuses `dst` when the objects
+/// of the destination are large enough. Size of the array is stored in the pointer at `temp`.
+fn copy_dynamic_array(
+    dynamic: &DynamicField,
+    temp: &TokenStream,
+    dst: &TokenStream,
+    capacity: &TokenStream,
+) -> TokenStream {
+    let deserializer = &dynamic.deserializer;
+    let size_of = &dynamic.size_of;
+    quote! {
+        let array_size = #temp as usize;
+        let capacity = match #dst.is_null() {
+            true => 0,
+            false => ::cdump::internal::dynamic_array_size(#dst as *const ::std::ffi::c_void, #capacity.len, #size_of),
+        };
+        ::cdump::internal::align_reader::<__CDumpBuffer, usize>(buf);
+        buf.add_read(array_size);
+
+        let array = policy.try_dynamic_destination(#dst as *mut ::std::ffi::c_void, array_size, capacity)? as *mut u8;
+        let mut offset: usize = 0;
+        for _ in 0..len {
+            let (element, size) = #deserializer(buf);
+            offset = ::cdump::internal::try_copy_to_dynamic_array(array, array_size, offset, element as _, size)?;
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use darling::FromDeriveInput;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{spanned::Spanned, Data, DeriveInput, Error, Ident};
+
+#[derive(darling::FromDeriveInput)]
+#[darling(attributes(cdump), supports(enum_unit))]
+struct EnumReceiver {
+    fallback: Option<Ident>,
+}
+
+/// Fieldless enum, which is serialized as its integer representation.
+struct Enum {
+    repr: TokenStream,
+    variants: Vec<Ident>,
+    fallback: Option<Ident>,
+}
+
+fn get_enum(ast: &DeriveInput) -> Result<Enum, Error> {
+    let receiver = EnumReceiver::from_derive_input(ast)
+        .map_err(|err| Error::new(err.span(), err.to_string()))?;
+    let Data::Enum(data) = &ast.data else {
+        unreachable!("enum shape should be validated before");
+    };
+    let variants = data
+        .variants
+        .iter()
+        .map(|variant| variant.ident.clone())
+        .collect::<Vec<_>>();
+
+    if variants.is_empty() {
+        return Err(Error::new(
+            ast.ident.span(),
+            "enum must have at least one variant",
+        ));
+    }
+
+    if let Some(fallback) = &receiver.fallback {
+        if !variants.contains(fallback) {
+            return Err(Error::new(
+                fallback.span(),
+                "fallback must be a variant of the enum",
+            ));
+        }
+    }
+
+    Ok(Enum {
+        repr: get_repr(ast)?,
+        variants,
+        fallback: receiver.fallback,
+    })
+}
+
+/// Get integer type of the enum, where `#[repr(C)]` enums have size of C's `int`.
+pub fn get_repr(ast: &DeriveInput) -> Result<TokenStream, Error> {
+    let mut repr = None;
+    for attr in ast.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            let ident = meta.path.get_ident().map(ToString::to_string);
+            match ident.as_deref() {
+                Some(
+                    "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64" | "isize",
+                ) => {
+                    let ident = Ident::new(ident.as_deref().unwrap(), Span::call_site());
+                    repr = Some(quote! { #ident });
+                }
+                Some("C") => {
+                    repr.get_or_insert_with(|| quote! { ::std::ffi::c_int });
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+    }
+
+    repr.ok_or_else(|| {
+        Error::new(
+            ast.span(),
+            "expected `#[repr(C)]` or `#[repr]` with integer type",
+        )
+    })
+}
+
+/// Get the const expression which computes the layout hash of the enum from its variants.
+fn layout_hash(name: &Ident, value: &Enum) -> TokenStream {
+    let name_str = name.to_string();
+    let repr = &value.repr;
+    let variants = &value.variants;
+    let variants_str = variants.iter().map(ToString::to_string);
+
+    quote! {
+        ::cdump::internal::LayoutHasher::new()
+            .write_str(#name_str)
+            .write_usize(::std::mem::size_of::<Self>())
+            .write_usize(::std::mem::align_of::<Self>())
+            #(
+                .write_str(#variants_str)
+                .write_u64(Self::#variants as #repr as u64)
+            )*
+            .finish()
+    }
+}
+
+pub fn serialize_impl(ast: &DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let value = match get_enum(ast) {
+        Ok(value) => value,
+        Err(err) => return err.to_compile_error(),
+    };
+    let layout_hash = layout_hash(name, &value);
+
+    quote! {
+        impl<__CDumpBuffer: ::cdump::CDumpWriter> ::cdump::CSerialize<__CDumpBuffer> for #name {
+            const LAYOUT_HASH: u64 = #layout_hash;
+
+            unsafe fn try_serialize(&self, buf: &mut __CDumpBuffer) -> Result<(), ::cdump::Error> {
+                buf.try_align::<Self>()?;
+                buf.try_push_slice(::cdump::internal::as_bytes(self))
+            }
+
+            unsafe fn try_serialize_without_shallow_copy(
+                &self,
+                _buf: &mut __CDumpBuffer,
+                _start_index: usize,
+            ) -> Result<(), ::cdump::Error> {
+                Ok(())
+            }
+        }
+
+        impl ::cdump::CSerializedSize for #name {
+            unsafe fn serialized_end(&self, start: usize) -> usize {
+                ::cdump::internal::align_index::<Self>(start).saturating_add(::std::mem::size_of::<Self>())
+            }
+
+            unsafe fn serialized_end_without_shallow_copy(&self, end: usize) -> usize {
+                end
+            }
+        }
+    }
+}
+
+pub fn deserialize_impl(ast: &DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let value = match get_enum(ast) {
+        Ok(value) => value,
+        Err(err) => return err.to_compile_error(),
+    };
+    let layout_hash = layout_hash(name, &value);
+    let Enum {
+        repr,
+        variants,
+        fallback,
+    } = &value;
+
+    let unknown = match fallback {
+        Some(fallback) => quote! {
+            ::std::ptr::write(dst, Self::#fallback);
+            Ok(())
+        },
+        None => quote! {
+            Err(::cdump::Error::UnknownDiscriminant(value as i64))
+        },
+    };
+    let validate = Ident::new(
+        "do_not_use_cdump_internal_function_validate_discriminant",
+        Span::call_site(),
+    );
+
+    quote! {
+        const _: () = assert!(
+            ::std::mem::size_of::<#name>() == ::std::mem::size_of::<#repr>(),
+            "size of the enum must be equal to size of its representation",
+        );
+
+        impl #name {
+            /// Check the discriminant of the copy in the buffer, and replace unknown one with the fallback.
+            #[doc(hidden)]
+            unsafe fn #validate(dst: *mut Self) -> Result<(), ::cdump::Error> {
+                let value = ::std::ptr::read(dst as *const #repr);
+                if #(value == Self::#variants as #repr)||* {
+                    return Ok(());
+                }
+                #unknown
+            }
+        }
+
+        unsafe impl ::cdump::CDeepCopy for #name {
+            unsafe fn null_without_shallow_copy(_obj: *mut Self) {}
+
+            unsafe fn try_deep_copy_without_shallow_copy<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                _src: *const Self,
+                _dst: *mut Self,
+                _alloc: &__CDumpAllocator,
+            ) -> Result<(), ::cdump::Error> {
+                Ok(())
+            }
+
+            unsafe fn deep_free_without_shallow_copy<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                _obj: *mut Self,
+                _alloc: &__CDumpAllocator,
+            ) {
+            }
+        }
+
+        impl<__CDumpBuffer: ::cdump::CDumpReader> ::cdump::CDeserialize<__CDumpBuffer> for #name {
+            const LAYOUT_HASH: u64 = #layout_hash;
+
+            unsafe fn deserialize_to<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                buf: &mut __CDumpBuffer,
+                dst: *mut Self,
+                policy: &::cdump::alloc::DestinationPolicy<__CDumpAllocator>,
+            ) -> Result<(), ::cdump::Error> {
+                ::cdump::internal::deserialize_shallow_copied_to(buf, dst, policy)
+            }
+
+            unsafe fn deserialize_to_without_shallow_copy<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                _buf: &mut __CDumpBuffer,
+                temp: *mut Self,
+                _dst: *mut Self,
+                _policy: &::cdump::alloc::DestinationPolicy<__CDumpAllocator>,
+            ) -> Result<(), ::cdump::Error> {
+                Self::#validate(temp)
+            }
+
+            unsafe fn deserialize_ref_mut(buf: &mut __CDumpBuffer) -> &mut Self {
+                ::cdump::internal::align_reader::<__CDumpBuffer, Self>(buf);
+                let reference = buf.read_raw_slice(::std::mem::size_of::<Self>()) as *mut Self;
+                Self::deserialize_ref_mut_without_shallow_copy(buf, reference);
+                &mut *reference
+            }
+
+            unsafe fn deserialize_ref_mut_without_shallow_copy(_buf: &mut __CDumpBuffer, dst: *mut Self) {
+                // Trusted buffer is not validated, but unknown values are still replaced by the fallback.
+                _ = Self::#validate(dst);
+            }
+
+            unsafe fn try_deserialize_ref_mut(buf: &mut __CDumpBuffer) -> Result<&mut Self, ::cdump::Error> {
+                Ok(&mut *::cdump::internal::try_deserialize_shallow_copied::<__CDumpBuffer, Self>(buf)?)
+            }
+
+            unsafe fn try_deserialize_ref_mut_without_shallow_copy(
+                _buf: &mut __CDumpBuffer,
+                dst: *mut Self,
+            ) -> Result<(), ::cdump::Error> {
+                Self::#validate(dst)
+            }
+
+            unsafe fn try_validate_shallow(dst: *mut Self) -> Result<(), ::cdump::Error> {
+                Self::#validate(dst)
+            }
+        }
+    }
+}
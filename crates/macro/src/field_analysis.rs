@@ -1,10 +1,22 @@
-use darling::{ast::Data, FromDeriveInput};
-use syn::{spanned::Spanned, DeriveInput, Error, Expr, Ident, Type, TypeArray, TypePath};
+use darling::{FromDeriveInput, FromField};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse_quote, spanned::Spanned, Data, DeriveInput, Error, Expr, ExprField, Fields, Ident, Index,
+    Member, Path, Type, TypeArray, TypePath,
+};
 
 pub struct Field {
-    pub ident: Option<Ident>,
+    /// Name of the field, or its index in tuple struct.
+    pub ident: Member,
     pub path: Option<TypePath>,
     pub ty: FieldType,
+    /// Capacity of the destination string in bytes, used by `deserialize_to`.
+    pub capacity: Option<Expr>,
+    /// Pointer to the next object of the same type, which chain is traversed in the loop instead of recursion.
+    pub iterative: bool,
+    /// Discriminator value which selects the member of the union.
+    pub case: Option<Expr>,
 }
 
 pub enum FieldType {
@@ -12,37 +24,109 @@ pub enum FieldType {
     InlineArray(TypeArray),
     Reference,
     CString,
-    Array(Expr, Box<Field>),
+    /// Wide string of characters of the path, which ends with the zero character.
+    WString,
+    /// Opaque bytes under pointer, which length is given by the expression.
+    Bytes(BytesField),
+    Array(ArrayLen, Box<Field>),
     Dynamic(DynamicField),
+    /// Union by value, which active member is selected by the discriminator expression.
+    Union(Expr),
+    /// Struct by value, which pointers are followed as a part of the parent.
+    Nested,
+    /// Array by value, which elements are followed like the inner field.
+    DeepInlineArray(TypeArray, Box<Field>),
+}
+
+/// Length of the array under pointer, which is the field itself, or an element of the outer array.
+pub struct ArrayLen {
+    pub expr: Expr,
+    /// Name of the index of the current element, which lengths of inner arrays can use.
+    pub index: Option<Ident>,
+    /// Level of the array, where 0 is the field itself.
+    pub depth: usize,
+    /// Names of indices of the outer arrays, which the length can use.
+    pub outer_indices: Vec<Ident>,
+    /// Check of the element which terminates the array, when its length is found by `expr` scanning to it.
+    pub terminator: Option<TokenStream>,
+}
+
+impl ToTokens for ArrayLen {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.expr.to_tokens(tokens);
+    }
+}
+
+pub struct BytesField {
+    pub len: Expr,
+    /// Alignment of the bytes, which is a power of two up to 16.
+    pub align: usize,
+}
+
+impl BytesField {
+    /// Get the zero-sized type with the alignment of the bytes.
+    pub fn align_type(&self) -> TokenStream {
+        let ident = Ident::new(&format!("Align{}", self.align), Span::call_site());
+        quote! { ::cdump::internal::#ident }
+    }
 }
 
 pub struct DynamicField {
-    pub serializer: Ident,
-    pub deserializer: Ident,
-    pub size_of: Ident,
+    pub serializer: Option<TokenStream>,
+    pub try_serializer: Option<TokenStream>,
+    pub deserializer: TokenStream,
+    pub try_deserializer: Option<TokenStream>,
+    pub size_of: TokenStream,
+    pub size: Option<TokenStream>,
+    pub skip: Option<TokenStream>,
+    pub deep_copy: Option<TokenStream>,
+    pub free: Option<TokenStream>,
     pub ptr_level: usize,
     #[cfg(feature = "cdebug")]
-    pub cdebugger: Option<Ident>,
+    pub cdebugger: Option<TokenStream>,
 }
 
 #[cfg(feature = "cdebug")]
 impl DynamicField {
-    pub fn call_cdebugger(&self, ptr: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    pub fn call_cdebugger(&self, ptr: TokenStream) -> TokenStream {
         match &self.cdebugger {
-            Some(cdebugger) => quote::quote! { unsafe { #cdebugger(#ptr) } },
-            None => quote::quote! { &"<missing cdebugger>" },
+            Some(cdebugger) => quote! { unsafe { #cdebugger(#ptr) } },
+            None => quote! { &"<missing cdebugger>" },
         }
     }
 }
 
 pub fn get_fields(ast: &DeriveInput, skip_shallow_part: bool) -> Result<Vec<Field>, Error> {
-    let receiver = InputReceiver::from_derive_input(ast).unwrap();
+    let is_union = is_union(ast);
     let mut vec = Vec::new();
 
-    for field in &receiver.data.take_struct().unwrap().fields {
-        if let Type::Ptr(_) = &field.ty {
+    for (index, field) in get_field_receivers(ast)?.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(index)),
+        };
+
+        if is_union {
+            validate_union_member(field)?;
+        } else if field.case.is_some() {
+            return Err(Error::new(
+                field.ty.span(),
+                "case is supported only by members of union",
+            ));
+        }
+
+        if let Some(bytes) = &field.bytes {
+            vec.push(Field {
+                ident: member.clone(),
+                path: None,
+                ty: FieldType::Bytes(bytes_field(bytes, field)?),
+                capacity: None,
+                iterative: false,
+                case: field.case.clone(),
+            });
+        } else if let Type::Ptr(_) = &field.ty {
             let (ty, ptr_level) = extract_ptr(&field.ty);
-            let raw_ty = get_raw_field_type(ty);
+            let raw_ty = get_string_or_raw_field_type(ty, ptr_level, field)?;
 
             let path = match ty {
                 Type::Path(path) => Some(path.clone()),
@@ -50,47 +134,129 @@ pub fn get_fields(ast: &DeriveInput, skip_shallow_part: bool) -> Result<Vec<Fiel
             };
 
             validate_field(raw_ty, ptr_level, field)?;
+            if field.iterative.is_present() {
+                validate_iterative(&ast.ident, ty, ptr_level, field, &vec)?;
+            }
 
             let fty = match raw_ty {
                 RawFieldType::Reference => FieldType::Reference,
                 RawFieldType::CString => FieldType::CString,
-                RawFieldType::Dynamic => {
-                    let dynamic = field.dynamic.as_ref().unwrap();
-                    FieldType::Dynamic(DynamicField {
-                        serializer: dynamic.serializer.clone(),
-                        deserializer: dynamic.deserializer.clone(),
-                        size_of: dynamic.size_of.clone(),
+                RawFieldType::WString => FieldType::WString,
+                RawFieldType::Dynamic => match (&field.pnext, &field.tagged) {
+                    (Some(pnext), _) => FieldType::Dynamic(pnext_field(pnext)),
+                    (None, Some(tag)) => FieldType::Dynamic(tagged_field(tag, ptr_level)),
+                    (None, None) => FieldType::Dynamic(dynamic_field(
+                        field.dynamic.as_ref().unwrap(),
                         ptr_level,
-                        #[cfg(feature = "cdebug")]
-                        cdebugger: dynamic.cdebugger.clone(),
-                    })
-                }
+                    )),
+                },
             };
 
             vec.push(Field {
-                ident: field.ident.clone(),
+                ident: member.clone(),
                 path: path.clone(),
+                iterative: field.iterative.is_present(),
+                case: field.case.clone(),
+                capacity: match &field.array {
+                    Some(_) => None,
+                    None => field.capacity.clone(),
+                },
                 ty: match &field.array {
-                    Some(array) => FieldType::Array(
-                        array.len.clone(),
-                        Box::new(Field {
-                            ident: field.ident.clone(),
+                    Some(array) => {
+                        // Every array consumes one level of pointer, so the innermost elements are the structs by
+                        // value, or pointers.
+                        let leaf = Field {
+                            ident: member.clone(),
                             path,
+                            capacity: field.capacity.clone(),
+                            iterative: false,
+                            case: None,
                             ty: match fty {
-                                FieldType::Reference => match ptr_level == 1 {
+                                FieldType::Reference => match ptr_level == array.depth() {
                                     true => FieldType::Plain,
                                     false => FieldType::Reference,
                                 },
                                 _ => fty,
                             },
-                        }),
-                    ),
+                        };
+                        array_type(array, &member, leaf, 0, Vec::new())
+                    }
                     None => fty,
                 },
             });
+        } else if let Some(element) = get_inline_array_element(field, &member)? {
+            let Type::Array(array) = &field.ty else {
+                unreachable!("inline array should be validated before");
+            };
+
+            vec.push(Field {
+                ident: member.clone(),
+                path: None,
+                ty: FieldType::DeepInlineArray(array.clone(), Box::new(element)),
+                capacity: None,
+                iterative: false,
+                case: None,
+            });
+        } else if field.capacity.is_some() {
+            return Err(Error::new(
+                field.ty.span(),
+                "capacity is supported only by CString fields",
+            ));
+        } else if field.wstring.is_present() {
+            return Err(Error::new(
+                field.ty.span(),
+                "wstring is supported only by pointers to wide characters",
+            ));
+        } else if field.iterative.is_present() {
+            return Err(Error::new(
+                field.ty.span(),
+                "iterative is supported only by pointer to the same type",
+            ));
+        } else if field.pnext.is_some() {
+            return Err(Error::new(
+                field.ty.span(),
+                "pnext is supported only by pointer to c_void",
+            ));
+        } else if field.tagged.is_some() {
+            return Err(Error::new(
+                field.ty.span(),
+                "tagged is supported only by pointer to c_void",
+            ));
+        } else if field.nested.is_present() {
+            let (Type::Path(path), None) = (&field.ty, &field.discriminator) else {
+                return Err(Error::new(
+                    field.ty.span(),
+                    "nested is supported only by struct fields by value",
+                ));
+            };
+
+            vec.push(Field {
+                ident: member.clone(),
+                path: Some(path.clone()),
+                ty: FieldType::Nested,
+                capacity: None,
+                iterative: false,
+                case: None,
+            });
+        } else if let Some(discriminator) = &field.discriminator {
+            let Type::Path(path) = &field.ty else {
+                return Err(Error::new(
+                    field.ty.span(),
+                    "discriminator is supported only by union fields",
+                ));
+            };
+
+            vec.push(Field {
+                ident: member.clone(),
+                path: Some(path.clone()),
+                ty: FieldType::Union(discriminator.clone()),
+                capacity: None,
+                iterative: false,
+                case: None,
+            });
         } else if !skip_shallow_part {
             vec.push(Field {
-                ident: field.ident.clone(),
+                ident: member.clone(),
                 path: match &field.ty {
                     Type::Path(path) => Some(path.clone()),
                     _ => None,
@@ -99,6 +265,9 @@ pub fn get_fields(ast: &DeriveInput, skip_shallow_part: bool) -> Result<Vec<Fiel
                     Type::Array(array) => FieldType::InlineArray(array.clone()),
                     _ => FieldType::Plain,
                 },
+                capacity: None,
+                iterative: false,
+                case: field.case.clone(),
             })
         }
     }
@@ -106,11 +275,82 @@ pub fn get_fields(ast: &DeriveInput, skip_shallow_part: bool) -> Result<Vec<Fiel
     Ok(vec)
 }
 
+/// Check whether the derive input is a fieldless enum, which is serialized as its integer representation.
+pub fn is_enum(ast: &DeriveInput) -> bool {
+    matches!(ast.data, Data::Enum(_))
+}
+
+/// Check whether the derive input is a struct with unnamed fields.
+pub fn is_tuple_struct(ast: &DeriveInput) -> bool {
+    matches!(&ast.data, Data::Struct(data) if matches!(data.fields, Fields::Unnamed(_)))
+}
+
+/// Check whether the derive input is a union, which fields are its members.
+pub fn is_union(ast: &DeriveInput) -> bool {
+    matches!(ast.data, Data::Union(_))
+}
+
+fn get_field_receivers(ast: &DeriveInput) -> Result<Vec<FieldReceiver>, Error> {
+    let fields = match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+            Fields::Unit => Vec::new(),
+        },
+        Data::Union(data) => data.fields.named.iter().collect(),
+        Data::Enum(_) => return Err(Error::new(ast.ident.span(), "expected struct or union")),
+    };
+
+    fields
+        .into_iter()
+        .map(|field| FieldReceiver::from_field(field).map_err(|err| Error::new(err.span(), err)))
+        .collect()
+}
+
+fn validate_union_member(field: &FieldReceiver) -> Result<(), Error> {
+    let unsupported = [
+        (field.array.is_some(), "array"),
+        (field.bytes.is_some(), "bytes"),
+        (field.iterative.is_present(), "iterative"),
+        (field.discriminator.is_some(), "discriminator"),
+        (field.nested.is_present(), "nested"),
+    ];
+    if let Some((_, name)) = unsupported.iter().find(|(present, _)| *present) {
+        return Err(Error::new(
+            field.ty.span(),
+            format!("{name} is not supported by members of union"),
+        ));
+    }
+
+    if let (Type::Ptr(_), None) = (&field.ty, &field.case) {
+        return Err(Error::new(
+            field.ty.span(),
+            "member of union with pointer requires case",
+        ));
+    }
+
+    Ok(())
+}
+
 fn validate_field(
     raw_ty: RawFieldType,
     ptr_level: usize,
     field: &FieldReceiver,
 ) -> Result<(), Error> {
+    if field.discriminator.is_some() {
+        return Err(Error::new(
+            field.ty.span(),
+            "discriminator is supported only by union fields",
+        ));
+    }
+
+    if field.nested.is_present() {
+        return Err(Error::new(
+            field.ty.span(),
+            "nested is supported only by struct fields by value",
+        ));
+    }
+
     if ptr_level != 1 {
         if field.array.is_none() {
             return Err(Error::new(
@@ -119,7 +359,7 @@ fn validate_field(
             ));
         }
 
-        if ptr_level > 2 {
+        if ptr_level > 2 && field.array.as_ref().unwrap().depth() == 1 {
             return Err(Error::new(
                 field.ty.span(),
                 "more than two levels of pointer is not supported",
@@ -127,23 +367,473 @@ fn validate_field(
         }
     }
 
-    if raw_ty == RawFieldType::Dynamic {
-        if field.dynamic.is_none() {
+    if let Some(array) = &field.array {
+        validate_array(raw_ty, ptr_level, array, field)?;
+    }
+
+    if field.capacity.is_some() && raw_ty != RawFieldType::CString {
+        return Err(Error::new(
+            field.ty.span(),
+            "capacity is supported only by CString fields",
+        ));
+    }
+
+    if let Some(capacity) = &field.capacity {
+        validate_capacity(capacity)?;
+    }
+
+    if field.pnext.is_some() {
+        if raw_ty != RawFieldType::Dynamic || ptr_level != 1 || field.array.is_some() {
+            return Err(Error::new(
+                field.ty.span(),
+                "pnext is supported only by pointer to c_void",
+            ));
+        }
+
+        if field.dynamic.is_some() || field.tagged.is_some() {
+            return Err(Error::new(
+                field.ty.span(),
+                "pnext cannot be used together with dynamic or tagged",
+            ));
+        }
+    } else if field.tagged.is_some() {
+        if raw_ty != RawFieldType::Dynamic {
             return Err(Error::new(
                 field.ty.span(),
-                "dynamic field requires provide a serializer and deserializer",
+                "tagged is supported only by pointer to c_void",
             ));
-        } else if ptr_level == 1 && field.array.is_some() {
+        }
+
+        if field.dynamic.is_some() {
             return Err(Error::new(
                 field.ty.span(),
-                "array of dynamic field under one level of pointer is not supported",
+                "tagged and dynamic cannot be used together",
             ));
         }
+    } else if raw_ty == RawFieldType::Dynamic {
+        match &field.dynamic {
+            None => {
+                return Err(Error::new(
+                    field.ty.span(),
+                    "dynamic field requires provide a serializer and deserializer",
+                ))
+            }
+            Some(DynamicReceiver {
+                serializer: None,
+                try_serializer: None,
+                ..
+            }) => {
+                return Err(Error::new(
+                    field.ty.span(),
+                    "dynamic field requires provide a serializer or try_serializer",
+                ))
+            }
+            Some(DynamicReceiver {
+                deep_copy: Some(_),
+                free: None,
+                ..
+            })
+            | Some(DynamicReceiver {
+                deep_copy: None,
+                free: Some(_),
+                ..
+            }) => {
+                return Err(Error::new(
+                    field.ty.span(),
+                    "dynamic field requires provide both deep_copy and free, or none of them",
+                ))
+            }
+            _ => {}
+        }
     }
 
     Ok(())
 }
 
+fn validate_array(
+    raw_ty: RawFieldType,
+    ptr_level: usize,
+    array: &ArrayReceiver,
+    field: &FieldReceiver,
+) -> Result<(), Error> {
+    let depth = array.depth();
+    let mut innermost = array;
+    loop {
+        let modes = [
+            innermost.len.is_some(),
+            innermost.terminator.is_some(),
+            innermost.null_terminated.is_present(),
+        ];
+        if modes.iter().filter(|&&mode| mode).count() != 1 {
+            return Err(Error::new(
+                field.ty.span(),
+                "array requires exactly one of len, terminator or null_terminated",
+            ));
+        }
+
+        if innermost.is_terminated() && depth != 1 {
+            return Err(Error::new(
+                field.ty.span(),
+                "terminated arrays are not supported by arrays of arrays",
+            ));
+        }
+
+        match &innermost.array {
+            Some(inner) => innermost = inner,
+            None => break,
+        }
+    }
+    if innermost.index.is_some() {
+        return Err(Error::new(
+            field.ty.span(),
+            "index is supported only by arrays of arrays",
+        ));
+    }
+
+    if depth == 1 {
+        if array.is_terminated() && raw_ty == RawFieldType::Dynamic && ptr_level == 1 {
+            return Err(Error::new(
+                field.ty.span(),
+                "terminated arrays of dynamic objects under one level of pointer are not supported",
+            ));
+        }
+
+        return Ok(());
+    }
+
+    if ptr_level < depth || ptr_level > depth + 1 {
+        return Err(Error::new(
+            field.ty.span(),
+            "arrays of arrays require one level of pointer for every array, and at most one for elements",
+        ));
+    }
+
+    if raw_ty == RawFieldType::Dynamic {
+        return Err(Error::new(
+            field.ty.span(),
+            "dynamic fields are not supported by arrays of arrays",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Get the type of the array of the field, which elements are `leaf` or inner arrays.
+fn array_type(
+    array: &ArrayReceiver,
+    member: &Member,
+    leaf: Field,
+    depth: usize,
+    outer_indices: Vec<Ident>,
+) -> FieldType {
+    let terminator = match (&array.terminator, array.null_terminated.is_present()) {
+        (Some(terminator), _) => Some(quote! { |element| *element == (#terminator) }),
+        (None, true) => Some(quote! { |element| ::cdump::internal::is_zeroed(element) }),
+        (None, false) => None,
+    };
+    // Terminated arrays are only fields, which are scanned to the terminator.
+    let expr = match (&array.len, &terminator) {
+        (Some(len), _) => len.clone(),
+        (None, Some(terminator)) => parse_quote! {
+            unsafe { ::cdump::internal::terminated_len(self.#member, #terminator) }
+        },
+        (None, None) => unreachable!("length of array should be validated before"),
+    };
+    let len = ArrayLen {
+        expr,
+        index: array.index.clone(),
+        depth,
+        outer_indices: outer_indices.clone(),
+        terminator,
+    };
+    let inner = match &array.array {
+        Some(inner) => {
+            let path = leaf.path.clone();
+            let outer_indices = outer_indices
+                .into_iter()
+                .chain(array.index.clone())
+                .collect();
+            Field {
+                ident: member.clone(),
+                path,
+                ty: array_type(inner, member, leaf, depth + 1, outer_indices),
+                capacity: None,
+                iterative: false,
+                case: None,
+            }
+        }
+        None => leaf,
+    };
+
+    FieldType::Array(len, Box::new(inner))
+}
+
+fn validate_iterative(
+    name: &Ident,
+    ty: &Type,
+    ptr_level: usize,
+    field: &FieldReceiver,
+    fields: &[Field],
+) -> Result<(), Error> {
+    let is_same_type = match ty {
+        // Generic arguments of the type are expected to be the same as of `Self`.
+        Type::Path(path) => {
+            path.path.is_ident("Self")
+                || matches!(path.path.segments.iter().collect::<Vec<_>>()[..], [segment] if segment.ident == *name)
+        }
+        _ => false,
+    };
+    if !is_same_type || ptr_level != 1 || field.array.is_some() {
+        return Err(Error::new(
+            field.ty.span(),
+            "iterative is supported only by pointer to the same type",
+        ));
+    }
+
+    if fields.iter().any(|field| field.iterative) {
+        return Err(Error::new(
+            field.ty.span(),
+            "only one field can be iterative",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Get element of the inline array, which is followed when it is a pointer, or a struct with `nested` attribute.
+fn get_inline_array_element(
+    field: &FieldReceiver,
+    member: &Member,
+) -> Result<Option<Field>, Error> {
+    let Type::Array(array) = &field.ty else {
+        return Ok(None);
+    };
+    let (ty, ptr_level) = extract_ptr(&array.elem);
+    if ptr_level == 0 && !field.nested.is_present() {
+        return Ok(None);
+    }
+
+    let unsupported = [
+        (field.array.is_some(), "array"),
+        (field.iterative.is_present(), "iterative"),
+        (field.pnext.is_some(), "pnext"),
+        (field.discriminator.is_some(), "discriminator"),
+    ];
+    if let Some((_, name)) = unsupported.iter().find(|(present, _)| *present) {
+        return Err(Error::new(
+            field.ty.span(),
+            format!("{name} is not supported by inline arrays"),
+        ));
+    }
+
+    let element_ty =
+        match ptr_level {
+            0 => {
+                if field.dynamic.is_some() || field.tagged.is_some() || field.capacity.is_some() {
+                    return Err(Error::new(
+                    field.ty.span(),
+                    "nested inline array cannot be used together with dynamic, tagged or capacity",
+                ));
+                }
+                FieldType::Nested
+            }
+            1 => {
+                let raw_ty = get_string_or_raw_field_type(ty, ptr_level, field)?;
+                validate_field(raw_ty, ptr_level, field)?;
+                match raw_ty {
+                    RawFieldType::Reference => FieldType::Reference,
+                    RawFieldType::CString => FieldType::CString,
+                    RawFieldType::WString => FieldType::WString,
+                    RawFieldType::Dynamic => match &field.tagged {
+                        Some(tag) => FieldType::Dynamic(tagged_field(tag, ptr_level)),
+                        None => FieldType::Dynamic(dynamic_field(
+                            field.dynamic.as_ref().unwrap(),
+                            ptr_level,
+                        )),
+                    },
+                }
+            }
+            _ => return Err(Error::new(
+                field.ty.span(),
+                "elements of inline array with more than one level of pointer are not supported",
+            )),
+        };
+
+    Ok(Some(Field {
+        ident: member.clone(),
+        path: match ty {
+            Type::Path(path) => Some(path.clone()),
+            _ => None,
+        },
+        ty: element_ty,
+        capacity: field.capacity.clone(),
+        iterative: false,
+        case: None,
+    }))
+}
+
+/// Check that the capacity describes the destination. It is either a field of `self`, which keeps the destination's
+/// value after `deserialize_to`, or an expression which does not read other fields, that are overwritten by the sender.
+fn validate_capacity(capacity: &Expr) -> Result<(), Error> {
+    if capacity_member(capacity).is_some() || !reads_self(capacity.to_token_stream()) {
+        return Ok(());
+    }
+
+    Err(Error::new(
+        capacity.span(),
+        "capacity must be a field of self, or an expression which does not read self",
+    ))
+}
+
+fn reads_self(tokens: TokenStream) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "self",
+        TokenTree::Group(group) => reads_self(group.stream()),
+        _ => false,
+    })
+}
+
+/// Get the member of `self` which stores the capacity, when the capacity is a field of `self`.
+pub fn capacity_member(capacity: &Expr) -> Option<&Member> {
+    match capacity {
+        Expr::Field(ExprField { base, member, .. }) => match &**base {
+            Expr::Path(path) if path.path.is_ident("self") => Some(member),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Get the bytes of the field, which must be a pointer to `c_void` or `u8` without other attributes.
+fn bytes_field(bytes: &BytesReceiver, field: &FieldReceiver) -> Result<BytesField, Error> {
+    let (ty, ptr_level) = extract_ptr(&field.ty);
+    let is_byte = match ty {
+        Type::Path(path) => {
+            path.path.is_ident("u8") || get_raw_field_type(ty) == RawFieldType::Dynamic
+        }
+        _ => false,
+    };
+    if !is_byte || ptr_level != 1 {
+        return Err(Error::new(
+            field.ty.span(),
+            "bytes is supported only by pointers to c_void or u8",
+        ));
+    }
+
+    let unsupported = [
+        (field.array.is_some(), "array"),
+        (field.dynamic.is_some(), "dynamic"),
+        (field.capacity.is_some(), "capacity"),
+        (field.iterative.is_present(), "iterative"),
+        (field.pnext.is_some(), "pnext"),
+        (field.tagged.is_some(), "tagged"),
+        (field.discriminator.is_some(), "discriminator"),
+        (field.nested.is_present(), "nested"),
+        (field.wstring.is_present(), "wstring"),
+    ];
+    if let Some((_, name)) = unsupported.iter().find(|(present, _)| *present) {
+        return Err(Error::new(
+            field.ty.span(),
+            format!("bytes cannot be used together with {name}"),
+        ));
+    }
+
+    let align = bytes.align.unwrap_or(1);
+    if !align.is_power_of_two() || align > 16 {
+        return Err(Error::new(
+            field.ty.span(),
+            "align of bytes must be a power of two up to 16",
+        ));
+    }
+
+    Ok(BytesField {
+        len: bytes.len.clone(),
+        align,
+    })
+}
+
+fn dynamic_field(dynamic: &DynamicReceiver, ptr_level: usize) -> DynamicField {
+    let hook = |ident: &Option<Ident>| ident.as_ref().map(ToTokens::to_token_stream);
+    DynamicField {
+        serializer: hook(&dynamic.serializer),
+        try_serializer: hook(&dynamic.try_serializer),
+        deserializer: dynamic.deserializer.to_token_stream(),
+        try_deserializer: hook(&dynamic.try_deserializer),
+        size_of: dynamic.size_of.to_token_stream(),
+        size: hook(&dynamic.size),
+        skip: hook(&dynamic.skip),
+        deep_copy: hook(&dynamic.deep_copy),
+        free: hook(&dynamic.free),
+        ptr_level,
+        #[cfg(feature = "cdebug")]
+        cdebugger: hook(&dynamic.cdebugger),
+    }
+}
+
+/// Get dynamic field which dispatches nodes of the `pNext` chain to the structures of the registry.
+fn pnext_field(pnext: &PNextReceiver) -> DynamicField {
+    let registry = &pnext.registry;
+    DynamicField {
+        serializer: None,
+        try_serializer: Some(quote!(::cdump::pnext::try_serialize::<#registry, _>)),
+        deserializer: quote!(::cdump::pnext::deserialize::<#registry, _>),
+        try_deserializer: Some(quote!(::cdump::pnext::try_deserialize::<#registry, _>)),
+        size_of: quote!(::cdump::pnext::size_of::<#registry>),
+        size: Some(quote!(::cdump::pnext::serialized_end::<#registry>)),
+        skip: None,
+        deep_copy: Some(quote!(::cdump::pnext::try_deep_copy::<#registry, _>)),
+        free: Some(quote!(::cdump::pnext::deep_free::<#registry, _>)),
+        ptr_level: 1,
+        #[cfg(feature = "cdebug")]
+        cdebugger: Some(quote!(::cdump::pnext::cdebugger)),
+    }
+}
+
+/// Get dynamic field which dispatches the object by its leading tag to the structs registered in the tag type.
+fn tagged_field(tag: &Path, ptr_level: usize) -> DynamicField {
+    DynamicField {
+        serializer: None,
+        try_serializer: Some(quote!(::cdump::tagged::try_serialize::<#tag, _>)),
+        deserializer: quote!(::cdump::tagged::deserialize::<#tag, _>),
+        try_deserializer: Some(quote!(::cdump::tagged::try_deserialize::<#tag, _>)),
+        size_of: quote!(::cdump::tagged::size_of::<#tag>),
+        size: Some(quote!(::cdump::tagged::serialized_end::<#tag>)),
+        skip: None,
+        deep_copy: Some(quote!(::cdump::tagged::try_deep_copy::<#tag, _>)),
+        free: Some(quote!(::cdump::tagged::deep_free::<#tag, _>)),
+        ptr_level,
+        #[cfg(feature = "cdebug")]
+        cdebugger: Some(quote!(::cdump::tagged::cdebugger::<#tag>)),
+    }
+}
+
+/// Get value of the `stype` attribute, which registers the struct as a node of the `pNext` chain.
+pub fn get_structure_type(ast: &DeriveInput) -> Option<Expr> {
+    InputReceiver::from_derive_input(ast).unwrap().stype
+}
+
+/// Get tag type and value of the `tag` attribute, which registers the struct in the tag type. The tag type is the
+/// path of the value without its last segment, e.g. `DynamicType` of `DynamicType::Bar`.
+pub fn get_tag(ast: &DeriveInput) -> Result<Option<(Path, Expr)>, Error> {
+    let tag = match InputReceiver::from_derive_input(ast).unwrap().tag {
+        Some(tag) => tag,
+        None => return Ok(None),
+    };
+
+    match &tag {
+        Expr::Path(path) if path.qself.is_none() && path.path.segments.len() > 1 => {
+            let mut ty = path.path.clone();
+            ty.segments.pop();
+            ty.segments.pop_punct();
+            Ok(Some((ty, tag)))
+        }
+        _ => Err(Error::new(
+            tag.span(),
+            "tag must be a path to the variant or constant of the tag type",
+        )),
+    }
+}
+
 fn extract_ptr(ty: &Type) -> (&Type, usize) {
     match ty {
         Type::Ptr(ty) => {
@@ -154,12 +844,42 @@ fn extract_ptr(ty: &Type) -> (&Type, usize) {
     }
 }
 
+/// Get the raw type of the field, which is a wide string when the field has `wstring` attribute.
+fn get_string_or_raw_field_type(
+    ty: &Type,
+    ptr_level: usize,
+    field: &FieldReceiver,
+) -> Result<RawFieldType, Error> {
+    let raw_ty = get_raw_field_type(ty);
+    if !field.wstring.is_present() {
+        return Ok(raw_ty);
+    }
+
+    if raw_ty != RawFieldType::Reference || !matches!(ty, Type::Path(_)) {
+        return Err(Error::new(
+            field.ty.span(),
+            "wstring is supported only by pointers to wide characters",
+        ));
+    }
+
+    // Every array consumes one level of pointer, so the last one points to the string.
+    let depth = field.array.as_ref().map_or(0, ArrayReceiver::depth);
+    if ptr_level != depth + 1 {
+        return Err(Error::new(
+            field.ty.span(),
+            "wstring requires one level of pointer for the string, and one for every array",
+        ));
+    }
+
+    Ok(RawFieldType::WString)
+}
+
 fn get_raw_field_type(ty: &Type) -> RawFieldType {
     match ty {
         Type::Path(path) => {
-            if path.path.is_ident("c_char") || path.path.is_ident("std::ffi::c_char") {
+            if is_ffi_type(&path.path, "c_char") {
                 return RawFieldType::CString;
-            } else if path.path.is_ident("c_void") || path.path.is_ident("std::ffi::c_void") {
+            } else if is_ffi_type(&path.path, "c_void") {
                 return RawFieldType::Dynamic;
             }
             RawFieldType::Reference
@@ -168,9 +888,25 @@ fn get_raw_field_type(ty: &Type) -> RawFieldType {
     }
 }
 
+/// Check if the path is the type `name` of `std::ffi` or `core::ffi`, imported or written with the full path.
+fn is_ffi_type(path: &Path, name: &str) -> bool {
+    let segments = path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>();
+    match segments.as_slice() {
+        [ident] => path.leading_colon.is_none() && ident == name,
+        [krate, ffi, ident] => (krate == "std" || krate == "core") && ffi == "ffi" && ident == name,
+        _ => false,
+    }
+}
+
 #[derive(darling::FromDeriveInput)]
+#[darling(attributes(cdump))]
 struct InputReceiver {
-    data: Data<(), FieldReceiver>,
+    stype: Option<Expr>,
+    tag: Option<Expr>,
 }
 
 #[derive(darling::FromField)]
@@ -180,18 +916,60 @@ struct FieldReceiver {
     ty: Type,
     array: Option<ArrayReceiver>,
     dynamic: Option<DynamicReceiver>,
+    capacity: Option<Expr>,
+    iterative: darling::util::Flag,
+    pnext: Option<PNextReceiver>,
+    tagged: Option<Path>,
+    discriminator: Option<Expr>,
+    case: Option<Expr>,
+    nested: darling::util::Flag,
+    wstring: darling::util::Flag,
+    bytes: Option<BytesReceiver>,
 }
 
 #[derive(darling::FromMeta)]
-struct ArrayReceiver {
+struct BytesReceiver {
     len: Expr,
+    align: Option<usize>,
+}
+
+#[derive(darling::FromMeta)]
+struct PNextReceiver {
+    registry: Path,
+}
+
+#[derive(darling::FromMeta)]
+struct ArrayReceiver {
+    len: Option<Expr>,
+    terminator: Option<Expr>,
+    null_terminated: darling::util::Flag,
+    index: Option<Ident>,
+    array: Option<Box<ArrayReceiver>>,
+}
+
+impl ArrayReceiver {
+    /// Get the number of nested arrays, including this one.
+    fn depth(&self) -> usize {
+        1 + self.array.as_ref().map_or(0, |inner| inner.depth())
+    }
+
+    /// Check whether the length of the array is found by scanning to its terminator.
+    fn is_terminated(&self) -> bool {
+        self.terminator.is_some() || self.null_terminated.is_present()
+    }
 }
 
 #[derive(darling::FromMeta)]
 struct DynamicReceiver {
-    serializer: Ident,
+    serializer: Option<Ident>,
+    try_serializer: Option<Ident>,
     deserializer: Ident,
+    try_deserializer: Option<Ident>,
     size_of: Ident,
+    size: Option<Ident>,
+    skip: Option<Ident>,
+    deep_copy: Option<Ident>,
+    free: Option<Ident>,
     #[cfg(feature = "cdebug")]
     cdebugger: Option<Ident>,
 }
@@ -200,5 +978,6 @@ struct DynamicReceiver {
 enum RawFieldType {
     Reference,
     CString,
+    WString,
     Dynamic,
 }
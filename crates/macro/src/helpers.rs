@@ -1,7 +1,7 @@
 use proc_macro2::{Span, TokenStream};
-use quote::ToTokens;
+use quote::{quote, ToTokens};
 use syn::spanned::Spanned;
-use syn::{AttrStyle, Attribute, Error, Path};
+use syn::{parse_quote, AttrStyle, Attribute, Error, Generics, Path};
 
 pub fn is_primitive_type(path: &TokenStream) -> bool {
     let path = path.to_string();
@@ -71,6 +71,51 @@ pub fn validate_repr(attrs: &[Attribute], repr: &str, span: Span) -> Result<(),
     ))
 }
 
+/// Generics of the impl generated for the derived type, used as
+/// `impl #impl_generics Trait for Name #ty_generics #where_clause`.
+pub struct SplitGenerics {
+    pub impl_generics: TokenStream,
+    pub ty_generics: TokenStream,
+    pub where_clause: TokenStream,
+}
+
+/// Split generics of the derived type, where the impl declares extra `param` like the buffer type, and every type
+/// parameter of the derived type is bounded by `bound`.
+pub fn split_generics(
+    generics: &Generics,
+    param: Option<TokenStream>,
+    bound: Option<TokenStream>,
+) -> SplitGenerics {
+    let mut impl_generics = generics.clone();
+    if let Some(param) = param {
+        impl_generics.params.push(parse_quote!(#param));
+    }
+    if let Some(bound) = bound {
+        let where_clause = impl_generics.make_where_clause();
+        for param in generics.type_params() {
+            let param = &param.ident;
+            where_clause.predicates.push(parse_quote!(#param: #bound));
+        }
+    }
+
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let (impl_generics, _, where_clause) = impl_generics.split_for_impl();
+    SplitGenerics {
+        impl_generics: impl_generics.to_token_stream(),
+        ty_generics: ty_generics.to_token_stream(),
+        where_clause: where_clause.to_token_stream(),
+    }
+}
+
+/// Get where predicates which bound every type parameter of the derived type by `bound`, for functions with their
+/// own generics.
+pub fn bound_type_params(generics: &Generics, bound: &TokenStream) -> TokenStream {
+    let params = generics.type_params().map(|param| &param.ident);
+    quote! {
+        #(#params: #bound,)*
+    }
+}
+
 pub trait ErrorExt {
     fn to_compile_error(&self) -> proc_macro2::TokenStream;
 }
@@ -0,0 +1,143 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{Data, DeriveInput};
+
+use crate::field_analysis::{self, DynamicField, Field, FieldType};
+
+/// Const expressions which compute the layout hashes of the type.
+pub struct LayoutHash {
+    /// Hash of the type without layouts of types under pointers, so types can point to each other.
+    pub shallow: TokenStream,
+    /// Hash of the type combined with shallow hashes of types under pointers.
+    pub full: TokenStream,
+}
+
+/// Get the layout hashes of the type, where layout hashes of other types are taken from `layout_trait`.
+pub fn layout_hash(ast: &DeriveInput, layout_trait: &TokenStream) -> LayoutHash {
+    let (Data::Struct(data), Ok(fields)) = (&ast.data, field_analysis::get_fields(ast, false))
+    else {
+        return LayoutHash {
+            shallow: quote! { 0 },
+            full: quote! { 0 },
+        };
+    };
+
+    let name = ast.ident.to_string();
+    let mut shallow = quote! {
+        ::cdump::internal::LayoutHasher::new()
+            .write_str(#name)
+            .write_usize(::std::mem::size_of::<Self>())
+            .write_usize(::std::mem::align_of::<Self>())
+    };
+    let mut full = quote! {
+        ::cdump::internal::LayoutHasher::new()
+            .write_u64(<Self as #layout_trait>::SHALLOW_LAYOUT_HASH)
+    };
+
+    for (syn_field, field) in data.fields.iter().zip(&fields) {
+        let ident = &field.ident;
+        let ident_str = ident.to_token_stream().to_string();
+        let ty = &syn_field.ty;
+
+        shallow = quote! {
+            #shallow
+                .write_str(#ident_str)
+                .write_usize(::cdump::offset_of!(Self, #ident))
+                .write_usize(::std::mem::size_of::<#ty>())
+                .write_usize(::std::mem::align_of::<#ty>())
+        };
+
+        let (annotation, element) = match &field.ty {
+            FieldType::DeepInlineArray(_, element) => (
+                format!("inline_array {}", annotation_of(element)),
+                &**element,
+            ),
+            _ => (annotation_of(field), field),
+        };
+
+        if !annotation.is_empty() {
+            shallow = quote! {
+                #shallow.write_str(#annotation)
+            };
+        }
+        if let FieldType::Nested = element.ty {
+            let path = &element.path;
+            shallow = quote! {
+                #shallow.write_u64(<#path as #layout_trait>::SHALLOW_LAYOUT_HASH)
+            };
+            full = quote! {
+                #full.write_u64(<#path as #layout_trait>::LAYOUT_HASH)
+            };
+        }
+        if let Some(pointee) = pointee_of(element) {
+            shallow = quote! {
+                #shallow
+                    .write_usize(::std::mem::size_of::<#pointee>())
+                    .write_usize(::std::mem::align_of::<#pointee>())
+            };
+            full = quote! {
+                #full.write_u64(<#pointee as #layout_trait>::SHALLOW_LAYOUT_HASH)
+            };
+        }
+    }
+
+    LayoutHash {
+        shallow: quote! { #shallow.finish() },
+        full: quote! { #full.finish() },
+    }
+}
+
+fn annotation_of(field: &Field) -> String {
+    match &field.ty {
+        FieldType::Dynamic(dynamic) => dynamic_annotation(dynamic),
+        FieldType::Union(discriminator) => {
+            format!("union(discriminator = {})", discriminator.to_token_stream())
+        }
+        FieldType::Nested => "nested".to_string(),
+        FieldType::Reference => "reference".to_string(),
+        FieldType::CString => "cstring".to_string(),
+        FieldType::WString => format!("wstring {}", field.path.to_token_stream()),
+        FieldType::Bytes(bytes) => format!(
+            "bytes(len = {}, align = {})",
+            bytes.len.to_token_stream(),
+            bytes.align
+        ),
+        FieldType::Array(len, inner) => format!(
+            "array(len = {}) {}",
+            len.to_token_stream(),
+            annotation_of(inner)
+        ),
+        _ => String::new(),
+    }
+}
+
+fn dynamic_annotation(dynamic: &DynamicField) -> String {
+    let hooks = [
+        ("serializer", dynamic.serializer.as_ref()),
+        ("try_serializer", dynamic.try_serializer.as_ref()),
+        ("deserializer", Some(&dynamic.deserializer)),
+        ("try_deserializer", dynamic.try_deserializer.as_ref()),
+        ("size_of", Some(&dynamic.size_of)),
+        ("size", dynamic.size.as_ref()),
+    ];
+
+    let hooks = hooks
+        .iter()
+        .filter_map(|(name, hook)| hook.map(|hook| format!("{name} = {hook}")))
+        .collect::<Vec<_>>();
+    format!("dynamic({})", hooks.join(", "))
+}
+
+/// Get the type under the pointer, which layout is a part of the hash.
+fn pointee_of(field: &Field) -> Option<TokenStream> {
+    match &field.ty {
+        FieldType::Reference | FieldType::CString | FieldType::WString => {
+            field.path.as_ref().map(ToTokens::to_token_stream)
+        }
+        FieldType::Array(_, inner) => match &inner.ty {
+            FieldType::Plain => inner.path.as_ref().map(ToTokens::to_token_stream),
+            _ => pointee_of(inner),
+        },
+        _ => None,
+    }
+}
@@ -1,13 +1,25 @@
-use field_analysis::{DynamicField, Field, FieldType};
-use helpers::{is_primitive_type, validate_repr, ErrorExt};
+use field_analysis::{ArrayLen, BytesField, DynamicField, Field, FieldType};
+use helpers::{
+    bound_type_params, is_primitive_type, split_generics, validate_repr, ErrorExt, SplitGenerics,
+};
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
-use syn::{parse_macro_input, spanned::Spanned, DeriveInput, Error, Expr, Ident, TypePath};
+use syn::{parse_macro_input, spanned::Spanned, DeriveInput, Generics, Ident, TypePath};
 
 #[cfg(feature = "cdebug")]
 mod cdebug;
+mod deep_copy;
+mod deserialize_to;
+mod enumeration;
 mod field_analysis;
 mod helpers;
+mod layout;
+#[cfg(feature = "portable")]
+mod portable;
+mod size;
+mod tagged;
+mod union;
+mod view;
 
 #[proc_macro_derive(CSerialize, attributes(cdump))]
 pub fn c_serialize_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -16,26 +28,103 @@ pub fn c_serialize_derive(input: proc_macro::TokenStream) -> proc_macro::TokenSt
     let validate_repr = validate_repr(&ast.attrs, "C", ast.span()).to_compile_error();
 
     let name = ast.ident.clone();
+    if field_analysis::is_enum(&ast) {
+        return enumeration::serialize_impl(&ast).into();
+    }
+    if field_analysis::is_union(&ast) {
+        return match field_analysis::get_fields(&ast, false) {
+            Ok(members) => union::serialize_impl(&ast, &members, validate_repr),
+            Err(err) => err.to_compile_error(),
+        }
+        .into();
+    }
+
     let push_copy = push_copy();
+    let layout::LayoutHash {
+        shallow: shallow_layout_hash,
+        full: layout_hash,
+    } = layout::layout_hash(&ast, &quote! { ::cdump::CSerialize<__CDumpBuffer> });
+
+    let (deep_fields, serialized_size, iterative_functions) =
+        match field_analysis::get_fields(&ast, true) {
+            Ok(fields) => (
+                write_deep_fields(&fields, &name),
+                size::serialized_size_impl(&ast, &fields),
+                write_iterative_functions(&fields, &name, &ast.generics),
+            ),
+            Err(err) => (err.to_compile_error(), quote! {}, quote! {}),
+        };
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(&ast.generics, None, None);
+    let structure_type = field_analysis::get_structure_type(&ast).map(|stype| {
+        quote! {
+            impl #impl_generics ::cdump::pnext::CStructureType for #name #ty_generics #where_clause {
+                const STRUCTURE_TYPE: i32 = (#stype) as i32;
+            }
+        }
+    });
+    let tag = match field_analysis::get_tag(&ast) {
+        Ok(Some(_)) if !ast.generics.params.is_empty() => Some(
+            syn::Error::new_spanned(&ast.generics, "tagged struct cannot be generic")
+                .to_compile_error(),
+        ),
+        Ok(tag) => tag.map(|(ty, tag)| {
+            quote! {
+                impl ::cdump::tagged::CTagged<#ty> for #name {
+                    const TAG: #ty = #tag;
+                }
 
-    let deep_fields = match field_analysis::get_fields(&ast, true) {
-        Ok(fields) => write_deep_fields(fields),
-        Err(err) => err.to_compile_error(),
+                unsafe impl ::cdump::tagged::Registered<{ #tag as i64 }> for #ty {
+                    type Struct = #name;
+                }
+            }
+        }),
+        Err(err) => Some(err.to_compile_error()),
     };
 
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(
+        &ast.generics,
+        Some(quote! { __CDumpBuffer: ::cdump::CDumpWriter }),
+        Some(quote! { ::cdump::CSerialize<__CDumpBuffer> }),
+    );
+
     proc_macro::TokenStream::from(quote! {
-        impl<T: ::cdump::CDumpWriter> ::cdump::CSerialize<T> for #name {
-            unsafe fn serialize(&self, buf: &mut T) {
+        #serialized_size
+        #iterative_functions
+        #structure_type
+        #tag
+
+        impl #impl_generics ::cdump::CSerialize<__CDumpBuffer> for #name #ty_generics #where_clause {
+            const LAYOUT_HASH: u64 = #layout_hash;
+            const SHALLOW_LAYOUT_HASH: u64 = #shallow_layout_hash;
+
+            unsafe fn try_serialize(&self, buf: &mut __CDumpBuffer) -> Result<(), ::cdump::Error> {
                 #validate_repr
 
-                ::cdump::internal::align_writer::<T, Self>(buf);
+                buf.try_align::<Self>()?;
                 let start_index = buf.len();
+                buf.record_shared(
+                    self as *const Self as *const u8,
+                    ::cdump::shared::SharedKey::new(<Self as ::cdump::CSerialize<__CDumpBuffer>>::LAYOUT_HASH, 1),
+                );
                 #push_copy
-                unsafe { self.serialize_without_shallow_copy(buf, start_index); }
+                unsafe { self.try_serialize_without_shallow_copy(buf, start_index) }
             }
 
-            unsafe fn serialize_without_shallow_copy(&self, buf: &mut T, start_index: usize) {
+            unsafe fn try_serialize_without_shallow_copy(
+                &self,
+                buf: &mut __CDumpBuffer,
+                start_index: usize,
+            ) -> Result<(), ::cdump::Error> {
                 #deep_fields
+                Ok(())
             }
         }
     })
@@ -43,116 +132,332 @@ pub fn c_serialize_derive(input: proc_macro::TokenStream) -> proc_macro::TokenSt
 
 fn push_copy() -> TokenStream {
     quote! {
-        buf.push_slice(unsafe {
+        buf.try_push_slice(unsafe {
             std::slice::from_raw_parts(self as *const _ as *const u8, std::mem::size_of::<Self>())
-        });
+        })?;
     }
 }
 
-fn write_deep_fields(fields: Vec<Field>) -> TokenStream {
-    let mut start_index = false;
-    let mut quotes = Vec::new();
+/// Wraps code of the field in the closure, and adds the field to the returned error.
+fn in_field(name: &Ident, field: &Field, code: TokenStream) -> TokenStream {
+    let name_str = name.to_string();
+    let field_str = field.ident.to_token_stream().to_string();
+    quote! {
+        ::cdump::internal::in_field(#name_str, #field_str, || {
+            #code
+            Ok(())
+        })?;
+    }
+}
 
-    for field in fields {
-        quotes.push(write_deep_fields_inner(&mut start_index, &field, None));
+fn write_deep_fields(fields: &[Field], name: &Ident) -> TokenStream {
+    match fields.iter().position(|field| field.iterative) {
+        Some(index) => write_iterative_field(name, &fields[index]),
+        None => fields
+            .iter()
+            .map(|field| in_field(name, field, write_deep_fields_inner(field)))
+            .collect(),
     }
+}
 
-    quotes.into_iter().collect()
+fn iterative_function_idents() -> (Ident, Ident) {
+    (
+        Ident::new(
+            "do_not_use_cdump_internal_function_serialize_before_iterative",
+            Span::call_site(),
+        ),
+        Ident::new(
+            "do_not_use_cdump_internal_function_serialize_after_iterative",
+            Span::call_site(),
+        ),
+    )
 }
 
-fn write_deep_fields_inner(
-    start_index: &mut bool,
-    field: &Field,
-    ptr_offset: Option<TokenStream>,
-) -> TokenStream {
+/// Get functions which serialize fields before and after the iterative field of one object in the chain.
+fn write_iterative_functions(fields: &[Field], name: &Ident, generics: &Generics) -> TokenStream {
+    let Some(index) = fields.iter().position(|field| field.iterative) else {
+        return quote! {};
+    };
+
+    let (before_function, after_function) = iterative_function_idents();
+    let before = fields[..index]
+        .iter()
+        .map(|field| in_field(name, field, write_deep_fields_inner(field)));
+    let after = fields[index + 1..]
+        .iter()
+        .map(|field| in_field(name, field, write_deep_fields_inner(field)));
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(generics, None, None);
+    let bounds = bound_type_params(generics, &quote! { ::cdump::CSerialize<__CDumpBuffer> });
+
+    quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[doc(hidden)]
+            unsafe fn #before_function<__CDumpBuffer: ::cdump::CDumpWriter>(
+                &self,
+                buf: &mut __CDumpBuffer,
+                start_index: usize,
+            ) -> Result<(), ::cdump::Error>
+            where
+                #bounds
+            {
+                #(#before)*
+                Ok(())
+            }
+
+            #[doc(hidden)]
+            unsafe fn #after_function<__CDumpBuffer: ::cdump::CDumpWriter>(
+                &self,
+                buf: &mut __CDumpBuffer,
+                start_index: usize,
+            ) -> Result<(), ::cdump::Error>
+            where
+                #bounds
+            {
+                #(#after)*
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Serialize the chain of the iterative field in the loop, where fields after it are serialized when the rest of the
+/// chain is done.
+fn write_iterative_field(name: &Ident, field: &Field) -> TokenStream {
     let field_ident = &field.ident;
-    let ident = match &ptr_offset {
-        Some(ptr_offset) => quote! {
-            self.#field_ident.add(#ptr_offset)
-        },
-        None => quote! {
-            self.#field_ident
-        },
+    let name_str = name.to_string();
+    let field_str = field.ident.to_token_stream().to_string();
+    let key = shared_key(field, &quote! { ::cdump::CSerialize<__CDumpBuffer> });
+    let (before_function, after_function) = iterative_function_idents();
+
+    quote! {
+        let mut pending = ::std::vec::Vec::new();
+        let mut node: *const Self = self;
+        let mut start_index = start_index;
+        'nodes: loop {
+            (*node).#before_function(buf, start_index)?;
+
+            let next = (*node).#field_ident;
+            if !next.is_null() {
+                let shared = ::cdump::internal::in_field(#name_str, #field_str, || {
+                    buf.try_align::<Self>()?;
+                    Ok(buf.share(start_index + ::cdump::offset_of!(Self, #field_ident), next as *const u8, #key))
+                })?;
+
+                if !shared {
+                    let next_start_index = buf.len();
+                    ::cdump::internal::in_field(#name_str, #field_str, || {
+                        buf.try_push_slice(::cdump::internal::as_bytes(&*next))
+                    })?;
+
+                    pending.push((node, start_index));
+                    node = next;
+                    start_index = next_start_index;
+                    continue;
+                }
+            }
+
+            loop {
+                (*node).#after_function(buf, start_index)?;
+
+                match pending.pop() {
+                    Some((parent, parent_start_index)) => {
+                        node = parent;
+                        start_index = parent_start_index;
+                    }
+                    None => break 'nodes,
+                }
+            }
+        }
+    }
+}
+
+fn write_deep_fields_inner(field: &Field) -> TokenStream {
+    let field_ident = &field.ident;
+    let slot = quote! {
+        start_index + ::cdump::offset_of!(Self, #field_ident)
     };
+    match &field.ty {
+        FieldType::DeepInlineArray(array, element) => {
+            let (len, elem) = (&array.len, &array.elem);
+            let write = write_deep_field_at(
+                element,
+                quote! { self.#field_ident[i] },
+                quote! { #slot + ::std::mem::size_of::<#elem>() * i },
+            );
+            quote! {
+                for i in 0..(#len) {
+                    #write
+                }
+            }
+        }
+        _ => write_deep_field_at(field, quote! { self.#field_ident }, slot),
+    }
+}
+
+/// Serialize the data under pointers of the field, or of the element of the inline array, at `ident`, where `slot` is
+/// its index in the buffer.
+fn write_deep_field_at(field: &Field, ident: TokenStream, slot: TokenStream) -> TokenStream {
+    let serialize_trait = quote! { ::cdump::CSerialize<__CDumpBuffer> };
 
     let result = match &field.ty {
         FieldType::Plain | FieldType::InlineArray(_) => {
             unreachable!("shallow fields should not be under first level pointer")
         }
+        FieldType::DeepInlineArray(..) => unreachable!("inline arrays are followed by elements"),
+        FieldType::Nested => {
+            return quote! {
+                ::cdump::CSerialize::try_serialize_without_shallow_copy(&#ident, buf, #slot)?;
+            };
+        }
+        FieldType::Union(discriminator) => {
+            return quote! {
+                ::cdump::union::CUnionSerialize::try_serialize_member(
+                    &#ident,
+                    (#discriminator) as i64,
+                    buf,
+                    #slot,
+                )?;
+            };
+        }
         FieldType::Reference => {
-            let path = field.path.to_token_stream();
-            if is_primitive_type(&path) {
-                quote! {
-                    ::cdump::internal::align_writer::<T, #path>(buf);
-                    buf.push_slice(&(*#ident).to_ne_bytes());
-                }
-            } else {
+            let path = &field.path;
+            let write = write_shared(
+                &slot,
+                &ident,
+                shared_key(field, &serialize_trait),
                 quote! {
-                    ::cdump::CSerialize::serialize(&*#ident, buf);
-                }
+                    ::cdump::CSerialize::try_serialize(&*#ident, buf)?;
+                },
+            );
+            quote! {
+                buf.try_align::<#path>()?;
+                #write
             }
         }
         FieldType::CString => {
-            *start_index = true;
-
-            let set_len = match ptr_offset.is_none() {
-                true => quote! {
-                    ::cdump::internal::set_length_in_ptr(buf, start_index + ::cdump::offset_of!(Self, #field_ident), len);
+            let write = write_shared(
+                &slot,
+                &ident,
+                shared_key(field, &serialize_trait),
+                quote! {
+                    ::cdump::internal::set_length_in_ptr(buf, #slot, len);
+                    buf.try_push_slice(::std::slice::from_raw_parts(#ident as *const _ as *const u8, len))?;
                 },
-                false => quote! {},
-            };
-
+            );
             quote! {
                 let len = ::cdump::internal::libc_strlen(#ident) + 1;
-                #set_len
-                buf.push_slice(::std::slice::from_raw_parts(#ident as *const _ as *const u8, len));
+                #write
             }
         }
-        FieldType::Dynamic(dynamic) => {
-            let serializer = &dynamic.serializer;
+        FieldType::WString => {
+            let path = &field.path;
+            let write = write_shared(
+                &slot,
+                &ident,
+                shared_key(field, &serialize_trait),
+                quote! {
+                    ::cdump::internal::set_length_in_ptr(buf, #slot, len);
+                    buf.try_push_slice(::std::slice::from_raw_parts(
+                        #ident as *const _ as *const u8,
+                        len * ::std::mem::size_of::<#path>(),
+                    ))?;
+                },
+            );
+            quote! {
+                let len = ::cdump::wide::wcslen(#ident) + 1;
+                buf.try_align::<#path>()?;
+                #write
+            }
+        }
+        FieldType::Bytes(bytes) => {
+            let len = &bytes.len;
+            let align_type = bytes.align_type();
+            let write = write_shared(
+                &slot,
+                &ident,
+                shared_key(field, &serialize_trait),
+                quote! {
+                    buf.try_push_slice(::std::slice::from_raw_parts(#ident as *const u8, len))?;
+                },
+            );
             quote! {
-                #serializer(buf, #ident);
+                let len = ::cdump::internal::try_array_len(#len)?;
+                buf.try_align::<#align_type>()?;
+                #write
             }
         }
+        FieldType::Dynamic(dynamic) => write_shared(
+            &slot,
+            &ident,
+            shared_key(field, &serialize_trait),
+            serialize_dynamic(dynamic, &ident),
+        ),
         FieldType::Array(len, inner) => {
-            let inner_path = inner.path.to_token_stream();
             let alignment_type = get_alignment_type(inner);
 
-            let mut result = quote! {
-                let len = (#len) as usize;
-                let size = ::std::mem::size_of::<#alignment_type>();
-
-                ::cdump::internal::align_writer::<T, #alignment_type>(buf);
-                let array_start_index = buf.len();
-                buf.push_slice(::std::slice::from_raw_parts(#ident as *const _ as *const u8, size * len));
+            // Length of terminated array is unknown without the original data, so it is stored in the pointer.
+            let store_len = match len.terminator {
+                Some(_) => quote! {
+                    ::cdump::internal::set_length_in_ptr(buf, #slot, len);
+                },
+                None => quote! {},
+            };
+            let push_array = quote! {
+                #store_len
+                buf.try_push_slice(::std::slice::from_raw_parts(
+                    #ident as *const _ as *const u8,
+                    ::cdump::internal::try_array_size(size, len)?,
+                ))?;
             };
 
-            if !is_primitive_type(&inner_path) {
-                let (inner, push_shallow) = get_inner_of_array_serialize(inner, &ident);
+            if is_primitive_element(inner) {
+                let write = write_shared(
+                    &slot,
+                    &ident,
+                    shared_key(field, &serialize_trait),
+                    push_array,
+                );
+                quote! {
+                    let len = ::cdump::internal::try_array_len(#len)?;
+                    let size = ::std::mem::size_of::<#alignment_type>();
 
-                if !push_shallow {
-                    result = quote! {
-                        let len = (#len) as usize;
-                        let mut read: usize = 0;
-                    }
+                    buf.try_align::<#alignment_type>()?;
+                    let array_start_index = buf.len();
+                    #write
                 }
+            } else if let FieldType::Dynamic(dynamic @ DynamicField { ptr_level: 1, .. }) =
+                &inner.ty
+            {
+                write_dynamic_array(dynamic, &ident, &slot, len)
+            } else {
+                let inner = get_inner_of_array_serialize(inner, &ident);
+                let bind_index = bind_index(len);
+                let write = write_shared(
+                    &slot,
+                    &ident,
+                    shared_key(field, &serialize_trait),
+                    quote! {
+                        #push_array
 
-                result = quote! {
-                    #result
+                        for i in 0..len {
+                            #bind_index
+                            #inner
+                        }
+                    },
+                );
+                quote! {
+                    let len = ::cdump::internal::try_array_len(#len)?;
+                    let size = ::std::mem::size_of::<#alignment_type>();
 
-                    for i in 0..len {
-                        #inner
-                    }
-                };
-            } else if let FieldType::Reference = inner.ty {
-                return Error::new(
-                    inner.ident.span(),
-                    "pointer to array of pointers to primitive type is not supported",
-                )
-                .to_compile_error();
+                    buf.try_align::<#alignment_type>()?;
+                    let array_start_index = buf.len();
+                    #write
+                }
             }
-
-            result
         }
     };
 
@@ -163,52 +468,211 @@ fn write_deep_fields_inner(
     }
 }
 
-fn get_inner_of_array_serialize(inner: &Field, ident: &TokenStream) -> (TokenStream, bool) {
+/// Wraps serialization of the data under pointer stored at `slot` index, which is skipped when the writer shares the
+/// data already written.
+fn write_shared(
+    slot: &TokenStream,
+    ptr: &TokenStream,
+    key: TokenStream,
+    code: TokenStream,
+) -> TokenStream {
+    quote! {
+        if !buf.share(#slot, #ptr as *const u8, #key) {
+            #code
+        }
+    }
+}
+
+/// Get the `SharedKey` of the data under pointer of the field, with layout hashes of the `layout_trait`. Keys of arrays
+/// use `len` variable.
+fn shared_key(field: &Field, layout_trait: &TokenStream) -> TokenStream {
+    match &field.ty {
+        FieldType::Plain | FieldType::Reference => {
+            let path = &field.path;
+            quote! {
+                ::cdump::shared::SharedKey::new(<#path as #layout_trait>::LAYOUT_HASH, 1)
+            }
+        }
+        FieldType::CString => quote! {
+            ::cdump::shared::SharedKey::cstring()
+        },
+        FieldType::WString => {
+            let path = &field.path;
+            quote! {
+                ::cdump::shared::SharedKey::wstring(::std::mem::size_of::<#path>())
+            }
+        }
+        FieldType::Bytes(bytes) => {
+            let align = bytes.align;
+            quote! {
+                ::cdump::shared::SharedKey::bytes(#align, len)
+            }
+        }
+        FieldType::Dynamic(dynamic) => {
+            let name = dynamic.deserializer.to_string();
+            quote! {
+                ::cdump::shared::SharedKey::dynamic(#name)
+            }
+        }
+        FieldType::Array(_, inner) => {
+            let inner_key = shared_key(inner, layout_trait);
+            match inner.ty {
+                FieldType::Plain => quote! {
+                    ::cdump::shared::SharedKey::new(#inner_key.type_hash, len)
+                },
+                FieldType::Array(..) => quote! {
+                    ::cdump::shared::SharedKey::arrays(#inner_key.type_hash, len)
+                },
+                _ => quote! {
+                    ::cdump::shared::SharedKey::pointers(#inner_key.type_hash, len)
+                },
+            }
+        }
+        FieldType::InlineArray(_)
+        | FieldType::DeepInlineArray(..)
+        | FieldType::Union(_)
+        | FieldType::Nested => {
+            unreachable!("inline arrays, unions and nested structs are not under pointer")
+        }
+    }
+}
+
+fn serialize_dynamic(dynamic: &DynamicField, ptr: &TokenStream) -> TokenStream {
+    match (&dynamic.try_serializer, &dynamic.serializer) {
+        (Some(try_serializer), _) => quote! {
+            #try_serializer(buf, #ptr)?;
+        },
+        (None, Some(serializer)) => quote! {
+            #serializer(buf, #ptr);
+        },
+        (None, None) => unreachable!("serializer should be validated before"),
+    }
+}
+
+/// Serialize the array of dynamic objects stored one after another under one pointer. Space for the whole array, which
+/// size is stored at `slot`, is reserved before the objects, so deserialization can copy them next to each other.
+/// Objects are aligned by their serializer, so the array is never shared.
+fn write_dynamic_array(
+    dynamic: &DynamicField,
+    ident: &TokenStream,
+    slot: &TokenStream,
+    len: &ArrayLen,
+) -> TokenStream {
+    let size_of = &dynamic.size_of;
+    let serialize = serialize_dynamic(dynamic, &quote! { element });
+    quote! {
+        let len = ::cdump::internal::try_array_len(#len)?;
+        let array_size = ::cdump::internal::dynamic_array_size(#ident as *const ::std::ffi::c_void, len, #size_of);
+
+        buf.try_align::<usize>()?;
+        ::cdump::internal::set_length_in_ptr(buf, #slot, array_size);
+        buf.try_push_slice(::std::slice::from_raw_parts(#ident as *const u8, array_size))?;
+
+        let mut offset: usize = 0;
+        for _ in 0..len {
+            let element = #ident.byte_add(offset) as *const ::std::ffi::c_void;
+            #serialize
+            offset += #size_of(element);
+        }
+    }
+}
+
+fn get_inner_of_array_serialize(inner: &Field, ident: &TokenStream) -> TokenStream {
+    let slot = quote! { array_start_index + size * i };
+    let element = quote! { element };
+    let serialize_trait = quote! { ::cdump::CSerialize<__CDumpBuffer> };
+
     match &inner.ty {
-        FieldType::Plain => {
-            let ident = &inner.ident;
-            (
+        FieldType::Plain => quote! {
+            ::cdump::CSerialize::try_serialize_without_shallow_copy(&*#ident.add(i), buf, array_start_index + size * i)?;
+        },
+        FieldType::Array(..) => {
+            let write = write_deep_field_at(inner, quote! { element }, quote! { slot });
+            quote! {
+                let element = *#ident.add(i);
+                let slot = array_start_index + size * i;
+                #write
+            }
+        }
+        FieldType::Reference => {
+            let path = &inner.path;
+            let write = write_shared(
+                &slot,
+                &element,
+                shared_key(inner, &serialize_trait),
                 quote! {
-                    ::cdump::CSerialize::serialize_without_shallow_copy(&*self.#ident.add(i), buf, array_start_index + size * i);
+                    ::cdump::CSerialize::try_serialize(&*element, buf)?;
                 },
-                true,
-            )
+            );
+            quote! {
+                let element = *#ident.add(i);
+                if !element.is_null() {
+                    buf.try_align::<#path>()?;
+                    #write
+                }
+            }
         }
-        FieldType::Reference => (
+        FieldType::CString => {
+            let write = write_shared(
+                &slot,
+                &element,
+                shared_key(inner, &serialize_trait),
+                quote! {
+                    ::cdump::internal::set_length_in_ptr(buf, #slot, len);
+                    buf.try_push_slice(std::slice::from_raw_parts(element as *const _ as *const u8, len))?;
+                },
+            );
             quote! {
-                ::cdump::CSerialize::serialize(&**#ident.add(i), buf);
-            },
-            true,
-        ),
-        FieldType::CString => (
+                let element = *#ident.add(i);
+                if !element.is_null() {
+                    let len = ::cdump::internal::libc_strlen(element) + 1;
+                    #write
+                }
+            }
+        }
+        FieldType::WString => {
+            let path = &inner.path;
+            let write = write_shared(
+                &slot,
+                &element,
+                shared_key(inner, &serialize_trait),
+                quote! {
+                    ::cdump::internal::set_length_in_ptr(buf, #slot, len);
+                    buf.try_push_slice(std::slice::from_raw_parts(
+                        element as *const _ as *const u8,
+                        len * ::std::mem::size_of::<#path>(),
+                    ))?;
+                },
+            );
             quote! {
-                let ptr = *#ident.add(i);
-                let len = ::cdump::internal::libc_strlen(ptr) + 1;
-                ::cdump::internal::set_length_in_ptr(buf, array_start_index + size * i, len);
-                buf.push_slice(std::slice::from_raw_parts(ptr as *const _ as *const u8, len));
-            },
-            true,
-        ),
-        FieldType::Dynamic(dynamic) => {
-            let serializer = &dynamic.serializer;
-            match dynamic.ptr_level {
-                1 => (
-                    quote! {
-                        read += #serializer(buf, #ident.byte_add(read));
-                    },
-                    false,
-                ),
-                2 => (
-                    quote! {
-                        #serializer(buf, *#ident.add(i));
-                    },
-                    true,
-                ),
-                _ => {
-                    unimplemented!("three or more level of pointer to dynamic type is unsupported")
+                let element = *#ident.add(i);
+                if !element.is_null() {
+                    let len = ::cdump::wide::wcslen(element) + 1;
+                    buf.try_align::<#path>()?;
+                    #write
                 }
             }
         }
+        FieldType::Dynamic(dynamic) => match dynamic.ptr_level {
+            1 => {
+                unreachable!("dynamic objects under one pointer are written by write_dynamic_array")
+            }
+            2 => {
+                let write = write_shared(
+                    &slot,
+                    &element,
+                    shared_key(inner, &serialize_trait),
+                    serialize_dynamic(dynamic, &element),
+                );
+                quote! {
+                    let element = *#ident.add(i);
+                    if !element.is_null() {
+                        #write
+                    }
+                }
+            }
+            _ => unimplemented!("three or more level of pointer to dynamic type is unsupported"),
+        },
         _ => unimplemented!("2D arrays"),
     }
 }
@@ -218,265 +682,916 @@ pub fn c_deserialize_derive(input: proc_macro::TokenStream) -> proc_macro::Token
     let ast = parse_macro_input!(input as DeriveInput);
     let validate_repr = validate_repr(&ast.attrs, "C", ast.span()).to_compile_error();
     let name = ast.ident.clone();
+    if field_analysis::is_enum(&ast) {
+        return enumeration::deserialize_impl(&ast).into();
+    }
+    if field_analysis::is_union(&ast) {
+        return match field_analysis::get_fields(&ast, false) {
+            Ok(members) => union::deserialize_impl(&ast, &members, validate_repr),
+            Err(err) => err.to_compile_error(),
+        }
+        .into();
+    }
 
-    let (deep_fields_to, deep_fields_ref) = match field_analysis::get_fields(&ast, true) {
-        Ok(fields) => (
-            read_deep_fields(&fields, &name, true),
-            read_deep_fields(&fields, &name, false),
-        ),
-        Err(err) => (err.to_compile_error(), quote! {}),
+    let layout::LayoutHash {
+        shallow: shallow_layout_hash,
+        full: layout_hash,
+    } = layout::layout_hash(&ast, &quote! { ::cdump::CDeserialize<__CDumpBuffer> });
+    let root_key = quote! {
+        ::cdump::shared::SharedKey::new(<Self as ::cdump::CDeserialize<__CDumpBuffer>>::LAYOUT_HASH, 1)
+    };
+
+    let validate_shallow = match field_analysis::get_fields(&ast, false) {
+        Ok(fields) => validate_shallow_fields(&fields, &name),
+        Err(_) => quote! {},
     };
+    let (len_functions, deep_fields_to, deep_fields_ref, deep_fields_try_ref, deep_copy) =
+        match field_analysis::get_fields(&ast, true) {
+            Ok(fields) => (
+                array_len_functions(&fields, &name, &ast.generics),
+                deserialize_to::read_deep_fields_to(&fields, &name),
+                read_deep_fields(&fields, &name, ReadMode::Ref),
+                read_deep_fields(&fields, &name, ReadMode::TryRef),
+                deep_copy::deep_copy_impl(&ast, &fields),
+            ),
+            Err(err) => (
+                err.to_compile_error(),
+                quote! {},
+                quote! {},
+                quote! {},
+                quote! {},
+            ),
+        };
+
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(
+        &ast.generics,
+        Some(quote! { __CDumpBuffer: ::cdump::CDumpReader }),
+        Some(quote! { ::cdump::CDeserialize<__CDumpBuffer> }),
+    );
 
     proc_macro::TokenStream::from(quote! {
-        impl<T: ::cdump::CDumpReader> ::cdump::CDeserialize<T> for #name {
-            unsafe fn deserialize_to(buf: &mut T, dst: *mut Self) {
+        #len_functions
+        #deep_copy
+
+        impl #impl_generics ::cdump::CDeserialize<__CDumpBuffer> for #name #ty_generics #where_clause {
+            const LAYOUT_HASH: u64 = #layout_hash;
+            const SHALLOW_LAYOUT_HASH: u64 = #shallow_layout_hash;
+
+            unsafe fn deserialize_to<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                buf: &mut __CDumpBuffer,
+                dst: *mut Self,
+                policy: &::cdump::alloc::DestinationPolicy<__CDumpAllocator>,
+            ) -> Result<(), ::cdump::Error> {
                 #validate_repr
 
-                ::cdump::internal::align_reader::<T, Self>(buf);
-                let temp = buf.as_mut_ptr_at(buf.get_read());
-                buf.add_read(::std::mem::size_of::<Self>());
-                Self::deserialize_to_without_shallow_copy(buf, temp, dst);
-                ::std::ptr::copy_nonoverlapping(temp, dst, 1);
+                ::cdump::internal::deserialize_root_to(buf, dst, policy)
             }
 
-            unsafe fn deserialize_to_without_shallow_copy(buf: &mut T, temp: *mut Self, dst: *mut Self) {
+            unsafe fn deserialize_to_without_shallow_copy<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                buf: &mut __CDumpBuffer,
+                temp: *mut Self,
+                dst: *mut Self,
+                policy: &::cdump::alloc::DestinationPolicy<__CDumpAllocator>,
+            ) -> Result<(), ::cdump::Error> {
+                <Self as ::cdump::CDeserialize<__CDumpBuffer>>::try_validate_shallow(temp)?;
                 #deep_fields_to
+                Ok(())
             }
 
-            unsafe fn deserialize_ref_mut(buf: &mut T) -> &mut Self {
-                ::cdump::internal::align_reader::<T, Self>(buf);
-                let reference = buf.as_mut_ptr_at(buf.get_read());
+            unsafe fn deserialize_ref_mut(buf: &mut __CDumpBuffer) -> &mut Self {
+                ::cdump::internal::align_reader::<__CDumpBuffer, Self>(buf);
+                let offset = buf.get_read();
+                let reference = buf.as_mut_ptr_at(offset);
+                buf.record_shared(offset, #root_key, reference as *mut u8);
                 buf.add_read(::std::mem::size_of::<Self>());
                 Self::deserialize_ref_mut_without_shallow_copy(buf, reference);
                 &mut *reference
             }
 
-            unsafe fn deserialize_ref_mut_without_shallow_copy(buf: &mut T, dst: *mut Self) {
+            unsafe fn deserialize_ref_mut_without_shallow_copy(buf: &mut __CDumpBuffer, dst: *mut Self) {
+                // Trusted buffer is not validated, but unknown values of enums are still replaced by the fallback.
+                _ = <Self as ::cdump::CDeserialize<__CDumpBuffer>>::try_validate_shallow(dst);
                 #deep_fields_ref
             }
+
+            unsafe fn try_deserialize_ref_mut(buf: &mut __CDumpBuffer) -> Result<&mut Self, ::cdump::Error> {
+                buf.try_align::<Self>()?;
+                let offset = buf.get_read();
+                buf.record_shared(offset, #root_key, buf.as_mut_ptr_at(offset));
+                Ok(&mut *::cdump::internal::try_deserialize_shallow_copied::<__CDumpBuffer, Self>(buf)?)
+            }
+
+            unsafe fn try_deserialize_ref_mut_without_shallow_copy(
+                buf: &mut __CDumpBuffer,
+                dst: *mut Self,
+            ) -> Result<(), ::cdump::Error> {
+                <Self as ::cdump::CDeserialize<__CDumpBuffer>>::try_validate_shallow(dst)?;
+                #deep_fields_try_ref
+                Ok(())
+            }
+
+            unsafe fn try_validate_shallow(dst: *mut Self) -> Result<(), ::cdump::Error> {
+                #validate_shallow
+                Ok(())
+            }
         }
     })
 }
 
-fn read_deep_fields(
-    fields: &[Field],
-    name: &proc_macro2::Ident,
-    to_src_destination: bool,
-) -> TokenStream {
-    let mut quotes = Vec::new();
+/// Validate fields by value, which are not primitive types, like enums, by `CDeserialize::try_validate_shallow` of
+/// their type, which is resolved to no validation for types which do not implement `CDeserialize`.
+fn validate_shallow_fields(fields: &[Field], name: &Ident) -> TokenStream {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let ident = &field.ident;
+            let validate = |ty: &TokenStream| {
+                quote! {
+                    #[allow(unused_imports)]
+                    use ::cdump::internal::NoShallowValidation as _;
+                    ::cdump::internal::ShallowValidator::<#ty, __CDumpBuffer>::new().try_validate(element)?;
+                }
+            };
+            let code = match &field.ty {
+                FieldType::Plain => {
+                    let path = field.path.as_ref()?.to_token_stream();
+                    if is_primitive_type(&path) {
+                        return None;
+                    }
+                    let validate = validate(&path);
+                    quote! {
+                        let element = ::std::ptr::addr_of_mut!((*dst).#ident);
+                        #validate
+                    }
+                }
+                FieldType::InlineArray(array) => {
+                    // Elements of multidimensional arrays are validated as one flat array.
+                    let mut element = &*array.elem;
+                    while let syn::Type::Array(inner) = element {
+                        element = &inner.elem;
+                    }
+                    let syn::Type::Path(path) = element else {
+                        return None;
+                    };
+                    let path = path.to_token_stream();
+                    if is_primitive_type(&path) {
+                        return None;
+                    }
+                    let validate = validate(&path);
+                    quote! {
+                        let array = ::std::ptr::addr_of_mut!((*dst).#ident) as *mut #path;
+                        let len = ::std::mem::size_of::<#array>()
+                            .checked_div(::std::mem::size_of::<#path>())
+                            .unwrap_or(0);
+                        for i in 0..len {
+                            let element = array.add(i);
+                            #validate
+                        }
+                    }
+                }
+                _ => return None,
+            };
+            Some(in_field(name, field, code))
+        })
+        .collect()
+}
 
-    for (index, field) in fields.iter().enumerate() {
-        quotes.push(read_deep_fields_inner(
-            field,
-            name,
-            index,
-            to_src_destination,
-        ));
-    }
+/// Mode of the generated deserialization code.
+#[derive(Clone, Copy, PartialEq)]
+enum ReadMode {
+    /// Rewrites pointers in the buffer, used by `deserialize_ref_mut`.
+    Ref,
+    /// Rewrites pointers in the buffer after checking bounds of the data, used by `try_deserialize_ref_mut`.
+    TryRef,
+}
 
-    quotes.into_iter().collect()
+fn len_function_ident(field_index: usize) -> Ident {
+    Ident::new(
+        &format!(
+            "do_not_use_cdump_internal_function_len_of_array_at_index_{}",
+            field_index
+        ),
+        Span::call_site(),
+    )
 }
 
-fn read_deep_fields_inner(
-    field: &Field,
-    name: &proc_macro2::Ident,
-    field_index: usize,
-    to_src_destination: bool,
-) -> TokenStream {
-    let field_ident = &field.ident;
-    let ident = quote! {
-        (*dst).#field_ident
-    };
-    let temp_ident = match to_src_destination {
-        true => Some(quote! {
-            (*temp).#field_ident
-        }),
-        false => None,
-    };
-    let path = &field.path;
+fn capacity_function_ident(field_index: usize) -> Ident {
+    Ident::new(
+        &format!(
+            "do_not_use_cdump_internal_function_capacity_of_string_at_index_{}",
+            field_index
+        ),
+        Span::call_site(),
+    )
+}
 
-    let result = match &field.ty {
-        FieldType::Plain | FieldType::InlineArray(_) => {
-            unreachable!("shallow fields should not be under first level pointer")
+fn inner_len_function_ident(field_index: usize, depth: usize) -> Ident {
+    Ident::new(
+        &format!(
+            "do_not_use_cdump_internal_function_len_of_array_at_index_{}_depth_{}",
+            field_index, depth
+        ),
+        Span::call_site(),
+    )
+}
+
+/// Get the call of the function which returns the length of the array, with indices of the outer arrays, e.g.
+/// `(*dst).#call`.
+fn array_len_call(len: &ArrayLen, field_index: usize) -> TokenStream {
+    match len.depth {
+        0 => {
+            let len_function = len_function_ident(field_index);
+            quote! { #len_function() }
         }
-        FieldType::Reference => deserialize_reference(field, &ident, &temp_ident),
-        FieldType::CString => {
-            quote! {
-                #ident = buf.read_raw_slice(#ident as usize) as *mut ::std::ffi::c_char;
-            }
+        depth => {
+            let len_function = inner_len_function_ident(field_index, depth);
+            let indices = &len.outer_indices;
+            quote! { #len_function(#(#indices),*) }
         }
-        FieldType::Dynamic(dynamic) => deserialize_dynamic(dynamic, &ident, &temp_ident),
+    }
+}
+
+fn discriminator_function_ident(field_index: usize) -> Ident {
+    Ident::new(
+        &format!(
+            "do_not_use_cdump_internal_function_discriminator_of_union_at_index_{}",
+            field_index
+        ),
+        Span::call_site(),
+    )
+}
+
+fn array_len_functions(fields: &[Field], name: &Ident, generics: &Generics) -> TokenStream {
+    let functions = fields
+        .iter()
+        .enumerate()
+        .flat_map(|(index, field)| {
+            let len = match &field.ty {
+                FieldType::Array(len, inner) => {
+                    let len_function = len_function_ident(index);
+                    let inner_len_functions = inner_len_functions(inner, index);
+                    Some(quote! {
+                        #[inline]
+                        #[doc(hidden)]
+                        fn #len_function(&self) -> usize {
+                            (#len) as usize
+                        }
+
+                        #inner_len_functions
+                    })
+                }
+                FieldType::Bytes(bytes) => {
+                    let len_function = len_function_ident(index);
+                    let len = &bytes.len;
+                    Some(quote! {
+                        #[inline]
+                        #[doc(hidden)]
+                        fn #len_function(&self) -> usize {
+                            (#len) as usize
+                        }
+                    })
+                }
+                FieldType::Union(discriminator) => {
+                    let discriminator_function = discriminator_function_ident(index);
+                    Some(quote! {
+                        #[inline]
+                        #[doc(hidden)]
+                        fn #discriminator_function(&self) -> i64 {
+                            (#discriminator) as i64
+                        }
+                    })
+                }
+                _ => None,
+            };
+
+            let mut element = field;
+            while let FieldType::Array(_, inner) | FieldType::DeepInlineArray(_, inner) =
+                &element.ty
+            {
+                element = inner;
+            }
+
+            let capacity = element.capacity.as_ref().map(|capacity| {
+                let capacity_function = capacity_function_ident(index);
+                quote! {
+                    #[inline]
+                    #[doc(hidden)]
+                    fn #capacity_function(&self) -> usize {
+                        (#capacity) as usize
+                    }
+                }
+            });
+
+            len.into_iter().chain(capacity)
+        })
+        .collect::<Vec<_>>();
+
+    if functions.is_empty() {
+        return quote! {};
+    }
+
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(generics, None, None);
+    quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#functions)*
+        }
+    }
+}
+
+/// Get functions which return lengths of the inner arrays, from indices of their outer arrays.
+fn inner_len_functions(inner: &Field, field_index: usize) -> TokenStream {
+    let FieldType::Array(len, inner) = &inner.ty else {
+        return quote! {};
+    };
+
+    let len_function = inner_len_function_ident(field_index, len.depth);
+    let indices = &len.outer_indices;
+    let inner_len_functions = inner_len_functions(inner, field_index);
+    quote! {
+        #[inline]
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        unsafe fn #len_function(&self, #(#indices: usize),*) -> usize {
+            (#len) as usize
+        }
+
+        #inner_len_functions
+    }
+}
+
+fn read_deep_fields(fields: &[Field], name: &Ident, mode: ReadMode) -> TokenStream {
+    let mut quotes = Vec::new();
+
+    for (index, field) in fields.iter().enumerate() {
+        let code = read_deep_fields_inner(field, index, mode);
+        quotes.push(match mode {
+            ReadMode::TryRef => in_field(name, field, code),
+            ReadMode::Ref => code,
+        });
+    }
+
+    match fields.iter().position(|field| field.iterative) {
+        Some(index) => read_iterative_field(name, &fields[index], index, &quotes, mode),
+        None => quotes.into_iter().collect(),
+    }
+}
+
+/// Deserialize the chain of the iterative field in the loop, where fields after it are deserialized when the rest of
+/// the chain is done.
+fn read_iterative_field(
+    name: &Ident,
+    field: &Field,
+    index: usize,
+    quotes: &[TokenStream],
+    mode: ReadMode,
+) -> TokenStream {
+    let field_ident = &field.ident;
+    let name_str = name.to_string();
+    let field_str = field.ident.to_token_stream().to_string();
+    let key = shared_key(field, &quote! { ::cdump::CDeserialize<__CDumpBuffer> });
+    let before = &quotes[..index];
+    let after = &quotes[index + 1..];
+
+    let read = match mode {
+        ReadMode::Ref => quote! {
+            ::cdump::internal::align_reader::<__CDumpBuffer, Self>(buf);
+            let shared = buf.shared_pointer((*dst).#field_ident as usize, #key).expect("invalid back-reference");
+            let offset = buf.get_read();
+        },
+        ReadMode::TryRef => quote! {
+            let shared = ::cdump::internal::in_field(#name_str, #field_str, || {
+                buf.try_align::<Self>()?;
+                buf.shared_pointer((*dst).#field_ident as usize, #key)
+            })?;
+            let offset = buf.get_read();
+            if shared.is_none() {
+                ::cdump::internal::in_field(#name_str, #field_str, || {
+                    buf.try_read_raw_slice(::std::mem::size_of::<Self>())
+                })?;
+            }
+        },
+    };
+    let add_read = match mode {
+        ReadMode::Ref => quote! {
+            buf.add_read(::std::mem::size_of::<Self>());
+        },
+        ReadMode::TryRef => quote! {},
+    };
+
+    quote! {
+        let mut pending = ::std::vec::Vec::new();
+        let mut dst = dst;
+        'nodes: loop {
+            #(#before)*
+
+            if !(*dst).#field_ident.is_null() {
+                #read
+                let slot = ::std::ptr::addr_of!((*dst).#field_ident) as *const u8;
+                match shared {
+                    Some(shared) => {
+                        (*dst).#field_ident = shared as _;
+                        buf.patched_pointer(slot);
+                    }
+                    None => {
+                        let next = buf.as_mut_ptr_at::<Self>(offset);
+                        buf.record_shared(offset, #key, next as *mut u8);
+                        #add_read
+                        (*dst).#field_ident = next as _;
+                        buf.patched_pointer(slot);
+
+                        pending.push(dst);
+                        dst = next;
+                        continue;
+                    }
+                }
+            }
+
+            loop {
+                #(#after)*
+
+                match pending.pop() {
+                    Some(parent) => dst = parent,
+                    None => break 'nodes,
+                }
+            }
+        }
+    }
+}
+
+fn read_deep_fields_inner(field: &Field, field_index: usize, mode: ReadMode) -> TokenStream {
+    let field_ident = &field.ident;
+    match &field.ty {
+        FieldType::DeepInlineArray(array, element) => {
+            let len = &array.len;
+            let read = read_deep_field_at(
+                element,
+                field_index,
+                quote! { (*dst).#field_ident[i] },
+                mode,
+            );
+            quote! {
+                for i in 0..(#len) {
+                    #read
+                }
+            }
+        }
+        _ => read_deep_field_at(field, field_index, quote! { (*dst).#field_ident }, mode),
+    }
+}
+
+/// Deserialize the data under pointers of the field, or of the element of the inline array, at `ident`.
+fn read_deep_field_at(
+    field: &Field,
+    field_index: usize,
+    ident: TokenStream,
+    mode: ReadMode,
+) -> TokenStream {
+    match &field.ty {
+        FieldType::Union(_) => return deserialize_union(field_index, &ident, mode),
+        FieldType::Nested => return deserialize_nested(&ident, mode),
+        _ => {}
+    }
+
+    let path = &field.path;
+    let key = shared_key(field, &quote! { ::cdump::CDeserialize<__CDumpBuffer> });
+
+    let result = match &field.ty {
+        FieldType::Plain | FieldType::InlineArray(_) => {
+            unreachable!("shallow fields should not be under first level pointer")
+        }
+        FieldType::DeepInlineArray(..) => unreachable!("inline arrays are followed by elements"),
+        FieldType::Union(_) | FieldType::Nested => {
+            unreachable!("unions and nested structs are deserialized before")
+        }
+        FieldType::Reference => deserialize_reference(field, &ident, key, mode),
+        FieldType::CString => {
+            let read = match mode {
+                ReadMode::TryRef => quote! {
+                    #ident = ::cdump::internal::try_read_cstring(buf, #ident as usize)?;
+                },
+                ReadMode::Ref => quote! {
+                    #ident = buf.read_raw_slice(#ident as usize) as *mut ::std::ffi::c_char;
+                },
+            };
+            read_shared(mode, &ident, key, read, false)
+        }
+        FieldType::WString => {
+            let (align, read) = match mode {
+                ReadMode::TryRef => (
+                    quote! {
+                        buf.try_align::<#path>()?;
+                    },
+                    quote! {
+                        #ident = ::cdump::internal::try_read_wstring::<__CDumpBuffer, #path>(buf, #ident as usize)?;
+                    },
+                ),
+                ReadMode::Ref => (
+                    quote! {
+                        ::cdump::internal::align_reader::<__CDumpBuffer, #path>(buf);
+                    },
+                    quote! {
+                        #ident = buf.read_raw_slice(#ident as usize * ::std::mem::size_of::<#path>()) as *mut #path;
+                    },
+                ),
+            };
+            let read = read_shared(mode, &ident, key, read, false);
+            quote! {
+                #align
+                #read
+            }
+        }
+        FieldType::Bytes(bytes) => deserialize_bytes(bytes, field_index, &ident, key, mode),
+        FieldType::Dynamic(dynamic) => match (mode, &dynamic.try_deserializer) {
+            (ReadMode::TryRef, None) => quote! {
+                return Err(::cdump::Error::UnsupportedDynamic);
+            },
+            _ => read_shared(
+                mode,
+                &ident,
+                key,
+                deserialize_dynamic(dynamic, &ident, mode),
+                true,
+            ),
+        },
         FieldType::Array(len, inner) => {
-            deserialize_array(len, inner, path, field_index, name, &ident, &temp_ident)
+            deserialize_array(len, inner, path, field_index, &ident, key, mode)
         }
     };
 
-    match temp_ident {
-        Some(temp_ident) => quote! {
-            if !#temp_ident.is_null() {
-                debug_assert!(!(*dst).#field_ident.is_null(), "destination field is null, but source field is not");
+    match mode {
+        // Checked deserialization of dynamic field without checked deserializer always returns an error.
+        ReadMode::TryRef if is_unchecked_dynamic(field) => quote! {
+            if !#ident.is_null() {
                 #result
             }
         },
-        None => quote! {
+        _ => quote! {
             if !#ident.is_null() {
                 #result
+                buf.patched_pointer(::std::ptr::addr_of!(#ident) as *const u8);
             }
         },
     }
 }
 
-fn deserialize_reference(
-    field: &Field,
+/// Deserialize the bytes of the field, which length is given by the deserialized parent.
+fn deserialize_bytes(
+    bytes: &BytesField,
+    field_index: usize,
     ident: &TokenStream,
-    temp_ident: &Option<TokenStream>,
+    key: TokenStream,
+    mode: ReadMode,
 ) -> TokenStream {
-    let path = field.path.to_token_stream();
-    if temp_ident.is_none() {
-        return match is_primitive_type(&path) {
-            true => quote! {
-                ::cdump::internal::align_reader::<T, #path>(buf);
-                #ident = buf.read_raw_slice(::std::mem::size_of::<#path>()) as *mut #path;
+    let len_function = len_function_ident(field_index);
+    let align_type = bytes.align_type();
+    let (align, read) = match mode {
+        ReadMode::Ref => (
+            quote! {
+                ::cdump::internal::align_reader::<__CDumpBuffer, #align_type>(buf);
+            },
+            quote! {
+                #ident = buf.read_raw_slice(len) as _;
+            },
+        ),
+        ReadMode::TryRef => (
+            quote! {
+                buf.try_align::<#align_type>()?;
             },
-            false => quote! {
-                #ident = ::cdump::internal::deserialize_shallow_copied(buf);
+            quote! {
+                #ident = buf.try_read_raw_slice(len)? as _;
             },
-        };
+        ),
+    };
+
+    let read = read_shared(mode, ident, key, read, false);
+    quote! {
+        let len = (*dst).#len_function();
+        #align
+        #read
     }
+}
 
-    match is_primitive_type(&path) {
-        true => quote! {
-            ::cdump::internal::align_reader::<T, #path>(buf);
-            let size = ::std::mem::size_of::<#path>();
-            let ptr = buf.read_raw_slice(size);
-            ::std::ptr::copy_nonoverlapping(ptr as *const u8, #ident as *mut u8, size);
-            #temp_ident = #ident;
+/// Wraps deserialization of the data under pointer `target`, which is replaced by the data already read when the
+/// pointer is a back-reference. New data is recorded before `code`, so it can be referenced by its own fields, or after
+/// it for dynamic objects, which location is known only after deserialization.
+fn read_shared(
+    mode: ReadMode,
+    target: &TokenStream,
+    key: TokenStream,
+    code: TokenStream,
+    dynamic: bool,
+) -> TokenStream {
+    let shared = match mode {
+        ReadMode::Ref => quote! {
+            buf.shared_pointer(#target as usize, shared_key).expect("invalid back-reference")
+        },
+        ReadMode::TryRef => quote! {
+            buf.shared_pointer(#target as usize, shared_key)?
         },
+    };
+    let code = match dynamic {
         false => quote! {
-            ::cdump::internal::deserialize_shallow_copied_to(buf, #ident as *mut #path);
-            #temp_ident = #ident;
+            let shared_ptr = buf.as_mut_ptr_at::<u8>(shared_offset);
+            buf.record_shared(shared_offset, shared_key, shared_ptr);
+            #code
         },
+        true => quote! {
+            #code
+            buf.record_shared(shared_offset, shared_key, #target as *mut u8);
+        },
+    };
+
+    quote! {
+        let shared_key = #key;
+        let shared_offset = buf.get_read();
+        match #shared {
+            Some(shared) => #target = shared as _,
+            None => {
+                #code
+            }
+        }
     }
 }
 
-fn deserialize_dynamic(
-    dynamic: &DynamicField,
+fn is_unchecked_dynamic(field: &Field) -> bool {
+    match &field.ty {
+        FieldType::Dynamic(dynamic) => dynamic.try_deserializer.is_none(),
+        FieldType::Array(_, inner) => is_unchecked_dynamic(inner),
+        _ => false,
+    }
+}
+
+fn deserialize_reference(
+    field: &Field,
     ident: &TokenStream,
-    temp_ident: &Option<TokenStream>,
+    key: TokenStream,
+    mode: ReadMode,
 ) -> TokenStream {
-    let deserializer = &dynamic.deserializer;
-    if temp_ident.is_none() {
-        return quote! {
-            #ident = #deserializer(buf).0;
-        };
-    }
+    let path = field.path.to_token_stream();
+    let (align, read) = match mode {
+        ReadMode::Ref => (
+            quote! {
+                ::cdump::internal::align_reader::<__CDumpBuffer, #path>(buf);
+            },
+            match is_primitive_type(&path) {
+                true => quote! {
+                    #ident = buf.read_raw_slice(::std::mem::size_of::<#path>()) as *mut #path;
+                },
+                false => quote! {
+                    #ident = ::cdump::internal::deserialize_shallow_copied(buf);
+                },
+            },
+        ),
+        ReadMode::TryRef => (
+            quote! {
+                buf.try_align::<#path>()?;
+            },
+            match is_primitive_type(&path) {
+                true => quote! {
+                    #ident = <#path as ::cdump::CDeserialize<__CDumpBuffer>>::try_deserialize_ref_mut(buf)? as *mut #path;
+                },
+                false => quote! {
+                    #ident = ::cdump::internal::try_deserialize_shallow_copied(buf)?;
+                },
+            },
+        ),
+    };
 
-    let size_of = &dynamic.size_of;
+    let read = read_shared(mode, ident, key, read, false);
     quote! {
-        let (ptr, size) = #deserializer(buf);
-        debug_assert!(#size_of(#ident) >= size, "field size is smaller than expected");
-        ::std::ptr::copy_nonoverlapping(ptr as *const u8, #ident as *mut u8, size);
-        #temp_ident = #ident;
+        #align
+        #read
+    }
+}
+
+/// Deserialize the deep part of the union member selected by the discriminator of the parent.
+fn deserialize_union(field_index: usize, ident: &TokenStream, mode: ReadMode) -> TokenStream {
+    let discriminator_function = discriminator_function_ident(field_index);
+    match mode {
+        ReadMode::Ref => quote! {
+            ::cdump::union::CUnionDeserialize::deserialize_ref_mut_member(
+                buf,
+                ::std::ptr::addr_of_mut!(#ident),
+                (*dst).#discriminator_function(),
+            );
+        },
+        ReadMode::TryRef => quote! {
+            ::cdump::union::CUnionDeserialize::try_deserialize_ref_mut_member(
+                buf,
+                ::std::ptr::addr_of_mut!(#ident),
+                (*dst).#discriminator_function(),
+            )?;
+        },
+    }
+}
+
+fn deserialize_nested(ident: &TokenStream, mode: ReadMode) -> TokenStream {
+    match mode {
+        ReadMode::Ref => quote! {
+            ::cdump::CDeserialize::deserialize_ref_mut_without_shallow_copy(buf, ::std::ptr::addr_of_mut!(#ident));
+        },
+        ReadMode::TryRef => quote! {
+            ::cdump::CDeserialize::try_deserialize_ref_mut_without_shallow_copy(
+                buf,
+                ::std::ptr::addr_of_mut!(#ident),
+            )?;
+        },
+    }
+}
+
+fn deserialize_dynamic(dynamic: &DynamicField, ident: &TokenStream, mode: ReadMode) -> TokenStream {
+    let deserializer = &dynamic.deserializer;
+    match mode {
+        ReadMode::Ref => quote! {
+            #ident = #deserializer(buf).0;
+        },
+        ReadMode::TryRef => match &dynamic.try_deserializer {
+            Some(try_deserializer) => quote! {
+                #ident = #try_deserializer(buf)?.0;
+            },
+            None => quote! {
+                return Err(::cdump::Error::UnsupportedDynamic);
+            },
+        },
     }
 }
 
 fn deserialize_array(
-    len: &Expr,
+    len: &ArrayLen,
     inner: &Field,
     path: &Option<TypePath>,
     field_index: usize,
-    name: &proc_macro2::Ident,
     ident: &TokenStream,
-    temp_ident: &Option<TokenStream>,
+    key: TokenStream,
+    mode: ReadMode,
 ) -> TokenStream {
-    let inner_path = inner.path.to_token_stream();
     let alignment_type = get_alignment_type(inner);
+    let len_function = array_len_call(len, field_index);
 
-    let len_function = Ident::new(
-        &format!(
-            "do_not_use_cdump_internal_function_len_of_array_at_index_{}{}",
-            match temp_ident.is_some() {
-                true => "to",
-                false => "",
-            },
-            field_index
-        ),
-        Span::call_site(),
-    );
+    let bind_index = bind_index(len);
+    let check_terminator = match &len.terminator {
+        Some(terminator) => quote! {
+            ::cdump::internal::try_check_terminator(#ident, len, array_start_index, #terminator)?;
+        },
+        None => quote! {},
+    };
+    let len = match len.terminator {
+        Some(_) => quote! { #ident as usize },
+        None => quote! { (*dst).#len_function },
+    };
 
-    let mut result = quote! {
-        impl #name {
-            #[inline]
-            #[doc(hidden)]
-            fn #len_function(&self) -> usize {
-                (#len) as usize
-            }
+    if mode == ReadMode::TryRef {
+        if let FieldType::Dynamic(DynamicField {
+            try_deserializer: None,
+            ..
+        }) = &inner.ty
+        {
+            return quote! {
+                return Err(::cdump::Error::UnsupportedDynamic);
+            };
         }
+    }
 
-        let size = ::std::mem::size_of::<#alignment_type>();
-        ::cdump::internal::align_reader::<T, #alignment_type>(buf);
-    };
+    if let FieldType::Dynamic(dynamic @ DynamicField { ptr_level: 1, .. }) = &inner.ty {
+        return read_dynamic_array(dynamic, ident, len, mode);
+    }
 
-    result = match temp_ident.is_some() {
-        true => quote! {
-            #result
-            let len = (*temp).#len_function();
-        },
-        false => quote! {
-            #result
-            let len = (*dst).#len_function();
-        },
-    };
+    if mode == ReadMode::TryRef {
+        let inner = match is_primitive_element(inner) {
+            true => quote! {},
+            false => {
+                let inner = get_inner_of_array_try_deserialize(inner, path, field_index);
+                quote! {
+                    for i in 0..len {
+                        #bind_index
+                        #inner
+                    }
+                }
+            }
+        };
 
-    if !is_primitive_type(&inner_path) {
-        let (prefix, start, inner) = get_inner_of_array_deserialize(inner, ident, path);
-        result = match temp_ident {
-            Some(_) => quote! {
-                unimplemented!("temp_ident is not supported for array of non-primitive type");
-            },
-            None => quote! {
-                #result
-                let array_start_index = buf.get_read();
+        let read = read_shared(
+            mode,
+            ident,
+            key,
+            quote! {
+                buf.try_read_raw_slice(::cdump::internal::try_array_size(size, len)?)?;
 
-                #prefix
-                for i in #start..len {
-                    #inner
-                }
+                #inner
+                #ident = buf.as_mut_ptr_at(array_start_index);
+                #check_terminator
             },
+            false,
+        );
+        return quote! {
+            let len = #len;
+            let size = ::std::mem::size_of::<#alignment_type>();
+            buf.try_align::<#alignment_type>()?;
+            let array_start_index = buf.get_read();
+            #read
         };
-    } else {
-        if let FieldType::Reference = inner.ty {
-            return Error::new(
-                inner.ident.span(),
-                "pointer to array of pointers to primitive type is not supported",
-            )
-            .to_compile_error();
-        }
+    }
 
-        result = match temp_ident {
-            Some(temp_ident) => quote! {
-                #result
-                debug_assert!((*dst).#len_function() >= len, "array size is smaller than expected, expected: {}, got: {}", (*dst).#len_function(), len);
-                let whole_size = size * len;
-                ::std::ptr::copy_nonoverlapping(buf.read_raw_slice(whole_size), #ident as *mut u8, whole_size);
-                #temp_ident = #ident;
-            },
-            None => quote! {
-                #result
+    let read = if !is_primitive_element(inner) {
+        let (prefix, start, inner_code) =
+            get_inner_of_array_deserialize(inner, ident, path, field_index);
+        let read = quote! {
+            let array_start_index = buf.get_read();
+
+            #prefix
+            for i in #start..len {
+                #bind_index
+                #inner_code
+            }
+        };
+
+        read_shared(mode, ident, key, read, false)
+    } else {
+        read_shared(
+            mode,
+            ident,
+            key,
+            quote! {
                 #ident = buf.as_mut_ptr_at(buf.get_read());
                 buf.add_read(size * len);
             },
-        };
+            false,
+        )
+    };
+
+    quote! {
+        let size = ::std::mem::size_of::<#alignment_type>();
+        ::cdump::internal::align_reader::<__CDumpBuffer, #alignment_type>(buf);
+        let len = #len;
+        #read
     }
+}
+
+/// Deserialize the array of dynamic objects under one pointer, which are copied one after another to the space reserved
+/// before them, whose size is stored in the pointer.
+fn read_dynamic_array(
+    dynamic: &DynamicField,
+    ident: &TokenStream,
+    len: TokenStream,
+    mode: ReadMode,
+) -> TokenStream {
+    match mode {
+        ReadMode::Ref => {
+            let deserializer = &dynamic.deserializer;
+            quote! {
+                let len = #len;
+                let array_size = #ident as usize;
+                ::cdump::internal::align_reader::<__CDumpBuffer, usize>(buf);
+                let array = buf.read_raw_slice(array_size) as *mut u8;
 
-    result
+                let mut offset: usize = 0;
+                for _ in 0..len {
+                    let (element, size) = #deserializer(buf);
+                    ::std::ptr::copy_nonoverlapping(element as *const u8, array.add(offset), size);
+                    offset += size;
+                }
+                #ident = array as _;
+            }
+        }
+        ReadMode::TryRef => {
+            let try_deserializer = dynamic
+                .try_deserializer
+                .as_ref()
+                .expect("checked deserializer should be validated before");
+            quote! {
+                let len = #len;
+                let array_size = #ident as usize;
+                buf.try_align::<usize>()?;
+                let array = buf.try_read_raw_slice(array_size)? as *mut u8;
+
+                let mut offset: usize = 0;
+                for _ in 0..len {
+                    let (element, size) = #try_deserializer(buf)?;
+                    offset = ::cdump::internal::try_copy_to_dynamic_array(array, array_size, offset, element as _, size)?;
+                }
+                #ident = array as _;
+            }
+        }
+    }
 }
 
 fn get_inner_of_array_deserialize(
     inner: &Field,
     ident: &TokenStream,
     path: &Option<TypePath>,
+    field_index: usize,
 ) -> (TokenStream, TokenStream, TokenStream) {
+    let element = quote! { *ptr };
+    let key = shared_key(inner, &quote! { ::cdump::CDeserialize<__CDumpBuffer> });
+
     match &inner.ty {
         FieldType::Plain => (
             quote! {
@@ -485,56 +1600,237 @@ fn get_inner_of_array_deserialize(
             },
             quote! { 1 },
             quote! {
-                _ = ::cdump::internal::deserialize_shallow_copied_at::<T, #path>(buf, array_start_index + size * i);
-            },
-        ),
-        FieldType::Reference => (
-            quote! {
-                #ident = buf.read_raw_slice(size * len) as *const *const #path;
-            },
-            quote! { 0 },
-            quote! {
-                let ptr = buf.as_mut_ptr_at(array_start_index + size * i);
-                *ptr = ::cdump::internal::deserialize_shallow_copied::<T, #path>(buf);
-            },
-        ),
-        FieldType::CString => (
-            quote! {
-                #ident = buf.read_raw_slice(size * len) as *const *const ::std::ffi::c_char;
-            },
-            quote! { 0 },
-            quote! {
-                let ptr = buf.as_mut_ptr_at(array_start_index + size * i);
-                *ptr = buf.read_raw_slice(*ptr as usize) as *const ::std::ffi::c_char;
+                _ = ::cdump::internal::deserialize_shallow_copied_at::<__CDumpBuffer, #path>(buf, array_start_index + size * i);
             },
         ),
+        FieldType::Reference => {
+            let read = read_shared(
+                ReadMode::Ref,
+                &element,
+                key,
+                quote! {
+                    *ptr = ::cdump::internal::deserialize_shallow_copied::<__CDumpBuffer, #path>(buf);
+                },
+                false,
+            );
+            (
+                quote! {
+                    #ident = buf.read_raw_slice(size * len) as _;
+                },
+                quote! { 0 },
+                quote! {
+                    let ptr = buf.as_mut_ptr_at::<*mut #path>(array_start_index + size * i);
+                    if !(*ptr).is_null() {
+                        ::cdump::internal::align_reader::<__CDumpBuffer, #path>(buf);
+                        #read
+                        buf.patched_pointer(ptr as *const u8);
+                    }
+                },
+            )
+        }
+        FieldType::CString => {
+            let read = read_shared(
+                ReadMode::Ref,
+                &element,
+                key,
+                quote! {
+                    *ptr = buf.read_raw_slice(*ptr as usize) as *const ::std::ffi::c_char;
+                },
+                false,
+            );
+            (
+                quote! {
+                    #ident = buf.read_raw_slice(size * len) as _;
+                },
+                quote! { 0 },
+                quote! {
+                    let ptr = buf.as_mut_ptr_at::<*const ::std::ffi::c_char>(array_start_index + size * i);
+                    if !(*ptr).is_null() {
+                        #read
+                        buf.patched_pointer(ptr as *const u8);
+                    }
+                },
+            )
+        }
+        FieldType::WString => {
+            let path = &inner.path;
+            let read = read_shared(
+                ReadMode::Ref,
+                &element,
+                key,
+                quote! {
+                    *ptr = buf.read_raw_slice(*ptr as usize * ::std::mem::size_of::<#path>()) as *const #path;
+                },
+                false,
+            );
+            (
+                quote! {
+                    #ident = buf.read_raw_slice(size * len) as _;
+                },
+                quote! { 0 },
+                quote! {
+                    let ptr = buf.as_mut_ptr_at::<*const #path>(array_start_index + size * i);
+                    if !(*ptr).is_null() {
+                        ::cdump::internal::align_reader::<__CDumpBuffer, #path>(buf);
+                        #read
+                        buf.patched_pointer(ptr as *const u8);
+                    }
+                },
+            )
+        }
         FieldType::Dynamic(dynamic) => {
             let deserializer = &dynamic.deserializer;
             match dynamic.ptr_level {
-                1 => (
-                    quote! {
-                        #ident = #deserializer(buf);
-                    },
-                    quote! { 1 },
-                    quote! {
-                        _ = #deserializer(buf);
-                    },
-                ),
-                2 => (
-                    quote! {
-                        #ident = buf.read_raw_slice(size * len) as *const *const ::std::ffi::c_void;
-                    },
-                    quote! { 0 },
-                    quote! {
-                        let ptr = buf.as_mut_ptr_at(array_start_index + size * i);
-                        *ptr = #deserializer(buf);
-                    },
-                ),
+                1 => {
+                    unreachable!("dynamic objects under one pointer are read by read_dynamic_array")
+                }
+                2 => {
+                    let read = read_shared(
+                        ReadMode::Ref,
+                        &element,
+                        key,
+                        quote! {
+                            *ptr = #deserializer(buf).0;
+                        },
+                        true,
+                    );
+                    (
+                        quote! {
+                            #ident = buf.read_raw_slice(size * len) as _;
+                        },
+                        quote! { 0 },
+                        quote! {
+                            let ptr = buf.as_mut_ptr_at::<*const ::std::ffi::c_void>(array_start_index + size * i);
+                            if !(*ptr).is_null() {
+                                #read
+                                buf.patched_pointer(ptr as *const u8);
+                            }
+                        },
+                    )
+                }
                 _ => {
                     unimplemented!("three or more level of pointer to dynamic type is unsupported")
                 }
             }
         }
+        FieldType::Array(..) => {
+            let element_type = element_type(inner);
+            let read = read_deep_field_at(inner, field_index, quote! { (*element) }, ReadMode::Ref);
+            (
+                quote! {
+                    #ident = buf.read_raw_slice(size * len) as _;
+                },
+                quote! { 0 },
+                quote! {
+                    let element = buf.as_mut_ptr_at::<#element_type>(array_start_index + size * i);
+                    #read
+                },
+            )
+        }
+        _ => unimplemented!("2D arrays"),
+    }
+}
+
+fn get_inner_of_array_try_deserialize(
+    inner: &Field,
+    path: &Option<TypePath>,
+    field_index: usize,
+) -> TokenStream {
+    let element = quote! { *ptr };
+    let key = shared_key(inner, &quote! { ::cdump::CDeserialize<__CDumpBuffer> });
+
+    match &inner.ty {
+        FieldType::Plain => quote! {
+            ::cdump::internal::try_deserialize_shallow_copied_at::<__CDumpBuffer, #path>(buf, array_start_index + size * i)?;
+        },
+        FieldType::Reference => {
+            let read = read_shared(
+                ReadMode::TryRef,
+                &element,
+                key,
+                quote! {
+                    *ptr = ::cdump::internal::try_deserialize_shallow_copied::<__CDumpBuffer, #path>(buf)?;
+                },
+                false,
+            );
+            quote! {
+                let ptr = buf.as_mut_ptr_at::<*mut #path>(array_start_index + size * i);
+                if !(*ptr).is_null() {
+                    buf.try_align::<#path>()?;
+                    #read
+                    buf.patched_pointer(ptr as *const u8);
+                }
+            }
+        }
+        FieldType::CString => {
+            let read = read_shared(
+                ReadMode::TryRef,
+                &element,
+                key,
+                quote! {
+                    *ptr = ::cdump::internal::try_read_cstring(buf, *ptr as usize)?;
+                },
+                false,
+            );
+            quote! {
+                let ptr = buf.as_mut_ptr_at::<*mut ::std::ffi::c_char>(array_start_index + size * i);
+                if !(*ptr).is_null() {
+                    #read
+                    buf.patched_pointer(ptr as *const u8);
+                }
+            }
+        }
+        FieldType::WString => {
+            let path = &inner.path;
+            let read = read_shared(
+                ReadMode::TryRef,
+                &element,
+                key,
+                quote! {
+                    *ptr = ::cdump::internal::try_read_wstring::<__CDumpBuffer, #path>(buf, *ptr as usize)?;
+                },
+                false,
+            );
+            quote! {
+                let ptr = buf.as_mut_ptr_at::<*mut #path>(array_start_index + size * i);
+                if !(*ptr).is_null() {
+                    buf.try_align::<#path>()?;
+                    #read
+                    buf.patched_pointer(ptr as *const u8);
+                }
+            }
+        }
+        FieldType::Dynamic(dynamic) => match (dynamic.ptr_level, &dynamic.try_deserializer) {
+            (2, Some(try_deserializer)) => {
+                let read = read_shared(
+                    ReadMode::TryRef,
+                    &element,
+                    key,
+                    quote! {
+                        *ptr = #try_deserializer(buf)?.0;
+                    },
+                    true,
+                );
+                quote! {
+                    let ptr = buf.as_mut_ptr_at::<*const ::std::ffi::c_void>(array_start_index + size * i);
+                    if !(*ptr).is_null() {
+                        #read
+                        buf.patched_pointer(ptr as *const u8);
+                    }
+                }
+            }
+            (_, None) => unreachable!("checked deserializer should be validated before"),
+            _ => unimplemented!("three or more level of pointer to dynamic type is unsupported"),
+        },
+        FieldType::Array(..) => {
+            let element_type = element_type(inner);
+            let read =
+                read_deep_field_at(inner, field_index, quote! { (*element) }, ReadMode::TryRef);
+            quote! {
+                let element = buf.as_mut_ptr_at::<#element_type>(array_start_index + size * i);
+                #read
+            }
+        }
         _ => unimplemented!("2D arrays"),
     }
 }
@@ -542,15 +1838,66 @@ fn get_inner_of_array_deserialize(
 fn get_alignment_type(inner: &Field) -> TokenStream {
     match inner.ty {
         // Align two levels of pointers to size of pointer
-        FieldType::CString | FieldType::Reference | FieldType::Dynamic(_) => {
+        FieldType::CString
+        | FieldType::WString
+        | FieldType::Reference
+        | FieldType::Dynamic(_)
+        | FieldType::Array(..) => {
             quote! { usize }
         }
         _ => inner.path.to_token_stream(),
     }
 }
 
+/// Check whether elements of the array are primitives by value, which are copied without traversal.
+fn is_primitive_element(inner: &Field) -> bool {
+    matches!(inner.ty, FieldType::Plain) && is_primitive_type(&inner.path.to_token_stream())
+}
+
+/// Get the type of elements of the array, which `inner` describes.
+fn element_type(inner: &Field) -> TokenStream {
+    match &inner.ty {
+        FieldType::Reference | FieldType::WString => {
+            let path = &inner.path;
+            quote! { *mut #path }
+        }
+        FieldType::CString => quote! { *mut ::std::ffi::c_char },
+        FieldType::Dynamic(_) => quote! { *mut ::std::ffi::c_void },
+        FieldType::Array(_, inner) => {
+            let element_type = element_type(inner);
+            quote! { *mut #element_type }
+        }
+        _ => inner.path.to_token_stream(),
+    }
+}
+
+/// Bind the index of the current element of the array to the name given by its `index`, which lengths of inner arrays
+/// use. Loops over elements use `i`.
+fn bind_index(len: &ArrayLen) -> TokenStream {
+    match &len.index {
+        Some(index) => quote! { let #index = i; },
+        None => quote! {},
+    }
+}
+
+#[proc_macro_derive(CTag, attributes(cdump))]
+pub fn c_tag_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    tagged::c_tag_derive(input)
+}
+
+#[proc_macro_derive(CView, attributes(cdump))]
+pub fn c_view_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    view::c_view_derive(input)
+}
+
 #[cfg(feature = "cdebug")]
 #[proc_macro_derive(CDebug, attributes(cdump))]
 pub fn c_debug_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     cdebug::c_debug_derive(input)
 }
+
+#[cfg(feature = "portable")]
+#[proc_macro_derive(CPortable, attributes(cdump))]
+pub fn c_portable_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    portable::c_portable_derive(input)
+}
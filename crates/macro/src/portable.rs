@@ -0,0 +1,364 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, spanned::Spanned, DeriveInput, Error};
+
+use crate::{
+    field_analysis::{self, Field, FieldType},
+    helpers::{is_primitive_type, split_generics, validate_repr, ErrorExt, SplitGenerics},
+    in_field,
+};
+
+pub fn c_portable_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let validate_repr = validate_repr(&ast.attrs, "C", ast.span()).to_compile_error();
+    let name = ast.ident.clone();
+
+    if field_analysis::is_union(&ast) {
+        return Error::new(ast.ident.span(), "unions are not supported by `CPortable`")
+            .to_compile_error()
+            .into();
+    }
+
+    let fields = field_analysis::get_fields(&ast, false).and_then(|fields| {
+        if let Some(field) = fields.iter().find(|field| is_dynamic(&field.ty)) {
+            return Err(Error::new(
+                field.ident.span(),
+                "dynamic fields are not supported by `CPortable`",
+            ));
+        }
+        for field in &fields {
+            let message = match &field.ty {
+                FieldType::Union(_) => "union fields are not supported by `CPortable`",
+                FieldType::Array(_, inner) if matches!(inner.ty, FieldType::Array(..)) => {
+                    "arrays of arrays are not supported by `CPortable`"
+                }
+                FieldType::Array(len, _) if len.terminator.is_some() => {
+                    "terminated arrays are not supported by `CPortable`"
+                }
+                FieldType::WString => "wide strings are not supported by `CPortable`",
+                FieldType::Bytes(_) => "bytes are not supported by `CPortable`",
+                FieldType::Array(_, inner) if matches!(inner.ty, FieldType::WString) => {
+                    "wide strings are not supported by `CPortable`"
+                }
+                FieldType::Nested => "nested fields are not supported by `CPortable`",
+                FieldType::DeepInlineArray(..) => {
+                    "inline arrays with pointers are not supported by `CPortable`"
+                }
+                _ => continue,
+            };
+            return Err(Error::new(field.ident.span(), message));
+        }
+        Ok(fields)
+    });
+    let fields = match fields {
+        Ok(fields) => fields,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let deep_fields = fields
+        .iter()
+        .filter(|field| !matches!(field.ty, FieldType::Plain | FieldType::InlineArray(_)))
+        .collect::<Vec<_>>();
+
+    let write_shallow = fields.iter().map(write_shallow).collect::<TokenStream>();
+    let read_shallow = fields.iter().map(read_shallow).collect::<TokenStream>();
+    let transcode_shallow = fields
+        .iter()
+        .map(transcode_shallow)
+        .collect::<TokenStream>();
+    let write_deep = deep_fields
+        .iter()
+        .map(|field| in_field(&name, field, write_deep(field)))
+        .collect::<TokenStream>();
+    let transcode_deep = deep_fields
+        .iter()
+        .map(|field| in_field(&name, field, transcode_deep(field)))
+        .collect::<TokenStream>();
+
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(&ast.generics, None, Some(quote! { ::cdump::CPortable }));
+
+    proc_macro::TokenStream::from(quote! {
+        impl #impl_generics ::cdump::CPortable for #name #ty_generics #where_clause {
+            unsafe fn try_write_portable_shallow<W: ::cdump::CDumpWriter>(
+                &self,
+                buf: &mut W,
+            ) -> Result<(), ::cdump::Error> {
+                #validate_repr
+
+                #write_shallow
+                Ok(())
+            }
+
+            unsafe fn try_write_portable_deep<W: ::cdump::CDumpWriter>(
+                &self,
+                buf: &mut W,
+            ) -> Result<(), ::cdump::Error> {
+                #write_deep
+                Ok(())
+            }
+
+            unsafe fn try_read_portable_shallow<R: ::cdump::CDumpReader>(
+                buf: &mut R,
+            ) -> Result<Self, ::cdump::Error> {
+                Ok(Self {
+                    #read_shallow
+                })
+            }
+
+            unsafe fn try_transcode_portable_shallow<R: ::cdump::CDumpReader, W: ::cdump::CDumpWriter>(
+                src: &mut R,
+                dst: &mut W,
+                index: usize,
+            ) -> Result<(), ::cdump::Error> {
+                #transcode_shallow
+                Ok(())
+            }
+
+            unsafe fn try_transcode_portable_deep<R: ::cdump::CDumpReader, W: ::cdump::CDumpWriter>(
+                &self,
+                src: &mut R,
+                dst: &mut W,
+            ) -> Result<(), ::cdump::Error> {
+                #transcode_deep
+                Ok(())
+            }
+        }
+    })
+}
+
+fn is_dynamic(ty: &FieldType) -> bool {
+    match ty {
+        FieldType::Dynamic(_) => true,
+        FieldType::Array(_, inner) => is_dynamic(&inner.ty),
+        _ => false,
+    }
+}
+
+fn write_shallow(field: &Field) -> TokenStream {
+    let ident = &field.ident;
+    match &field.ty {
+        FieldType::Plain | FieldType::InlineArray(_) => quote! {
+            ::cdump::CPortable::try_write_portable_shallow(&self.#ident, buf)?;
+        },
+        FieldType::CString => quote! {
+            ::cdump::internal::write_slot(buf, match self.#ident.is_null() {
+                true => 0,
+                false => ::cdump::internal::libc_strlen(self.#ident) + 1,
+            })?;
+        },
+        _ => quote! {
+            ::cdump::internal::write_slot(buf, !self.#ident.is_null() as usize)?;
+        },
+    }
+}
+
+fn read_shallow(field: &Field) -> TokenStream {
+    let ident = &field.ident;
+    match &field.ty {
+        FieldType::Plain | FieldType::InlineArray(_) => quote! {
+            #ident: ::cdump::CPortable::try_read_portable_shallow(buf)?,
+        },
+        _ => quote! {
+            #ident: ::cdump::internal::read_slot(buf)? as _,
+        },
+    }
+}
+
+/// Transcode the field to its offset in the native buffer, which is zeroed, so padding between fields stays zeroed.
+fn transcode_shallow(field: &Field) -> TokenStream {
+    let ident = &field.ident;
+    let index = quote! { index + ::cdump::offset_of!(Self, #ident) };
+    let ty = match &field.ty {
+        FieldType::Plain => field.path.to_token_stream(),
+        FieldType::InlineArray(array) => array.to_token_stream(),
+        _ => {
+            return quote! {
+                ::cdump::internal::transcode_slot(src, dst, #index)?;
+            }
+        }
+    };
+
+    quote! {
+        <#ty as ::cdump::CPortable>::try_transcode_portable_shallow(src, dst, #index)?;
+    }
+}
+
+fn write_deep(field: &Field) -> TokenStream {
+    let field_ident = &field.ident;
+    let ident = quote! {
+        self.#field_ident
+    };
+
+    let result = match &field.ty {
+        FieldType::Reference => quote! {
+            ::cdump::CPortable::try_serialize_portable(&*#ident, buf)?;
+        },
+        FieldType::CString => write_cstring(&ident),
+        FieldType::Array(len, inner) => {
+            let inner_path = inner.path.to_token_stream();
+            let element = quote! { #ident.add(i) };
+
+            let inner = match &inner.ty {
+                FieldType::Plain => {
+                    let deep = match is_primitive_type(&inner_path) {
+                        true => quote! {},
+                        false => quote! {
+                            for i in 0..len {
+                                ::cdump::CPortable::try_write_portable_deep(&*#element, buf)?;
+                            }
+                        },
+                    };
+                    quote! {
+                        for i in 0..len {
+                            ::cdump::CPortable::try_write_portable_shallow(&*#element, buf)?;
+                        }
+                        #deep
+                    }
+                }
+                FieldType::Reference => quote! {
+                    for i in 0..len {
+                        ::cdump::internal::write_slot(buf, !(*#element).is_null() as usize)?;
+                    }
+                    for i in 0..len {
+                        let ptr = *#element;
+                        if !ptr.is_null() {
+                            ::cdump::CPortable::try_serialize_portable(&*ptr, buf)?;
+                        }
+                    }
+                },
+                FieldType::CString => {
+                    let write_cstring = write_cstring(&quote! { ptr });
+                    quote! {
+                        for i in 0..len {
+                            let ptr = *#element;
+                            ::cdump::internal::write_slot(buf, match ptr.is_null() {
+                                true => 0,
+                                false => ::cdump::internal::libc_strlen(ptr) + 1,
+                            })?;
+                        }
+                        for i in 0..len {
+                            let ptr = *#element;
+                            if !ptr.is_null() {
+                                #write_cstring
+                            }
+                        }
+                    }
+                }
+                _ => unimplemented!("2D arrays"),
+            };
+
+            quote! {
+                let len = ::cdump::internal::try_array_len(#len)?;
+                #inner
+            }
+        }
+        _ => unreachable!("only pointer fields have deep data"),
+    };
+
+    quote! {
+        if !#ident.is_null() {
+            #result
+        }
+    }
+}
+
+fn write_cstring(ptr: &TokenStream) -> TokenStream {
+    quote! {
+        let len = ::cdump::internal::libc_strlen(#ptr) + 1;
+        buf.try_push_slice(::std::slice::from_raw_parts(#ptr as *const u8, len))?;
+    }
+}
+
+fn transcode_deep(field: &Field) -> TokenStream {
+    let field_ident = &field.ident;
+    let ident = quote! {
+        self.#field_ident
+    };
+    let path = field.path.to_token_stream();
+
+    let result = match &field.ty {
+        FieldType::Reference => quote! {
+            <#path as ::cdump::CPortable>::try_transcode_portable(src, dst)?;
+        },
+        FieldType::CString => transcode_cstring(&quote! { #ident as usize }),
+        FieldType::Array(len, inner) => {
+            let inner_path = inner.path.to_token_stream();
+
+            let inner = match &inner.ty {
+                FieldType::Plain => {
+                    let deep = match is_primitive_type(&inner_path) {
+                        true => quote! {},
+                        false => quote! {
+                            for i in 0..len {
+                                let element: #inner_path = ::cdump::internal::read_native(
+                                    dst,
+                                    array_start_index + ::std::mem::size_of::<#inner_path>() * i,
+                                );
+                                element.try_transcode_portable_deep(src, dst)?;
+                            }
+                        },
+                    };
+                    quote! {
+                        dst.try_align::<#inner_path>()?;
+                        let array_start_index = dst.len();
+                        for _ in 0..len {
+                            ::cdump::internal::transcode_shallow::<#inner_path, _, _>(src, dst)?;
+                        }
+                        #deep
+                    }
+                }
+                FieldType::Reference => quote! {
+                    let array_start_index = ::cdump::internal::transcode_slots(src, dst, len)?;
+                    for i in 0..len {
+                        let slot: usize = ::cdump::internal::read_native(
+                            dst,
+                            array_start_index + ::std::mem::size_of::<usize>() * i,
+                        );
+                        if slot != 0 {
+                            <#inner_path as ::cdump::CPortable>::try_transcode_portable(src, dst)?;
+                        }
+                    }
+                },
+                FieldType::CString => {
+                    let transcode_cstring = transcode_cstring(&quote! { slot });
+                    quote! {
+                        let array_start_index = ::cdump::internal::transcode_slots(src, dst, len)?;
+                        for i in 0..len {
+                            let slot: usize = ::cdump::internal::read_native(
+                                dst,
+                                array_start_index + ::std::mem::size_of::<usize>() * i,
+                            );
+                            if slot != 0 {
+                                #transcode_cstring
+                            }
+                        }
+                    }
+                }
+                _ => unimplemented!("2D arrays"),
+            };
+
+            quote! {
+                let len = ::cdump::internal::try_array_len(#len)?;
+                #inner
+            }
+        }
+        _ => unreachable!("only pointer fields have deep data"),
+    };
+
+    quote! {
+        if !#ident.is_null() {
+            #result
+        }
+    }
+}
+
+fn transcode_cstring(len: &TokenStream) -> TokenStream {
+    quote! {
+        let len = #len;
+        let ptr = ::cdump::internal::try_read_cstring(src, len)?;
+        dst.try_push_slice(::std::slice::from_raw_parts(ptr as *const u8, len))?;
+    }
+}
@@ -0,0 +1,239 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{
+    bind_index,
+    field_analysis::{ArrayLen, DynamicField, Field, FieldType},
+    get_alignment_type,
+    helpers::{split_generics, SplitGenerics},
+    is_primitive_element,
+};
+
+pub fn serialized_size_impl(ast: &syn::DeriveInput, fields: &[Field]) -> TokenStream {
+    let name = &ast.ident;
+    let fields = fields.iter().map(size_of_field).collect::<TokenStream>();
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(
+        &ast.generics,
+        None,
+        Some(quote! { ::cdump::CSerializedSize }),
+    );
+
+    quote! {
+        impl #impl_generics ::cdump::CSerializedSize for #name #ty_generics #where_clause {
+            unsafe fn serialized_end(&self, start: usize) -> usize {
+                let start_index = ::cdump::internal::align_index::<Self>(start);
+                self.serialized_end_without_shallow_copy(
+                    start_index.saturating_add(::std::mem::size_of::<Self>()),
+                )
+            }
+
+            unsafe fn serialized_end_without_shallow_copy(&self, end: usize) -> usize {
+                let Some(_visit) = ::cdump::internal::Visit::enter(self as *const Self) else {
+                    return usize::MAX;
+                };
+                let mut end = end;
+                #fields
+                end
+            }
+        }
+    }
+}
+
+pub fn size_of_field(field: &Field) -> TokenStream {
+    let field_ident = &field.ident;
+    match &field.ty {
+        FieldType::DeepInlineArray(array, element) => {
+            let len = &array.len;
+            let size = size_of_field_at(element, quote! { self.#field_ident[i] });
+            quote! {
+                for i in 0..(#len) {
+                    #size
+                }
+            }
+        }
+        _ => size_of_field_at(field, quote! { self.#field_ident }),
+    }
+}
+
+/// Get size of the data under pointers of the field, or of the element of the inline array, at `ident`.
+fn size_of_field_at(field: &Field, ident: TokenStream) -> TokenStream {
+    let result = match &field.ty {
+        FieldType::Nested => {
+            return quote! {
+                end = ::cdump::CSerializedSize::serialized_end_without_shallow_copy(&#ident, end);
+            };
+        }
+        FieldType::Plain | FieldType::InlineArray(_) => {
+            unreachable!("shallow fields should not be under first level pointer")
+        }
+        FieldType::DeepInlineArray(..) => unreachable!("inline arrays are followed by elements"),
+        FieldType::Union(discriminator) => {
+            return quote! {
+                end = ::cdump::union::CUnionSerializedSize::serialized_end_member(
+                    &#ident,
+                    (#discriminator) as i64,
+                    end,
+                );
+            };
+        }
+        FieldType::Reference => quote! {
+            end = ::cdump::CSerializedSize::serialized_end(&*#ident, end);
+        },
+        FieldType::CString => quote! {
+            end = end.saturating_add(::cdump::internal::libc_strlen(#ident) + 1);
+        },
+        FieldType::WString => size_of_wstring(field, &ident),
+        FieldType::Bytes(bytes) => {
+            let len = &bytes.len;
+            let align_type = bytes.align_type();
+            quote! {
+                let len = match ::cdump::internal::try_array_len(#len) {
+                    Ok(len) => len,
+                    Err(_) => return usize::MAX,
+                };
+                end = ::cdump::internal::align_index::<#align_type>(end).saturating_add(len);
+            }
+        }
+        FieldType::Dynamic(dynamic) => size_of_dynamic(dynamic, &ident),
+        FieldType::Array(len, inner) => match &inner.ty {
+            FieldType::Dynamic(dynamic @ DynamicField { ptr_level: 1, .. }) => {
+                size_of_dynamic_array(dynamic, &ident, len)
+            }
+            _ => {
+                let alignment_type = get_alignment_type(inner);
+                let inner = match is_primitive_element(inner) {
+                    true => quote! {},
+                    false => {
+                        let bind_index = bind_index(len);
+                        let inner = size_of_array_element(inner, &ident);
+                        quote! {
+                            for i in 0..len {
+                                #bind_index
+                                #inner
+                            }
+                        }
+                    }
+                };
+
+                quote! {
+                    let len = match ::cdump::internal::try_array_len(#len) {
+                        Ok(len) => len,
+                        Err(_) => return usize::MAX,
+                    };
+                    end = ::cdump::internal::align_index::<#alignment_type>(end)
+                        .saturating_add(::std::mem::size_of::<#alignment_type>().saturating_mul(len));
+                    #inner
+                }
+            }
+        },
+    };
+
+    quote! {
+        if !#ident.is_null() {
+            #result
+        }
+    }
+}
+
+fn size_of_array_element(inner: &Field, ident: &TokenStream) -> TokenStream {
+    match &inner.ty {
+        FieldType::Plain => quote! {
+            end = ::cdump::CSerializedSize::serialized_end_without_shallow_copy(&*#ident.add(i), end);
+        },
+        FieldType::Reference => quote! {
+            let element = *#ident.add(i);
+            if !element.is_null() {
+                end = ::cdump::CSerializedSize::serialized_end(&*element, end);
+            }
+        },
+        FieldType::CString => quote! {
+            let element = *#ident.add(i);
+            if !element.is_null() {
+                end = end.saturating_add(::cdump::internal::libc_strlen(element) + 1);
+            }
+        },
+        FieldType::WString => {
+            let size = size_of_wstring(inner, &quote! { element });
+            quote! {
+                let element = *#ident.add(i);
+                if !element.is_null() {
+                    #size
+                }
+            }
+        }
+        FieldType::Dynamic(dynamic) => match dynamic.ptr_level {
+            1 => {
+                unreachable!("dynamic objects under one pointer are sized by size_of_dynamic_array")
+            }
+            2 => {
+                let size = size_of_dynamic(dynamic, &quote! { element });
+                quote! {
+                    let element = *#ident.add(i);
+                    if !element.is_null() {
+                        #size
+                    }
+                }
+            }
+            _ => unimplemented!("three or more level of pointer to dynamic type is unsupported"),
+        },
+        FieldType::Array(..) => {
+            let size = size_of_field_at(inner, quote! { element });
+            quote! {
+                let element = *#ident.add(i);
+                #size
+            }
+        }
+        _ => unimplemented!("2D arrays"),
+    }
+}
+
+/// Get size of the wide string with its terminator, which is aligned to its character.
+fn size_of_wstring(field: &Field, ident: &TokenStream) -> TokenStream {
+    let path = &field.path;
+    quote! {
+        end = ::cdump::internal::align_index::<#path>(end)
+            .saturating_add(::std::mem::size_of::<#path>().saturating_mul(::cdump::wide::wcslen(#ident) + 1));
+    }
+}
+
+/// Get size of the dynamic objects stored one after another under one pointer, which are preceded by space for all of
+/// them.
+fn size_of_dynamic_array(
+    dynamic: &DynamicField,
+    ident: &TokenStream,
+    len: &ArrayLen,
+) -> TokenStream {
+    let size_of = &dynamic.size_of;
+    let size = size_of_dynamic(dynamic, &quote! { element });
+    quote! {
+        let len = match ::cdump::internal::try_array_len(#len) {
+            Ok(len) => len,
+            Err(_) => return usize::MAX,
+        };
+        let array_size = ::cdump::internal::dynamic_array_size(#ident as *const ::std::ffi::c_void, len, #size_of);
+        end = ::cdump::internal::align_index::<usize>(end).saturating_add(array_size);
+
+        let mut offset: usize = 0;
+        for _ in 0..len {
+            let element = #ident.byte_add(offset) as *const ::std::ffi::c_void;
+            offset += #size_of(element);
+            #size
+        }
+    }
+}
+
+/// Add size of the dynamic object under `ptr`, which is unknown without `size` function, so the size is saturated.
+fn size_of_dynamic(dynamic: &DynamicField, ptr: &TokenStream) -> TokenStream {
+    match &dynamic.size {
+        Some(size) => quote! {
+            end = #size(#ptr, end);
+        },
+        None => quote! {
+            return usize::MAX;
+        },
+    }
+}
@@ -0,0 +1,131 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error};
+
+use crate::enumeration::get_repr;
+
+pub fn c_tag_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let variants = match &ast.data {
+        Data::Enum(data) => data.variants.iter().map(|v| &v.ident).collect::<Vec<_>>(),
+        _ => {
+            return Error::new_spanned(&ast.ident, "`CTag` can be derived only for enums")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let name = &ast.ident;
+    let indexes = (0..variants.len() as u32).collect::<Vec<_>>();
+    let registries = variants
+        .iter()
+        .map(|variant| quote! { ::cdump::tagged::Registry::<#name, { #name::#variant as i64 }>::new() })
+        .collect::<Vec<_>>();
+    let repr = match get_repr(&ast) {
+        Ok(repr) => repr,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let dispatch = |method: TokenStream| dispatch(&indexes, &registries, method);
+    let size_of = dispatch(quote! { size_of() });
+    let try_serialize = dispatch(quote! { try_serialize(obj, buf) });
+    let serialized_end = dispatch(quote! { serialized_end(obj, start) });
+    let deserialize = dispatch(quote! { deserialize(buf) });
+    let try_deserialize = dispatch(quote! { try_deserialize(buf) });
+    let try_deep_copy = dispatch(quote! { try_deep_copy(obj, alloc) });
+    let deep_free = dispatch(quote! { deep_free(obj, alloc) });
+    #[cfg(feature = "cdebug")]
+    let debug = {
+        let debug = dispatch(quote! { debug(obj) });
+        quote! {
+            unsafe fn debug(index: u32, obj: *const ::std::ffi::c_void) -> &'static dyn ::std::fmt::Debug {
+                #debug.unwrap_or(&"<unregistered tag>")
+            }
+        }
+    };
+    #[cfg(not(feature = "cdebug"))]
+    let debug = quote! {};
+
+    quote! {
+        unsafe impl ::cdump::tagged::CTag for #name {
+            fn index_of(tag: Self) -> Option<u32> {
+                #[allow(unused_imports)]
+                use ::cdump::tagged::NotRegistered as _;
+                match tag {
+                    #(
+                        #name::#variants => #registries.size_of().map(|_| #indexes),
+                    )*
+                }
+            }
+
+            unsafe fn discriminant(obj: *const ::std::ffi::c_void) -> i64 {
+                *(obj as *const #repr) as i64
+            }
+
+            fn size_of(index: u32) -> Option<usize> {
+                #size_of
+            }
+
+            unsafe fn try_serialize<T: ::cdump::CDumpWriter>(
+                index: u32,
+                obj: *const ::std::ffi::c_void,
+                buf: &mut T,
+            ) -> Result<(), ::cdump::Error> {
+                #try_serialize.unwrap_or(Err(::cdump::Error::UnknownDiscriminant(index as i64)))
+            }
+
+            unsafe fn serialized_end(index: u32, obj: *const ::std::ffi::c_void, start: usize) -> usize {
+                #serialized_end.unwrap_or(usize::MAX)
+            }
+
+            unsafe fn deserialize<T: ::cdump::CDumpReader>(
+                index: u32,
+                buf: &mut T,
+            ) -> Option<*mut ::std::ffi::c_void> {
+                #deserialize
+            }
+
+            unsafe fn try_deserialize<T: ::cdump::CDumpReader>(
+                index: u32,
+                buf: &mut T,
+            ) -> Option<Result<*mut ::std::ffi::c_void, ::cdump::Error>> {
+                #try_deserialize
+            }
+
+            unsafe fn try_deep_copy<A: ::cdump::alloc::CDumpAllocator>(
+                index: u32,
+                obj: *const ::std::ffi::c_void,
+                alloc: &A,
+            ) -> Result<*mut ::std::ffi::c_void, ::cdump::Error> {
+                #try_deep_copy.unwrap_or(Err(::cdump::Error::UnknownDiscriminant(index as i64)))
+            }
+
+            unsafe fn deep_free<A: ::cdump::alloc::CDumpAllocator>(
+                index: u32,
+                obj: *mut ::std::ffi::c_void,
+                alloc: &A,
+            ) {
+                #deep_free;
+            }
+
+            #debug
+        }
+    }
+    .into()
+}
+
+/// Call `method` of the registry of the variant at `index`, which returns `None` for unregistered variants.
+fn dispatch(indexes: &[u32], registries: &[TokenStream], method: TokenStream) -> TokenStream {
+    quote! {
+        {
+            #[allow(unused_imports)]
+            use ::cdump::tagged::NotRegistered as _;
+            match index {
+                #(
+                    #indexes => #registries.#method,
+                )*
+                _ => None,
+            }
+        }
+    }
+}
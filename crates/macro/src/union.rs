@@ -0,0 +1,248 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+use crate::{
+    array_len_functions, deep_copy, deserialize_to,
+    field_analysis::{Field, FieldType},
+    helpers::{split_generics, SplitGenerics},
+    in_field, read_deep_fields_inner, size, write_deep_fields_inner, ReadMode,
+};
+
+pub fn serialize_impl(
+    ast: &DeriveInput,
+    members: &[Field],
+    validate_repr: TokenStream,
+) -> TokenStream {
+    let name = &ast.ident;
+    let serialize = dispatch(members, |_, member| {
+        in_field(name, member, write_deep_fields_inner(member))
+    });
+    let size = dispatch(members, |_, member| size::size_of_field(member));
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(
+        &ast.generics,
+        Some(quote! { __CDumpBuffer: ::cdump::CDumpWriter }),
+        Some(quote! { ::cdump::CSerialize<__CDumpBuffer> }),
+    );
+    let size_generics = split_generics(
+        &ast.generics,
+        None,
+        Some(quote! { ::cdump::CSerializedSize }),
+    );
+    let (size_impl_generics, size_where_clause) =
+        (size_generics.impl_generics, size_generics.where_clause);
+
+    quote! {
+        impl #impl_generics ::cdump::union::CUnionSerialize<__CDumpBuffer> for #name #ty_generics #where_clause {
+            unsafe fn try_serialize_member(
+                &self,
+                discriminator: i64,
+                buf: &mut __CDumpBuffer,
+                start_index: usize,
+            ) -> Result<(), ::cdump::Error> {
+                #validate_repr
+
+                #serialize
+                Ok(())
+            }
+        }
+
+        impl #size_impl_generics ::cdump::union::CUnionSerializedSize for #name #ty_generics #size_where_clause {
+            unsafe fn serialized_end_member(&self, discriminator: i64, end: usize) -> usize {
+                let mut end = end;
+                #size
+                end
+            }
+        }
+    }
+}
+
+pub fn deserialize_impl(
+    ast: &DeriveInput,
+    members: &[Field],
+    validate_repr: TokenStream,
+) -> TokenStream {
+    let name = &ast.ident;
+    let len_functions = array_len_functions(members, name, &ast.generics);
+    let read_ref = dispatch(members, |index, member| {
+        read_deep_fields_inner(member, index, ReadMode::Ref)
+    });
+    let read_try_ref = dispatch(members, |index, member| {
+        in_field(
+            name,
+            member,
+            read_deep_fields_inner(member, index, ReadMode::TryRef),
+        )
+    });
+    let read_to = dispatch(members, |index, member| {
+        let capture = deserialize_to::capture_capacity(member, index);
+        let read = deserialize_to::read_deep_field_to(member, index);
+        in_field(
+            name,
+            member,
+            quote! {
+                #capture
+                #read
+            },
+        )
+    });
+    let null = dispatch(members, |_, member| {
+        let ident = &member.ident;
+        quote! {
+            (*obj).#ident = ::std::mem::zeroed();
+        }
+    });
+    let copy = dispatch(members, |index, member| {
+        in_field(name, member, deep_copy::copy_field(member, index))
+    });
+    let free = dispatch(members, |index, member| {
+        deep_copy::free_field(member, index)
+    });
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(
+        &ast.generics,
+        Some(quote! { __CDumpBuffer: ::cdump::CDumpReader }),
+        Some(quote! { ::cdump::CDeserialize<__CDumpBuffer> }),
+    );
+    let copy_generics = split_generics(&ast.generics, None, Some(quote! { ::cdump::CDeepCopy }));
+    let (copy_impl_generics, copy_where_clause) =
+        (copy_generics.impl_generics, copy_generics.where_clause);
+
+    quote! {
+        #len_functions
+
+        impl #impl_generics ::cdump::union::CUnionDeserialize<__CDumpBuffer> for #name #ty_generics #where_clause {
+            unsafe fn deserialize_ref_mut_member(buf: &mut __CDumpBuffer, dst: *mut Self, discriminator: i64) {
+                #validate_repr
+
+                #read_ref
+            }
+
+            unsafe fn try_deserialize_ref_mut_member(
+                buf: &mut __CDumpBuffer,
+                dst: *mut Self,
+                discriminator: i64,
+            ) -> Result<(), ::cdump::Error> {
+                #read_try_ref
+                Ok(())
+            }
+
+            unsafe fn deserialize_to_member<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                buf: &mut __CDumpBuffer,
+                temp: *mut Self,
+                dst: *mut Self,
+                policy: &::cdump::alloc::DestinationPolicy<__CDumpAllocator>,
+                discriminator: i64,
+            ) -> Result<(), ::cdump::Error> {
+                #read_to
+                Ok(())
+            }
+        }
+
+        unsafe impl #copy_impl_generics ::cdump::union::CUnionDeepCopy for #name #ty_generics #copy_where_clause {
+            unsafe fn null_member(obj: *mut Self, discriminator: i64) {
+                #null
+            }
+
+            unsafe fn try_deep_copy_member<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                src: *const Self,
+                dst: *mut Self,
+                alloc: &__CDumpAllocator,
+                discriminator: i64,
+            ) -> Result<(), ::cdump::Error> {
+                #copy
+                Ok(())
+            }
+
+            unsafe fn deep_free_member<__CDumpAllocator: ::cdump::alloc::CDumpAllocator>(
+                obj: *mut Self,
+                alloc: &__CDumpAllocator,
+                discriminator: i64,
+            ) {
+                #free
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cdebug")]
+pub fn debug_impl(ast: &DeriveInput, members: &[Field]) -> TokenStream {
+    let name = &ast.ident;
+    let name_str = name.to_string();
+    let fmt = |member: &Field| {
+        let field = crate::cdebug::write_fmt(std::slice::from_ref(member));
+        quote! {
+            debug #field;
+        }
+    };
+    let active = members
+        .iter()
+        .filter(|member| member.case.is_some())
+        .map(|member| {
+            let case = &member.case;
+            let field = fmt(member);
+            quote! {
+                if discriminator == (#case) as i64 {
+                    #field
+                } else
+            }
+        });
+    let shallow = members
+        .iter()
+        .filter(|member| member.case.is_none())
+        .map(fmt);
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(&ast.generics, None, Some(quote! { ::std::fmt::Debug }));
+
+    quote! {
+        impl #impl_generics ::cdump::union::CUnionDebug for #name #ty_generics #where_clause {
+            #[allow(unused_unsafe)]
+            unsafe fn fmt_member(
+                &self,
+                discriminator: i64,
+                f: &mut ::std::fmt::Formatter<'_>,
+            ) -> ::std::fmt::Result {
+                let mut debug = f.debug_struct(#name_str);
+                #(#active)* {
+                    #(#shallow)*
+                }
+                debug.finish()
+            }
+        }
+
+        impl #impl_generics ::std::fmt::Debug for #name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.debug_struct(#name_str).finish_non_exhaustive()
+            }
+        }
+    }
+}
+
+/// Match `discriminator` to the members with pointers, where `body` gives code of the selected member. Other members
+/// are shallow, so they are never matched.
+fn dispatch(members: &[Field], body: impl Fn(usize, &Field) -> TokenStream) -> TokenStream {
+    members
+        .iter()
+        .enumerate()
+        .filter(|(_, member)| !matches!(member.ty, FieldType::Plain | FieldType::InlineArray(_)))
+        .map(|(index, member)| {
+            let case = &member.case;
+            let body = body(index, member);
+            quote! {
+                if discriminator == (#case) as i64 {
+                    #body
+                }
+            }
+        })
+        .collect()
+}
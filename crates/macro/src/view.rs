@@ -0,0 +1,317 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_macro_input, parse_quote, spanned::Spanned, DeriveInput, Error, Ident};
+
+use crate::{
+    field_analysis::{self, Field, FieldType},
+    helpers::{is_primitive_type, split_generics, validate_repr, ErrorExt, SplitGenerics},
+};
+
+pub fn c_view_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let validate_repr = validate_repr(&ast.attrs, "C", ast.span()).to_compile_error();
+    let name = ast.ident.clone();
+    let name_str = name.to_string();
+    let view_name = format_ident!("{}View", name);
+    let vis = &ast.vis;
+
+    if field_analysis::is_union(&ast) {
+        return Error::new(ast.ident.span(), "unions are not supported by `CView`")
+            .to_compile_error()
+            .into();
+    }
+
+    if field_analysis::is_tuple_struct(&ast) {
+        return Error::new(
+            ast.ident.span(),
+            "tuple structs are not supported by `CView`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let fields = match field_analysis::get_fields(&ast, true) {
+        Ok(fields) => fields,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let mut view_fields = Vec::new();
+    let mut accessors = Vec::new();
+    let mut reads = Vec::new();
+    let mut len_functions = Vec::new();
+
+    for (index, field) in fields.iter().enumerate() {
+        let ident = &field.ident;
+        let ident_str = ident.to_token_stream().to_string();
+
+        let view = match view_of_field(field, index) {
+            Ok(view) => view,
+            Err(err) => return err.to_compile_error().into(),
+        };
+        let FieldView { ty, read, by_ref } = view;
+
+        if let FieldType::Array(len, _) = &field.ty {
+            let len_function = len_function_ident(index);
+            len_functions.push(quote! {
+                #[inline]
+                #[doc(hidden)]
+                fn #len_function(&self) -> Result<usize, ::cdump::Error> {
+                    ::cdump::internal::try_array_len(#len)
+                }
+            });
+        }
+
+        view_fields.push(quote! {
+            #ident: Option<#ty>,
+        });
+        accessors.push(match by_ref {
+            true => quote! {
+                pub fn #ident(&self) -> Option<&#ty> {
+                    self.#ident.as_ref()
+                }
+            },
+            false => quote! {
+                pub fn #ident(&self) -> Option<#ty> {
+                    self.#ident
+                }
+            },
+        });
+        reads.push(quote! {
+            let #ident: Option<#ty> = ::cdump::internal::in_field(#name_str, #ident_str, || {
+                if shallow.#ident.is_null() {
+                    return Ok(None);
+                }
+                #read
+            })?;
+        });
+    }
+
+    let idents = fields.iter().map(|field| &field.ident);
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(&ast.generics, None, None);
+    let len_functions = match len_functions.is_empty() {
+        true => quote! {},
+        false => quote! {
+            impl #impl_generics #name #ty_generics #where_clause {
+                #(#len_functions)*
+            }
+        },
+    };
+    let doc = format!("Read-only view of the serialized [`{}`].", name);
+
+    // View borrows the buffer for `'buf`, which precedes generics of the struct.
+    let mut view_generics = ast.generics.clone();
+    view_generics.params.insert(0, parse_quote!('buf));
+    let SplitGenerics {
+        impl_generics: view_impl_generics,
+        ty_generics: view_ty_generics,
+        where_clause: view_where_clause,
+    } = split_generics(&view_generics, None, Some(quote! { ::cdump::CView }));
+    let SplitGenerics {
+        impl_generics,
+        ty_generics,
+        where_clause,
+    } = split_generics(&ast.generics, None, Some(quote! { ::cdump::CView }));
+
+    proc_macro::TokenStream::from(quote! {
+        #len_functions
+
+        #[doc = #doc]
+        #vis struct #view_name #view_impl_generics #view_where_clause {
+            shallow: ::cdump::view::Shallow<'buf, #name #ty_generics>,
+            #(#view_fields)*
+        }
+
+        impl #view_impl_generics #view_name #view_ty_generics #view_where_clause {
+            #(#accessors)*
+        }
+
+        impl #view_impl_generics ::std::ops::Deref for #view_name #view_ty_generics #view_where_clause {
+            type Target = #name #ty_generics;
+
+            fn deref(&self) -> &Self::Target {
+                self.shallow.get()
+            }
+        }
+
+        impl #impl_generics ::cdump::CView for #name #ty_generics #where_clause {
+            type View<'buf> = #view_name #view_ty_generics;
+
+            unsafe fn try_view_without_shallow_copy<'buf>(
+                data: &'buf [u8],
+                shallow: usize,
+                index: usize,
+            ) -> Result<(#view_name #view_ty_generics, usize), ::cdump::Error> {
+                #validate_repr
+
+                let shallow_copy = ::cdump::view::Shallow::<'buf, Self>::new(data, shallow);
+                let shallow = shallow_copy.get();
+                let mut index = index;
+                #(#reads)*
+
+                Ok((
+                    #view_name {
+                        shallow: shallow_copy,
+                        #(#idents,)*
+                    },
+                    index,
+                ))
+            }
+        }
+    })
+}
+
+struct FieldView {
+    /// Type of the view of not null field.
+    ty: TokenStream,
+    /// Code which reads the view of not null field, and moves the `index` to the end of its data.
+    read: TokenStream,
+    /// Whether the accessor returns the view by reference.
+    by_ref: bool,
+}
+
+fn len_function_ident(field_index: usize) -> Ident {
+    Ident::new(
+        &format!(
+            "do_not_use_cdump_internal_function_view_len_of_array_at_index_{}",
+            field_index
+        ),
+        Span::call_site(),
+    )
+}
+
+fn view_of_field(field: &Field, field_index: usize) -> Result<FieldView, Error> {
+    let ident = &field.ident;
+    let path = field.path.to_token_stream();
+
+    Ok(match &field.ty {
+        FieldType::Plain | FieldType::InlineArray(_) => {
+            unreachable!("shallow fields should not be under first level pointer")
+        }
+        FieldType::Union(_) => {
+            return Err(Error::new(
+                ident.span(),
+                "union fields are not supported by `CView`",
+            ))
+        }
+        FieldType::Nested => {
+            return Err(Error::new(
+                ident.span(),
+                "nested fields are not supported by `CView`",
+            ))
+        }
+        FieldType::DeepInlineArray(..) => {
+            return Err(Error::new(
+                ident.span(),
+                "inline arrays with pointers are not supported by `CView`",
+            ))
+        }
+        FieldType::WString => {
+            return Err(Error::new(
+                ident.span(),
+                "wide strings are not supported by `CView`",
+            ))
+        }
+        FieldType::Bytes(_) => {
+            return Err(Error::new(
+                ident.span(),
+                "bytes are not supported by `CView`",
+            ))
+        }
+        FieldType::Reference => FieldView {
+            ty: quote! { <#path as ::cdump::CView>::View<'buf> },
+            read: quote! {
+                let (view, end) = <#path as ::cdump::CView>::try_view_at(data, index)?;
+                index = end;
+                Ok(Some(view))
+            },
+            by_ref: true,
+        },
+        FieldType::CString => FieldView {
+            ty: quote! { &'buf ::std::ffi::CStr },
+            read: quote! {
+                let (view, end) = ::cdump::view::try_view_cstring(data, index, shallow.#ident as usize)?;
+                index = end;
+                Ok(Some(view))
+            },
+            by_ref: false,
+        },
+        FieldType::Dynamic(dynamic) => match &dynamic.skip {
+            Some(skip) => FieldView {
+                ty: quote! { ::cdump::view::CDynamicView<'buf> },
+                read: quote! {
+                    let start = index;
+                    index = #skip(data, index)?;
+                    Ok(Some(::cdump::view::CDynamicView::new(data, start)))
+                },
+                by_ref: false,
+            },
+            None => {
+                return Err(Error::new(
+                    ident.span(),
+                    "dynamic field requires provide a skip function to derive `CView`",
+                ))
+            }
+        },
+        FieldType::Array(len, _) if len.terminator.is_some() => {
+            return Err(Error::new(
+                ident.span(),
+                "terminated arrays are not supported by `CView`",
+            ))
+        }
+        FieldType::Array(_, inner) => {
+            let len_function = len_function_ident(field_index);
+            let inner_path = inner.path.to_token_stream();
+
+            let element = match &inner.ty {
+                FieldType::Plain if is_primitive_type(&inner_path) => {
+                    return Ok(FieldView {
+                        ty: quote! { &'buf [#inner_path] },
+                        read: quote! {
+                            let len = shallow.#len_function()?;
+                            let (view, end) = ::cdump::view::try_view_slice(data, index, len)?;
+                            index = end;
+                            Ok(Some(view))
+                        },
+                        by_ref: false,
+                    })
+                }
+                FieldType::Plain => quote! { ::cdump::view::ByValue<#inner_path> },
+                FieldType::Reference => quote! { ::cdump::view::ByPointer<#inner_path> },
+                FieldType::CString => quote! { ::cdump::view::CStringPointer },
+                FieldType::Array(..) => {
+                    return Err(Error::new(
+                        ident.span(),
+                        "arrays of arrays are not supported by `CView`",
+                    ))
+                }
+                FieldType::WString => {
+                    return Err(Error::new(
+                        ident.span(),
+                        "wide strings are not supported by `CView`",
+                    ))
+                }
+                _ => {
+                    return Err(Error::new(
+                        ident.span(),
+                        "array of dynamic fields is not supported by `CView`",
+                    ))
+                }
+            };
+
+            FieldView {
+                ty: quote! { ::cdump::view::CViewArray<'buf, #element> },
+                read: quote! {
+                    let len = shallow.#len_function()?;
+                    let (view, end) = ::cdump::view::CViewArray::try_new(data, index, len)?;
+                    index = end;
+                    Ok(Some(view))
+                },
+                by_ref: false,
+            }
+        }
+    })
+}
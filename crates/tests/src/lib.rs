@@ -1,6 +1,9 @@
-use std::{ffi::c_void, fmt::Debug, ptr};
+use std::{alloc::Layout, cell::Cell, ffi::c_void, fmt::Debug, ptr};
 
-use cdump::{CDumpReader, CDumpWriter};
+use cdump::{
+    alloc::{CDumpAllocator, GlobalAllocator},
+    CDumpBufferReader, CDumpBufferWriter, CDumpReader, CDumpWriter, CSerialize, Error,
+};
 
 pub fn empty_serializer<T: CDumpWriter>(_buf: &mut T, _obj: *const c_void) {}
 
@@ -18,3 +21,93 @@ where
 {
     println!("{:?}", obj);
 }
+
+/// Serialize the object, and returns copy of the serialized bytes.
+pub fn serialize_to_bytes<T>(obj: &T) -> Vec<u8>
+where
+    T: CSerialize<CDumpBufferWriter>,
+{
+    let mut buf = CDumpBufferWriter::new(16);
+    unsafe {
+        obj.serialize(&mut buf);
+        std::slice::from_raw_parts(buf.as_mut_ptr_at(0), buf.len()).to_vec()
+    }
+}
+
+/// Create a reader with a copy of the bytes.
+pub fn reader_from_bytes(bytes: &[u8]) -> CDumpBufferReader {
+    let mut buf = CDumpBufferWriter::new(16);
+    buf.push_slice(bytes);
+    buf.into_reader()
+}
+
+/// Allocator which counts allocations, fails after `limit` of them, and checks that every allocation is freed.
+#[derive(Default)]
+pub struct CountingAllocator {
+    live: Cell<isize>,
+    total: Cell<usize>,
+    limit: Option<usize>,
+}
+
+impl CountingAllocator {
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            live: Cell::new(0),
+            total: Cell::new(0),
+            limit: Some(limit),
+        }
+    }
+
+    /// Get the number of allocations which are not freed.
+    pub fn live(&self) -> isize {
+        self.live.get()
+    }
+
+    /// Get the number of all allocations.
+    pub fn total(&self) -> usize {
+        self.total.get()
+    }
+}
+
+unsafe impl CDumpAllocator for CountingAllocator {
+    fn allocate(&self, layout: Layout) -> Result<*mut u8, Error> {
+        if Some(self.total.get()) == self.limit {
+            return Err(Error::AllocationFailed {
+                size: layout.size(),
+                align: layout.align(),
+            });
+        }
+
+        self.live.set(self.live.get() + 1);
+        self.total.set(self.total.get() + 1);
+        GlobalAllocator.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        self.live.set(self.live.get() - 1);
+        GlobalAllocator.deallocate(ptr, layout)
+    }
+}
+
+impl Drop for CountingAllocator {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            assert_eq!(0, self.live.get(), "allocations leaked");
+        }
+    }
+}
+
+/// Fail the `copy` at each of its first `allocations`, and check that it frees what it allocated before.
+pub fn assert_frees_partial_copy_on_error(
+    allocations: usize,
+    copy: impl Fn(&CountingAllocator) -> Result<(), Error>,
+) {
+    for limit in 0..allocations {
+        let alloc = CountingAllocator::with_limit(limit);
+        let err = copy(&alloc).unwrap_err();
+        assert!(
+            matches!(err.root_cause(), Error::AllocationFailed { .. }),
+            "{err:?}"
+        );
+    }
+}
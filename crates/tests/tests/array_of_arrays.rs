@@ -0,0 +1,360 @@
+use std::{
+    ffi::{c_char, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize, Error,
+};
+use tests::{
+    assert_frees_partial_copy_on_error, eval_debug, reader_from_bytes, serialize_to_bytes,
+    CountingAllocator,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Extent {
+    width: u32,
+    height: u32,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Queue {
+    priority: f32,
+    label: *const c_char,
+}
+
+/// Arrays with one inner array per queue family, which lengths are in `queue_counts`.
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct QueueFamilies {
+    family_count: u32,
+    #[cdump(array(len = self.family_count))]
+    queue_counts: *const u32,
+    #[cdump(array(len = self.family_count, index = family, array(len = *self.queue_counts.add(family))))]
+    priorities: *const *const f32,
+    #[cdump(array(len = self.family_count, index = family, array(len = *self.queue_counts.add(family))))]
+    queues: *const *const Queue,
+    #[cdump(array(len = self.family_count, index = family, array(len = *self.queue_counts.add(family))))]
+    names: *const *const *const c_char,
+    #[cdump(array(len = self.family_count, index = family, array(len = *self.queue_counts.add(family))))]
+    extents: *const *const *const Extent,
+    #[cdump(array(len = 2, index = x, array(len = x + 1, index = y, array(len = x + y + 1))))]
+    cube: *const *const *const u8,
+}
+
+fn with_families<T>(f: impl FnOnce(&QueueFamilies) -> T) -> T {
+    let queue_counts = [2u32, 1, 0];
+
+    let graphics = [1.0f32, 0.5];
+    let compute = [0.25f32];
+    let priorities = [graphics.as_ptr(), compute.as_ptr(), ptr::null()];
+
+    let graphics = [
+        Queue {
+            priority: 1.0,
+            label: c"main".as_ptr(),
+        },
+        Queue {
+            priority: 0.5,
+            label: ptr::null(),
+        },
+    ];
+    let compute = [Queue {
+        priority: 0.25,
+        label: c"async".as_ptr(),
+    }];
+    let queues = [graphics.as_ptr(), compute.as_ptr(), ptr::null()];
+
+    let graphics = [c"present".as_ptr(), c"transfer".as_ptr()];
+    let compute = [c"compute".as_ptr()];
+    let names = [graphics.as_ptr(), compute.as_ptr(), ptr::null()];
+
+    let small = Extent {
+        width: 4,
+        height: 8,
+    };
+    let large = Extent {
+        width: 640,
+        height: 480,
+    };
+    let graphics = [&large as *const Extent, &small];
+    let compute = [&small as *const Extent];
+    let extents = [graphics.as_ptr(), compute.as_ptr(), ptr::null()];
+
+    let plane_0 = [[1u8].as_ptr()];
+    let row_1_0 = [2u8, 3];
+    let row_1_1 = [4u8, 5, 6];
+    let plane_1 = [row_1_0.as_ptr(), row_1_1.as_ptr()];
+    let cube = [plane_0.as_ptr(), plane_1.as_ptr()];
+
+    f(&QueueFamilies {
+        family_count: queue_counts.len() as u32,
+        queue_counts: queue_counts.as_ptr(),
+        priorities: priorities.as_ptr(),
+        queues: queues.as_ptr(),
+        names: names.as_ptr(),
+        extents: extents.as_ptr(),
+        cube: cube.as_ptr(),
+    })
+}
+
+unsafe fn slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    std::slice::from_raw_parts(ptr, len)
+}
+
+unsafe fn assert_families(copy: &QueueFamilies) {
+    assert_eq!([2, 1, 0], slice(copy.queue_counts, 3));
+
+    let priorities = slice(copy.priorities, 3);
+    assert_eq!([1.0, 0.5], slice(priorities[0], 2));
+    assert_eq!([0.25], slice(priorities[1], 1));
+    assert!(priorities[2].is_null());
+
+    let queues = slice(copy.queues, 3);
+    let graphics = slice(queues[0], 2);
+    assert_eq!(1.0, graphics[0].priority);
+    assert_eq!(c"main", CStr::from_ptr(graphics[0].label));
+    assert!(graphics[1].label.is_null());
+    assert_eq!(c"async", CStr::from_ptr((*queues[1]).label));
+    assert!(queues[2].is_null());
+
+    let names = slice(copy.names, 3);
+    assert_eq!(c"present", CStr::from_ptr(*names[0]));
+    assert_eq!(c"transfer", CStr::from_ptr(*names[0].add(1)));
+    assert_eq!(c"compute", CStr::from_ptr(*names[1]));
+    assert!(names[2].is_null());
+
+    let extents = slice(copy.extents, 3);
+    let graphics = slice(extents[0], 2);
+    assert_eq!(
+        Extent {
+            width: 640,
+            height: 480
+        },
+        *graphics[0]
+    );
+    assert_eq!(
+        Extent {
+            width: 4,
+            height: 8
+        },
+        *graphics[1]
+    );
+    assert_eq!(4, (**extents[1]).width);
+    assert!(extents[2].is_null());
+
+    let cube = slice(copy.cube, 2);
+    assert_eq!([1], slice(*cube[0], 1));
+    let plane = slice(cube[1], 2);
+    assert_eq!([2, 3], slice(plane[0], 2));
+    assert_eq!([4, 5, 6], slice(plane[1], 3));
+}
+
+#[test]
+fn array_of_arrays() {
+    let mut reader = with_families(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { QueueFamilies::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_families(copy) };
+}
+
+#[test]
+fn array_of_arrays_checked() {
+    let mut reader = reader_from_bytes(&with_families(serialize_to_bytes));
+    unsafe { assert_families(QueueFamilies::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn array_of_arrays_checked_rejects_truncated_buffer() {
+    let bytes = with_families(serialize_to_bytes);
+    let mut reader = reader_from_bytes(&bytes[..bytes.len() - 1]);
+    assert!(unsafe { QueueFamilies::try_deserialize_ref(&mut reader) }.is_err());
+}
+
+#[test]
+fn array_of_arrays_serialized_size() {
+    with_families(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn array_of_arrays_owned() {
+    let owned = with_families(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_families(&owned) };
+}
+
+#[test]
+fn array_of_arrays_deserialize_to() {
+    let mut reader = reader_from_bytes(&with_families(serialize_to_bytes));
+    let mut copy = unsafe { mem::zeroed::<QueueFamilies>() };
+    unsafe {
+        QueueFamilies::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate())
+            .unwrap();
+        assert_families(&copy);
+    }
+}
+
+#[test]
+fn array_of_arrays_debug() {
+    with_families(|obj| {
+        let debug = format!("{obj:?}");
+        for expected in [
+            "priorities: Some([Some([1.0, 0.5]), Some([0.25]), None])",
+            "names: Some([Some([Some(\"present\"), Some(\"transfer\")]), Some([Some(\"compute\")]), None])",
+            "extents: Some([Some([Some(Extent { width: 640, height: 480 }), Some(Extent { width: 4, height: 8 })]), Some([Some(Extent { width: 4, height: 8 })]), None])",
+            "cube: Some([Some([Some([1])]), Some([Some([2, 3]), Some([4, 5, 6])])])",
+        ] {
+            assert!(debug.contains(expected), "{debug}");
+        }
+    });
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Matrix {
+    rows: u32,
+    #[cdump(array(len = self.rows))]
+    columns: *const u32,
+    #[cdump(array(len = self.rows, index = row, array(len = *self.columns.add(row))))]
+    values: *const *const f64,
+}
+
+#[test]
+fn array_of_arrays_deserialize_to_reuses_destination() {
+    let columns = [3u32, 1];
+    let first = [1.0, 2.0, 3.0];
+    let second = [4.0];
+    let values = [first.as_ptr(), second.as_ptr()];
+    let obj = Matrix {
+        rows: columns.len() as u32,
+        columns: columns.as_ptr(),
+        values: values.as_ptr(),
+    };
+    let bytes = serialize_to_bytes(&obj);
+
+    let mut copy = unsafe { mem::zeroed::<Matrix>() };
+    let err = unsafe {
+        Matrix::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::reject(),
+        )
+    }
+    .unwrap_err();
+    assert!(matches!(
+        err.root_cause(),
+        Error::DestinationTooSmall { .. }
+    ));
+
+    unsafe {
+        Matrix::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        let values = copy.values;
+
+        // Arrays of the previous copy are large enough, so nothing is allocated.
+        Matrix::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::reject(),
+        )
+        .unwrap();
+        assert_eq!(values, copy.values);
+        assert_eq!([1.0, 2.0, 3.0], slice(*copy.values, 3));
+        assert_eq!([4.0], slice(*copy.values.add(1), 1));
+    }
+}
+
+#[test]
+fn array_of_arrays_deserialize_to_rejects_longer_inner_arrays() {
+    let narrow = [vec![1.0], vec![2.0]];
+    let wide = [vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+    let serialize = |rows: &[Vec<f64>]| {
+        let columns = rows.iter().map(|row| row.len() as u32).collect::<Vec<_>>();
+        let values = rows.iter().map(|row| row.as_ptr()).collect::<Vec<_>>();
+        serialize_to_bytes(&Matrix {
+            rows: rows.len() as u32,
+            columns: columns.as_ptr(),
+            values: values.as_ptr(),
+        })
+    };
+
+    let mut copy = unsafe { mem::zeroed::<Matrix>() };
+    unsafe {
+        Matrix::deserialize_to(
+            &mut reader_from_bytes(&serialize(&narrow)),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+    }
+
+    // Capacities of inner arrays are in `columns` of the destination, which are overwritten before `values`.
+    let err = unsafe {
+        Matrix::deserialize_to(
+            &mut reader_from_bytes(&serialize(&wide)),
+            &mut copy,
+            &DestinationPolicy::reject(),
+        )
+    }
+    .unwrap_err();
+    assert!(
+        matches!(
+            err.root_cause(),
+            Error::DestinationTooSmall {
+                len: 3,
+                capacity: 1
+            }
+        ),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn array_of_arrays_layout_hash_depends_on_depth() {
+    #[derive(CSerialize)]
+    #[repr(C)]
+    struct Flat {
+        len: u32,
+        #[cdump(array(len = self.len))]
+        values: *const *const Extent,
+    }
+
+    #[derive(CSerialize)]
+    #[repr(C)]
+    struct Nested {
+        len: u32,
+        #[cdump(array(len = self.len, array(len = 1)))]
+        values: *const *const Extent,
+    }
+
+    assert_ne!(
+        <Flat as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH,
+        <Nested as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH
+    );
+}
+
+#[test]
+fn array_of_arrays_owned_frees_partial_copy_on_error() {
+    with_families(|obj| unsafe {
+        let alloc = CountingAllocator::default();
+        drop(COwned::try_new_in(obj, &alloc).unwrap());
+        assert_frees_partial_copy_on_error(alloc.total(), |alloc| {
+            COwned::try_new_in(obj, alloc).map(drop)
+        });
+    });
+}
@@ -0,0 +1,176 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+/// Shader code, which is read as words, preceded by a byte string, so it needs alignment.
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct ShaderModule {
+    name: *const c_char,
+    code_size: usize,
+    #[cdump(bytes(len = self.code_size, align = 4))]
+    code: *const c_void,
+    initial_data_size: u32,
+    #[cdump(bytes(len = self.initial_data_size))]
+    initial_data: *const u8,
+}
+
+const CODE: [u32; 5] = [0x07230203, 0x00010000, 0x0008000b, 0x00000006, 0x00000000];
+const INITIAL_DATA: [u8; 3] = [0xca, 0xfe, 0x01];
+
+fn with_module<T>(f: impl FnOnce(&ShaderModule) -> T) -> T {
+    f(&ShaderModule {
+        name: c"main".as_ptr(),
+        code_size: mem::size_of_val(&CODE),
+        code: CODE.as_ptr() as *const c_void,
+        initial_data_size: INITIAL_DATA.len() as u32,
+        initial_data: INITIAL_DATA.as_ptr(),
+    })
+}
+
+unsafe fn assert_module(obj: &ShaderModule, copy: &ShaderModule) {
+    assert_eq!(c"main", CStr::from_ptr(copy.name));
+    assert_ne!(obj.code, copy.code);
+    assert_eq!(0, copy.code as usize % mem::align_of::<u32>());
+    assert_eq!(
+        CODE,
+        std::slice::from_raw_parts(copy.code as *const u32, CODE.len())
+    );
+    assert_eq!(
+        INITIAL_DATA,
+        std::slice::from_raw_parts(copy.initial_data, INITIAL_DATA.len())
+    );
+}
+
+#[test]
+fn bytes() {
+    with_module(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+
+        let mut reader = buf.into_reader();
+        let copy = unsafe { ShaderModule::deserialize_ref(&mut reader) };
+        eval_debug(copy);
+        unsafe { assert_module(obj, copy) };
+    });
+}
+
+#[test]
+fn bytes_checked() {
+    with_module(|obj| {
+        let mut reader = reader_from_bytes(&serialize_to_bytes(obj));
+        let copy = unsafe { ShaderModule::try_deserialize_ref(&mut reader) }.unwrap();
+        unsafe { assert_module(obj, copy) };
+    });
+}
+
+#[test]
+fn bytes_checked_rejects_truncated_buffer() {
+    let bytes = with_module(serialize_to_bytes);
+    let mut reader = reader_from_bytes(&bytes[..bytes.len() - 1]);
+    assert!(unsafe { ShaderModule::try_deserialize_ref(&mut reader) }.is_err());
+}
+
+#[test]
+fn bytes_serialized_size() {
+    with_module(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn bytes_deserialize_to() {
+    with_module(|obj| unsafe {
+        let bytes = serialize_to_bytes(obj);
+        let mut copy = mem::zeroed::<ShaderModule>();
+        ShaderModule::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        assert_module(obj, &copy);
+
+        // Bytes of the previous copy are large enough, so they are reused instead of allocated.
+        let code = copy.code;
+        ShaderModule::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        assert_eq!(code, copy.code);
+        assert_module(obj, &copy);
+    });
+}
+
+#[test]
+fn bytes_owned() {
+    with_module(|obj| {
+        let owned = unsafe { COwned::try_new(obj) }.unwrap();
+        unsafe { assert_module(obj, &owned) };
+    });
+}
+
+#[test]
+fn bytes_null() {
+    let obj = ShaderModule {
+        name: ptr::null(),
+        code_size: 0,
+        code: ptr::null(),
+        initial_data_size: 16,
+        initial_data: ptr::null(),
+    };
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { ShaderModule::try_deserialize_ref(&mut reader) }.unwrap();
+    assert!(copy.code.is_null());
+    assert!(copy.initial_data.is_null());
+    assert!(format!("{copy:?}").contains("initial_data: None"));
+}
+
+#[test]
+fn bytes_debug() {
+    with_module(|obj| {
+        let debug = format!("{obj:?}");
+        for expected in [
+            "code: Some(<20 bytes: 03 02 23 07 00 00 01 00 0b 00 08 00 06 00 00 00 ...>)",
+            "initial_data: Some(<3 bytes: ca fe 01>)",
+        ] {
+            assert!(debug.contains(expected), "{debug}");
+        }
+    });
+}
+
+#[test]
+fn bytes_layout_hash_depends_on_align() {
+    #[derive(CSerialize)]
+    #[repr(C)]
+    struct Unaligned {
+        len: usize,
+        #[cdump(bytes(len = self.len))]
+        data: *const c_void,
+    }
+
+    #[derive(CSerialize)]
+    #[repr(C)]
+    struct Aligned {
+        len: usize,
+        #[cdump(bytes(len = self.len, align = 8))]
+        data: *const c_void,
+    }
+
+    assert_ne!(
+        <Unaligned as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH,
+        <Aligned as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH
+    );
+}
@@ -0,0 +1,268 @@
+use std::{
+    ffi::{c_char, CStr},
+    ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferReader, CDumpBufferWriter,
+    CDumpSharedReader, CDumpSharedWriter, COwned, CSerialize, CSerializedSize, Error,
+};
+use tests::{serialize_to_bytes, CountingAllocator};
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Node {
+    value: u32,
+    #[cdump(iterative)]
+    next: *const Node,
+    prev: *const Node,
+    name: *const c_char,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Parent {
+    value: u32,
+    child: *const Child,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Child {
+    parent: *const Parent,
+    name: *const c_char,
+}
+
+fn serialize_shared<T: CSerialize<CDumpSharedWriter<CDumpBufferWriter>>>(
+    obj: &T,
+) -> CDumpSharedReader<CDumpBufferReader> {
+    let mut buf = CDumpSharedWriter::new(CDumpBufferWriter::new(16));
+    unsafe { obj.serialize(&mut buf) };
+    CDumpSharedReader::new(buf.into_inner().into_reader())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Links {
+    Next,
+    NextAndPrev,
+    Circular,
+}
+
+/// Create the list with `len` nodes.
+fn with_list<T>(len: usize, links: Links, f: impl FnOnce(&Node) -> T) -> T {
+    let mut nodes = (0..len)
+        .map(|i| Node {
+            value: i as u32,
+            next: ptr::null(),
+            prev: ptr::null(),
+            name: c"node".as_ptr(),
+        })
+        .collect::<Vec<_>>();
+
+    let base = nodes.as_mut_ptr();
+    for i in 0..len {
+        unsafe {
+            if i + 1 < len {
+                (*base.add(i)).next = base.add(i + 1);
+            }
+            if i > 0 && links != Links::Next {
+                (*base.add(i)).prev = base.add(i - 1);
+            }
+        }
+    }
+    if links == Links::Circular {
+        unsafe {
+            (*base.add(len - 1)).next = base;
+            (*base).prev = base.add(len - 1);
+        }
+    }
+
+    f(&nodes[0])
+}
+
+unsafe fn assert_list(root: *const Node, len: usize, circular: bool) {
+    let mut node = root;
+    for i in 0..len {
+        assert_eq!(i as u32, (*node).value);
+        assert_eq!(c"node", CStr::from_ptr((*node).name));
+        if i > 0 {
+            assert_eq!(node, (*(*node).prev).next);
+        }
+        node = (*node).next;
+    }
+
+    match circular {
+        true => {
+            assert_eq!(root, node);
+            assert_eq!(root, (*(*root).prev).next);
+        }
+        false => {
+            assert!(node.is_null());
+            assert!((*root).prev.is_null());
+        }
+    }
+}
+
+#[test]
+fn circular_list() {
+    let mut reader = with_list(3, Links::Circular, serialize_shared);
+    unsafe { assert_list(Node::deserialize_ref(&mut reader), 3, true) };
+}
+
+#[test]
+fn circular_list_checked() {
+    let mut reader = with_list(3, Links::Circular, serialize_shared);
+    unsafe { assert_list(Node::try_deserialize_ref(&mut reader).unwrap(), 3, true) };
+}
+
+#[test]
+fn self_reference() {
+    let mut reader = with_list(1, Links::Circular, serialize_shared);
+    unsafe {
+        let node = Node::try_deserialize_ref(&mut reader).unwrap();
+        assert_eq!(node as *const Node, node.next);
+        assert_eq!(node as *const Node, node.prev);
+    }
+}
+
+#[test]
+fn back_edge_to_root() {
+    let mut parent = Parent {
+        value: 42,
+        child: ptr::null(),
+    };
+    let child = Child {
+        parent: &parent,
+        name: c"child".as_ptr(),
+    };
+    parent.child = &child;
+
+    let mut reader = serialize_shared(&parent);
+    unsafe {
+        let copy = Parent::try_deserialize_ref(&mut reader).unwrap();
+        assert_eq!(42, copy.value);
+        assert_eq!(copy as *const Parent, (*copy.child).parent);
+        assert_eq!(c"child", CStr::from_ptr((*copy.child).name));
+    }
+}
+
+#[test]
+fn long_list() {
+    const LEN: usize = 100_000;
+
+    let mut reader = with_list(LEN, Links::NextAndPrev, serialize_shared);
+    unsafe { assert_list(Node::deserialize_ref(&mut reader), LEN, false) };
+
+    let mut reader = with_list(LEN, Links::NextAndPrev, serialize_shared);
+    unsafe { assert_list(Node::try_deserialize_ref(&mut reader).unwrap(), LEN, false) };
+}
+
+#[test]
+fn long_list_without_sharing() {
+    const LEN: usize = 100_000;
+
+    // Without sharing every `prev` would be serialized again, so only `next` is set.
+    let mut reader = with_list(LEN, Links::Next, |root| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { root.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    unsafe {
+        let mut node = Node::try_deserialize_ref(&mut reader).unwrap() as *const Node;
+        for i in 0..LEN {
+            assert_eq!(i as u32, (*node).value);
+            assert!((*node).prev.is_null());
+            node = (*node).next;
+        }
+        assert!(node.is_null());
+    }
+}
+
+#[test]
+fn cycle_debug() {
+    let debug = with_list(2, Links::Circular, |root| format!("{root:?}"));
+    assert_eq!(
+        "Node { value: 0, next: Some(Node { value: 1, next: Some(<cycle>), prev: Some(<cycle>), \
+         name: Some(\"node\") }), prev: Some(Node { value: 1, next: Some(<cycle>), prev: Some(<cycle>), \
+         name: Some(\"node\") }), name: Some(\"node\") }",
+        debug
+    );
+}
+
+#[test]
+fn cycle_serialized_size() {
+    assert_eq!(
+        usize::MAX,
+        with_list(3, Links::Circular, |root| unsafe { root.serialized_size() })
+    );
+    assert_eq!(
+        usize::MAX,
+        with_list(3, Links::NextAndPrev, |root| unsafe {
+            root.serialized_size()
+        })
+    );
+}
+
+#[test]
+fn cycle_owned() {
+    let alloc = CountingAllocator::default();
+    let err = with_list(3, Links::Circular, |root| unsafe {
+        COwned::try_new_in(root, &alloc)
+    })
+    .err()
+    .unwrap();
+    assert_eq!(&Error::Cycle { ty: "Node" }, err.root_cause());
+    assert_eq!(
+        "`Node::next`: `Node::next`: `Node::next`: object of type `Node` is reached again by its pointers",
+        err.to_string()
+    );
+}
+
+#[test]
+fn shared_node_is_not_cycle() {
+    let leaf = Node {
+        value: 1,
+        next: ptr::null(),
+        prev: ptr::null(),
+        name: c"leaf".as_ptr(),
+    };
+    let root = Node {
+        value: 0,
+        next: &leaf,
+        prev: &leaf,
+        name: c"root".as_ptr(),
+    };
+
+    let debug = format!("{root:?}");
+    assert_eq!(2, debug.matches("leaf").count(), "{debug}");
+    assert_eq!(serialize_to_bytes(&root).len(), unsafe {
+        root.serialized_size()
+    });
+
+    let copy = unsafe { COwned::try_new(&root) }.unwrap();
+    unsafe {
+        assert_eq!(c"leaf", CStr::from_ptr((*copy.next).name));
+        assert_eq!(c"leaf", CStr::from_ptr((*copy.prev).name));
+    }
+}
+
+#[test]
+fn back_reference_in_deserialize_to() {
+    let mut reader = with_list(3, Links::Circular, serialize_shared);
+    let mut dst = Node {
+        value: 0,
+        next: ptr::null(),
+        prev: ptr::null(),
+        name: ptr::null(),
+    };
+    let policy = DestinationPolicy::allocate();
+    let err = unsafe { Node::deserialize_to(&mut reader, &mut dst, &policy) }
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err.root_cause(), Error::InvalidBackReference { .. }),
+        "{err:?}"
+    );
+    assert!(dst.next.is_null());
+}
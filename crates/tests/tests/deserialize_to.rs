@@ -1,7 +1,15 @@
-use std::{ffi::c_void, mem};
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    mem, ptr,
+};
 
-use cdump::{CDebug, CDeserialize, CDumpBufferReader, CDumpReader, CDumpWriter, CSerialize};
-use tests::eval_debug;
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferReader, CDumpReader, CDumpWriter,
+    CSerialize, Error,
+};
+use tests::{
+    assert_frees_partial_copy_on_error, eval_debug, reader_from_bytes, serialize_to_bytes,
+};
 
 #[derive(Copy, Clone, CSerialize, CDeserialize, CDebug)]
 #[repr(C)]
@@ -26,6 +34,31 @@ struct ShallowBar {
     ptr: *mut c_void,
 }
 
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Collection {
+    name: *const c_char,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *mut ShallowBar,
+    #[cdump(array(len = self.len))]
+    names: *const *const c_char,
+    #[cdump(array(len = self.len))]
+    #[cdump(dynamic(serializer = custom_serializer, deserializer = custom_deserializer, size_of = custom_sizeof))]
+    dynamics: *const *const c_void,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Named {
+    name_capacity: u32,
+    #[cdump(capacity = self.name_capacity)]
+    name: *mut c_char,
+    len: u32,
+    #[cdump(array(len = self.len), capacity = 8)]
+    names: *mut *mut c_char,
+}
+
 #[derive(Clone, Copy, CDebug, PartialEq, CSerialize, CDeserialize)]
 #[repr(C)]
 struct Dynamic {
@@ -77,7 +110,7 @@ fn deserialize_to_original_memory_tree() {
 
     let mut reader = prepare_buffer();
     unsafe {
-        DeepFoo::deserialize_to(&mut reader, &mut deep_foo);
+        DeepFoo::deserialize_to(&mut reader, &mut deep_foo, &DestinationPolicy::reject()).unwrap();
     }
 
     eval_debug(&deep_foo);
@@ -105,6 +138,395 @@ fn deserialize_to_original_memory_tree() {
     assert_eq!(dynamic2.value, 128);
 }
 
+#[test]
+fn deserialize_to_rejects_missing_destination() {
+    let mut deep_foo = unsafe { mem::zeroed::<DeepFoo>() };
+
+    let mut reader = prepare_buffer();
+    let err = unsafe {
+        DeepFoo::deserialize_to(&mut reader, &mut deep_foo, &DestinationPolicy::reject())
+    }
+    .unwrap_err();
+
+    assert_eq!(
+        Error::DestinationTooSmall {
+            len: 1,
+            capacity: 0
+        }
+        .in_field("DeepFoo", "b"),
+        err
+    );
+}
+
+#[test]
+fn deserialize_to_allocates_missing_destination() {
+    let mut deep_foo = unsafe { mem::zeroed::<DeepFoo>() };
+
+    let mut reader = prepare_buffer();
+    unsafe {
+        DeepFoo::deserialize_to(&mut reader, &mut deep_foo, &DestinationPolicy::allocate())
+            .unwrap();
+    }
+
+    eval_debug(&deep_foo);
+
+    unsafe {
+        assert_eq!(deep_foo.a, 19);
+        assert_eq!((*deep_foo.b).a, 19.84);
+        assert_eq!((*((*deep_foo.b).ptr as *const Dynamic)).value, 10);
+        assert_eq!((*deep_foo.d).c, 7864);
+        assert_eq!((*((*deep_foo.d).ptr as *const Dynamic)).value, 128);
+        assert_eq!(*deep_foo.number_ptr, 0x13);
+        assert_eq!(
+            [5, 7, 13, 0],
+            *(deep_foo.array_primitive as *const [u16; 4])
+        );
+    }
+}
+
+#[test]
+fn deserialize_to_too_small_array() {
+    let mut shallow = ShallowBar {
+        a: 0.0,
+        b: 0,
+        c: 0,
+        ptr: ptr::null_mut(),
+    };
+    let mut number_ptr = 0;
+    let mut array_primitive = [0u16; 2];
+    let mut deep_foo = DeepFoo {
+        a: 0,
+        b: &mut shallow,
+        c: 0.0,
+        d: &mut shallow,
+        number_ptr: &mut number_ptr,
+        array_primitive_len: array_primitive.len() as u32,
+        array_primitive: array_primitive.as_mut_ptr(),
+    };
+
+    let mut reader = prepare_buffer();
+    let err = unsafe {
+        DeepFoo::deserialize_to(&mut reader, &mut deep_foo, &DestinationPolicy::reject())
+    }
+    .unwrap_err();
+    assert_eq!(
+        Error::DestinationTooSmall {
+            len: 1,
+            capacity: 0
+        }
+        .in_field("ShallowBar", "ptr")
+        .in_field("DeepFoo", "b"),
+        err.clone()
+    );
+
+    unsafe { (*deep_foo.b).ptr = &mut number_ptr as *mut u32 as *mut c_void };
+    let mut reader = prepare_buffer();
+    let err = unsafe {
+        DeepFoo::deserialize_to(&mut reader, &mut deep_foo, &DestinationPolicy::reject())
+    }
+    .unwrap_err();
+    assert_eq!(
+        Error::DestinationTooSmall {
+            len: 4,
+            capacity: 2
+        }
+        .in_field("DeepFoo", "array_primitive"),
+        err
+    );
+
+    deep_foo.array_primitive_len = array_primitive.len() as u32;
+    deep_foo.array_primitive = array_primitive.as_mut_ptr();
+    let mut reader = prepare_buffer();
+    unsafe {
+        DeepFoo::deserialize_to(&mut reader, &mut deep_foo, &DestinationPolicy::allocate())
+            .unwrap();
+    }
+
+    assert_eq!(deep_foo.array_primitive_len, 4);
+    assert_ne!(deep_foo.array_primitive, array_primitive.as_mut_ptr());
+    assert_eq!(array_primitive, [0, 0]);
+    assert_eq!([5, 7, 13, 0], unsafe {
+        *(deep_foo.array_primitive as *const [u16; 4])
+    });
+}
+
+#[test]
+fn deserialize_to_arrays() {
+    let texts = [c"first", c"second"];
+    let mut dynamics = [Dynamic { value: 1 }, Dynamic { value: 2 }];
+    let mut bars = [
+        ShallowBar {
+            a: 1.0,
+            b: 2,
+            c: 3,
+            ptr: &mut dynamics[1] as *mut _ as *mut c_void,
+        },
+        ShallowBar {
+            a: 4.0,
+            b: 5,
+            c: 6,
+            ptr: ptr::null_mut(),
+        },
+    ];
+    let names = [texts[1].as_ptr(), texts[0].as_ptr()];
+    let dynamic_ptrs = [
+        &dynamics[0] as *const _ as *const c_void,
+        &dynamics[1] as *const _ as *const c_void,
+    ];
+    let obj = Collection {
+        name: c"collection".as_ptr(),
+        len: 2,
+        bars: bars.as_mut_ptr(),
+        names: names.as_ptr(),
+        dynamics: dynamic_ptrs.as_ptr(),
+    };
+
+    let mut buf = cdump::CDumpBufferWriter::new(16);
+    unsafe { obj.serialize(&mut buf) };
+    let mut reader = buf.into_reader();
+
+    // Existing array of structs is reused, and other destinations are allocated.
+    let mut existing_dynamic = Dynamic { value: 0 };
+    let mut existing_bars = [
+        ShallowBar {
+            a: 0.0,
+            b: 0,
+            c: 0,
+            ptr: &mut existing_dynamic as *mut _ as *mut c_void,
+        },
+        ShallowBar {
+            a: 0.0,
+            b: 0,
+            c: 0,
+            ptr: ptr::null_mut(),
+        },
+    ];
+    let mut copy = Collection {
+        name: ptr::null(),
+        len: 2,
+        bars: existing_bars.as_mut_ptr(),
+        names: ptr::null(),
+        dynamics: ptr::null(),
+    };
+    unsafe {
+        Collection::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap();
+    }
+
+    eval_debug(&copy);
+
+    assert_eq!(copy.bars, existing_bars.as_mut_ptr());
+    assert_eq!(existing_bars[0].c, 3);
+    assert_eq!(
+        existing_bars[0].ptr,
+        &mut existing_dynamic as *mut _ as *mut c_void
+    );
+    assert_eq!(existing_dynamic.value, 2);
+    assert_eq!(existing_bars[1].a, 4.0);
+    assert!(existing_bars[1].ptr.is_null());
+
+    unsafe {
+        assert_eq!(c"collection", CStr::from_ptr(copy.name));
+        assert_eq!(c"second", CStr::from_ptr(*copy.names));
+        assert_eq!(c"first", CStr::from_ptr(*copy.names.add(1)));
+        assert_eq!(1, (*(*copy.dynamics as *const Dynamic)).value);
+        assert_eq!(2, (*(*copy.dynamics.add(1) as *const Dynamic)).value);
+    }
+}
+
+#[test]
+fn deserialize_to_rolls_back_on_error() {
+    let dynamic = Dynamic { value: 1 };
+    let bars = [ShallowBar {
+        a: 1.0,
+        b: 2,
+        c: 3,
+        ptr: &dynamic as *const _ as *mut c_void,
+    }];
+    let names = [c"first".as_ptr()];
+    let dynamics = [&dynamic as *const _ as *const c_void];
+    let bytes = serialize_to_bytes(&Collection {
+        name: c"collection".as_ptr(),
+        len: 1,
+        bars: bars.as_ptr() as *mut _,
+        names: names.as_ptr(),
+        dynamics: dynamics.as_ptr(),
+    });
+
+    // Name, dynamic object of the existing bar, array of names with a name, and array of dynamics with an object.
+    assert_frees_partial_copy_on_error(6, |alloc| unsafe {
+        let mut existing_bars = [ShallowBar {
+            a: 0.0,
+            b: 0,
+            c: 0,
+            ptr: ptr::null_mut(),
+        }];
+        let mut copy = Collection {
+            name: ptr::null(),
+            len: 1,
+            bars: existing_bars.as_mut_ptr(),
+            names: ptr::null(),
+            dynamics: ptr::null(),
+        };
+        let result = Collection::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::Allocate(alloc),
+        );
+
+        // Existing bar is overwritten before the error, but it does not point to the freed object.
+        assert!(existing_bars[0].ptr.is_null());
+        assert!(copy.name.is_null());
+        assert!(copy.names.is_null());
+        result
+    });
+}
+
+fn prepare_named_buffer() -> CDumpBufferReader {
+    let mut name = *b"Hello world!\0";
+    let mut first = *b"first\0";
+    let mut second = *b"second\0";
+    let mut names = [
+        first.as_mut_ptr() as *mut c_char,
+        second.as_mut_ptr() as *mut c_char,
+    ];
+    let obj = Named {
+        name_capacity: 0,
+        name: name.as_mut_ptr() as *mut c_char,
+        len: 2,
+        names: names.as_mut_ptr(),
+    };
+
+    let mut buf = cdump::CDumpBufferWriter::new(16);
+    unsafe { obj.serialize(&mut buf) };
+    buf.into_reader()
+}
+
+#[test]
+fn deserialize_to_cstring_in_existing_storage() {
+    let mut name = [0 as c_char; 16];
+    let mut first = [0 as c_char; 8];
+    let mut second = [0 as c_char; 8];
+    let mut names = [first.as_mut_ptr(), second.as_mut_ptr()];
+    let mut copy = Named {
+        name_capacity: name.len() as u32,
+        name: name.as_mut_ptr(),
+        len: names.len() as u32,
+        names: names.as_mut_ptr(),
+    };
+
+    let reader = prepare_named_buffer();
+    unsafe {
+        let mut reader = reader;
+        Named::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::reject()).unwrap();
+    }
+
+    eval_debug(&copy);
+
+    assert_eq!(copy.name, name.as_mut_ptr());
+    assert_eq!(copy.names, names.as_mut_ptr());
+    assert_eq!(names, [first.as_mut_ptr(), second.as_mut_ptr()]);
+    unsafe {
+        assert_eq!(c"Hello world!", CStr::from_ptr(name.as_ptr()));
+        assert_eq!(c"first", CStr::from_ptr(first.as_ptr()));
+        assert_eq!(c"second", CStr::from_ptr(second.as_ptr()));
+    }
+}
+
+#[test]
+fn deserialize_to_cstring_too_small() {
+    let mut name = [0 as c_char; 12];
+    let mut copy = Named {
+        name_capacity: name.len() as u32,
+        name: name.as_mut_ptr(),
+        len: 0,
+        names: ptr::null_mut(),
+    };
+
+    let mut reader = prepare_named_buffer();
+    let err =
+        unsafe { Named::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::reject()) }
+            .unwrap_err();
+    assert_eq!(
+        Error::DestinationTooSmall {
+            len: 13,
+            capacity: 12
+        }
+        .in_field("Named", "name"),
+        err
+    );
+
+    let mut reader = prepare_named_buffer();
+    unsafe {
+        Named::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap();
+    }
+
+    assert_ne!(copy.name, name.as_mut_ptr());
+    assert_eq!(name, [0; 12]);
+    unsafe {
+        assert_eq!(c"Hello world!", CStr::from_ptr(copy.name));
+        assert_eq!(c"second", CStr::from_ptr(*copy.names.add(1)));
+    }
+}
+
+#[test]
+fn deserialize_to_cstring_keeps_destination_capacity() {
+    let serialize = |name_capacity: u32, name: &CStr, names: &[&CStr]| {
+        let mut names = names
+            .iter()
+            .map(|name| name.as_ptr() as *mut c_char)
+            .collect::<Vec<_>>();
+        serialize_to_bytes(&Named {
+            name_capacity,
+            name: name.as_ptr() as *mut c_char,
+            len: names.len() as u32,
+            names: names.as_mut_ptr(),
+        })
+    };
+
+    let mut copy = unsafe { mem::zeroed::<Named>() };
+    unsafe {
+        Named::deserialize_to(
+            &mut reader_from_bytes(&serialize(1000, c"hi", &[c"a"])),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+    }
+    assert_eq!(0, copy.name_capacity);
+
+    // The sender's capacity is not used for the storage allocated for "hi".
+    let long = CString::new("x".repeat(50)).unwrap();
+    let err = unsafe {
+        Named::deserialize_to(
+            &mut reader_from_bytes(&serialize(1000, &long, &[c"seventh"])),
+            &mut copy,
+            &DestinationPolicy::reject(),
+        )
+    }
+    .unwrap_err();
+    assert_eq!(
+        Error::DestinationTooSmall {
+            len: 51,
+            capacity: 0
+        }
+        .in_field("Named", "name"),
+        err
+    );
+
+    // Strings of `names` were allocated with their declared capacity, so they are reused.
+    let names = unsafe { *copy.names };
+    unsafe {
+        Named::deserialize_to(
+            &mut reader_from_bytes(&serialize(0, c"hi", &[c"seventh"])),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        assert_eq!(names, *copy.names);
+        assert_eq!(c"seventh", CStr::from_ptr(*copy.names));
+    }
+}
+
 fn prepare_buffer() -> CDumpBufferReader {
     let mut dynamic1 = Dynamic { value: 10 };
     let mut shallow1 = ShallowBar {
@@ -4,14 +4,14 @@ use std::{
     mem,
 };
 
-use cdump::{CDebug, CDeserialize, CDumpReader, CDumpWriter, CSerialize};
-use tests::eval_debug;
+use cdump::{CDebug, CDeserialize, CDumpReader, CDumpWriter, CSerialize, Error};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
 
 #[derive(CDebug, CSerialize, CDeserialize)]
 #[repr(C)]
 struct Foo {
     a: u32,
-    #[cdump(dynamic(serializer = custom_serializer, deserializer = custom_deserializer, size_of = custom_sizeof, cdebugger = custom_cdebugger))]
+    #[cdump(dynamic(serializer = custom_serializer, deserializer = custom_deserializer, try_deserializer = custom_try_deserializer, size_of = custom_sizeof, cdebugger = custom_cdebugger))]
     d: *const c_void,
     text: *const c_char,
 }
@@ -52,6 +52,16 @@ fn dynamic() {
     let copy = unsafe { Foo::deserialize_ref(&mut reader) };
 
     eval_debug(&copy);
+    assert_foo_eq(&obj, copy);
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { Foo::try_deserialize_ref(&mut reader) }.unwrap();
+
+    eval_debug(&copy);
+    assert_foo_eq(&obj, copy);
+}
+
+fn assert_foo_eq(obj: &Foo, copy: &Foo) {
     assert_eq!(obj.a, copy.a);
     assert_ne!(obj.d, copy.d);
     unsafe {
@@ -97,6 +107,22 @@ unsafe fn custom_deserializer<T: CDumpReader>(buf: &mut T) -> (*const c_void, us
     (ptr, size)
 }
 
+unsafe fn custom_try_deserializer<T: CDumpReader>(
+    buf: &mut T,
+) -> Result<(*const c_void, usize), Error> {
+    buf.try_align::<DynamicBar>()?;
+    let index = buf.get_read();
+    buf.check_bounds(index, mem::size_of::<u32>())?;
+    let ty = *buf.as_mut_ptr_at::<u32>(index);
+    match ty {
+        1 => {
+            let ptr = cdump::internal::try_deserialize_shallow_copied::<T, DynamicBar>(buf)?;
+            Ok((ptr as *const c_void, mem::size_of::<DynamicBar>()))
+        }
+        ty => Err(Error::UnknownDiscriminant(ty as i64)),
+    }
+}
+
 unsafe fn custom_cdebugger(obj: *const c_void) -> &'static dyn Debug {
     let ty = *(obj as *const DynamicType);
     match ty {
@@ -0,0 +1,260 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    fmt::Debug,
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, CDumpReader, CDumpWriter,
+    COwned, CSerialize, CSerializedSize, CTag, Error,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+/// Descriptors of different types and sizes, which are stored one after another under one pointer.
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Descriptors {
+    count: u32,
+    #[cdump(array(len = self.count), tagged = DescriptorType)]
+    descriptors: *const c_void,
+    text: *const c_char,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, CTag)]
+#[repr(u32)]
+enum DescriptorType {
+    Sampler = 1,
+    Buffer = 2,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[cdump(tag = DescriptorType::Sampler)]
+#[repr(C)]
+struct Sampler {
+    ty: DescriptorType,
+    filter: u32,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[cdump(tag = DescriptorType::Buffer)]
+#[repr(C)]
+struct Buffer {
+    ty: DescriptorType,
+    size: u64,
+    name: *const c_char,
+}
+
+/// Memory of the array, as C stores it.
+#[repr(C)]
+struct Storage {
+    buffer: Buffer,
+    sampler: Sampler,
+    named: Buffer,
+}
+
+fn storage() -> Storage {
+    Storage {
+        buffer: Buffer {
+            ty: DescriptorType::Buffer,
+            size: 256,
+            name: c"uniforms".as_ptr(),
+        },
+        sampler: Sampler {
+            ty: DescriptorType::Sampler,
+            filter: 3,
+        },
+        named: Buffer {
+            ty: DescriptorType::Buffer,
+            size: 1984,
+            name: c"vertices".as_ptr(),
+        },
+    }
+}
+
+fn with_descriptors<T>(f: impl FnOnce(&Descriptors) -> T) -> T {
+    let storage = storage();
+    f(&Descriptors {
+        count: 3,
+        descriptors: &storage as *const Storage as *const c_void,
+        text: c"Hello world!".as_ptr(),
+    })
+}
+
+unsafe fn assert_descriptors(copy: &Descriptors) {
+    assert_eq!(3, copy.count);
+    assert_eq!(c"Hello world!", CStr::from_ptr(copy.text));
+
+    let storage = &*(copy.descriptors as *const Storage);
+    assert_eq!(DescriptorType::Buffer, storage.buffer.ty);
+    assert_eq!(256, storage.buffer.size);
+    assert_eq!(c"uniforms", CStr::from_ptr(storage.buffer.name));
+    assert_eq!(DescriptorType::Sampler, storage.sampler.ty);
+    assert_eq!(3, storage.sampler.filter);
+    assert_eq!(1984, storage.named.size);
+    assert_eq!(c"vertices", CStr::from_ptr(storage.named.name));
+}
+
+#[test]
+fn dynamic_array() {
+    let mut reader = with_descriptors(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Descriptors::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_descriptors(copy) };
+}
+
+#[test]
+fn dynamic_array_checked() {
+    let mut reader = reader_from_bytes(&with_descriptors(serialize_to_bytes));
+    unsafe { assert_descriptors(Descriptors::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn dynamic_array_checked_rejects_too_small_array() {
+    let mut bytes = with_descriptors(serialize_to_bytes);
+    // Size of the array is stored in place of the pointer, and the array follows the shallow copy.
+    let slot = mem::offset_of!(Descriptors, descriptors);
+    let size = usize::from_ne_bytes(bytes[slot..slot + 8].try_into().unwrap());
+    bytes[slot..slot + 8].copy_from_slice(&(size - 8).to_ne_bytes());
+    let end = mem::size_of::<Descriptors>() + size;
+    bytes.drain(end - 8..end);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Descriptors::try_deserialize_ref(&mut reader) }.unwrap_err();
+    assert!(
+        matches!(err.root_cause(), Error::OutOfBounds { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn dynamic_array_serialized_size() {
+    with_descriptors(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn dynamic_array_deserialize_to() {
+    let bytes = with_descriptors(serialize_to_bytes);
+    let mut copy = unsafe { mem::zeroed::<Descriptors>() };
+    let err = unsafe {
+        Descriptors::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::reject(),
+        )
+    }
+    .unwrap_err();
+    assert!(matches!(
+        err.root_cause(),
+        Error::DestinationTooSmall { .. }
+    ));
+
+    // Descriptors of the destination are large enough, so they are reused.
+    let mut storage = storage();
+    copy.count = 3;
+    copy.descriptors = &mut storage as *mut Storage as *const c_void;
+    unsafe {
+        Descriptors::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        assert_eq!(
+            &storage as *const Storage as *const c_void,
+            copy.descriptors
+        );
+        assert_descriptors(&copy);
+    }
+}
+
+#[test]
+fn dynamic_array_owned_is_unsupported() {
+    let err = with_descriptors(|obj| unsafe { COwned::try_new(obj) })
+        .err()
+        .unwrap();
+    assert!(matches!(err.root_cause(), Error::UnsupportedDynamic));
+}
+
+#[test]
+fn dynamic_array_debug() {
+    with_descriptors(|obj| {
+        let debug = format!("{obj:?}");
+        let expected = "descriptors: Some([\
+            Buffer { ty: Buffer, size: 256, name: Some(\"uniforms\") }, \
+            Sampler { ty: Sampler, filter: 3 }, \
+            Buffer { ty: Buffer, size: 1984, name: Some(\"vertices\") }])";
+        assert!(debug.contains(expected), "{debug}");
+    });
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Samplers {
+    count: u32,
+    #[cdump(array(len = self.count), dynamic(serializer = sampler_serializer, deserializer = sampler_deserializer, size_of = sampler_size_of, cdebugger = sampler_cdebugger))]
+    samplers: *const c_void,
+}
+
+unsafe fn sampler_serializer<T: CDumpWriter>(buf: &mut T, obj: *const c_void) {
+    (*(obj as *const Sampler)).serialize(buf);
+}
+
+unsafe fn sampler_deserializer<T: CDumpReader>(buf: &mut T) -> (*mut c_void, usize) {
+    let ptr = Sampler::deserialize_ref_mut(buf) as *mut Sampler;
+    (ptr as *mut c_void, mem::size_of::<Sampler>())
+}
+
+unsafe fn sampler_size_of(_obj: *const c_void) -> usize {
+    mem::size_of::<Sampler>()
+}
+
+unsafe fn sampler_cdebugger(obj: *const c_void) -> &'static dyn Debug {
+    &*(obj as *const Sampler)
+}
+
+#[test]
+fn dynamic_array_with_custom_functions() {
+    let samplers = [1, 2, 3].map(|filter| Sampler {
+        ty: DescriptorType::Sampler,
+        filter,
+    });
+    let obj = Samplers {
+        count: samplers.len() as u32,
+        samplers: samplers.as_ptr() as *const c_void,
+    };
+    eval_debug(&obj);
+
+    let mut buf = CDumpBufferWriter::new(16);
+    unsafe { obj.serialize(&mut buf) };
+    let mut reader = buf.into_reader();
+    let copy = unsafe { Samplers::deserialize_ref(&mut reader) };
+
+    assert_ne!(obj.samplers, copy.samplers);
+    let copied = unsafe { std::slice::from_raw_parts(copy.samplers as *const Sampler, 3) };
+    assert_eq!(
+        [1, 2, 3],
+        [copied[0].filter, copied[1].filter, copied[2].filter]
+    );
+}
+
+#[test]
+fn dynamic_array_null() {
+    let obj = Samplers {
+        count: 2,
+        samplers: ptr::null(),
+    };
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { Samplers::deserialize_ref(&mut reader) };
+    assert!(copy.samplers.is_null());
+    assert!(format!("{copy:?}").contains("samplers: None"));
+}
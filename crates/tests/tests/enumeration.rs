@@ -0,0 +1,290 @@
+use std::{mem, ptr};
+
+use cdump::{
+    alloc::DestinationPolicy, CDeserialize, CDumpBufferWriter, COwned, CSerialize, CSerializedSize,
+    Error,
+};
+use tests::{reader_from_bytes, serialize_to_bytes};
+
+#[derive(Debug, Clone, Copy, PartialEq, CSerialize, CDeserialize)]
+#[repr(u32)]
+enum Format {
+    Undefined = 0,
+    Rgba8 = 37,
+    Depth32 = 126,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, CSerialize, CDeserialize)]
+#[cdump(fallback = Unknown)]
+#[repr(C)]
+enum Filter {
+    Nearest = 0,
+    Linear = 1,
+    Unknown = -1,
+}
+
+mod changed {
+    use cdump::{CDeserialize, CSerialize};
+
+    /// Same enum as `Format` with different value of the last variant.
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(u32)]
+    pub enum Format {
+        Undefined = 0,
+        Rgba8 = 37,
+        Depth32 = 127,
+    }
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Attachment {
+    format: *const Format,
+    filter: *const Filter,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    formats: *const Format,
+}
+
+fn with_attachment<T>(f: impl FnOnce(&Attachment) -> T) -> T {
+    let format = Format::Depth32;
+    let filter = Filter::Linear;
+    let formats = [Format::Rgba8, Format::Undefined, Format::Depth32];
+
+    f(&Attachment {
+        format: &format,
+        filter: &filter,
+        len: formats.len() as u32,
+        formats: formats.as_ptr(),
+    })
+}
+
+unsafe fn assert_attachment(copy: &Attachment) {
+    assert_eq!(Format::Depth32, *copy.format);
+    assert_eq!(Filter::Linear, *copy.filter);
+    assert_eq!(
+        [Format::Rgba8, Format::Undefined, Format::Depth32],
+        std::slice::from_raw_parts(copy.formats, copy.len as usize)
+    );
+}
+
+/// Replace the value of the format, which is at the `index` of the serialized attachment.
+fn corrupt(bytes: &mut [u8], index: usize, value: u32) {
+    bytes[index..index + mem::size_of::<u32>()].copy_from_slice(&value.to_ne_bytes());
+}
+
+#[test]
+fn enumeration() {
+    let mut reader = with_attachment(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    unsafe { assert_attachment(Attachment::deserialize_ref(&mut reader)) };
+}
+
+#[test]
+fn enumeration_checked() {
+    let mut reader = reader_from_bytes(&with_attachment(serialize_to_bytes));
+    unsafe { assert_attachment(Attachment::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn enumeration_serialized_size() {
+    with_attachment(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+    assert_eq!(4, unsafe { Format::Rgba8.serialized_size() });
+}
+
+#[test]
+fn enumeration_owned() {
+    let owned = with_attachment(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_attachment(&owned) };
+}
+
+#[test]
+fn enumeration_deserialize_to() {
+    let mut reader = reader_from_bytes(&with_attachment(serialize_to_bytes));
+    let mut copy = unsafe { mem::zeroed::<Attachment>() };
+    unsafe {
+        Attachment::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap();
+        assert_attachment(&copy);
+    }
+}
+
+#[test]
+fn unknown_discriminant() {
+    let mut bytes = serialize_to_bytes(&Format::Rgba8);
+    corrupt(&mut bytes, 0, 5);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Format::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(Error::UnknownDiscriminant(5), err);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let mut copy = Format::Undefined;
+    let err =
+        unsafe { Format::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()) }
+            .err()
+            .unwrap();
+    assert_eq!(Error::UnknownDiscriminant(5), err);
+    assert_eq!(Format::Undefined, copy);
+}
+
+#[test]
+fn unknown_discriminant_in_array() {
+    let mut bytes = with_attachment(serialize_to_bytes);
+    // Array of formats is the last data of the attachment.
+    let index = bytes.len() - mem::size_of::<Format>();
+    corrupt(&mut bytes, index, 1000);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Attachment::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(&Error::UnknownDiscriminant(1000), err.root_cause());
+    assert_eq!(
+        "`Attachment::formats`: unknown discriminant 1000",
+        err.to_string()
+    );
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Sampler {
+    format: Format,
+    filters: [Filter; 2],
+}
+
+#[test]
+fn unknown_discriminant_in_field_by_value() {
+    let obj = Sampler {
+        format: Format::Rgba8,
+        filters: [Filter::Nearest, Filter::Linear],
+    };
+    let mut bytes = serialize_to_bytes(&obj);
+    corrupt(&mut bytes, mem::offset_of!(Sampler, format), 5);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Sampler::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!("`Sampler::format`: unknown discriminant 5", err.to_string());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let mut copy = Sampler {
+        format: Format::Undefined,
+        filters: [Filter::Nearest; 2],
+    };
+    let err =
+        unsafe { Sampler::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()) }
+            .err()
+            .unwrap();
+    assert_eq!(&Error::UnknownDiscriminant(5), err.root_cause());
+    assert_eq!(Format::Undefined, copy.format);
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[test]
+fn unknown_discriminant_in_generic_field() {
+    let mut bytes = serialize_to_bytes(&Wrapper {
+        value: Format::Depth32,
+    });
+    corrupt(&mut bytes, 0, 5);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Wrapper::<Format>::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!("`Wrapper::value`: unknown discriminant 5", err.to_string());
+}
+
+#[test]
+fn fallback_in_field_by_value() {
+    let obj = Sampler {
+        format: Format::Rgba8,
+        filters: [Filter::Nearest, Filter::Linear],
+    };
+    let mut bytes = serialize_to_bytes(&obj);
+    corrupt(
+        &mut bytes,
+        mem::offset_of!(Sampler, filters) + mem::size_of::<Filter>(),
+        7,
+    );
+
+    let mut reader = reader_from_bytes(&bytes);
+    let copy = unsafe { Sampler::try_deserialize_ref(&mut reader) }.unwrap();
+    assert_eq!([Filter::Nearest, Filter::Unknown], copy.filters);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let copy = unsafe { Sampler::deserialize_ref(&mut reader) };
+    assert_eq!([Filter::Nearest, Filter::Unknown], copy.filters);
+}
+
+#[test]
+fn fallback() {
+    let mut bytes = serialize_to_bytes(&Filter::Nearest);
+    corrupt(&mut bytes, 0, 7);
+
+    let mut reader = reader_from_bytes(&bytes);
+    assert_eq!(
+        Filter::Unknown,
+        *unsafe { Filter::try_deserialize_ref(&mut reader) }.unwrap()
+    );
+
+    let mut reader = reader_from_bytes(&bytes);
+    assert_eq!(Filter::Unknown, *unsafe {
+        Filter::deserialize_ref(&mut reader)
+    });
+
+    let mut reader = reader_from_bytes(&bytes);
+    let mut copy = Filter::Nearest;
+    unsafe {
+        Filter::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap()
+    };
+    assert_eq!(Filter::Unknown, copy);
+}
+
+#[test]
+fn repr_c_has_size_of_int() {
+    assert_eq!(mem::size_of::<std::ffi::c_int>(), mem::size_of::<Filter>());
+    assert_eq!(
+        mem::size_of::<std::ffi::c_int>(),
+        serialize_to_bytes(&Filter::Linear).len()
+    );
+    // Null pointers to enums are not followed.
+    let obj = Attachment {
+        format: ptr::null(),
+        filter: ptr::null(),
+        len: 0,
+        formats: ptr::null(),
+    };
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    assert!(unsafe { Attachment::try_deserialize_ref(&mut reader) }
+        .unwrap()
+        .format
+        .is_null());
+}
+
+#[test]
+fn layout_hash_covers_variants() {
+    assert_ne!(
+        <Format as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH,
+        <changed::Format as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH
+    );
+    assert_eq!(
+        <Format as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH,
+        <Format as CDeserialize<cdump::CDumpBufferReader>>::LAYOUT_HASH
+    );
+}
@@ -0,0 +1,185 @@
+use std::ffi::{c_char, CStr};
+
+use cdump::{
+    envelope::{self, Header, FORMAT_VERSION, HEADER_LEN, MAGIC},
+    CDeserialize, CDumpBufferReader, CDumpBufferWriter, CDumpReader, CDumpWriter, CSerialize,
+    Error,
+};
+use tests::reader_from_bytes;
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    a: u32,
+    text: *const c_char,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *const Bar,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Bar {
+    value: u128,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Other {
+    a: u32,
+}
+
+fn serialize_foo() -> Vec<u8> {
+    let text = c"Hello world!";
+    let bars = [Bar { value: 7 }, Bar { value: 8 }];
+    let obj = Foo {
+        a: 1984,
+        text: text.as_ptr(),
+        len: bars.len() as u32,
+        bars: bars.as_ptr(),
+    };
+
+    let mut buf = CDumpBufferWriter::new(16);
+    unsafe { envelope::try_serialize(&obj, &mut buf) }.unwrap();
+    unsafe { std::slice::from_raw_parts(buf.as_mut_ptr_at(0), buf.len()).to_vec() }
+}
+
+fn assert_foo(copy: &Foo) {
+    assert_eq!(copy.a, 1984);
+    assert_eq!(unsafe { CStr::from_ptr(copy.text) }, c"Hello world!");
+    assert_eq!(copy.len, 2);
+    unsafe {
+        assert_eq!((*copy.bars).value, 7);
+        assert_eq!((*copy.bars.add(1)).value, 8);
+    }
+}
+
+#[test]
+fn header() {
+    let bytes = serialize_foo();
+    assert_eq!(bytes[..4], MAGIC);
+
+    let header = Header::from_bytes(bytes[..HEADER_LEN].try_into().unwrap()).unwrap();
+    assert_eq!(header.version, FORMAT_VERSION);
+    assert_eq!(header.align as usize, std::mem::align_of::<u128>());
+    assert_eq!(
+        header.type_id,
+        <Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH
+    );
+    assert_eq!(header.payload_len as usize, bytes.len() - HEADER_LEN);
+    assert_eq!(header.to_bytes(), bytes[..HEADER_LEN]);
+}
+
+#[test]
+fn round_trip() {
+    let mut reader = reader_from_bytes(&serialize_foo());
+    let copy = unsafe { envelope::try_deserialize_ref::<Foo, _>(&mut reader) }.unwrap();
+    assert_foo(copy);
+}
+
+#[test]
+fn consecutive_envelopes() {
+    let mut bytes = serialize_foo();
+    bytes.extend(serialize_foo());
+
+    let mut reader = reader_from_bytes(&bytes);
+    for _ in 0..2 {
+        let copy = unsafe { envelope::try_deserialize_ref::<Foo, _>(&mut reader) }.unwrap();
+        assert_foo(copy);
+    }
+    assert_eq!(reader.get_read(), bytes.len());
+}
+
+#[test]
+fn invalid_magic() {
+    let mut bytes = serialize_foo();
+    bytes[0] = b'X';
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { envelope::try_deserialize_ref::<Foo, _>(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::InvalidMagic(*b"XDMP"));
+}
+
+#[test]
+fn unsupported_version() {
+    let mut bytes = serialize_foo();
+    bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { envelope::try_deserialize_ref::<Foo, _>(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::UnsupportedVersion(FORMAT_VERSION + 1));
+}
+
+#[test]
+fn other_pointer_width() {
+    let mut bytes = serialize_foo();
+    bytes[7] = 2;
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { envelope::try_deserialize_ref::<Foo, _>(&mut reader) }
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        Error::IncompatiblePlatform {
+            pointer_width: 2,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn other_root_type() {
+    let mut reader = reader_from_bytes(&serialize_foo());
+    let err = unsafe { envelope::try_deserialize_ref::<Other, _>(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        Error::LayoutMismatch {
+            expected: <Other as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+            found: <Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+        }
+    );
+}
+
+#[test]
+fn truncated_payload() {
+    let bytes = serialize_foo();
+
+    let mut reader = reader_from_bytes(&bytes[..bytes.len() - 1]);
+    let err = unsafe { envelope::try_deserialize_ref::<Foo, _>(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        Error::OutOfBounds {
+            offset: HEADER_LEN,
+            len: bytes.len() - HEADER_LEN,
+            buffer_len: bytes.len() - 1,
+        }
+    );
+}
+
+#[test]
+fn payload_shorter_than_object() {
+    let mut bytes = serialize_foo();
+    let payload_len = (bytes.len() - HEADER_LEN - 1) as u64;
+    bytes[24..32].copy_from_slice(&payload_len.to_le_bytes());
+    bytes.extend(serialize_foo());
+
+    // Deserialization stops at the end of the payload, instead of reading the next envelope.
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { envelope::try_deserialize_ref::<Foo, _>(&mut reader) }
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Field { field: "bars", .. }), "{err:?}");
+    assert!(matches!(
+        err.root_cause(),
+        Error::OutOfBounds { buffer_len, .. } if *buffer_len == HEADER_LEN + payload_len as usize
+    ));
+}
@@ -0,0 +1,243 @@
+use std::{
+    ffi::{c_char, CStr},
+    marker::PhantomData,
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize, CView,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+/// Array of any serializable elements.
+#[derive(CDebug, CSerialize, CDeserialize, CView)]
+#[repr(C)]
+struct Array<T> {
+    len: u32,
+    #[cdump(array(len = self.len))]
+    data: *const T,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Point(f32, f32);
+
+/// Tuple struct, which array uses the sibling field for its length.
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Samples(u32, #[cdump(array(len = self.0))] *const u16, *const c_char);
+
+/// Named value with a lifetime and a where clause, where `Buffer` does not clash with types of the generated impls.
+#[derive(CDebug, CSerialize, CDeserialize, CView)]
+#[repr(C)]
+struct Named<'a, Buffer, const N: usize>
+where
+    Buffer: Copy,
+{
+    name: [c_char; N],
+    value: *const Buffer,
+    marker: PhantomData<&'a Buffer>,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Node<T> {
+    value: T,
+    #[cdump(iterative)]
+    next: *const Node<T>,
+}
+
+fn with_points<T>(f: impl FnOnce(&Array<Point>) -> T) -> T {
+    let points = [Point(1.0, 2.0), Point(3.0, 4.0)];
+    f(&Array {
+        len: points.len() as u32,
+        data: points.as_ptr(),
+    })
+}
+
+unsafe fn assert_points(copy: &Array<Point>) {
+    let points = std::slice::from_raw_parts(copy.data, copy.len as usize);
+    assert_eq!(2, points.len());
+    assert_eq!((1.0, 2.0), (points[0].0, points[0].1));
+    assert_eq!((3.0, 4.0), (points[1].0, points[1].1));
+}
+
+fn with_samples<T>(f: impl FnOnce(&Samples) -> T) -> T {
+    let samples = [1u16, 2, 3];
+    f(&Samples(
+        samples.len() as u32,
+        samples.as_ptr(),
+        c"samples".as_ptr(),
+    ))
+}
+
+unsafe fn assert_samples(copy: &Samples) {
+    assert_eq!(
+        [1, 2, 3],
+        std::slice::from_raw_parts(copy.1, copy.0 as usize)
+    );
+    assert_eq!(c"samples", CStr::from_ptr(copy.2));
+}
+
+#[test]
+fn generic() {
+    let mut reader = with_points(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Array::<Point>::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_points(copy) };
+}
+
+#[test]
+fn generic_checked() {
+    let mut reader = reader_from_bytes(&with_points(serialize_to_bytes));
+    unsafe { assert_points(Array::<Point>::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn generic_primitive() {
+    let values = [5u64, 6, 7];
+    let obj = Array {
+        len: values.len() as u32,
+        data: values.as_ptr(),
+    };
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { Array::<u64>::try_deserialize_ref(&mut reader) }.unwrap();
+    assert_eq!([5, 6, 7], unsafe {
+        std::slice::from_raw_parts(copy.data, copy.len as usize)
+    });
+    assert_eq!(
+        "Array { len: 3, data: Some([5, 6, 7]) }",
+        format!("{copy:?}")
+    );
+}
+
+#[test]
+fn generic_owned_and_deserialize_to() {
+    let owned = with_points(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_points(&owned) };
+
+    let mut reader = reader_from_bytes(&with_points(serialize_to_bytes));
+    let mut copy = unsafe { mem::zeroed::<Array<Point>>() };
+    unsafe {
+        Array::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap();
+        assert_points(&copy);
+    }
+}
+
+#[test]
+fn generic_serialized_size() {
+    with_points(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn generic_layout_hash_depends_on_arguments() {
+    assert_ne!(
+        <Array<u8> as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH,
+        <Array<u64> as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH
+    );
+}
+
+#[test]
+fn generic_view() {
+    let values = [3u64, 5, 8];
+    let obj = Array {
+        len: values.len() as u32,
+        data: values.as_ptr(),
+    };
+
+    let bytes = serialize_to_bytes(&obj);
+    let view = unsafe { Array::<u64>::try_view(&bytes) }.unwrap();
+    assert_eq!(3, view.len);
+    let data = view.data().unwrap();
+    assert_eq!(vec![&3, &5, &8], data.iter().collect::<Vec<_>>());
+
+    let err = unsafe { Array::<u64>::try_view(&bytes[..bytes.len() - 1]) }
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err.root_cause(), cdump::Error::OutOfBounds { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn lifetime_and_where_clause() {
+    let value = 42u32;
+    let mut name = [0 as c_char; 8];
+    for (dst, &src) in name.iter_mut().zip(b"name") {
+        *dst = src as c_char;
+    }
+    let obj = Named {
+        name,
+        value: &value,
+        marker: PhantomData,
+    };
+
+    let bytes = serialize_to_bytes(&obj);
+    let view = unsafe { Named::<u32, 8>::try_view(&bytes) }.unwrap();
+    assert_eq!(Some(&&42), view.value());
+    assert_eq!(b'n' as c_char, view.name[0]);
+
+    let mut reader = reader_from_bytes(&bytes);
+    let copy = unsafe { Named::<u32, 8>::try_deserialize_ref(&mut reader) }.unwrap();
+    assert_eq!(42, unsafe { *copy.value });
+    assert_eq!(
+        "Named { name: \"name\", value: Some(42), marker: PhantomData<&u32> }",
+        format!("{copy:?}")
+    );
+}
+
+#[test]
+fn generic_iterative() {
+    let last = Node {
+        value: Point(5.0, 6.0),
+        next: ptr::null(),
+    };
+    let first = Node {
+        value: Point(1.0, 2.0),
+        next: &last,
+    };
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&first));
+    let copy = unsafe { Node::<Point>::try_deserialize_ref(&mut reader) }.unwrap();
+    assert_eq!(
+        "Node { value: Point(1.0, 2.0), next: Some(Node { value: Point(5.0, 6.0), next: None }) }",
+        format!("{copy:?}")
+    );
+}
+
+#[test]
+fn tuple_struct() {
+    let mut reader = with_samples(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Samples::deserialize_ref(&mut reader) };
+    unsafe { assert_samples(copy) };
+    assert_eq!(
+        "Samples(3, Some([1, 2, 3]), Some(\"samples\"))",
+        format!("{copy:?}")
+    );
+}
+
+#[test]
+fn tuple_struct_checked() {
+    let mut reader = reader_from_bytes(&with_samples(serialize_to_bytes));
+    unsafe { assert_samples(Samples::try_deserialize_ref(&mut reader).unwrap()) };
+
+    let owned = with_samples(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_samples(&owned) };
+}
@@ -0,0 +1,218 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize, CTag,
+};
+use tests::{
+    assert_frees_partial_copy_on_error, eval_debug, reader_from_bytes, serialize_to_bytes,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Extent {
+    width: u32,
+    height: u32,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Attachment {
+    name: *const c_char,
+    extent: *const Extent,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Pass {
+    layer_names: [*const c_char; 4],
+    extents: [*const Extent; 2],
+    #[cdump(nested)]
+    attachments: [Attachment; 3],
+    offsets: [u32; 2],
+}
+
+fn with_pass<T>(f: impl FnOnce(&Pass) -> T) -> T {
+    let small = Extent {
+        width: 4,
+        height: 8,
+    };
+    let large = Extent {
+        width: 640,
+        height: 480,
+    };
+
+    f(&Pass {
+        layer_names: [
+            c"base".as_ptr(),
+            ptr::null(),
+            c"detail".as_ptr(),
+            ptr::null(),
+        ],
+        extents: [&large, ptr::null()],
+        attachments: [
+            Attachment {
+                name: c"color".as_ptr(),
+                extent: &large,
+            },
+            Attachment {
+                name: ptr::null(),
+                extent: ptr::null(),
+            },
+            Attachment {
+                name: c"depth".as_ptr(),
+                extent: &small,
+            },
+        ],
+        offsets: [16, 32],
+    })
+}
+
+unsafe fn assert_pass(copy: &Pass) {
+    assert_eq!(c"base", CStr::from_ptr(copy.layer_names[0]));
+    assert!(copy.layer_names[1].is_null());
+    assert_eq!(c"detail", CStr::from_ptr(copy.layer_names[2]));
+    assert!(copy.layer_names[3].is_null());
+
+    assert_eq!(
+        Extent {
+            width: 640,
+            height: 480
+        },
+        *copy.extents[0]
+    );
+    assert!(copy.extents[1].is_null());
+
+    let [color, empty, depth] = &copy.attachments;
+    assert_eq!(c"color", CStr::from_ptr(color.name));
+    assert_eq!(640, (*color.extent).width);
+    assert!(empty.name.is_null());
+    assert!(empty.extent.is_null());
+    assert_eq!(c"depth", CStr::from_ptr(depth.name));
+    assert_eq!(
+        Extent {
+            width: 4,
+            height: 8
+        },
+        *depth.extent
+    );
+
+    assert_eq!([16, 32], copy.offsets);
+}
+
+#[test]
+fn inline_array() {
+    let mut reader = with_pass(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Pass::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_pass(copy) };
+}
+
+#[test]
+fn inline_array_checked() {
+    let mut reader = reader_from_bytes(&with_pass(serialize_to_bytes));
+    unsafe { assert_pass(Pass::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn inline_array_serialized_size() {
+    with_pass(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn inline_array_owned() {
+    let owned = with_pass(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_pass(&owned) };
+}
+
+#[test]
+fn inline_array_deserialize_to() {
+    let mut reader = reader_from_bytes(&with_pass(serialize_to_bytes));
+    let mut copy = unsafe { mem::zeroed::<Pass>() };
+    unsafe {
+        Pass::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap();
+        assert_pass(&copy);
+    }
+}
+
+#[test]
+fn inline_array_debug() {
+    with_pass(|obj| {
+        let debug = format!("{obj:?}");
+        for expected in [
+            "layer_names: [Some(\"base\"), None, Some(\"detail\"), None]",
+            "extents: [Some(Extent { width: 640, height: 480 }), None]",
+            "Attachment { name: None, extent: None }",
+            "offsets: [16, 32]",
+        ] {
+            assert!(debug.contains(expected), "{debug}");
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, CTag)]
+#[repr(u32)]
+enum LayerType {
+    Layer = 1,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[cdump(tag = LayerType::Layer)]
+#[repr(C)]
+struct Layer {
+    ty: LayerType,
+    name: *const c_char,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Layers {
+    #[cdump(tagged = LayerType)]
+    layers: [*const c_void; 2],
+}
+
+#[test]
+fn inline_array_tagged() {
+    let layer = Layer {
+        ty: LayerType::Layer,
+        name: c"overlay".as_ptr(),
+    };
+    let obj = Layers {
+        layers: [ptr::null(), &layer as *const Layer as *const c_void],
+    };
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { Layers::try_deserialize_ref(&mut reader) }.unwrap();
+    assert!(copy.layers[0].is_null());
+    unsafe {
+        let layer = &*(copy.layers[1] as *const Layer);
+        assert_eq!(LayerType::Layer, layer.ty);
+        assert_eq!(c"overlay", CStr::from_ptr(layer.name));
+    }
+
+    let owned = unsafe { COwned::try_new(&obj) }.unwrap();
+    assert_eq!(
+        "Layers { layers: [None, Some(Layer { ty: Layer, name: Some(\"overlay\") })] }",
+        format!("{:?}", &*owned)
+    );
+}
+
+#[test]
+fn inline_array_owned_frees_partial_copy_on_error() {
+    with_pass(|obj| unsafe {
+        // Root, two layer names, an extent, and names and extents of two attachments.
+        assert_frees_partial_copy_on_error(8, |alloc| COwned::try_new_in(obj, alloc).map(drop));
+    });
+}
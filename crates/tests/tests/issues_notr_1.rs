@@ -1,4 +1,4 @@
-use cdump::{CDeserialize, CDumpBufferWriter, CSerialize};
+use cdump::{alloc::DestinationPolicy, CDeserialize, CDumpBufferWriter, CSerialize};
 
 #[test]
 fn builtin_deserialize_to() {
@@ -12,7 +12,14 @@ fn builtin_deserialize_to() {
     let mut reader = buf.into_reader();
     let mut dst = [0u32; 4];
     for i in 0..dst.len() {
-        unsafe { u32::deserialize_to(&mut reader, dst.as_mut_ptr().add(i)) };
+        unsafe {
+            u32::deserialize_to(
+                &mut reader,
+                dst.as_mut_ptr().add(i),
+                &DestinationPolicy::reject(),
+            )
+        }
+        .unwrap();
     }
 
     for (a, b) in initial.iter().zip(dst.iter()) {
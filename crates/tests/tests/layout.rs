@@ -0,0 +1,273 @@
+use std::mem;
+
+use cdump::{
+    internal::LayoutHasher, CDeserialize, CDumpBufferReader, CDumpBufferWriter, CSerialize, Error,
+};
+
+mod v1 {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Foo {
+        pub a: u32,
+        pub text: *const c_char,
+        pub len: u32,
+        #[cdump(array(len = self.len))]
+        pub bars: *const Bar,
+    }
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Bar {
+        pub value: u32,
+    }
+}
+
+mod same {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Foo {
+        pub a: u32,
+        pub text: *const c_char,
+        pub len: u32,
+        #[cdump(array(len = self.len))]
+        pub bars: *const Bar,
+    }
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Bar {
+        pub value: u32,
+    }
+}
+
+mod renamed_field {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Foo {
+        pub b: u32,
+        pub text: *const c_char,
+        pub len: u32,
+        #[cdump(array(len = self.len))]
+        pub bars: *const super::v1::Bar,
+    }
+}
+
+mod other_length {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Foo {
+        pub a: u32,
+        pub text: *const c_char,
+        pub len: u32,
+        #[cdump(array(len = self.len - 1))]
+        pub bars: *const super::v1::Bar,
+    }
+}
+
+mod bigger_pointee {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Foo {
+        pub a: u32,
+        pub text: *const c_char,
+        pub len: u32,
+        #[cdump(array(len = self.len))]
+        pub bars: *const Bar,
+    }
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Bar {
+        pub value: u64,
+    }
+}
+
+mod renamed_pointee_field {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Foo {
+        pub a: u32,
+        pub text: *const c_char,
+        pub len: u32,
+        #[cdump(array(len = self.len))]
+        pub bars: *const Bar,
+    }
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Bar {
+        pub other: u32,
+    }
+}
+
+mod full_path {
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Foo {
+        pub a: u32,
+        pub text: *const std::ffi::c_char,
+        pub len: u32,
+        #[cdump(array(len = self.len))]
+        pub bars: *const super::v1::Bar,
+    }
+}
+
+mod nested {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Outer {
+        #[cdump(nested)]
+        pub inner: Inner,
+    }
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Inner {
+        pub text: *const c_char,
+        pub value: u32,
+    }
+}
+
+mod nested_renamed_field {
+    use std::ffi::c_char;
+
+    use cdump::{CDeserialize, CSerialize};
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Outer {
+        #[cdump(nested)]
+        pub inner: Inner,
+    }
+
+    #[derive(CSerialize, CDeserialize)]
+    #[repr(C)]
+    pub struct Inner {
+        pub text: *const c_char,
+        pub other: u32,
+    }
+}
+
+const V1: u64 = <v1::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH;
+
+#[test]
+fn hash_is_equal_for_same_definition() {
+    assert_eq!(V1, <v1::Foo as CSerialize<CDumpBufferWriter>>::LAYOUT_HASH);
+    assert_eq!(
+        V1,
+        <same::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH
+    );
+    assert_eq!(
+        V1,
+        <full_path::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH
+    );
+}
+
+#[test]
+fn hash_covers_layout_and_annotations() {
+    let hashes = [
+        V1,
+        <renamed_field::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+        <other_length::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+        <bigger_pointee::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+        <renamed_pointee_field::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+    ];
+    for (i, a) in hashes.iter().enumerate() {
+        for b in &hashes[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+}
+
+#[test]
+fn hash_covers_nested_types() {
+    assert_ne!(
+        <nested::Outer as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+        <nested_renamed_field::Outer as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH
+    );
+}
+
+#[test]
+fn hash_covers_size_of_primitives() {
+    // Width of `usize` depends on the platform, so the name alone is not enough.
+    assert_eq!(
+        LayoutHasher::new()
+            .write_str("usize")
+            .write_usize(mem::size_of::<usize>())
+            .write_usize(mem::align_of::<usize>())
+            .finish(),
+        <usize as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH
+    );
+    assert_ne!(
+        LayoutHasher::new().write_str("usize").finish(),
+        <usize as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH
+    );
+}
+
+fn serialize_v1() -> CDumpBufferWriter {
+    let text = c"Hello world!";
+    let bars = [v1::Bar { value: 7 }];
+    let obj = v1::Foo {
+        a: 1984,
+        text: text.as_ptr(),
+        len: bars.len() as u32,
+        bars: bars.as_ptr(),
+    };
+
+    let mut buf = CDumpBufferWriter::new(16);
+    unsafe { obj.try_serialize_with_layout(&mut buf) }.unwrap();
+    buf
+}
+
+#[test]
+fn matching_layout() {
+    let mut reader = serialize_v1().into_reader();
+    let copy = unsafe { same::Foo::try_deserialize_ref_with_layout(&mut reader) }.unwrap();
+    assert_eq!(copy.a, 1984);
+    assert_eq!(unsafe { (*copy.bars).value }, 7);
+}
+
+#[test]
+fn mismatched_layout() {
+    let mut reader = serialize_v1().into_reader();
+    let err = unsafe { bigger_pointee::Foo::try_deserialize_ref_with_layout(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        Error::LayoutMismatch {
+            expected: <bigger_pointee::Foo as CDeserialize<CDumpBufferReader>>::LAYOUT_HASH,
+            found: V1,
+        }
+    );
+}
@@ -0,0 +1,213 @@
+use std::{
+    ffi::{c_char, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize,
+};
+use tests::{
+    assert_frees_partial_copy_on_error, eval_debug, reader_from_bytes, serialize_to_bytes,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, CSerialize, CDeserialize)]
+#[repr(C)]
+struct MapEntry {
+    constant_id: u32,
+    offset: u32,
+    size: usize,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct SpecializationInfo {
+    map_entry_count: u32,
+    #[cdump(array(len = self.map_entry_count))]
+    map_entries: *const MapEntry,
+    data_size: usize,
+    #[cdump(array(len = self.data_size))]
+    data: *const u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Offset {
+    x: i32,
+    y: i32,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Text {
+    value: *const c_char,
+}
+
+/// Struct by value in the nested struct, which pointers are followed too.
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Label {
+    offset: Offset,
+    #[cdump(nested)]
+    text: Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, CSerialize, CDeserialize)]
+#[repr(u32)]
+enum Stage {
+    Vertex = 1,
+    Fragment = 16,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct ShaderStage {
+    #[cdump(nested)]
+    stage: Stage,
+    name: *const c_char,
+    #[cdump(nested)]
+    specialization: SpecializationInfo,
+    #[cdump(nested)]
+    label: Label,
+}
+
+fn with_stage<T>(f: impl FnOnce(&ShaderStage) -> T) -> T {
+    let entries = [
+        MapEntry {
+            constant_id: 0,
+            offset: 0,
+            size: 4,
+        },
+        MapEntry {
+            constant_id: 1,
+            offset: 4,
+            size: 4,
+        },
+    ];
+    let data = [1u8, 2, 3, 4, 5, 6, 7, 8];
+
+    f(&ShaderStage {
+        stage: Stage::Fragment,
+        name: c"main".as_ptr(),
+        specialization: SpecializationInfo {
+            map_entry_count: entries.len() as u32,
+            map_entries: entries.as_ptr(),
+            data_size: data.len(),
+            data: data.as_ptr(),
+        },
+        label: Label {
+            offset: Offset { x: 3, y: -4 },
+            text: Text {
+                value: c"shadow pass".as_ptr(),
+            },
+        },
+    })
+}
+
+unsafe fn assert_stage(copy: &ShaderStage) {
+    assert_eq!(Stage::Fragment, copy.stage);
+    assert_eq!(c"main", CStr::from_ptr(copy.name));
+
+    let specialization = &copy.specialization;
+    let entries = std::slice::from_raw_parts(
+        specialization.map_entries,
+        specialization.map_entry_count as usize,
+    );
+    assert_eq!(2, entries.len());
+    assert_eq!(4, entries[1].offset);
+    assert_eq!(
+        [1, 2, 3, 4, 5, 6, 7, 8],
+        std::slice::from_raw_parts(specialization.data, specialization.data_size)
+    );
+
+    assert_eq!(Offset { x: 3, y: -4 }, copy.label.offset);
+    assert_eq!(c"shadow pass", CStr::from_ptr(copy.label.text.value));
+}
+
+#[test]
+fn nested() {
+    let mut reader = with_stage(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { ShaderStage::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_stage(copy) };
+}
+
+#[test]
+fn nested_checked() {
+    let mut reader = reader_from_bytes(&with_stage(serialize_to_bytes));
+    unsafe { assert_stage(ShaderStage::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn nested_serialized_size() {
+    with_stage(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn nested_owned() {
+    let owned = with_stage(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_stage(&owned) };
+}
+
+#[test]
+fn nested_deserialize_to() {
+    let mut reader = reader_from_bytes(&with_stage(serialize_to_bytes));
+    // Stage has no zero variant, so the destination cannot be zeroed.
+    let mut copy = ShaderStage {
+        stage: Stage::Vertex,
+        name: ptr::null(),
+        specialization: unsafe { mem::zeroed() },
+        label: unsafe { mem::zeroed() },
+    };
+    unsafe {
+        ShaderStage::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate())
+            .unwrap();
+        assert_stage(&copy);
+    }
+}
+
+#[test]
+fn nested_debug() {
+    with_stage(|obj| {
+        let debug = format!("{obj:?}");
+        assert!(
+            debug.contains(
+                "label: Label { offset: Offset { x: 3, y: -4 }, text: Text { value: Some(\"shadow pass\") } }"
+            ),
+            "{debug}"
+        );
+    });
+}
+
+#[test]
+fn nested_enum_is_validated() {
+    let mut bytes = with_stage(serialize_to_bytes);
+    // Stage is the first field of the root object.
+    bytes[..mem::size_of::<u32>()].copy_from_slice(&3u32.to_ne_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { ShaderStage::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        "`ShaderStage::stage`: unknown discriminant 3",
+        err.to_string()
+    );
+}
+
+#[test]
+fn nested_owned_frees_partial_copy_on_error() {
+    with_stage(|obj| unsafe {
+        // Root, name, map entries, data, and text of the label.
+        assert_frees_partial_copy_on_error(5, |alloc| COwned::try_new_in(obj, alloc).map(drop));
+    });
+}
@@ -0,0 +1,288 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    ptr,
+};
+
+use cdump::{
+    alloc::{CDumpAllocator, LibcAllocator},
+    CDebug, CDeserialize, COwned, CSerialize, Error,
+};
+use tests::{
+    assert_frees_partial_copy_on_error, eval_debug, reader_from_bytes, serialize_to_bytes,
+    CountingAllocator,
+};
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    a: u32,
+    text: *const c_char,
+    bar: *const Bar,
+    number: *const u64,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *const Bar,
+    #[cdump(array(len = self.len))]
+    bar_ptrs: *const *const Bar,
+    #[cdump(array(len = self.len))]
+    texts: *const *const c_char,
+    #[cdump(array(len = self.len))]
+    numbers: *const u16,
+    #[cdump(dynamic(serializer = dynamic_serializer, deserializer = dynamic_deserializer, try_deserializer = dynamic_try_deserializer, size_of = dynamic_size_of, deep_copy = dynamic_deep_copy, free = dynamic_free))]
+    dynamic: *const c_void,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Bar {
+    value: u32,
+    text: *const c_char,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Unsupported {
+    text: *const c_char,
+    #[cdump(dynamic(serializer = dynamic_serializer, deserializer = dynamic_deserializer, size_of = dynamic_size_of))]
+    dynamic: *const c_void,
+}
+
+unsafe fn dynamic_serializer<T: cdump::CDumpWriter>(buf: &mut T, obj: *const c_void) {
+    Bar::serialize(&*(obj as *const Bar), buf)
+}
+
+unsafe fn dynamic_deserializer<T: cdump::CDumpReader>(buf: &mut T) -> (*mut c_void, usize) {
+    (
+        Bar::deserialize_ref_mut(buf) as *mut Bar as *mut c_void,
+        std::mem::size_of::<Bar>(),
+    )
+}
+
+unsafe fn dynamic_try_deserializer<T: cdump::CDumpReader>(
+    buf: &mut T,
+) -> Result<(*mut c_void, usize), Error> {
+    Ok((
+        Bar::try_deserialize_ref_mut(buf)? as *mut Bar as *mut c_void,
+        std::mem::size_of::<Bar>(),
+    ))
+}
+
+fn dynamic_size_of(_obj: *const c_void) -> usize {
+    std::mem::size_of::<Bar>()
+}
+
+unsafe fn dynamic_deep_copy<A: CDumpAllocator>(
+    obj: *const c_void,
+    alloc: &A,
+) -> Result<*mut c_void, Error> {
+    Ok(cdump::alloc::try_deep_copy(obj as *const Bar, alloc)? as *mut c_void)
+}
+
+unsafe fn dynamic_free<A: CDumpAllocator>(obj: *mut c_void, alloc: &A) {
+    cdump::alloc::deep_free(obj as *mut Bar, alloc)
+}
+
+extern "C" {
+    fn free(ptr: *mut c_void);
+}
+
+fn with_foo(f: impl FnOnce(&Foo)) {
+    let text = c"Hello world!";
+    let bar_texts = [c"first", c"second"];
+    let bars = [
+        Bar {
+            value: 1,
+            text: bar_texts[0].as_ptr(),
+        },
+        Bar {
+            value: 2,
+            text: bar_texts[1].as_ptr(),
+        },
+    ];
+    let bar_ptrs = [&bars[1] as *const Bar, &bars[0] as *const Bar];
+    let texts = [text.as_ptr(), bar_texts[1].as_ptr()];
+    let numbers = [7u16, 8];
+    let number = 1984u64;
+    let dynamic = Bar {
+        value: 3,
+        text: text.as_ptr(),
+    };
+
+    f(&Foo {
+        a: 42,
+        text: text.as_ptr(),
+        bar: &bars[0],
+        number: &number,
+        len: 2,
+        bars: bars.as_ptr(),
+        bar_ptrs: bar_ptrs.as_ptr(),
+        texts: texts.as_ptr(),
+        numbers: numbers.as_ptr(),
+        dynamic: &dynamic as *const Bar as *const c_void,
+    })
+}
+
+unsafe fn assert_bar_eq(obj: *const Bar, copy: *const Bar) {
+    assert_ne!(obj, copy);
+    assert_eq!((*obj).value, (*copy).value);
+    assert_ne!((*obj).text, (*copy).text);
+    assert_eq!(CStr::from_ptr((*obj).text), CStr::from_ptr((*copy).text));
+}
+
+unsafe fn assert_foo_eq(obj: &Foo, copy: &Foo) {
+    assert_eq!(obj.a, copy.a);
+    assert_ne!(obj.text, copy.text);
+    assert_eq!(CStr::from_ptr(obj.text), CStr::from_ptr(copy.text));
+    assert_bar_eq(obj.bar, copy.bar);
+    assert_ne!(obj.number, copy.number);
+    assert_eq!(*obj.number, *copy.number);
+    assert_eq!(obj.len, copy.len);
+    for i in 0..obj.len as usize {
+        assert_bar_eq(obj.bars.add(i), copy.bars.add(i));
+        assert_bar_eq(*obj.bar_ptrs.add(i), *copy.bar_ptrs.add(i));
+        assert_ne!(*obj.texts.add(i), *copy.texts.add(i));
+        assert_eq!(
+            CStr::from_ptr(*obj.texts.add(i)),
+            CStr::from_ptr(*copy.texts.add(i))
+        );
+        assert_eq!(*obj.numbers.add(i), *copy.numbers.add(i));
+    }
+    assert_ne!(obj.numbers, copy.numbers);
+    assert_bar_eq(obj.dynamic as *const Bar, copy.dynamic as *const Bar);
+}
+
+#[test]
+fn owned() {
+    with_foo(|obj| unsafe {
+        let copy = COwned::try_new(obj).unwrap();
+        eval_debug(&copy);
+        assert_foo_eq(obj, &copy);
+    });
+}
+
+#[test]
+fn owned_frees_every_allocation() {
+    with_foo(|obj| unsafe {
+        let alloc = CountingAllocator::default();
+        let copy = COwned::try_new_in(obj, &alloc).unwrap();
+        assert_foo_eq(obj, &copy);
+        // Root, text, bar with text, number, bars array with 2 texts, pointer array with 2 bars with texts,
+        // texts array with 2 texts, numbers array, and dynamic bar with text.
+        assert_eq!(19, alloc.live());
+        drop(copy);
+    });
+}
+
+#[test]
+fn owned_outlives_buffer() {
+    let copy = with_foo_bytes(|bytes| unsafe {
+        let mut reader = reader_from_bytes(bytes);
+        COwned::<Foo>::try_deserialize(&mut reader).unwrap()
+    });
+
+    unsafe {
+        assert_eq!(42, copy.a);
+        assert_eq!(c"Hello world!", CStr::from_ptr(copy.text));
+        assert_eq!(c"second", CStr::from_ptr((*copy.bars.add(1)).text));
+        assert_eq!(1, (**copy.bar_ptrs.add(1)).value);
+    }
+}
+
+fn with_foo_bytes<T>(f: impl FnOnce(&[u8]) -> T) -> T {
+    let mut result = None;
+    with_foo(|obj| result = Some(f(&serialize_to_bytes(obj))));
+    result.unwrap()
+}
+
+#[test]
+fn owned_frees_partial_copy_on_error() {
+    with_foo(|obj| unsafe {
+        assert_frees_partial_copy_on_error(19, |alloc| COwned::try_new_in(obj, alloc).map(drop));
+    });
+}
+
+#[test]
+fn owned_unsupported_dynamic() {
+    let text = c"Hello world!";
+    let bar = Bar {
+        value: 1,
+        text: text.as_ptr(),
+    };
+    let obj = Unsupported {
+        text: text.as_ptr(),
+        dynamic: &bar as *const Bar as *const c_void,
+    };
+
+    let alloc = CountingAllocator::default();
+    let err = unsafe { COwned::try_new_in(&obj, &alloc) }.err().unwrap();
+    assert_eq!(
+        Error::UnsupportedDynamic.in_field("Unsupported", "dynamic"),
+        err
+    );
+
+    let obj = Unsupported {
+        text: text.as_ptr(),
+        dynamic: ptr::null(),
+    };
+    let copy = unsafe { COwned::try_new_in(&obj, &alloc) }.unwrap();
+    assert!(copy.dynamic.is_null());
+}
+
+#[test]
+fn owned_into_raw_freed_by_c() {
+    with_foo(|obj| unsafe {
+        let copy = COwned::try_new_in(obj, LibcAllocator).unwrap();
+        assert_foo_eq(obj, &copy);
+
+        let raw = copy.into_raw();
+        free((*raw).text as *mut c_void);
+        free((*(*raw).bar).text as *mut c_void);
+        free((*raw).bar as *mut c_void);
+        free((*raw).number as *mut c_void);
+        for i in 0..(*raw).len as usize {
+            free((*(*raw).bars.add(i)).text as *mut c_void);
+            free((**(*raw).bar_ptrs.add(i)).text as *mut c_void);
+            free(*(*raw).bar_ptrs.add(i) as *mut c_void);
+            free(*(*raw).texts.add(i) as *mut c_void);
+        }
+        free((*raw).bars as *mut c_void);
+        free((*raw).bar_ptrs as *mut c_void);
+        free((*raw).texts as *mut c_void);
+        free((*raw).numbers as *mut c_void);
+        free((*((*raw).dynamic as *const Bar)).text as *mut c_void);
+        free((*raw).dynamic as *mut c_void);
+        free(raw as *mut c_void);
+    });
+}
+
+#[test]
+fn owned_empty_arrays_freed_by_c() {
+    let obj = Foo {
+        a: 0,
+        text: ptr::null(),
+        bar: ptr::null(),
+        number: ptr::null(),
+        len: 0,
+        bars: [].as_ptr(),
+        bar_ptrs: [].as_ptr(),
+        texts: [].as_ptr(),
+        numbers: [].as_ptr(),
+        dynamic: ptr::null(),
+    };
+
+    unsafe {
+        let raw = COwned::try_new_in(&obj, LibcAllocator).unwrap().into_raw();
+
+        // Empty arrays are allocated too, so C code can free every pointer of the copy.
+        for array in [
+            (*raw).bars as *mut c_void,
+            (*raw).bar_ptrs as *mut c_void,
+            (*raw).texts as *mut c_void,
+            (*raw).numbers as *mut c_void,
+        ] {
+            assert!(!array.is_null());
+            free(array);
+        }
+        free(raw as *mut c_void);
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize, Error,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+const STRUCTURE_TYPE_CREATE_INFO: i32 = 3;
+const STRUCTURE_TYPE_FEATURES: i32 = 1000059000;
+const STRUCTURE_TYPE_LAYERS: i32 = 1000059001;
+const STRUCTURE_TYPE_UNKNOWN: i32 = 1000999000;
+const STRUCTURE_TYPE_UNKNOWN_I64: i64 = STRUCTURE_TYPE_UNKNOWN as i64;
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct CreateInfo {
+    s_type: i32,
+    #[cdump(pnext(registry = Chain))]
+    p_next: *const c_void,
+    queue_count: u32,
+    name: *const c_char,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[cdump(stype = STRUCTURE_TYPE_FEATURES)]
+#[repr(C)]
+struct Features {
+    s_type: i32,
+    #[cdump(pnext(registry = Chain))]
+    p_next: *mut c_void,
+    robust_access: u32,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[cdump(stype = STRUCTURE_TYPE_LAYERS)]
+#[repr(C)]
+struct Layers {
+    s_type: i32,
+    #[cdump(pnext(registry = Chain))]
+    p_next: *const c_void,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    names: *const *const c_char,
+}
+
+#[repr(C)]
+struct Unknown {
+    s_type: i32,
+    p_next: *const c_void,
+    value: u64,
+}
+
+cdump::pnext_registry! {
+    struct Chain {
+        Features,
+        Layers,
+    }
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct LenientCreateInfo {
+    s_type: i32,
+    #[cdump(pnext(registry = LenientChain))]
+    p_next: *const c_void,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[cdump(stype = STRUCTURE_TYPE_FEATURES)]
+#[repr(C)]
+struct LenientFeatures {
+    s_type: i32,
+    #[cdump(pnext(registry = LenientChain))]
+    p_next: *mut c_void,
+    robust_access: u32,
+}
+
+cdump::pnext_registry! {
+    struct LenientChain {
+        LenientFeatures,
+    }
+    unknown = Skip;
+}
+
+fn with_create_info<T>(f: impl FnOnce(&CreateInfo) -> T) -> T {
+    let names = [c"validation".as_ptr(), c"profiles".as_ptr()];
+    let layers = Layers {
+        s_type: STRUCTURE_TYPE_LAYERS,
+        p_next: ptr::null(),
+        len: names.len() as u32,
+        names: names.as_ptr(),
+    };
+    let features = Features {
+        s_type: STRUCTURE_TYPE_FEATURES,
+        p_next: &layers as *const Layers as *mut c_void,
+        robust_access: 1,
+    };
+
+    f(&CreateInfo {
+        s_type: STRUCTURE_TYPE_CREATE_INFO,
+        p_next: &features as *const Features as *const c_void,
+        queue_count: 4,
+        name: c"device".as_ptr(),
+    })
+}
+
+unsafe fn assert_chain(copy: &CreateInfo) {
+    assert_eq!(4, copy.queue_count);
+    assert_eq!(c"device", CStr::from_ptr(copy.name));
+
+    let features = &*(copy.p_next as *const Features);
+    assert_eq!(STRUCTURE_TYPE_FEATURES, features.s_type);
+    assert_eq!(1, features.robust_access);
+
+    let layers = &*(features.p_next as *const Layers);
+    assert_eq!(STRUCTURE_TYPE_LAYERS, layers.s_type);
+    assert!(layers.p_next.is_null());
+    assert_eq!(2, layers.len);
+    assert_eq!(c"validation", CStr::from_ptr(*layers.names));
+    assert_eq!(c"profiles", CStr::from_ptr(*layers.names.add(1)));
+}
+
+#[test]
+fn chain() {
+    let mut reader = with_create_info(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { CreateInfo::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_chain(copy) };
+}
+
+#[test]
+fn chain_debug() {
+    with_create_info(|obj| {
+        let debug = format!("{obj:?}");
+        assert!(
+            debug.contains("p_next: Some([1000059000, 1000059001])"),
+            "{debug}"
+        );
+    });
+}
+
+#[test]
+fn chain_checked() {
+    let mut reader = reader_from_bytes(&with_create_info(serialize_to_bytes));
+    unsafe { assert_chain(CreateInfo::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn chain_serialized_size() {
+    with_create_info(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn chain_owned() {
+    let owned = with_create_info(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_chain(&owned) };
+}
+
+#[test]
+fn chain_deserialize_to() {
+    let mut reader = reader_from_bytes(&with_create_info(serialize_to_bytes));
+    let mut copy = unsafe { mem::zeroed::<CreateInfo>() };
+    unsafe {
+        CreateInfo::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap();
+        assert_chain(&copy);
+    }
+}
+
+#[test]
+fn unknown_structure_is_rejected() {
+    let unknown = Unknown {
+        s_type: STRUCTURE_TYPE_UNKNOWN,
+        p_next: ptr::null(),
+        value: 42,
+    };
+    let obj = CreateInfo {
+        s_type: STRUCTURE_TYPE_CREATE_INFO,
+        p_next: &unknown as *const Unknown as *const c_void,
+        queue_count: 1,
+        name: c"device".as_ptr(),
+    };
+
+    let mut buf = CDumpBufferWriter::new(16);
+    let err = unsafe { obj.try_serialize(&mut buf) }.err().unwrap();
+    assert!(matches!(
+        err.root_cause(),
+        Error::UnknownDiscriminant(STRUCTURE_TYPE_UNKNOWN_I64)
+    ));
+    assert_eq!(usize::MAX, unsafe { obj.serialized_size() });
+}
+
+#[test]
+fn unknown_structure_is_skipped() {
+    let last = Unknown {
+        s_type: STRUCTURE_TYPE_UNKNOWN,
+        p_next: ptr::null(),
+        value: 3,
+    };
+    let second = LenientFeatures {
+        s_type: STRUCTURE_TYPE_FEATURES,
+        p_next: &last as *const Unknown as *mut c_void,
+        robust_access: 2,
+    };
+    let middle = Unknown {
+        s_type: STRUCTURE_TYPE_UNKNOWN,
+        p_next: &second as *const LenientFeatures as *const c_void,
+        value: 2,
+    };
+    let features = LenientFeatures {
+        s_type: STRUCTURE_TYPE_FEATURES,
+        p_next: &middle as *const Unknown as *mut c_void,
+        robust_access: 1,
+    };
+    let first = Unknown {
+        s_type: STRUCTURE_TYPE_UNKNOWN,
+        p_next: &features as *const LenientFeatures as *const c_void,
+        value: 1,
+    };
+    let obj = LenientCreateInfo {
+        s_type: STRUCTURE_TYPE_CREATE_INFO,
+        p_next: &first as *const Unknown as *const c_void,
+    };
+
+    let bytes = serialize_to_bytes(&obj);
+    assert_eq!(bytes.len(), unsafe { obj.serialized_size() });
+
+    let mut reader = reader_from_bytes(&bytes);
+    unsafe {
+        let copy = LenientCreateInfo::try_deserialize_ref(&mut reader).unwrap();
+        let features = &*(copy.p_next as *const LenientFeatures);
+        assert_eq!(STRUCTURE_TYPE_FEATURES, features.s_type);
+        assert_eq!(1, features.robust_access);
+
+        // Unknown structure between the features is skipped too.
+        let second = &*(features.p_next as *const LenientFeatures);
+        assert_eq!(2, second.robust_access);
+        assert!(second.p_next.is_null());
+    }
+
+    let owned = unsafe { COwned::try_new(&obj) }.unwrap();
+    unsafe {
+        let features = &*(owned.p_next as *const LenientFeatures);
+        let second = &*(features.p_next as *const LenientFeatures);
+        assert_eq!(2, second.robust_access);
+        assert!(second.p_next.is_null());
+    }
+}
+
+#[test]
+fn unregistered_structure_in_buffer() {
+    let mut bytes = with_create_info(serialize_to_bytes);
+    // `sType` of the first node follows its presence flag after the shallow copy of `CreateInfo`.
+    let index = mem::size_of::<CreateInfo>() + mem::size_of::<i32>();
+    bytes[index..index + mem::size_of::<i32>()]
+        .copy_from_slice(&STRUCTURE_TYPE_UNKNOWN.to_ne_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { CreateInfo::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err.root_cause(),
+        Error::UnknownDiscriminant(STRUCTURE_TYPE_UNKNOWN_I64)
+    ));
+}
+
+#[test]
+fn cyclic_chain() {
+    let mut layers = Layers {
+        s_type: STRUCTURE_TYPE_LAYERS,
+        p_next: ptr::null(),
+        len: 0,
+        names: ptr::null(),
+    };
+    let layers_ptr = ptr::addr_of_mut!(layers);
+    let features = Features {
+        s_type: STRUCTURE_TYPE_FEATURES,
+        p_next: layers_ptr as *mut c_void,
+        robust_access: 1,
+    };
+    unsafe { (*layers_ptr).p_next = &features as *const Features as *const c_void };
+    let obj = CreateInfo {
+        s_type: STRUCTURE_TYPE_CREATE_INFO,
+        p_next: &features as *const Features as *const c_void,
+        queue_count: 4,
+        name: ptr::null(),
+    };
+
+    let debug = format!("{obj:?}");
+    assert!(
+        debug.contains("p_next: Some([1000059000, 1000059001, <cycle>])"),
+        "{debug}"
+    );
+    assert_eq!(usize::MAX, unsafe { obj.serialized_size() });
+    let err = unsafe { COwned::try_new(&obj) }.err().unwrap();
+    assert_eq!(&Error::Cycle { ty: "Features" }, err.root_cause());
+}
+
+#[test]
+fn cyclic_chain_of_unknown_structures() {
+    let mut first = Unknown {
+        s_type: STRUCTURE_TYPE_UNKNOWN,
+        p_next: ptr::null(),
+        value: 1,
+    };
+    let first_ptr = ptr::addr_of_mut!(first);
+    let second = Unknown {
+        s_type: STRUCTURE_TYPE_UNKNOWN,
+        p_next: first_ptr as *const c_void,
+        value: 2,
+    };
+    unsafe { (*first_ptr).p_next = &second as *const Unknown as *const c_void };
+    let obj = LenientCreateInfo {
+        s_type: STRUCTURE_TYPE_CREATE_INFO,
+        p_next: first_ptr as *const c_void,
+    };
+
+    let mut buf = CDumpBufferWriter::new(16);
+    let err = unsafe { obj.try_serialize(&mut buf) }.err().unwrap();
+    assert!(matches!(err.root_cause(), Error::Cycle { .. }), "{err:?}");
+    assert_eq!(usize::MAX, unsafe { obj.serialized_size() });
+    let err = unsafe { COwned::try_new(&obj) }.err().unwrap();
+    assert!(matches!(err.root_cause(), Error::Cycle { .. }), "{err:?}");
+}
@@ -0,0 +1,224 @@
+use std::{
+    ffi::{c_char, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    offset_of, CDeserialize, CDumpBufferWriter, CDumpWriter, CPortable, CSerialize, Error,
+};
+use tests::reader_from_bytes;
+
+#[derive(CSerialize, CDeserialize, CPortable)]
+#[repr(C)]
+struct Foo {
+    a: u16,
+    flag: bool,
+    text: *const c_char,
+    value: f32,
+    size: usize,
+    bar: *const Bar,
+    inline: [i16; 2],
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *const Bar,
+    #[cdump(array(len = self.len))]
+    texts: *const *const c_char,
+    #[cdump(array(len = self.len))]
+    numbers: *const u64,
+}
+
+#[derive(CSerialize, CDeserialize, CPortable)]
+#[repr(C)]
+struct Bar {
+    value: u32,
+    text: *const c_char,
+}
+
+#[derive(CSerialize, CDeserialize, CPortable)]
+#[repr(C)]
+struct Small {
+    a: u16,
+    text: *const c_char,
+    b: u32,
+}
+
+fn serialize_portable<T: CPortable>(obj: &T) -> Vec<u8> {
+    let mut buf = CDumpBufferWriter::new(16);
+    unsafe {
+        obj.try_serialize_portable(&mut buf).unwrap();
+        std::slice::from_raw_parts(buf.as_mut_ptr_at(0), buf.len()).to_vec()
+    }
+}
+
+fn transcode<T: CPortable>(bytes: &[u8]) -> Result<CDumpBufferWriter, Error> {
+    let mut src = reader_from_bytes(bytes);
+    let mut dst = CDumpBufferWriter::new(16);
+    unsafe { T::try_transcode_portable(&mut src, &mut dst) }?;
+    Ok(dst)
+}
+
+#[test]
+fn round_trip() {
+    let text = c"Hello world!";
+    let bar_text = c"Hello bar!";
+    let bar = Bar {
+        value: 7,
+        text: bar_text.as_ptr(),
+    };
+    let bars = [
+        Bar {
+            value: 1,
+            text: text.as_ptr(),
+        },
+        Bar {
+            value: 2,
+            text: ptr::null(),
+        },
+    ];
+    let texts = [bar_text.as_ptr(), text.as_ptr()];
+    let numbers = [u64::MAX, 1984];
+    let obj = Foo {
+        a: 0x1234,
+        flag: true,
+        text: text.as_ptr(),
+        value: 1.5,
+        size: 4096,
+        bar: &bar,
+        inline: [-1, 2],
+        len: bars.len() as u32,
+        bars: bars.as_ptr(),
+        texts: texts.as_ptr(),
+        numbers: numbers.as_ptr(),
+    };
+
+    let buf = transcode::<Foo>(&serialize_portable(&obj)).unwrap();
+    let mut reader = buf.into_reader();
+    let copy = unsafe { Foo::try_deserialize_ref(&mut reader) }.unwrap();
+
+    assert_eq!(obj.a, copy.a);
+    assert_eq!(obj.flag, copy.flag);
+    assert_eq!(unsafe { CStr::from_ptr(copy.text) }, text);
+    assert_eq!(obj.value, copy.value);
+    assert_eq!(obj.size, copy.size);
+    unsafe {
+        assert_eq!((*copy.bar).value, bar.value);
+        assert_eq!(CStr::from_ptr((*copy.bar).text), bar_text);
+    }
+    assert_eq!(obj.inline, copy.inline);
+    assert_eq!(obj.len, copy.len);
+    for i in 0..bars.len() {
+        unsafe {
+            assert_eq!((*copy.bars.add(i)).value, bars[i].value);
+            assert_eq!(CStr::from_ptr(*copy.texts.add(i)), CStr::from_ptr(texts[i]));
+            assert_eq!(*copy.numbers.add(i), numbers[i]);
+        }
+    }
+    unsafe {
+        assert_eq!(CStr::from_ptr((*copy.bars).text), text);
+        assert!((*copy.bars.add(1)).text.is_null());
+    }
+}
+
+#[test]
+fn null_cstring_in_array() {
+    let texts = [c"first".as_ptr(), ptr::null(), c"third".as_ptr()];
+    let obj = Foo {
+        a: 0,
+        flag: false,
+        text: ptr::null(),
+        value: 0.0,
+        size: 0,
+        bar: ptr::null(),
+        inline: [0, 0],
+        len: texts.len() as u32,
+        bars: ptr::null(),
+        texts: texts.as_ptr(),
+        numbers: ptr::null(),
+    };
+
+    let buf = transcode::<Foo>(&serialize_portable(&obj)).unwrap();
+    let mut reader = buf.into_reader();
+    let copy = unsafe { Foo::try_deserialize_ref(&mut reader) }.unwrap();
+    unsafe {
+        assert_eq!(c"first", CStr::from_ptr(*copy.texts));
+        assert!((*copy.texts.add(1)).is_null());
+        assert_eq!(c"third", CStr::from_ptr(*copy.texts.add(2)));
+    }
+}
+
+#[test]
+fn fixed_encoding() {
+    let text = c"hi";
+    let obj = Small {
+        a: 0x0102,
+        text: text.as_ptr(),
+        b: 0x03040506,
+    };
+
+    // Same bytes are produced on every platform, without padding and with 8 bytes long pointer slot.
+    let expected = [
+        0x02, 0x01, // a
+        3, 0, 0, 0, 0, 0, 0, 0, // text slot
+        0x06, 0x05, 0x04, 0x03, // b
+        b'h', b'i', 0, // text
+    ];
+    assert_eq!(serialize_portable(&obj), expected);
+
+    let buf = transcode::<Small>(&expected).unwrap();
+    let mut reader = buf.into_reader();
+    let copy = unsafe { Small::deserialize_ref(&mut reader) };
+    assert_eq!(copy.a, obj.a);
+    assert_eq!(unsafe { CStr::from_ptr(copy.text) }, text);
+    assert_eq!(copy.b, obj.b);
+}
+
+#[test]
+fn truncated_data() {
+    let text = c"hi";
+    let obj = Small {
+        a: 1,
+        text: text.as_ptr(),
+        b: 2,
+    };
+
+    let bytes = serialize_portable(&obj);
+    let err = transcode::<Small>(&bytes[..bytes.len() - 1]).err().unwrap();
+    assert!(matches!(err.root_cause(), Error::OutOfBounds { .. }));
+    assert!(matches!(err, Error::Field { field: "text", .. }));
+}
+
+#[test]
+fn missing_nul_terminator() {
+    let mut bytes = serialize_portable(&Small {
+        a: 1,
+        text: c"hi".as_ptr(),
+        b: 2,
+    });
+    *bytes.last_mut().unwrap() = b'!';
+
+    let err = transcode::<Small>(&bytes).err().unwrap();
+    assert!(matches!(
+        err.root_cause(),
+        Error::MissingNulTerminator { .. }
+    ));
+}
+
+#[test]
+fn padding_is_zeroed() {
+    let bytes = serialize_portable(&Small {
+        a: 1,
+        text: c"hi".as_ptr(),
+        b: 2,
+    });
+
+    let mut buf = transcode::<Small>(&bytes).unwrap();
+    let shallow =
+        unsafe { std::slice::from_raw_parts(buf.as_mut_ptr_at(0), mem::size_of::<Small>()) };
+    let padding = [
+        mem::size_of::<u16>()..offset_of!(Small, text),
+        offset_of!(Small, b) + mem::size_of::<u32>()..mem::size_of::<Small>(),
+    ];
+    for range in padding {
+        assert!(shallow[range].iter().all(|&byte| byte == 0));
+    }
+}
@@ -0,0 +1,138 @@
+use std::mem;
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Counters {
+    len: u32,
+    #[cdump(array(len = self.len))]
+    values: *const *const u32,
+    #[cdump(array(len = self.len))]
+    weights: *mut *mut f64,
+    #[cdump(array(len = self.len))]
+    flags: *const *const u8,
+}
+
+fn with_counters<T>(f: impl FnOnce(&Counters) -> T) -> T {
+    let (first, second, third) = (7u32, 1984u32, u32::MAX);
+    let values = [&first as *const u32, &second, &third];
+    let (mut light, mut heavy) = (0.25f64, 1024.5f64);
+    let heavy = &mut heavy as *mut f64;
+    let mut weights = [&mut light as *mut f64, heavy, heavy];
+    let (on, off) = (1u8, 0u8);
+    let flags = [&on as *const u8, &off, &on];
+
+    f(&Counters {
+        len: values.len() as u32,
+        values: values.as_ptr(),
+        weights: weights.as_mut_ptr(),
+        flags: flags.as_ptr(),
+    })
+}
+
+unsafe fn assert_counters(obj: &Counters, copy: &Counters) {
+    assert_eq!(obj.len, copy.len);
+    for i in 0..copy.len as usize {
+        assert_ne!(*obj.values.add(i), *copy.values.add(i));
+        assert_eq!(**obj.values.add(i), **copy.values.add(i));
+        assert_eq!(**obj.weights.add(i), **copy.weights.add(i));
+        assert_eq!(**obj.flags.add(i), **copy.flags.add(i));
+    }
+    assert_eq!(
+        *copy.values.add(2) as usize % mem::align_of::<u32>(),
+        0,
+        "element is not aligned"
+    );
+    assert_eq!(*copy.weights.add(1) as usize % mem::align_of::<f64>(), 0);
+}
+
+#[test]
+fn primitive_pointer_array() {
+    with_counters(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+
+        let mut reader = buf.into_reader();
+        let copy = unsafe { Counters::deserialize_ref(&mut reader) };
+        eval_debug(copy);
+        unsafe { assert_counters(obj, copy) };
+    });
+}
+
+#[test]
+fn primitive_pointer_array_checked() {
+    with_counters(|obj| {
+        let mut reader = reader_from_bytes(&serialize_to_bytes(obj));
+        let copy = unsafe { Counters::try_deserialize_ref(&mut reader) }.unwrap();
+        unsafe { assert_counters(obj, copy) };
+    });
+}
+
+#[test]
+fn primitive_pointer_array_checked_rejects_truncated_buffer() {
+    let bytes = with_counters(serialize_to_bytes);
+    let mut reader = reader_from_bytes(&bytes[..bytes.len() - 1]);
+    assert!(unsafe { Counters::try_deserialize_ref(&mut reader) }.is_err());
+}
+
+#[test]
+fn primitive_pointer_array_serialized_size() {
+    with_counters(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn primitive_pointer_array_deserialize_to() {
+    with_counters(|obj| unsafe {
+        let bytes = serialize_to_bytes(obj);
+        let mut copy = mem::zeroed::<Counters>();
+        Counters::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        assert_counters(obj, &copy);
+
+        // Elements of the previous copy are reused.
+        let weight = *copy.weights.add(1);
+        Counters::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::reject(),
+        )
+        .unwrap();
+        assert_eq!(weight, *copy.weights.add(1));
+        assert_counters(obj, &copy);
+    });
+}
+
+#[test]
+fn primitive_pointer_array_owned() {
+    with_counters(|obj| {
+        let owned = unsafe { COwned::try_new(obj) }.unwrap();
+        unsafe { assert_counters(obj, &owned) };
+    });
+}
+
+#[test]
+fn primitive_pointer_array_debug() {
+    with_counters(|obj| {
+        let debug = format!("{obj:?}");
+        for expected in [
+            "values: Some([Some(7), Some(1984), Some(4294967295)])",
+            "weights: Some([Some(0.25), Some(1024.5), Some(1024.5)])",
+            "flags: Some([Some(1), Some(0), Some(1)])",
+        ] {
+            assert!(debug.contains(expected), "{debug}");
+        }
+    });
+}
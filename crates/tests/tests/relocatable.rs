@@ -0,0 +1,200 @@
+use std::{
+    ffi::{c_char, CStr},
+    mem,
+};
+
+use cdump::{
+    offset_of, relocatable::IMAGE_BASE, CDeserialize, CDumpBufferWriter, CDumpRelocatableBuffer,
+    CSerialize, Error,
+};
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    a: u32,
+    text: *const c_char,
+    bar: *const Bar,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *const Bar,
+    #[cdump(array(len = self.len))]
+    texts: *const *const c_char,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Bar {
+    value: u32,
+    text: *const c_char,
+}
+
+fn relocatable_foo() -> CDumpRelocatableBuffer {
+    let text = c"Hello world!";
+    let bar = Bar {
+        value: 7,
+        text: c"Hello bar!".as_ptr(),
+    };
+    let bars = [
+        Bar {
+            value: 1,
+            text: text.as_ptr(),
+        },
+        Bar {
+            value: 2,
+            text: c"Second".as_ptr(),
+        },
+    ];
+    let texts = [c"first".as_ptr(), c"second".as_ptr()];
+    let obj = Foo {
+        a: 1984,
+        text: text.as_ptr(),
+        bar: &bar,
+        len: bars.len() as u32,
+        bars: bars.as_ptr(),
+        texts: texts.as_ptr(),
+    };
+
+    let mut buf = CDumpBufferWriter::new(16);
+    unsafe { obj.serialize(&mut buf) };
+    unsafe { CDumpRelocatableBuffer::try_new::<Foo>(buf) }.unwrap()
+}
+
+fn assert_foo(copy: &Foo) {
+    unsafe {
+        assert_eq!(copy.a, 1984);
+        assert_eq!(CStr::from_ptr(copy.text), c"Hello world!");
+        assert_eq!((*copy.bar).value, 7);
+        assert_eq!(CStr::from_ptr((*copy.bar).text), c"Hello bar!");
+        assert_eq!((*copy.bars.add(1)).value, 2);
+        assert_eq!(CStr::from_ptr((*copy.bars.add(1)).text), c"Second");
+        assert_eq!(CStr::from_ptr(*copy.texts.add(1)), c"second");
+    }
+}
+
+#[test]
+fn fixup_table() {
+    let buffer = relocatable_foo();
+    // text, bar, bar.text, bars, bars[0..2].text, texts, texts[0..2]
+    assert_eq!(buffer.fixups().len(), 9);
+    assert_eq!(buffer.base(), IMAGE_BASE);
+}
+
+#[test]
+fn copied_image() {
+    let buffer = relocatable_foo();
+    let image = buffer.as_bytes().to_vec();
+    drop(buffer);
+
+    let mut copy =
+        CDumpRelocatableBuffer::try_from_image(&image, relocatable_foo().fixups().to_vec(), 16)
+            .unwrap();
+    assert_foo(unsafe { copy.root::<Foo>() });
+}
+
+#[test]
+fn moved_buffer() {
+    let mut buffers = vec![relocatable_foo()];
+    assert_foo(unsafe { buffers[0].root::<Foo>() });
+
+    // Reallocation of the vector moves only the owner, and rebasing to the image base creates the image again.
+    buffers.reserve(64);
+    buffers[0].rebase(IMAGE_BASE);
+    assert_eq!(slots(&buffers[0]), slots(&relocatable_foo()));
+}
+
+fn slots(buffer: &CDumpRelocatableBuffer) -> Vec<usize> {
+    let bytes = buffer.as_bytes();
+    buffer
+        .fixups()
+        .iter()
+        .map(|&fixup| {
+            usize::from_ne_bytes(
+                bytes[fixup..fixup + mem::size_of::<usize>()]
+                    .try_into()
+                    .unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn resolve_without_patching() {
+    let buffer = relocatable_foo();
+    let image = buffer.as_bytes().to_vec();
+
+    let root = unsafe { buffer.root_unpatched::<Foo>() };
+    assert_eq!(root.a, 1984);
+    unsafe {
+        assert_eq!(CStr::from_ptr(buffer.resolve(root.text)), c"Hello world!");
+        let bar = &*buffer.resolve(root.bar);
+        assert_eq!(CStr::from_ptr(buffer.resolve(bar.text)), c"Hello bar!");
+        let texts = buffer.resolve(root.texts);
+        assert_eq!(CStr::from_ptr(buffer.resolve(*texts.add(1))), c"second");
+    }
+    assert_eq!(buffer.as_bytes(), image);
+}
+
+#[test]
+fn invalid_fixup() {
+    let buffer = relocatable_foo();
+    let len = buffer.as_bytes().len();
+
+    let err = CDumpRelocatableBuffer::try_from_image(buffer.as_bytes(), vec![len - 4], 16)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        Error::OutOfBounds {
+            offset: len - 4,
+            len: mem::size_of::<usize>(),
+            buffer_len: len,
+        }
+    );
+
+    let err = CDumpRelocatableBuffer::try_from_image(buffer.as_bytes(), vec![1], 16)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        Error::Misaligned {
+            offset: 1,
+            align: mem::align_of::<usize>(),
+        }
+    );
+}
+
+#[test]
+fn invalid_fixup_target() {
+    let buffer = relocatable_foo();
+    let fixup = buffer.fixups()[0];
+    let len = buffer.as_bytes().len();
+
+    for ptr in [0, IMAGE_BASE + len + 1] {
+        let mut image = buffer.as_bytes().to_vec();
+        image[fixup..fixup + mem::size_of::<usize>()].copy_from_slice(&ptr.to_ne_bytes());
+
+        let err = CDumpRelocatableBuffer::try_from_image(&image, buffer.fixups().to_vec(), 16)
+            .err()
+            .unwrap();
+        assert_eq!(err, Error::InvalidRelocation { offset: fixup });
+    }
+}
+
+#[test]
+fn pointer_to_start_of_image() {
+    let buffer = relocatable_foo();
+    let fixup = offset_of!(Foo, bar);
+    assert!(buffer.fixups().contains(&fixup));
+
+    // Pointer to the offset 0 is stored as the image base, so it is not null.
+    let mut image = buffer.as_bytes().to_vec();
+    image[fixup..fixup + mem::size_of::<usize>()].copy_from_slice(&IMAGE_BASE.to_ne_bytes());
+    let copy =
+        CDumpRelocatableBuffer::try_from_image(&image, buffer.fixups().to_vec(), 16).unwrap();
+
+    let root = unsafe { copy.root_unpatched::<Foo>() };
+    assert_eq!(
+        copy.as_bytes().as_ptr(),
+        copy.resolve(root.bar) as *const u8
+    );
+}
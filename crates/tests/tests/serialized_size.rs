@@ -0,0 +1,163 @@
+use std::{
+    ffi::{c_char, c_void},
+    mem, ptr,
+};
+
+use cdump::{
+    CDeserialize, CDumpBufferWriter, CDumpSliceWriter, CDumpWriter, CSerialize, CSerializedSize,
+};
+use tests::{empty_deserializer, empty_sizeof};
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    a: u8,
+    text: *const c_char,
+    bar: *const Bar,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *const Bar,
+    #[cdump(array(len = self.len))]
+    texts: *const *const c_char,
+    #[cdump(dynamic(serializer = custom_serializer, deserializer = empty_deserializer, size_of = empty_sizeof, size = custom_size))]
+    d: *const c_void,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Bar {
+    value: u16,
+    text: *const c_char,
+}
+
+fn with_foo(f: impl FnOnce(&Foo)) {
+    let text = c"Hello world!";
+    let bar_text = c"Hello bar!";
+    let bar = Bar {
+        value: 7,
+        text: bar_text.as_ptr(),
+    };
+    let bars = [
+        Bar {
+            value: 1,
+            text: text.as_ptr(),
+        },
+        Bar {
+            value: 2,
+            text: ptr::null(),
+        },
+    ];
+    let texts = [bar_text.as_ptr(), text.as_ptr()];
+    let dynamic = 1984u64;
+
+    f(&Foo {
+        a: 3,
+        text: text.as_ptr(),
+        bar: &bar,
+        len: bars.len() as u32,
+        bars: bars.as_ptr(),
+        texts: texts.as_ptr(),
+        d: &dynamic as *const _ as *const c_void,
+    });
+}
+
+#[test]
+fn size_equals_written_bytes() {
+    with_foo(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        assert_eq!(unsafe { obj.serialized_size() }, buf.len());
+    });
+}
+
+#[test]
+fn size_after_written_data() {
+    with_foo(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { 5u8.serialize(&mut buf) };
+        let start = buf.len();
+
+        unsafe { obj.serialize(&mut buf) };
+        assert_eq!(unsafe { obj.serialized_end(start) }, buf.len());
+    });
+}
+
+#[test]
+fn reserve_exact_buffer() {
+    with_foo(|obj| {
+        let size = unsafe { obj.serialized_size() };
+
+        let mut buf = CDumpBufferWriter::with_capacity(16, size);
+        let capacity = buf.capacity();
+        unsafe { obj.serialize(&mut buf) };
+        assert_eq!(buf.capacity(), capacity);
+
+        #[repr(C, align(16))]
+        struct Slot([u8; 512]);
+        let mut slot = Slot([0; 512]);
+        assert!(size <= slot.0.len());
+
+        let mut buf = CDumpSliceWriter::new(&mut slot.0[..size]);
+        unsafe { obj.try_serialize(&mut buf) }.unwrap();
+        assert_eq!(buf.as_slice().len(), size);
+    });
+}
+
+#[test]
+fn null_pointers() {
+    let obj = Foo {
+        a: 3,
+        text: ptr::null(),
+        bar: ptr::null(),
+        len: 0,
+        bars: ptr::null(),
+        texts: ptr::null(),
+        d: ptr::null(),
+    };
+    assert_eq!(unsafe { obj.serialized_size() }, mem::size_of::<Foo>());
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Negative {
+    len: i32,
+    #[cdump(array(len = self.len))]
+    numbers: *const u32,
+}
+
+#[test]
+fn negative_length_overflows() {
+    let numbers = [1u32];
+    let obj = Negative {
+        len: -1,
+        numbers: numbers.as_ptr(),
+    };
+    assert_eq!(unsafe { obj.serialized_size() }, usize::MAX);
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Unsized {
+    #[cdump(dynamic(serializer = custom_serializer, deserializer = empty_deserializer, size_of = empty_sizeof))]
+    d: *const c_void,
+}
+
+#[test]
+fn dynamic_without_size_overflows() {
+    let dynamic = 1984u64;
+    let obj = Unsized {
+        d: &dynamic as *const _ as *const c_void,
+    };
+    assert_eq!(unsafe { obj.serialized_size() }, usize::MAX);
+
+    let obj = Unsized { d: ptr::null() };
+    assert_eq!(unsafe { obj.serialized_size() }, mem::size_of::<Unsized>());
+}
+
+unsafe fn custom_serializer<T: CDumpWriter>(buf: &mut T, obj: *const c_void) {
+    (*(obj as *const u64)).serialize(buf);
+}
+
+unsafe fn custom_size(obj: *const c_void, start: usize) -> usize {
+    (*(obj as *const u64)).serialized_end(start)
+}
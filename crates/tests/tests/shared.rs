@@ -0,0 +1,171 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem,
+};
+
+use cdump::{
+    CDeserialize, CDumpBufferReader, CDumpBufferWriter, CDumpReader, CDumpSharedReader,
+    CDumpSharedWriter, CDumpWriter, CSerialize, Error,
+};
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    first: *const Bar,
+    second: *const Bar,
+    text: *const c_char,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *const *const Bar,
+    #[cdump(array(len = self.len))]
+    texts: *const *const c_char,
+    #[cdump(array(len = self.len))]
+    numbers: *const u32,
+    #[cdump(array(len = self.len))]
+    same_numbers: *const u32,
+    #[cdump(dynamic(serializer = dynamic_serializer, deserializer = dynamic_deserializer, try_deserializer = dynamic_try_deserializer, size_of = dynamic_size_of))]
+    dynamic: *const c_void,
+    #[cdump(dynamic(serializer = dynamic_serializer, deserializer = dynamic_deserializer, try_deserializer = dynamic_try_deserializer, size_of = dynamic_size_of))]
+    same_dynamic: *const c_void,
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct Bar {
+    value: u32,
+    text: *const c_char,
+}
+
+unsafe fn dynamic_serializer<T: CDumpWriter>(buf: &mut T, obj: *const c_void) {
+    (*(obj as *const Bar)).serialize(buf)
+}
+
+unsafe fn dynamic_deserializer<T: CDumpReader>(buf: &mut T) -> (*mut c_void, usize) {
+    (
+        Bar::deserialize_ref_mut(buf) as *mut Bar as *mut c_void,
+        mem::size_of::<Bar>(),
+    )
+}
+
+unsafe fn dynamic_try_deserializer<T: CDumpReader>(
+    buf: &mut T,
+) -> Result<(*mut c_void, usize), Error> {
+    Ok((
+        Bar::try_deserialize_ref_mut(buf)? as *mut Bar as *mut c_void,
+        mem::size_of::<Bar>(),
+    ))
+}
+
+fn dynamic_size_of(_obj: *const c_void) -> usize {
+    mem::size_of::<Bar>()
+}
+
+fn with_foo<T>(f: impl FnOnce(&Foo) -> T) -> T {
+    let text = c"Hello world!";
+    let bar = Bar {
+        value: 7,
+        text: text.as_ptr(),
+    };
+    let other = Bar {
+        value: 8,
+        text: c"other".as_ptr(),
+    };
+    let bars = [&bar as *const Bar, &other, &bar];
+    let texts = [text.as_ptr(), c"second".as_ptr(), text.as_ptr()];
+    let numbers = [1u32, 2, 3];
+
+    f(&Foo {
+        first: &bar,
+        second: &bar,
+        text: text.as_ptr(),
+        len: bars.len() as u32,
+        bars: bars.as_ptr(),
+        texts: texts.as_ptr(),
+        numbers: numbers.as_ptr(),
+        same_numbers: numbers.as_ptr(),
+        dynamic: &other as *const Bar as *const c_void,
+        same_dynamic: &other as *const Bar as *const c_void,
+    })
+}
+
+fn serialize_shared(obj: &Foo) -> CDumpSharedReader<CDumpBufferReader> {
+    let mut buf = CDumpSharedWriter::new(CDumpBufferWriter::new(16));
+    unsafe { obj.serialize(&mut buf) };
+    CDumpSharedReader::new(buf.into_inner().into_reader())
+}
+
+unsafe fn assert_aliasing(copy: &Foo) {
+    assert_eq!(copy.first, copy.second);
+    assert_eq!(7, (*copy.first).value);
+    assert_eq!(copy.text, (*copy.first).text);
+    assert_eq!(c"Hello world!", CStr::from_ptr(copy.text));
+
+    assert_eq!(copy.first, *copy.bars);
+    assert_eq!(*copy.bars, *copy.bars.add(2));
+    assert_ne!(*copy.bars, *copy.bars.add(1));
+    assert_eq!(copy.text, *copy.texts);
+    assert_eq!(copy.text, *copy.texts.add(2));
+    assert_eq!(c"second", CStr::from_ptr(*copy.texts.add(1)));
+
+    assert_eq!(copy.numbers, copy.same_numbers);
+    assert_eq!(3, *copy.numbers.add(2));
+
+    assert_eq!(copy.dynamic, copy.same_dynamic);
+    assert_eq!(8, (*(copy.dynamic as *const Bar)).value);
+}
+
+#[test]
+fn shared_pointers() {
+    let mut reader = with_foo(serialize_shared);
+    unsafe { assert_aliasing(Foo::deserialize_ref(&mut reader)) };
+}
+
+#[test]
+fn shared_pointers_checked() {
+    let mut reader = with_foo(serialize_shared);
+    unsafe { assert_aliasing(Foo::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn shared_pointers_are_written_once() {
+    let (shared_len, len) = with_foo(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        (serialize_shared(obj).len(), buf.len())
+    });
+
+    // Bar with text, text, numbers and dynamic Bar are skipped.
+    assert!(shared_len < len, "{shared_len} >= {len}");
+}
+
+#[test]
+fn default_writer_does_not_share() {
+    let mut reader = with_foo(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Foo::deserialize_ref(&mut reader) };
+    assert_ne!(copy.first, copy.second);
+    assert_ne!(copy.numbers, copy.same_numbers);
+}
+
+#[test]
+fn invalid_back_reference() {
+    let mut reader = with_foo(serialize_shared);
+    unsafe {
+        // Back-reference of `second` points to the text of the first Bar, instead of the Bar.
+        let slot = reader.as_mut_ptr_at::<usize>(mem::offset_of!(Foo, second));
+        let first = *slot & !cdump::shared::BACK_REFERENCE;
+        *slot = cdump::shared::BACK_REFERENCE | (first + mem::size_of::<Bar>());
+    }
+
+    let err = unsafe { Foo::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err.root_cause(),
+        Error::InvalidBackReference { .. }
+    ));
+}
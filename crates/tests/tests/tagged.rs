@@ -0,0 +1,207 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize, CSerializedSize, CTag, Error,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    a: u32,
+    #[cdump(tagged = DynamicType)]
+    d: *const c_void,
+    len: u32,
+    #[cdump(array(len = self.len), tagged = DynamicType)]
+    items: *const *const c_void,
+    text: *const c_char,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, CTag)]
+#[repr(u32)]
+enum DynamicType {
+    Bar = 1,
+    Number = 2,
+    Unregistered = 3,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[cdump(tag = DynamicType::Bar)]
+#[repr(C)]
+struct DynamicBar {
+    ty: DynamicType,
+    text: *const c_char,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[cdump(tag = DynamicType::Number)]
+#[repr(C)]
+struct DynamicNumber {
+    ty: DynamicType,
+    value: u64,
+    #[cdump(tagged = DynamicType)]
+    inner: *const c_void,
+}
+
+#[repr(C)]
+struct Unregistered {
+    ty: DynamicType,
+}
+
+fn with_foo<T>(f: impl FnOnce(&Foo) -> T) -> T {
+    let bar = DynamicBar {
+        ty: DynamicType::Bar,
+        text: c"Never coming back!".as_ptr(),
+    };
+    let number = DynamicNumber {
+        ty: DynamicType::Number,
+        value: 1984,
+        inner: &bar as *const DynamicBar as *const c_void,
+    };
+    let items = [
+        &number as *const DynamicNumber as *const c_void,
+        &bar as *const DynamicBar as *const c_void,
+    ];
+
+    f(&Foo {
+        a: 7,
+        d: &bar as *const DynamicBar as *const c_void,
+        len: items.len() as u32,
+        items: items.as_ptr(),
+        text: c"Hello world!".as_ptr(),
+    })
+}
+
+unsafe fn assert_bar(obj: *const c_void) {
+    let bar = &*(obj as *const DynamicBar);
+    assert_eq!(DynamicType::Bar, bar.ty);
+    assert_eq!(c"Never coming back!", CStr::from_ptr(bar.text));
+}
+
+unsafe fn assert_foo(copy: &Foo) {
+    assert_eq!(7, copy.a);
+    assert_bar(copy.d);
+    assert_eq!(c"Hello world!", CStr::from_ptr(copy.text));
+
+    assert_eq!(2, copy.len);
+    let number = &*(*copy.items as *const DynamicNumber);
+    assert_eq!(DynamicType::Number, number.ty);
+    assert_eq!(1984, number.value);
+    assert_bar(number.inner);
+    assert_bar(*copy.items.add(1));
+}
+
+#[test]
+fn tagged() {
+    let mut reader = with_foo(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Foo::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_foo(copy) };
+}
+
+#[test]
+fn tagged_checked() {
+    let mut reader = reader_from_bytes(&with_foo(serialize_to_bytes));
+    unsafe { assert_foo(Foo::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[test]
+fn tagged_debug() {
+    with_foo(|obj| {
+        let debug = format!("{obj:?}");
+        assert!(
+            debug.contains("d: Some(DynamicBar { ty: Bar, text: Some(\"Never coming back!\") })"),
+            "{debug}"
+        );
+    });
+}
+
+#[test]
+fn tagged_serialized_size() {
+    with_foo(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn tagged_owned() {
+    let owned = with_foo(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_foo(&owned) };
+}
+
+#[test]
+fn unregistered_tag() {
+    let unregistered = Unregistered {
+        ty: DynamicType::Unregistered,
+    };
+    let obj = Foo {
+        a: 7,
+        d: &unregistered as *const Unregistered as *const c_void,
+        len: 0,
+        items: ptr::null(),
+        text: ptr::null(),
+    };
+
+    let mut buf = CDumpBufferWriter::new(16);
+    let err = unsafe { obj.try_serialize(&mut buf) }.err().unwrap();
+    assert_eq!(
+        &Error::UnregisteredTag {
+            tag: "Unregistered".to_owned()
+        },
+        err.root_cause()
+    );
+    assert_eq!(
+        "`Foo::d`: tag Unregistered is not registered",
+        err.to_string()
+    );
+    assert_eq!(usize::MAX, unsafe { obj.serialized_size() });
+}
+
+#[test]
+fn unknown_index_in_buffer() {
+    let mut bytes = with_foo(serialize_to_bytes);
+    // Index of `d` in the registry follows the shallow copy of `Foo`.
+    let index = mem::size_of::<Foo>();
+    bytes[index..index + mem::size_of::<u32>()].copy_from_slice(&7u32.to_ne_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Foo::try_deserialize_ref(&mut reader) }
+        .err()
+        .unwrap();
+    assert_eq!(&Error::UnknownDiscriminant(7), err.root_cause());
+}
+
+#[test]
+fn mismatched_tag_in_buffer() {
+    // Tag of `d` follows its index in the registry.
+    let index = mem::size_of::<Foo>();
+    let tag = (index + mem::size_of::<u32>()).next_multiple_of(mem::align_of::<DynamicBar>());
+
+    for discriminant in [DynamicType::Number as u32, 7] {
+        let mut bytes = with_foo(serialize_to_bytes);
+        assert_eq!(
+            (DynamicType::Bar as u32).to_ne_bytes(),
+            bytes[tag..tag + mem::size_of::<u32>()]
+        );
+        bytes[tag..tag + mem::size_of::<u32>()].copy_from_slice(&discriminant.to_ne_bytes());
+
+        let mut reader = reader_from_bytes(&bytes);
+        let err = unsafe { Foo::try_deserialize_ref(&mut reader) }
+            .err()
+            .unwrap();
+        assert_eq!(
+            &Error::UnknownDiscriminant(discriminant as i64),
+            err.root_cause()
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    ffi::{c_char, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize, Error,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+const EGL_NONE: i32 = 0x3038;
+
+#[derive(Clone, Copy, Debug, PartialEq, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Binding {
+    slot: u32,
+    count: u32,
+}
+
+/// Arrays which lengths are found by scanning to their terminators.
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Launch {
+    #[cdump(array(null_terminated))]
+    argv: *const *const c_char,
+    #[cdump(array(terminator = EGL_NONE))]
+    attributes: *const i32,
+    #[cdump(array(null_terminated))]
+    bindings: *const Binding,
+}
+
+fn with_launch<T>(f: impl FnOnce(&Launch) -> T) -> T {
+    let argv = [c"cdump".as_ptr(), c"--verbose".as_ptr(), ptr::null()];
+    let attributes = [0x3024, 8, 0x3023, 8, EGL_NONE];
+    let bindings = [
+        Binding { slot: 1, count: 4 },
+        Binding { slot: 3, count: 1 },
+        Binding { slot: 0, count: 0 },
+    ];
+
+    f(&Launch {
+        argv: argv.as_ptr(),
+        attributes: attributes.as_ptr(),
+        bindings: bindings.as_ptr(),
+    })
+}
+
+unsafe fn slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    std::slice::from_raw_parts(ptr, len)
+}
+
+unsafe fn assert_launch(copy: &Launch) {
+    let argv = slice(copy.argv, 3);
+    assert_eq!(c"cdump", CStr::from_ptr(argv[0]));
+    assert_eq!(c"--verbose", CStr::from_ptr(argv[1]));
+    assert!(argv[2].is_null());
+
+    assert_eq!([0x3024, 8, 0x3023, 8, EGL_NONE], slice(copy.attributes, 5));
+
+    assert_eq!(
+        [
+            Binding { slot: 1, count: 4 },
+            Binding { slot: 3, count: 1 },
+            Binding { slot: 0, count: 0 },
+        ],
+        slice(copy.bindings, 3)
+    );
+}
+
+#[test]
+fn terminated_array() {
+    let mut reader = with_launch(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Launch::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_launch(copy) };
+}
+
+#[test]
+fn terminated_array_checked() {
+    let mut reader = reader_from_bytes(&with_launch(serialize_to_bytes));
+    unsafe { assert_launch(Launch::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Attributes {
+    #[cdump(array(terminator = EGL_NONE))]
+    values: *const i32,
+}
+
+#[test]
+fn terminated_array_checked_rejects_missing_terminator() {
+    let values = [0x3024, 8, EGL_NONE];
+    let obj = Attributes {
+        values: values.as_ptr(),
+    };
+    let mut bytes = serialize_to_bytes(&obj);
+    let end = bytes.len();
+    bytes[end - 4..].copy_from_slice(&0x3023i32.to_ne_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Attributes::try_deserialize_ref(&mut reader) }.unwrap_err();
+    assert!(
+        matches!(err.root_cause(), Error::MissingTerminator { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn terminated_array_serialized_size() {
+    with_launch(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn terminated_array_deserialize_to() {
+    let bytes = with_launch(serialize_to_bytes);
+    let mut copy = unsafe { mem::zeroed::<Launch>() };
+    unsafe {
+        Launch::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        assert_launch(&copy);
+    }
+}
+
+#[test]
+fn terminated_array_owned() {
+    let owned = with_launch(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_launch(&owned) };
+}
+
+#[test]
+fn terminated_array_null() {
+    let obj = Launch {
+        argv: ptr::null(),
+        attributes: ptr::null(),
+        bindings: ptr::null(),
+    };
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { Launch::try_deserialize_ref(&mut reader) }.unwrap();
+    assert!(copy.argv.is_null());
+    assert!(copy.attributes.is_null());
+    assert!(copy.bindings.is_null());
+}
+
+#[test]
+fn terminated_array_debug() {
+    with_launch(|obj| {
+        let debug = format!("{obj:?}");
+        for expected in [
+            "argv: Some([Some(\"cdump\"), Some(\"--verbose\"), None])",
+            "attributes: Some([12324, 8, 12323, 8, 12344])",
+            "bindings: Some([Binding { slot: 1, count: 4 }, Binding { slot: 3, count: 1 }, Binding { slot: 0, count: 0 }])",
+        ] {
+            assert!(debug.contains(expected), "{debug}");
+        }
+    });
+}
@@ -0,0 +1,166 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem, ptr,
+};
+
+use cdump::{offset_of, CDebug, CDeserialize, CSerialize, Error};
+use tests::{
+    empty_deserializer, empty_serializer, empty_sizeof, eval_debug, reader_from_bytes,
+    serialize_to_bytes,
+};
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    a: u32,
+    text: *const c_char,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    bars: *const Bar,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Bar {
+    value: f64,
+    text: *const c_char,
+}
+
+fn bytes_of_foo() -> Vec<u8> {
+    let text = c"Hello world!";
+    let bar_text1 = c"Meet";
+    let bar_text2 = c"me halfway";
+    let bars = [
+        Bar {
+            value: 19.84,
+            text: bar_text1.as_ptr(),
+        },
+        Bar {
+            value: 20.24,
+            text: bar_text2.as_ptr(),
+        },
+    ];
+    let obj = Foo {
+        a: 1984,
+        text: text.as_ptr(),
+        len: bars.len() as u32,
+        bars: bars.as_ptr(),
+    };
+
+    serialize_to_bytes(&obj)
+}
+
+#[test]
+fn valid_buffer() {
+    let mut reader = reader_from_bytes(&bytes_of_foo());
+    let copy = unsafe { Foo::try_deserialize_ref(&mut reader) }.unwrap();
+
+    eval_debug(&copy);
+    assert_eq!(copy.a, 1984);
+    assert_eq!(unsafe { CStr::from_ptr(copy.text) }, c"Hello world!");
+    assert_eq!(copy.len, 2);
+    unsafe {
+        assert_eq!((*copy.bars).value, 19.84);
+        assert_eq!(CStr::from_ptr((*copy.bars).text), c"Meet");
+        assert_eq!((*copy.bars.add(1)).value, 20.24);
+        assert_eq!(CStr::from_ptr((*copy.bars.add(1)).text), c"me halfway");
+    }
+}
+
+#[test]
+fn truncated_buffer() {
+    let bytes = bytes_of_foo();
+    for len in 0..bytes.len() {
+        let mut reader = reader_from_bytes(&bytes[..len]);
+        let result = unsafe { Foo::try_deserialize_ref(&mut reader) };
+        assert!(
+            matches!(
+                result.as_ref().map_err(Error::root_cause),
+                Err(Error::OutOfBounds { .. })
+            ),
+            "buffer truncated to {len} bytes was accepted"
+        );
+    }
+}
+
+#[test]
+fn cstring_length_out_of_bounds() {
+    let mut bytes = bytes_of_foo();
+    let offset = offset_of!(Foo, text);
+    bytes[offset..offset + mem::size_of::<usize>()].copy_from_slice(&usize::MAX.to_ne_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let result = unsafe { Foo::try_deserialize_ref(&mut reader) };
+    assert!(matches!(
+        result.as_ref().map_err(Error::root_cause),
+        Err(Error::OutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn cstring_without_nul_terminator() {
+    let mut bytes = bytes_of_foo();
+    let start = mem::size_of::<Foo>();
+    let len = c"Hello world!".to_bytes_with_nul().len();
+    bytes[start + len - 1] = b'!';
+
+    let mut reader = reader_from_bytes(&bytes);
+    let result = unsafe { Foo::try_deserialize_ref(&mut reader) };
+    assert_eq!(
+        result.err(),
+        Some(Error::MissingNulTerminator { offset: start }.in_field("Foo", "text"))
+    );
+}
+
+#[test]
+fn error_in_nested_field() {
+    let mut bytes = bytes_of_foo();
+    let last = bytes.len() - 1;
+    bytes[last] = b'!';
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Foo::try_deserialize_ref(&mut reader) }.unwrap_err();
+    assert!(matches!(
+        err.root_cause(),
+        Error::MissingNulTerminator { .. }
+    ));
+    assert!(err.to_string().starts_with("`Foo::bars`: `Bar::text`: "));
+}
+
+#[test]
+fn array_length_overflow() {
+    let mut bytes = bytes_of_foo();
+    let offset = offset_of!(Foo, len);
+    bytes[offset..offset + mem::size_of::<u32>()].copy_from_slice(&u32::MAX.to_ne_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let result = unsafe { Foo::try_deserialize_ref(&mut reader) };
+    assert!(result.is_err());
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct UncheckedDynamic {
+    #[cdump(dynamic(serializer = empty_serializer, deserializer = empty_deserializer, size_of = empty_sizeof))]
+    d: *const c_void,
+}
+
+#[test]
+fn dynamic_without_checked_deserializer() {
+    let value = 0u8;
+    let obj = UncheckedDynamic {
+        d: &value as *const _ as *const c_void,
+    };
+
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let result = unsafe { UncheckedDynamic::try_deserialize_ref(&mut reader) };
+    assert_eq!(
+        result.err(),
+        Some(Error::UnsupportedDynamic.in_field("UncheckedDynamic", "d"))
+    );
+
+    let obj = UncheckedDynamic { d: ptr::null() };
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { UncheckedDynamic::try_deserialize_ref(&mut reader) }.unwrap();
+    assert!(copy.d.is_null());
+}
@@ -0,0 +1,151 @@
+use std::{
+    ffi::{c_char, c_void, CStr},
+    mem, ptr,
+};
+
+use cdump::{CDebug, CDeserialize, CDumpSliceWriter, CDumpWriter, CSerialize, Error};
+use tests::{empty_deserializer, empty_sizeof, eval_debug, reader_from_bytes};
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Foo {
+    a: u32,
+    text: *const c_char,
+    len: u64,
+    #[cdump(array(len = self.len))]
+    numbers: *const u16,
+}
+
+#[test]
+fn slice_writer() {
+    let text = c"Hello world!";
+    let numbers = [1u16, 9, 8, 4];
+    let obj = Foo {
+        a: 1984,
+        text: text.as_ptr(),
+        len: numbers.len() as u64,
+        numbers: numbers.as_ptr(),
+    };
+
+    #[repr(C, align(16))]
+    struct Slot([u8; 128]);
+    let mut slot = Slot([0; 128]);
+
+    let mut buf = CDumpSliceWriter::new(&mut slot.0);
+    unsafe { obj.try_serialize(&mut buf) }.unwrap();
+
+    let mut reader = reader_from_bytes(buf.as_slice());
+    let copy = unsafe { Foo::try_deserialize_ref(&mut reader) }.unwrap();
+
+    eval_debug(&copy);
+    assert_eq!(obj.a, copy.a);
+    assert_eq!(unsafe { CStr::from_ptr(copy.text) }, text);
+    for (i, number) in numbers.iter().enumerate() {
+        assert_eq!(unsafe { *copy.numbers.add(i) }, *number);
+    }
+}
+
+#[test]
+fn buffer_full() {
+    let text = c"Hello world!";
+    let obj = Foo {
+        a: 1984,
+        text: text.as_ptr(),
+        len: 0,
+        numbers: ptr::null(),
+    };
+
+    #[repr(C, align(16))]
+    struct Slot([u8; 32]);
+    let mut slot = Slot([0; 32]);
+
+    let mut buf = CDumpSliceWriter::new(&mut slot.0);
+    let err = unsafe { obj.try_serialize(&mut buf) }.unwrap_err();
+    assert_eq!(
+        err,
+        Error::BufferFull {
+            len: text.to_bytes_with_nul().len(),
+            available: 32 - mem::size_of::<Foo>(),
+        }
+        .in_field("Foo", "text")
+    );
+}
+
+#[derive(CSerialize)]
+#[repr(C)]
+struct ArrayWithOverflow {
+    len: i32,
+    #[cdump(array(len = self.len))]
+    numbers: *const u16,
+}
+
+#[test]
+fn array_length_overflow() {
+    let numbers = [1u16];
+    let obj = ArrayWithOverflow {
+        len: -1,
+        numbers: numbers.as_ptr(),
+    };
+
+    let mut buf = cdump::CDumpBufferWriter::new(16);
+    let err = unsafe { obj.try_serialize(&mut buf) }.unwrap_err();
+    assert_eq!(
+        err,
+        Error::LengthOverflow.in_field("ArrayWithOverflow", "numbers")
+    );
+}
+
+#[derive(CSerialize, CDeserialize)]
+#[repr(C)]
+struct UnknownDynamic {
+    #[cdump(dynamic(try_serializer = custom_try_serializer, deserializer = empty_deserializer, size_of = empty_sizeof))]
+    d: *const c_void,
+}
+
+unsafe fn custom_try_serializer<T: CDumpWriter>(
+    buf: &mut T,
+    obj: *const c_void,
+) -> Result<(), Error> {
+    match *(obj as *const u32) {
+        1 => (*(obj as *const u32)).try_serialize(buf),
+        ty => Err(Error::UnknownDiscriminant(ty as i64)),
+    }
+}
+
+#[test]
+fn unknown_dynamic_type() {
+    let ty = 1000156000u32;
+    let obj = UnknownDynamic {
+        d: &ty as *const _ as *const c_void,
+    };
+
+    let mut buf = cdump::CDumpBufferWriter::new(16);
+    let err = unsafe { obj.try_serialize(&mut buf) }.unwrap_err();
+    assert_eq!(
+        err,
+        Error::UnknownDiscriminant(1000156000).in_field("UnknownDynamic", "d")
+    );
+
+    let ty = 1u32;
+    let obj = UnknownDynamic {
+        d: &ty as *const _ as *const c_void,
+    };
+    let mut buf = cdump::CDumpBufferWriter::new(16);
+    unsafe { obj.try_serialize(&mut buf) }.unwrap();
+    assert_eq!(
+        buf.len(),
+        mem::size_of::<UnknownDynamic>() + mem::size_of::<u32>()
+    );
+}
+
+#[test]
+#[should_panic(expected = "serialization failed")]
+fn serialize_panics_on_error() {
+    let obj = ArrayWithOverflow {
+        len: -1,
+        numbers: [1u16].as_ptr(),
+    };
+
+    let mut buf = cdump::CDumpBufferWriter::new(16);
+    unsafe { obj.serialize(&mut buf) };
+}
@@ -0,0 +1,187 @@
+use std::{
+    ffi::{c_char, CStr},
+    mem, ptr,
+};
+
+use cdump::{
+    alloc::DestinationPolicy, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+const DESCRIPTOR_TYPE_SAMPLER: u32 = 0;
+const DESCRIPTOR_TYPE_IMAGE: u32 = 1;
+const DESCRIPTOR_TYPE_LABEL: u32 = 2;
+const DESCRIPTOR_TYPE_ADDRESS: u32 = 3;
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Descriptor {
+    ty: u32,
+    #[cdump(discriminator = self.ty)]
+    data: DescriptorData,
+    name: *const c_char,
+}
+
+#[derive(Clone, Copy, CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+union DescriptorData {
+    #[cdump(case = DESCRIPTOR_TYPE_SAMPLER)]
+    sampler: *const u64,
+    #[cdump(case = DESCRIPTOR_TYPE_IMAGE)]
+    image: *const ImageInfo,
+    #[cdump(case = DESCRIPTOR_TYPE_LABEL)]
+    label: *const c_char,
+    address: u64,
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct ImageInfo {
+    sampler: u64,
+    layout: u32,
+    view: *const c_char,
+}
+
+fn with_descriptor<T>(ty: u32, f: impl FnOnce(&Descriptor) -> T) -> T {
+    let sampler = 42u64;
+    let image = ImageInfo {
+        sampler: 7,
+        layout: 5,
+        view: c"color".as_ptr(),
+    };
+    let data = match ty {
+        DESCRIPTOR_TYPE_SAMPLER => DescriptorData { sampler: &sampler },
+        DESCRIPTOR_TYPE_IMAGE => DescriptorData { image: &image },
+        DESCRIPTOR_TYPE_LABEL => DescriptorData {
+            label: c"Hello world!".as_ptr(),
+        },
+        // Address is not a pointer, so it must not be followed.
+        _ => DescriptorData {
+            address: 0xdead_beef,
+        },
+    };
+
+    f(&Descriptor {
+        ty,
+        data,
+        name: c"descriptor".as_ptr(),
+    })
+}
+
+unsafe fn assert_descriptor(ty: u32, copy: &Descriptor) {
+    assert_eq!(ty, copy.ty);
+    assert_eq!(c"descriptor", CStr::from_ptr(copy.name));
+
+    match ty {
+        DESCRIPTOR_TYPE_SAMPLER => assert_eq!(42, *copy.data.sampler),
+        DESCRIPTOR_TYPE_IMAGE => {
+            let image = &*copy.data.image;
+            assert_eq!(7, image.sampler);
+            assert_eq!(5, image.layout);
+            assert_eq!(c"color", CStr::from_ptr(image.view));
+        }
+        DESCRIPTOR_TYPE_LABEL => assert_eq!(c"Hello world!", CStr::from_ptr(copy.data.label)),
+        _ => assert_eq!(0xdead_beef, copy.data.address),
+    }
+}
+
+const TYPES: [u32; 4] = [
+    DESCRIPTOR_TYPE_SAMPLER,
+    DESCRIPTOR_TYPE_IMAGE,
+    DESCRIPTOR_TYPE_LABEL,
+    DESCRIPTOR_TYPE_ADDRESS,
+];
+
+#[test]
+fn union() {
+    for ty in TYPES {
+        let mut reader = with_descriptor(ty, |obj| {
+            let mut buf = CDumpBufferWriter::new(16);
+            unsafe { obj.serialize(&mut buf) };
+            buf.into_reader()
+        });
+
+        let copy = unsafe { Descriptor::deserialize_ref(&mut reader) };
+        eval_debug(copy);
+        unsafe { assert_descriptor(ty, copy) };
+    }
+}
+
+#[test]
+fn union_checked() {
+    for ty in TYPES {
+        let mut reader = reader_from_bytes(&with_descriptor(ty, serialize_to_bytes));
+        unsafe { assert_descriptor(ty, Descriptor::try_deserialize_ref(&mut reader).unwrap()) };
+    }
+}
+
+#[test]
+fn union_serialized_size() {
+    for ty in TYPES {
+        with_descriptor(ty, |obj| {
+            assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+                obj.serialized_size()
+            });
+        });
+    }
+}
+
+#[test]
+fn union_owned() {
+    for ty in TYPES {
+        let owned = with_descriptor(ty, |obj| unsafe { COwned::try_new(obj) }).unwrap();
+        unsafe { assert_descriptor(ty, &owned) };
+    }
+}
+
+#[test]
+fn union_deserialize_to() {
+    for ty in TYPES {
+        let mut reader = reader_from_bytes(&with_descriptor(ty, serialize_to_bytes));
+        let mut copy = unsafe { mem::zeroed::<Descriptor>() };
+        unsafe {
+            Descriptor::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate())
+                .unwrap();
+            assert_descriptor(ty, &copy);
+        }
+    }
+}
+
+#[test]
+fn union_deserialize_to_other_member() {
+    let mut reader = reader_from_bytes(&with_descriptor(
+        DESCRIPTOR_TYPE_SAMPLER,
+        serialize_to_bytes,
+    ));
+    // Address of the destination is not a pointer, so it is not reused for the sampler.
+    let mut copy = Descriptor {
+        ty: DESCRIPTOR_TYPE_ADDRESS,
+        data: DescriptorData { address: 1 },
+        name: ptr::null(),
+    };
+    unsafe {
+        Descriptor::deserialize_to(&mut reader, &mut copy, &DestinationPolicy::allocate()).unwrap();
+        assert_descriptor(DESCRIPTOR_TYPE_SAMPLER, &copy);
+    }
+}
+
+#[test]
+fn union_debug() {
+    with_descriptor(DESCRIPTOR_TYPE_IMAGE, |obj| {
+        let debug = format!("{obj:?}");
+        assert!(
+            debug.contains(
+                "data: DescriptorData { image: Some(ImageInfo { sampler: 7, layout: 5, view: Some(\"color\") }) }"
+            ),
+            "{debug}"
+        );
+    });
+    with_descriptor(DESCRIPTOR_TYPE_ADDRESS, |obj| {
+        let debug = format!("{obj:?}");
+        assert!(
+            debug.contains("data: DescriptorData { address: 3735928559 }"),
+            "{debug}"
+        );
+    });
+}
@@ -0,0 +1,150 @@
+use std::{
+    ffi::{c_char, c_void},
+    mem, thread,
+};
+
+use cdump::{CDeserialize, CDumpBufferWriter, CDumpWriter, CSerialize, CView, Error};
+use tests::empty_deserializer;
+
+#[derive(CSerialize, CDeserialize, CView)]
+#[repr(C)]
+struct Foo {
+    a: u32,
+    text: *const c_char,
+    bar: *const Bar,
+    len: u32,
+    #[cdump(array(len = self.len))]
+    numbers: *const u16,
+    #[cdump(array(len = self.len))]
+    bars: *const Bar,
+    #[cdump(array(len = self.len))]
+    texts: *const *const c_char,
+    #[cdump(array(len = self.len))]
+    ptrs: *const *const Bar,
+    #[cdump(dynamic(serializer = custom_serializer, deserializer = empty_deserializer, size_of = custom_sizeof, skip = custom_skip))]
+    d: *const c_void,
+}
+
+#[derive(CSerialize, CDeserialize, CView)]
+#[repr(C)]
+struct Bar {
+    value: u32,
+    text: *const c_char,
+}
+
+fn serialize_foo() -> CDumpBufferWriter {
+    let text = c"Hello world!";
+    let bar = Bar {
+        value: 7,
+        text: c"Hello bar!".as_ptr(),
+    };
+    let numbers = [1u16, 9];
+    let bars = [
+        Bar {
+            value: 1,
+            text: c"First".as_ptr(),
+        },
+        Bar {
+            value: 2,
+            text: std::ptr::null(),
+        },
+    ];
+    let texts = [c"first".as_ptr(), c"second".as_ptr()];
+    let ptrs = [&bars[0] as *const Bar, &bar];
+    let obj = Foo {
+        a: 1984,
+        text: text.as_ptr(),
+        bar: &bar,
+        len: 2,
+        numbers: numbers.as_ptr(),
+        bars: bars.as_ptr(),
+        texts: texts.as_ptr(),
+        ptrs: ptrs.as_ptr(),
+        d: &bar as *const _ as *const c_void,
+    };
+
+    let mut buf = CDumpBufferWriter::new(16);
+    unsafe { obj.serialize(&mut buf) };
+    buf
+}
+
+fn as_slice(buf: &mut CDumpBufferWriter) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(buf.as_mut_ptr_at(0), buf.len()) }
+}
+
+fn assert_view(data: &[u8]) {
+    let view = unsafe { Foo::try_view(data) }.unwrap();
+    assert_eq!(view.a, 1984);
+    assert_eq!(view.text(), Some(c"Hello world!"));
+
+    let bar = view.bar().unwrap();
+    assert_eq!(bar.value, 7);
+    assert_eq!(bar.text(), Some(c"Hello bar!"));
+
+    assert_eq!(view.numbers(), Some(&[1u16, 9][..]));
+
+    let bars = view.bars().unwrap();
+    assert_eq!(bars.len(), 2);
+    let values = bars.iter().map(|bar| bar.value).collect::<Vec<_>>();
+    assert_eq!(values, [1, 2]);
+    assert_eq!(bars.get(0).unwrap().text(), Some(c"First"));
+    assert_eq!(bars.get(1).unwrap().text(), None);
+
+    let texts = view.texts().unwrap().iter().collect::<Vec<_>>();
+    assert_eq!(texts, [Some(c"first"), Some(c"second")]);
+
+    let ptrs = view.ptrs().unwrap();
+    assert_eq!(ptrs.get(0).unwrap().unwrap().value, 1);
+    assert_eq!(ptrs.get(1).unwrap().unwrap().text(), Some(c"Hello bar!"));
+
+    let dynamic = unsafe { view.d().unwrap().view::<Bar>() }.unwrap();
+    assert_eq!(dynamic.value, 7);
+    assert_eq!(dynamic.text(), Some(c"Hello bar!"));
+}
+
+#[test]
+fn view_does_not_write() {
+    let mut buf = serialize_foo();
+    let data = as_slice(&mut buf);
+    let copy = data.to_vec();
+
+    assert_view(data);
+    assert_view(data);
+    assert_eq!(data, copy);
+}
+
+#[test]
+fn view_from_threads() {
+    let mut buf = serialize_foo();
+    let data = as_slice(&mut buf);
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| assert_view(data));
+        }
+    });
+}
+
+#[test]
+fn truncated_data() {
+    let mut buf = serialize_foo();
+    let data = as_slice(&mut buf);
+
+    let err = unsafe { Foo::try_view(&data[..data.len() - 1]) }
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Field { field: "d", .. }));
+    assert!(matches!(err.root_cause(), Error::OutOfBounds { .. }));
+}
+
+unsafe fn custom_serializer<T: CDumpWriter>(buf: &mut T, obj: *const c_void) {
+    (*(obj as *const Bar)).serialize(buf);
+}
+
+unsafe fn custom_sizeof(_obj: *const c_void) -> usize {
+    mem::size_of::<Bar>()
+}
+
+fn custom_skip(data: &[u8], index: usize) -> Result<usize, Error> {
+    unsafe { Bar::try_view_at(data, index) }.map(|(_, end)| end)
+}
@@ -0,0 +1,163 @@
+use std::{ffi::c_char, mem, ptr};
+
+use cdump::{
+    alloc::DestinationPolicy, wide, CDebug, CDeserialize, CDumpBufferWriter, COwned, CSerialize,
+    CSerializedSize, Error,
+};
+use tests::{eval_debug, reader_from_bytes, serialize_to_bytes};
+
+/// Wide strings of Windows `wchar_t`, and of Linux `wchar_t`, preceded by a byte string, so they need alignment.
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Window {
+    class: *const c_char,
+    #[cdump(wstring)]
+    title: *const u16,
+    #[cdump(wstring)]
+    path: *const u32,
+    argument_count: u32,
+    #[cdump(array(len = self.argument_count), wstring)]
+    arguments: *const *const u16,
+}
+
+fn utf16(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain([0]).collect()
+}
+
+fn utf32(text: &str) -> Vec<u32> {
+    text.chars().map(u32::from).chain([0]).collect()
+}
+
+fn with_window<T>(f: impl FnOnce(&Window) -> T) -> T {
+    let title = utf16("Zażółć 🌍");
+    let path = utf32("/home/ünïcode");
+    let first = utf16("--lang");
+    let second = utf16("pl");
+    let arguments = [first.as_ptr(), ptr::null(), second.as_ptr()];
+
+    f(&Window {
+        class: c"w".as_ptr(),
+        title: title.as_ptr(),
+        path: path.as_ptr(),
+        argument_count: arguments.len() as u32,
+        arguments: arguments.as_ptr(),
+    })
+}
+
+unsafe fn assert_window(copy: &Window) {
+    assert_eq!("Zażółć 🌍", wide::decode(copy.title));
+    assert_eq!(0, copy.title as usize % mem::align_of::<u16>());
+    assert_eq!("/home/ünïcode", wide::decode(copy.path));
+    assert_eq!(0, copy.path as usize % mem::align_of::<u32>());
+
+    assert_eq!(3, copy.argument_count);
+    assert_eq!("--lang", wide::decode(*copy.arguments));
+    assert!((*copy.arguments.add(1)).is_null());
+    assert_eq!("pl", wide::decode(*copy.arguments.add(2)));
+}
+
+#[test]
+fn wstring() {
+    let mut reader = with_window(|obj| {
+        let mut buf = CDumpBufferWriter::new(16);
+        unsafe { obj.serialize(&mut buf) };
+        buf.into_reader()
+    });
+
+    let copy = unsafe { Window::deserialize_ref(&mut reader) };
+    eval_debug(copy);
+    unsafe { assert_window(copy) };
+}
+
+#[test]
+fn wstring_checked() {
+    let mut reader = reader_from_bytes(&with_window(serialize_to_bytes));
+    unsafe { assert_window(Window::try_deserialize_ref(&mut reader).unwrap()) };
+}
+
+#[derive(CDebug, CSerialize, CDeserialize)]
+#[repr(C)]
+struct Title {
+    #[cdump(wstring)]
+    text: *const u16,
+}
+
+#[test]
+fn wstring_checked_rejects_missing_terminator() {
+    let text = utf16("title");
+    let obj = Title {
+        text: text.as_ptr(),
+    };
+    let mut bytes = serialize_to_bytes(&obj);
+    let end = bytes.len();
+    bytes[end - 2..].copy_from_slice(&u16::from(b'!').to_ne_bytes());
+
+    let mut reader = reader_from_bytes(&bytes);
+    let err = unsafe { Title::try_deserialize_ref(&mut reader) }.unwrap_err();
+    assert!(
+        matches!(err.root_cause(), Error::MissingNulTerminator { .. }),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn wstring_serialized_size() {
+    with_window(|obj| {
+        assert_eq!(serialize_to_bytes(obj).len(), unsafe {
+            obj.serialized_size()
+        });
+    });
+}
+
+#[test]
+fn wstring_deserialize_to() {
+    let bytes = with_window(serialize_to_bytes);
+    let mut copy = unsafe { mem::zeroed::<Window>() };
+    unsafe {
+        Window::deserialize_to(
+            &mut reader_from_bytes(&bytes),
+            &mut copy,
+            &DestinationPolicy::allocate(),
+        )
+        .unwrap();
+        assert_window(&copy);
+    }
+}
+
+#[test]
+fn wstring_owned() {
+    let owned = with_window(|obj| unsafe { COwned::try_new(obj) }).unwrap();
+    unsafe { assert_window(&owned) };
+}
+
+#[test]
+fn wstring_null() {
+    let obj = Title { text: ptr::null() };
+    let mut reader = reader_from_bytes(&serialize_to_bytes(&obj));
+    let copy = unsafe { Title::try_deserialize_ref(&mut reader) }.unwrap();
+    assert!(copy.text.is_null());
+    assert!(format!("{copy:?}").contains("text: None"));
+}
+
+#[test]
+fn wstring_debug() {
+    with_window(|obj| {
+        let debug = format!("{obj:?}");
+        for expected in [
+            "title: Some(\"Zażółć 🌍\")",
+            "path: Some(\"/home/ünïcode\")",
+            "arguments: Some([Some(\"--lang\"), None, Some(\"pl\")])",
+        ] {
+            assert!(debug.contains(expected), "{debug}");
+        }
+    });
+}
+
+#[test]
+fn wstring_debug_replaces_invalid_characters() {
+    let text = [0xd800u16, u16::from(b'a'), 0];
+    let obj = Title {
+        text: text.as_ptr(),
+    };
+    assert!(format!("{obj:?}").contains("text: Some(\"\u{fffd}a\")"));
+}